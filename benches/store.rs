@@ -0,0 +1,87 @@
+//! Measures what the store's operations cost, in time and heap allocations.
+//!
+//! Services that build an entry per request build tens of thousands of
+//! short-lived entries, so the allocations made for each show up in their
+//! allocator profiles, and services that read a credential per request
+//! care how many syscalls a read takes (see the crate docs on
+//! high-throughput reads). Run with `cargo bench` (in a session with a keyring).
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use linux_keyutils_keyring_store::keyring_core::api::{CredentialApi, CredentialStoreApi};
+use linux_keyutils_keyring_store::{Cred, Relink, Store, StoreBuilder};
+
+/// The system allocator, counting allocations.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const ITERATIONS: usize = 100_000;
+
+/// Run `f` many times, and report its mean time and allocations.
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = started.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{name:<24} {:>8.0} ns {:>6.1} allocations",
+        elapsed.as_nanos() as f64 / ITERATIONS as f64,
+        allocations as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let store = Store::new().expect("the session keyring is available");
+    bench("build entry", || {
+        black_box(store.build("bench-service", "bench-user", None).unwrap());
+    });
+    let entry = store.build("bench-service", "bench-user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    bench("clone credential", || {
+        black_box(cred.clone());
+    });
+
+    bench("set", || cred.set_password("bench password").unwrap());
+    bench("get", || {
+        black_box(cred.get_password().unwrap());
+    });
+    let fast = StoreBuilder::new().relink(Relink::Never).build().unwrap();
+    let unlinked = fast.build("bench-service", "bench-user", None).unwrap();
+    bench("get (relink never)", || {
+        black_box(unlinked.get_password().unwrap());
+    });
+    let pinned = cred.pin().unwrap();
+    bench("get (pinned)", || {
+        black_box(pinned.get_password().unwrap());
+    });
+    bench("set and delete", || {
+        cred.set_password("bench password").unwrap();
+        cred.delete_credential().unwrap();
+    });
+}
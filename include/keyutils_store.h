@@ -0,0 +1,54 @@
+/*
+ * C interface to linux-keyutils-keyring-store (built with the `ffi` feature).
+ *
+ * Functions returning int return KEYUTILS_STORE_OK (zero) on success, or
+ * another status code on failure, when keyutils_store_last_error() describes
+ * the failure. Secrets and lists returned by the library must be freed with
+ * its free functions.
+ */
+#ifndef KEYUTILS_STORE_H
+#define KEYUTILS_STORE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KEYUTILS_STORE_OK 0
+#define KEYUTILS_STORE_NO_ENTRY 1
+#define KEYUTILS_STORE_NO_ACCESS 2
+#define KEYUTILS_STORE_INVALID 3
+#define KEYUTILS_STORE_NOT_SUPPORTED 4
+#define KEYUTILS_STORE_FAILURE 5
+#define KEYUTILS_STORE_BAD_ARGUMENT 6
+
+typedef struct keyutils_store keyutils_store;
+
+/* Create a store from `count` config options (NULL on failure). */
+keyutils_store *keyutils_store_new(const char *const *names, const char *const *values,
+                                   size_t count);
+void keyutils_store_free(keyutils_store *store);
+
+int keyutils_store_set(const keyutils_store *store, const char *service, const char *user,
+                       const uint8_t *secret, size_t len);
+/* On success, free *secret with keyutils_store_free_secret. */
+int keyutils_store_get(const keyutils_store *store, const char *service, const char *user,
+                       uint8_t **secret, size_t *len);
+void keyutils_store_free_secret(uint8_t *secret, size_t len);
+int keyutils_store_delete(const keyutils_store *store, const char *service, const char *user);
+
+/* On success, free the lists with keyutils_store_free_list. */
+int keyutils_store_list(const keyutils_store *store, char ***services, char ***users,
+                        size_t *count);
+void keyutils_store_free_list(char **services, char **users, size_t count);
+
+/* The calling thread's last failure, or NULL; valid until its next failure. */
+const char *keyutils_store_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KEYUTILS_STORE_H */
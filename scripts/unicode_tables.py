@@ -0,0 +1,76 @@
+#!/usr/bin/env python3
+"""Generate src/normalize/tables.rs from Python's Unicode database.
+
+Run from the repository root: python3 scripts/unicode_tables.py
+"""
+import unicodedata
+
+HANGUL = range(0xAC00, 0xD7A4)
+
+
+def rust_char(c):
+    return "'\\u{%X}'" % c
+
+
+def rust_str(chars):
+    return '"' + "".join("\\u{%X}" % c for c in chars) + '"'
+
+
+classes = []  # (first, last, class)
+canonical = []  # (char, decomposition)
+compatibility = []  # (char, decomposition)
+compositions = []  # (first, second, composite)
+for c in range(0x110000):
+    ch = chr(c)
+    ccc = unicodedata.combining(ch)
+    if ccc:
+        if classes and classes[-1][1] == c - 1 and classes[-1][2] == ccc:
+            classes[-1] = (classes[-1][0], c, ccc)
+        else:
+            classes.append((c, c, ccc))
+    if c in HANGUL:
+        continue
+    decomposition = unicodedata.decomposition(ch)
+    if not decomposition:
+        continue
+    fields = decomposition.split()
+    if fields[0].startswith("<"):
+        compatibility.append((c, [int(f, 16) for f in fields[1:]]))
+        continue
+    parts = [int(f, 16) for f in fields]
+    canonical.append((c, parts))
+    # a primary composite is one that recomposes from its decomposition
+    if len(parts) == 2 and unicodedata.normalize("NFC", chr(parts[0]) + chr(parts[1])) == ch:
+        compositions.append((parts[0], parts[1], c))
+compositions.sort()
+
+out = []
+out.append("//! Unicode %s normalization data, generated by `scripts/unicode_tables.py`." % unicodedata.unidata_version)
+out.append("//! Do not edit by hand.")
+out.append("")
+out.append("/// The canonical combining classes other than zero, as `(first, last, class)` ranges.")
+out.append("pub(super) static COMBINING_CLASSES: &[(char, char, u8)] = &[")
+for first, last, ccc in classes:
+    out.append("    (%s, %s, %d)," % (rust_char(first), rust_char(last), ccc))
+out.append("];")
+out.append("")
+out.append("/// The canonical decompositions (one level deep), except Hangul syllables.")
+out.append("pub(super) static CANONICAL: &[(char, &str)] = &[")
+for c, parts in canonical:
+    out.append("    (%s, %s)," % (rust_char(c), rust_str(parts)))
+out.append("];")
+out.append("")
+out.append("/// The compatibility decompositions (one level deep).")
+out.append("pub(super) static COMPATIBILITY: &[(char, &str)] = &[")
+for c, parts in compatibility:
+    out.append("    (%s, %s)," % (rust_char(c), rust_str(parts)))
+out.append("];")
+out.append("")
+out.append("/// The primary composites, as `(first, second, composite)`, sorted by `(first, second)`.")
+out.append("pub(super) static COMPOSITIONS: &[(char, char, char)] = &[")
+for first, second, c in compositions:
+    out.append("    (%s, %s, %s)," % (rust_char(first), rust_char(second), rust_char(c)))
+out.append("];")
+
+with open("src/normalize/tables.rs", "w") as f:
+    f.write("\n".join(out) + "\n")
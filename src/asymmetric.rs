@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use keyring_core::api::CredentialApi;
+use keyring_core::{Credential, Entry, Error, Result};
+use linux_keyutils::KeySerialId;
+
+use super::error::KeyStoreError;
+use super::{KeyDescription, procfs, sys};
+
+/// The type of keys the kernel parses from certificates and public keys.
+const ASYMMETRIC: &str = "asymmetric";
+
+/// An `asymmetric` key in a store's keyrings, as a read-only credential.
+///
+/// The kernel parses these from X.509 certificates (and, where it's built
+/// to, PKCS#8 private keys), and never hands the key material back, so they
+/// can't be read, written, or deleted through the credential API. Their
+/// [attributes](keyring_core::Entry::get_attributes) describe them instead:
+///
+/// - `key_type`: `asymmetric`
+/// - `serial`: the key's serial number
+/// - `description`: the key's description, which for a certificate is
+///   taken from its subject and key identifier unless it was given one
+/// - `subtype`: how the kernel parsed the key, e.g. `X509.rsa`
+/// - `fingerprint`: the end of the key's identifier, in hex, as `keyctl show` gives it
+///
+/// The last two are only known when `/proc/keys` lists the key.
+/// [Store::asymmetric_keys](super::Store::asymmetric_keys) finds them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsymmetricKey {
+    /// The key's serial
+    pub serial: KeySerialId,
+    /// The key's description
+    pub description: String,
+    /// How the kernel parsed the key (e.g. `X509.rsa`)
+    pub subtype: Option<String>,
+    /// The end of the key's identifier, in hex
+    pub fingerprint: Option<String>,
+}
+
+impl AsymmetricKey {
+    /// Describe the asymmetric key `serial`, or return `None` if it isn't one.
+    pub(crate) fn describe(
+        serial: KeySerialId,
+        listed: &HashMap<i32, procfs::ProcKey>,
+    ) -> Option<Self> {
+        let KeyDescription {
+            key_type,
+            description,
+            ..
+        } = sys::describe(serial).ok()?;
+        if key_type != ASYMMETRIC {
+            return None;
+        }
+        // the summary is the subtype, the key id, and a note, e.g. `X509.rsa 3db7e65a []`
+        let summary = listed
+            .get(&serial.as_raw_id())
+            .and_then(|key| key.summary.as_deref())
+            .and_then(|summary| summary.split_once(" ["))
+            .map(|(summary, _)| summary);
+        let mut fields = summary.unwrap_or_default().split(' ');
+        let subtype = fields.next().filter(|s| !s.is_empty()).map(str::to_string);
+        let fingerprint = fields.next().map(str::to_string);
+        Some(AsymmetricKey {
+            serial,
+            description,
+            subtype,
+            fingerprint,
+        })
+    }
+
+    /// Check that the key is still there.
+    fn check(&self) -> Result<()> {
+        sys::describe(self.serial).map_err(KeyStoreError)?;
+        Ok(())
+    }
+
+    fn read_only() -> Error {
+        Error::NotSupportedByStore("asymmetric keys are read-only".to_string())
+    }
+}
+
+impl CredentialApi for AsymmetricKey {
+    /// Always fails: the kernel makes asymmetric keys from certificates, not secrets.
+    fn set_secret(&self, _: &[u8]) -> Result<()> {
+        Err(Self::read_only())
+    }
+
+    /// Fails with [NotSupportedByStore](Error::NotSupportedByStore), since
+    /// the kernel doesn't give out the key material of asymmetric keys,
+    /// or [NoEntry](Error::NoEntry) if the key is gone.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.check()?;
+        Err(Error::NotSupportedByStore(
+            "the kernel doesn't give out asymmetric keys".to_string(),
+        ))
+    }
+
+    /// The key's description, subtype, and fingerprint; see [AsymmetricKey].
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.check()?;
+        let mut attributes = HashMap::from([
+            ("key_type".to_string(), ASYMMETRIC.to_string()),
+            ("serial".to_string(), self.serial.as_raw_id().to_string()),
+            ("description".to_string(), self.description.clone()),
+        ]);
+        if let Some(subtype) = &self.subtype {
+            attributes.insert("subtype".to_string(), subtype.clone());
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            attributes.insert("fingerprint".to_string(), fingerprint.clone());
+        }
+        Ok(attributes)
+    }
+
+    /// Always fails: trusted keys are managed with `keyctl`, not through a store.
+    fn delete_credential(&self) -> Result<()> {
+        Err(Self::read_only())
+    }
+
+    /// See the keyring-core API docs.
+    fn get_credential(&self) -> Result<Option<Arc<Credential>>> {
+        self.check()?;
+        Ok(None)
+    }
+
+    /// Asymmetric keys have no service and user.
+    fn get_specifiers(&self) -> Option<(String, String)> {
+        None
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Entries for the asymmetric keys linked into `keyrings`, each listed once.
+pub(crate) fn entries(keyrings: &[KeySerialId]) -> Result<Vec<Entry>> {
+    let listed: HashMap<i32, procfs::ProcKey> = procfs::keys()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|key| (key.serial.as_raw_id(), key))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for keyring in keyrings {
+        for id in sys::keyring_links(*keyring).map_err(KeyStoreError::from)? {
+            if !seen.insert(id.as_raw_id()) {
+                continue;
+            }
+            // keys can vanish at any point during the walk
+            if let Some(key) = AsymmetricKey::describe(id, &listed) {
+                entries.push(Entry::new_with_credential(Arc::new(key)));
+            }
+        }
+    }
+    Ok(entries)
+}
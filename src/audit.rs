@@ -0,0 +1,122 @@
+use std::ffi::CString;
+use std::fmt::Debug;
+
+use keyring_core::Error;
+
+/// An operation on a credential, as recorded by an [AuditSink].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditOp {
+    /// The secret was read.
+    Get,
+    /// The secret was written.
+    Set,
+    /// The credential was deleted.
+    Delete,
+    /// The credential's key was revoked.
+    Revoke,
+}
+
+impl AuditOp {
+    /// The operation's name, as it appears in audit records.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOp::Get => "get",
+            AuditOp::Set => "set",
+            AuditOp::Delete => "delete",
+            AuditOp::Revoke => "revoke",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A record of one operation on a credential.
+///
+/// Events never carry the secret, only which key it was in and how the
+/// operation went.
+#[derive(Debug, Clone, Copy)]
+pub struct AuditEvent<'a> {
+    /// What was done
+    pub operation: AuditOp,
+    /// The description of the credential's key
+    pub description: &'a str,
+    /// The entry's `audit_context` modifier, if it has one
+    pub context: Option<&'a str>,
+    /// Whether the operation succeeded, and why not if it didn't
+    pub outcome: Result<(), &'a Error>,
+}
+
+impl std::fmt::Display for AuditEvent<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?}", self.operation, self.description)?;
+        if let Some(context) = self.context {
+            write!(f, " ({context:?})")?;
+        }
+        match self.outcome {
+            Ok(()) => f.write_str(": ok"),
+            Err(err) => write!(f, ": failed: {err}"),
+        }
+    }
+}
+
+/// Where a store records the operations on its credentials.
+///
+/// A sink is called after every read, write, delete, and revocation of a
+/// credential, on the thread that made it, so it should be quick (e.g. hand
+/// the event to a channel if it needs to do I/O that can block).
+pub trait AuditSink: Debug + Send + Sync {
+    /// Record `event`.
+    fn record(&self, event: &AuditEvent<'_>);
+}
+
+/// An [AuditSink] that sends events to syslog, and so to journald where it runs.
+///
+/// Events are logged with the `LOG_AUTHPRIV` facility, at `LOG_INFO` if the
+/// operation succeeded and `LOG_NOTICE` if it failed, prefixed with the
+/// sink's tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogSink {
+    tag: String,
+}
+
+impl SyslogSink {
+    /// A sink whose messages are tagged `keyring-store`.
+    pub fn new() -> Self {
+        Self::with_tag("keyring-store")
+    }
+
+    /// A sink whose messages are tagged `tag` (e.g. the application's name).
+    pub fn with_tag(tag: impl Into<String>) -> Self {
+        SyslogSink { tag: tag.into() }
+    }
+}
+
+impl Default for SyslogSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditSink for SyslogSink {
+    fn record(&self, event: &AuditEvent<'_>) {
+        let priority = match event.outcome {
+            Ok(()) => libc::LOG_INFO,
+            Err(_) => libc::LOG_NOTICE,
+        };
+        // descriptions and contexts are quoted with their NULs escaped,
+        // so only the tag could hold one
+        let message = format!("{}: {event}", self.tag).replace('\0', "\\0");
+        let message = CString::new(message).expect("NULs were escaped");
+        unsafe {
+            libc::syslog(
+                libc::LOG_AUTHPRIV | priority,
+                c"%s".as_ptr(),
+                message.as_ptr(),
+            )
+        };
+    }
+}
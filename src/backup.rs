@@ -0,0 +1,184 @@
+//! Encrypted on-disk backup of credentials.
+//!
+//! When a store is configured with a backup directory, every secret written to the
+//! kernel is also sealed with a master key and mirrored to a file in that directory.
+//! The master key itself is a `user` key that lives in the keyring, so the files are
+//! useless without it: after a reboot, entries can only be recovered once the master
+//! key has been re-provisioned (see [Store::provision_backup_key](crate::Store::provision_backup_key)).
+//!
+//! Each file is named by the SHA-256 of the entry's description and contains a
+//! version header, a random nonce, and the ChaCha20-Poly1305 ciphertext and tag.
+//! The header and description are the associated data, so a file (or bytes moved
+//! from one) can't be passed off as another entry's.
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeyRing};
+
+use super::compat::bad_store_format;
+use super::crypto::{self, KEY_LEN, NONCE_LEN, TAG_LEN};
+
+const HEADER: &[u8] = b"LKKS\x02";
+
+/// Errors specific to the backup subsystem.
+///
+/// These are returned as the platform error wrapped inside a keyring-core error.
+#[derive(Debug)]
+pub enum BackupError {
+    /// The master key could not be found in (or read from) the keyring.
+    MasterKeyUnavailable(String, KeyError),
+    /// A backup file failed its integrity check.
+    Corrupt(PathBuf),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::MasterKeyUnavailable(description, err) => write!(
+                f,
+                "backup master key '{description}' is not available: {err}"
+            ),
+            BackupError::Corrupt(path) => {
+                write!(
+                    f,
+                    "backup file {} failed its integrity check",
+                    path.display()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+/// Configuration of the on-disk backup.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    /// Directory holding the sealed files
+    pub dir: PathBuf,
+    /// Description of the `user` key holding the master key material
+    pub master: String,
+}
+
+impl Backup {
+    fn path(&self, description: &str) -> PathBuf {
+        self.dir
+            .join(crypto::to_hex(&crypto::sha256(description.as_bytes())))
+    }
+
+    /// The associated data of the file for `description`: everything the
+    /// tag covers besides the secret.
+    fn aad(description: &str) -> Vec<u8> {
+        [HEADER, description.as_bytes()].concat()
+    }
+
+    /// Derive the sealing key from the master key in the keyring.
+    fn key(&self, keyring: KeyRing) -> Result<[u8; KEY_LEN]> {
+        let unavailable = |err| {
+            Error::NoStorageAccess(
+                BackupError::MasterKeyUnavailable(self.master.clone(), err).into(),
+            )
+        };
+        let key = keyring.search(&self.master).map_err(unavailable)?;
+        let mut master = key.read_to_vec().map_err(unavailable)?;
+        let key = crypto::hmac_sha256(&master, &[b"linux-keyutils-keyring-store backup"]);
+        crypto::wipe(&mut master);
+        Ok(key)
+    }
+
+    /// Check that the master key is available, without touching any files.
+    pub(crate) fn check(&self, keyring: KeyRing) -> Result<()> {
+        crypto::wipe(&mut self.key(keyring)?);
+        Ok(())
+    }
+
+    /// Seal `secret` and write it to the backup file for `description`.
+    pub(crate) fn save(&self, keyring: KeyRing, description: &str, secret: &[u8]) -> Result<()> {
+        let mut key = self.key(keyring)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        crypto::random_bytes(&mut nonce).map_err(|e| Error::PlatformFailure(e.into()))?;
+        let mut ciphertext = Vec::with_capacity(secret.len() + TAG_LEN);
+        ciphertext.extend_from_slice(secret);
+        crypto::seal(&key, &nonce, &Self::aad(description), &mut ciphertext);
+        crypto::wipe(&mut key);
+        let mut sealed = Vec::with_capacity(HEADER.len() + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(HEADER);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+
+        let io_err = |e: std::io::Error| Error::PlatformFailure(e.into());
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)
+            .map_err(io_err)?;
+        let path = self.path(description);
+        // each write gets its own temporary file, so concurrent writers of
+        // the same entry can't interleave their bytes before the rename
+        let mut suffix = [0u8; 8];
+        crypto::random_bytes(&mut suffix).map_err(io_err)?;
+        let tmp = path.with_extension(format!("{}.tmp", crypto::to_hex(&suffix)));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&tmp)
+            .map_err(io_err)?;
+        let written = file
+            .write_all(&sealed)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written.map_err(io_err)
+    }
+
+    /// Read and unseal the backup file for `description`, if there is one.
+    pub(crate) fn load(&self, keyring: KeyRing, description: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(description);
+        let mut sealed = match fs::read(&path) {
+            Ok(sealed) => sealed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::PlatformFailure(e.into())),
+        };
+        let mut key = self.key(keyring)?;
+        let corrupt = || bad_store_format(BackupError::Corrupt(path.clone()).to_string());
+        if sealed.len() < HEADER.len() + NONCE_LEN + TAG_LEN || !sealed.starts_with(HEADER) {
+            crypto::wipe(&mut key);
+            return Err(corrupt());
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&sealed[HEADER.len()..HEADER.len() + NONCE_LEN]);
+        let mut secret = sealed.split_off(HEADER.len() + NONCE_LEN);
+        let opened = crypto::open(&key, &nonce, &Self::aad(description), &mut secret);
+        crypto::wipe(&mut key);
+        if !opened {
+            return Err(corrupt());
+        }
+        Ok(Some(secret))
+    }
+
+    /// The length of the secret in the backup file for `description`, if there is one.
+    pub(crate) fn secret_len(&self, description: &str) -> Result<Option<usize>> {
+        match fs::metadata(self.path(description)) {
+            Ok(meta) => Ok(Some(
+                (meta.len() as usize).saturating_sub(HEADER.len() + NONCE_LEN + TAG_LEN),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::PlatformFailure(e.into())),
+        }
+    }
+
+    /// Remove the backup file for `description`, reporting whether there was one.
+    pub(crate) fn remove(&self, description: &str) -> Result<bool> {
+        match fs::remove_file(self.path(description)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::PlatformFailure(e.into())),
+        }
+    }
+}
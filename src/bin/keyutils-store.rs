@@ -0,0 +1,194 @@
+//! Command-line access to credentials kept by the keyutils credential store.
+//!
+//! Build with `--features cli`. Run `keyutils-store help` for usage.
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+use linux_keyutils::Key;
+use linux_keyutils_keyring_store::keyring_core::api::CredentialStoreApi;
+use linux_keyutils_keyring_store::keyring_core::{Entry, Error};
+use linux_keyutils_keyring_store::{Cred, PamSession, PamStage, SecretBytes, Store};
+
+const USAGE: &str = "\
+usage: keyutils-store [-c key=value]... <command> [arguments]
+
+Store options:
+  -c, --config key=value   store configuration option (prefix, divider, suffix,
+                           keyring, backup_dir, ...); may be repeated
+
+Commands:
+  list                     list the descriptions of the store's credentials
+  get <service> <user>     print a credential's secret
+  set <service> <user>     set a credential's secret (prompted for, or read
+                           from standard input when it's not a terminal)
+  delete <service> <user>  delete a credential
+  describe <service> <user>
+                           show a credential's key description, serial,
+                           owner, and permissions
+  wipe --yes               delete all of the store's credentials
+  pam                      run as a pam_exec(8) session hook: set up the
+                           store's keyrings at open_session, and revoke the
+                           keys of a session-keyring store at close_session
+  help                     show this message
+
+Instead of <service> <user>, credentials can be named by their full key
+description with `-d <description>`.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(message)) => {
+            eprintln!("keyutils-store: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Failure::Store(err)) => {
+            eprintln!("keyutils-store: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+enum Failure {
+    Usage(String),
+    Store(Error),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Store(err)
+    }
+}
+
+fn usage<T>(message: &str) -> Result<T, Failure> {
+    Err(Failure::Usage(message.to_string()))
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    let mut config = Vec::new();
+    let mut args = args.iter().map(String::as_str);
+    let command = loop {
+        match args.next() {
+            Some("-c" | "--config") => match args.next().and_then(|kv| kv.split_once('=')) {
+                Some(option) => config.push(option),
+                None => return usage("--config needs a key=value argument"),
+            },
+            Some(command) => break command,
+            None => return usage("no command given"),
+        }
+    };
+    let rest: Vec<&str> = args.collect();
+    if command == "help" || command == "-h" || command == "--help" {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let store = Store::new_with_configuration(&config.into_iter().collect())?;
+    match command {
+        "list" => {
+            no_more(&rest)?;
+            for entry in store.entries()? {
+                println!("{}", cred(&entry).description);
+            }
+        }
+        "get" => {
+            let entry = entry(&store, &rest)?;
+            match entry.get_password() {
+                Ok(password) => println!("{password}"),
+                Err(Error::BadEncoding(bytes)) => println!("{}", hex(&bytes)),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        "set" => {
+            let entry = entry(&store, &rest)?;
+            let secret = read_secret()?;
+            // a trailing newline is almost always an artifact of echo or a heredoc
+            let secret = secret.strip_suffix(b"\n").unwrap_or(&secret);
+            entry.set_secret(secret)?;
+        }
+        "delete" => entry(&store, &rest)?.delete_credential()?,
+        "describe" => {
+            let entry = entry(&store, &rest)?;
+            // the key is found as reads find it, whatever its type, mirror, or alias
+            let found = entry.get_credential()?;
+            let cred = cred(&found);
+            println!("description: {}", cred.description);
+            println!("keyring: {}", cred.target);
+            let Some(serial) = cred.serial else {
+                return Err(Error::NoEntry.into());
+            };
+            let key = Key::from_id(serial);
+            println!("serial: {}", serial.as_raw_id());
+            if let Ok(metadata) = key.metadata() {
+                println!("uid: {}", metadata.get_uid());
+                println!("gid: {}", metadata.get_gid());
+                println!("permissions: {:08x}", metadata.get_perms().bits());
+            }
+            println!("length: {}", cred.secret_len()?);
+        }
+        "wipe" => {
+            if rest != ["--yes"] {
+                return usage("wipe deletes every credential in the store; confirm with --yes");
+            }
+            for entry in store.entries()? {
+                match entry.delete_credential() {
+                    Ok(()) | Err(Error::NoEntry) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+        "pam" => {
+            no_more(&rest)?;
+            let Some(stage) = PamStage::from_env() else {
+                return usage("pam must be run by pam_exec for open_session or close_session");
+            };
+            PamSession::new(store).run(stage)?;
+        }
+        other => return usage(&format!("unknown command '{other}'")),
+    }
+    Ok(())
+}
+
+fn no_more(rest: &[&str]) -> Result<(), Failure> {
+    match rest {
+        [] => Ok(()),
+        _ => usage(&format!("unexpected arguments: {}", rest.join(" "))),
+    }
+}
+
+/// The entry named by `<service> <user>` or `-d <description>`.
+fn entry(store: &Store, rest: &[&str]) -> Result<Entry, Failure> {
+    match rest {
+        ["-d" | "--description", description] => {
+            // the key's own specifiers are in its envelope, if anywhere
+            let modifiers = HashMap::from([
+                ("description", *description),
+                ("record_specifiers", "false"),
+            ]);
+            Ok(store.build("", "", Some(&modifiers))?)
+        }
+        [service, user] => Ok(store.build(service, user, None)?),
+        _ => usage("expected <service> <user> or -d <description>"),
+    }
+}
+
+fn cred(entry: &Entry) -> &Cred {
+    entry
+        .as_any()
+        .downcast_ref::<Cred>()
+        .expect("entries from this store hold keyutils credentials")
+}
+
+/// The secret to set, which needn't be UTF-8 when read from standard input.
+fn read_secret() -> Result<SecretBytes, Failure> {
+    let io_err = |e: std::io::Error| Failure::Store(Error::PlatformFailure(e.into()));
+    if std::io::stdin().is_terminal() {
+        let secret = rpassword::prompt_password("Secret: ").map_err(io_err)?;
+        return Ok(SecretBytes::new(secret.into_bytes()));
+    }
+    SecretBytes::read_from(std::io::stdin().lock()).map_err(io_err)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,657 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+
+use super::backup::Backup;
+use super::cred::{Keyrings, MAX_DESCRIPTION_LEN, delimiter_ambiguities};
+use super::crypto::DIGEST_LEN;
+use super::keyctl::kernel;
+use super::negative::NegativeCache;
+use super::readcache::ReadCache;
+use super::{
+    AuditSink, Capabilities, Cipher, ControlChars, DescriptionOverflow, HistoryPolicy, Integrity,
+    Keyctl, KeyringUser, MetricsObserver, Mirror, Normalization, Perm, PersistentPolicy, Redaction,
+    Relink, RetryPolicy, Store, Target, sys, user,
+};
+
+/// The description of the backup master key, unless configured otherwise.
+const DEFAULT_BACKUP_KEY: &str = "keyring-store:backup-master";
+
+/// A typed alternative to [Store::new_with_configuration].
+///
+/// Every option defaults to the same value as its configuration
+/// counterpart, so `StoreBuilder::new().build()` is equivalent to
+/// [Store::new].
+///
+/// ```
+/// use std::time::Duration;
+/// use linux_keyutils_keyring_store::{Perm, StoreBuilder, Target};
+///
+/// let store = StoreBuilder::new()
+///     .prefix("my-app:")
+///     .keyring(Target::User)
+///     .timeout(Duration::from_secs(3600))
+///     .permissions(Perm::possessor_all())
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StoreBuilder {
+    delimiters: [String; 3],
+    service_no_divider: bool,
+    user_no_divider: bool,
+    keyring: Target,
+    relink: Relink,
+    backup_dir: Option<PathBuf>,
+    backup_key: String,
+    timeout: Option<Duration>,
+    keepalive_timeout: Option<Duration>,
+    permissions: Option<Perm>,
+    keyring_user: KeyringUser,
+    envelope: bool,
+    integrity: Option<Integrity>,
+    cipher: Option<Arc<dyn Cipher>>,
+    hash_descriptions: bool,
+    record_specifiers: bool,
+    target_modifier: bool,
+    mirrors: Vec<Mirror>,
+    audit: Option<Arc<dyn AuditSink>>,
+    metrics: Option<Arc<dyn MetricsObserver>>,
+    retry: RetryPolicy,
+    history: Option<HistoryPolicy>,
+    max_description_len: usize,
+    description_overflow: DescriptionOverflow,
+    control_chars: ControlChars,
+    case_insensitive: bool,
+    normalization: Option<Normalization>,
+    max_read_len: Option<usize>,
+    verify_owner: bool,
+    read_only: bool,
+    redaction: Redaction,
+    lsm_hints: bool,
+    persistent: PersistentPolicy,
+    lazy_keyrings: bool,
+    negative_cache: Option<Duration>,
+    read_cache: Option<Duration>,
+    keyctl: Arc<dyn Keyctl>,
+}
+
+impl Default for StoreBuilder {
+    fn default() -> Self {
+        StoreBuilder {
+            delimiters: ["keyring:".to_string(), "@".to_string(), "".to_string()],
+            service_no_divider: false,
+            user_no_divider: false,
+            keyring: Target::Session,
+            relink: Relink::Always,
+            backup_dir: None,
+            backup_key: DEFAULT_BACKUP_KEY.to_string(),
+            timeout: None,
+            keepalive_timeout: None,
+            permissions: None,
+            keyring_user: KeyringUser::Current,
+            envelope: false,
+            integrity: None,
+            cipher: None,
+            hash_descriptions: false,
+            record_specifiers: false,
+            target_modifier: true,
+            mirrors: Vec::new(),
+            audit: None,
+            metrics: None,
+            retry: RetryPolicy::default(),
+            history: None,
+            max_description_len: MAX_DESCRIPTION_LEN,
+            description_overflow: DescriptionOverflow::Reject,
+            control_chars: ControlChars::Reject,
+            case_insensitive: false,
+            normalization: None,
+            max_read_len: None,
+            verify_owner: false,
+            read_only: false,
+            redaction: Redaction::Full,
+            lsm_hints: false,
+            persistent: PersistentPolicy::BestEffort,
+            lazy_keyrings: false,
+            negative_cache: None,
+            read_cache: None,
+            keyctl: kernel(),
+        }
+    }
+}
+
+impl StoreBuilder {
+    /// Start from the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The prefix of key descriptions (default `keyring:`).
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.delimiters[0] = prefix.into();
+        self
+    }
+
+    /// The divider between user and service in key descriptions (default `@`).
+    pub fn divider(mut self, divider: impl Into<String>) -> Self {
+        self.delimiters[1] = divider.into();
+        self
+    }
+
+    /// The suffix of key descriptions (default empty).
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.delimiters[2] = suffix.into();
+        self
+    }
+
+    /// Whether services may not contain the divider (default `false`).
+    pub fn service_no_divider(mut self, service_no_divider: bool) -> Self {
+        self.service_no_divider = service_no_divider;
+        self
+    }
+
+    /// Whether users may not contain the divider (default `false`).
+    pub fn user_no_divider(mut self, user_no_divider: bool) -> Self {
+        self.user_no_divider = user_no_divider;
+        self
+    }
+
+    /// The keyring keys are added to (default [Target::Session]).
+    pub fn keyring(mut self, keyring: Target) -> Self {
+        self.keyring = keyring;
+        self
+    }
+
+    /// Whether reads re-link keys into their keyrings (default [Relink::Always]).
+    pub fn relink(mut self, relink: Relink) -> Self {
+        self.relink = relink;
+        self
+    }
+
+    /// Keep an encrypted backup of every credential in `dir`.
+    pub fn backup_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.backup_dir = Some(dir.into());
+        self
+    }
+
+    /// The description of the backup master key (default `keyring-store:backup-master`).
+    pub fn backup_key(mut self, description: impl Into<String>) -> Self {
+        self.backup_key = description.into();
+        self
+    }
+
+    /// Have keys expire this long after they were last written.
+    ///
+    /// The kernel counts in whole seconds, so the timeout is rounded down
+    /// (but to no less than a second).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Have keys expire this long after they were last written or read.
+    ///
+    /// Each successful get re-arms the key's timeout, so a credential that
+    /// keeps being used (a session token, say) lives on, and one that's left
+    /// alone expires this long after its last use. Writes set the same
+    /// timeout, unless the store has a [timeout](StoreBuilder::timeout) of
+    /// its own for them. As with that, the timeout is rounded down to whole
+    /// seconds (but to no less than a second).
+    pub fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// The permissions to give keys when they are written.
+    ///
+    /// Permissions that leave possessors without `setattr` make it
+    /// impossible for the store to apply a timeout on later writes.
+    pub fn permissions(mut self, permissions: Perm) -> Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    /// Whose keyrings the store uses (default [KeyringUser::Current]).
+    ///
+    /// When this resolves to a user other than the effective one, the store
+    /// keeps its keys in that user's persistent keyring, whatever
+    /// [keyring](StoreBuilder::keyring) says, and gives each key to that user
+    /// when it's written.
+    pub fn keyring_user(mut self, user: KeyringUser) -> Self {
+        self.keyring_user = user;
+        self
+    }
+
+    /// Whether key payloads carry an [Envelope](crate::Envelope) of metadata (default `false`).
+    pub fn envelope(mut self, envelope: bool) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    /// Protect key payloads against corruption and tampering (which turns on envelopes).
+    pub fn integrity(mut self, integrity: Integrity) -> Self {
+        self.integrity = Some(integrity);
+        self
+    }
+
+    /// Encrypt key payloads with `cipher` before they're written, and
+    /// decrypt them after they're read.
+    pub fn cipher(mut self, cipher: Arc<dyn Cipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Whether key descriptions are hashes of the service and user, rather
+    /// than the service and user themselves (default `false`).
+    ///
+    /// This keeps service and user names out of `/proc/keys`. The service and
+    /// user are recorded in the key's [Envelope](crate::Envelope) instead, so
+    /// this turns on envelopes (add a [cipher](StoreBuilder::cipher) to keep
+    /// them from anyone who can read the key).
+    pub fn hash_descriptions(mut self, hash_descriptions: bool) -> Self {
+        self.hash_descriptions = hash_descriptions;
+        self
+    }
+
+    /// Whether entries built with a custom `description` keep the service and
+    /// user they were built with as their specifiers (default `false`).
+    ///
+    /// Entries with custom descriptions otherwise have no specifiers of their
+    /// own. With this, they report the service and user they were built with,
+    /// and record them in the key's [Envelope](crate::Envelope) (so this turns
+    /// on envelopes), where entries built with the description alone find
+    /// them. Entries can also opt in or out with the `record_specifiers` modifier.
+    pub fn record_specifiers(mut self, record_specifiers: bool) -> Self {
+        self.record_specifiers = record_specifiers;
+        self
+    }
+
+    /// Whether [build](keyring_core::api::CredentialStoreApi::build) accepts
+    /// a `target` modifier as an alias of `description` (default `true`).
+    ///
+    /// Code written for the `target` parameter of keyring v3 passes it
+    /// through unchanged. Strict deployments can turn the alias off, so
+    /// that only `description` gives a custom description.
+    pub fn target_modifier(mut self, target_modifier: bool) -> Self {
+        self.target_modifier = target_modifier;
+        self
+    }
+
+    /// Extra keyrings to link keys into on every write, and to search
+    /// (in this order) for keys missing from the store's keyring (default none).
+    ///
+    /// Mirrors can't be used with another user's keyring.
+    pub fn mirrors(mut self, mirrors: impl IntoIterator<Item = Mirror>) -> Self {
+        self.mirrors = mirrors.into_iter().collect();
+        self
+    }
+
+    /// Record every read, write, delete, and revocation of a credential with
+    /// `sink` (e.g. a [SyslogSink](crate::SyslogSink)).
+    pub fn audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    /// Report the size, latency, and outcome of every read, write, delete,
+    /// and revocation of a credential, and of every search, to `observer`.
+    pub fn metrics(mut self, observer: Arc<dyn MetricsObserver>) -> Self {
+        self.metrics = Some(observer);
+        self
+    }
+
+    /// How to retry kernel operations that fail for transient reasons (default no retries).
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Keep previous versions of each credential (default none).
+    pub fn history(mut self, history: HistoryPolicy) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// The longest key description the store will use, in bytes (default
+    /// 4095, the kernel's limit).
+    pub fn max_description_len(mut self, max_description_len: usize) -> Self {
+        self.max_description_len = max_description_len;
+        self
+    }
+
+    /// What the store does with descriptions that are too long (default
+    /// [DescriptionOverflow::Reject]).
+    pub fn description_overflow(mut self, overflow: DescriptionOverflow) -> Self {
+        self.description_overflow = overflow;
+        self
+    }
+
+    /// What the store does with services and users that contain control
+    /// characters (default [ControlChars::Reject]).
+    pub fn control_chars(mut self, control_chars: ControlChars) -> Self {
+        self.control_chars = control_chars;
+        self
+    }
+
+    /// Whether services and users are matched case-insensitively, by
+    /// lower-casing them in descriptions (default `false`).
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Which Unicode normalization form services and users are put into
+    /// (default none, using them as given).
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// The longest key payload, in bytes, that reads will allocate for
+    /// (default unlimited). Reads of longer payloads return a
+    /// [TooLong](keyring_core::Error::TooLong) error.
+    pub fn max_read_len(mut self, max_read_len: usize) -> Self {
+        self.max_read_len = Some(max_read_len);
+        self
+    }
+
+    /// Whether reads check who owns the key, and its permissions, before
+    /// returning its secret (default `false`).
+    ///
+    /// The key must belong to the user the entry gives keys to (or else the
+    /// store's keyring user, or else the effective user), and have exactly
+    /// the store's [permissions](StoreBuilder::permissions) (or else the
+    /// kernel's default), so that a key planted under the entry's description
+    /// by another process sharing the keyring isn't taken for the entry's own.
+    pub fn verify_owner(mut self, verify_owner: bool) -> Self {
+        self.verify_owner = verify_owner;
+        self
+    }
+
+    /// Whether the store refuses to change anything (default `false`).
+    ///
+    /// Reads work as usual, except that they neither re-link keys nor re-arm
+    /// keepalive timeouts, and secrets recovered from a backup aren't put
+    /// back into the kernel. Everything that would write, delete, move, or
+    /// link a key, through the store or its credentials, fails with a
+    /// [NoStorageAccess](Error::NoStorageAccess) error wrapping a
+    /// [ReadOnly](crate::ReadOnly), so audit and reporting tools can share
+    /// an application's configuration without being able to change its
+    /// credentials.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// How much of their descriptions credentials show in debug output
+    /// (default [Full](Redaction::Full)).
+    pub fn redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Whether credentials' access errors look for a security module that
+    /// may have caused them (default `false`).
+    ///
+    /// When access to a key or keyring is denied, the
+    /// [ErrorContext](crate::ErrorContext) of the error then says whether
+    /// SELinux or AppArmor is enforcing a policy on the process, and under
+    /// which context (see [LsmHint](crate::LsmHint)). Looking reads a few
+    /// files in `/sys` and `/proc`, on failure paths only.
+    pub fn lsm_hints(mut self, lsm_hints: bool) -> Self {
+        self.lsm_hints = lsm_hints;
+        self
+    }
+
+    /// What to do when the persistent keyring can't be had or linked to
+    /// (default [BestEffort](PersistentPolicy::BestEffort)).
+    ///
+    /// With [Require](PersistentPolicy::Require), [build](Self::build)
+    /// checks that the keyring is there, and a write or re-linking read
+    /// whose persistent link fails fails too. With
+    /// [Disabled](PersistentPolicy::Disabled), keys are never linked into
+    /// it, so they don't survive a logout.
+    pub fn persistent(mut self, persistent: PersistentPolicy) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    /// Whether the store's keyrings are resolved for each entry rather than
+    /// once, when the store is built (default `false`).
+    ///
+    /// Resolving them up front makes a store that can't reach its keyring
+    /// (e.g. in a container with no session keyring) fail to build, rather
+    /// than fail each entry's creation, and saves entries the syscalls.
+    /// Stores created before their keyrings are set up (e.g. by a PAM
+    /// module, or `keyctl session`), and stores meant to
+    /// [diagnose](Store::diagnose) why they can't be reached, should be lazy.
+    pub fn lazy_keyrings(mut self, lazy_keyrings: bool) -> Self {
+        self.lazy_keyrings = lazy_keyrings;
+        self
+    }
+
+    /// Remember credentials found missing for `ttl`, so that probing for
+    /// them again doesn't search the keyrings (default: misses aren't
+    /// remembered).
+    ///
+    /// This is for code that repeatedly looks for an optional credential
+    /// that usually isn't there. Writing any of the store's credentials
+    /// forgets every miss, but a key added by another process (or through
+    /// another store) is taken to be missing until the TTL runs out, so it
+    /// should be short: a second or less. Misses in the thread keyring,
+    /// which each thread has its own of, aren't remembered.
+    pub fn negative_cache(mut self, ttl: Duration) -> Self {
+        self.negative_cache = Some(ttl);
+        self
+    }
+
+    /// Serve secrets that are read from memory for up to `ttl` (default:
+    /// every read reads the key).
+    ///
+    /// This is for applications that read the same credentials very often
+    /// (e.g. a signing key on every request). Secrets are cached by the key
+    /// they were read from: each read still searches for the key, and
+    /// re-links it, checks its owner, and keeps it alive as the store says,
+    /// but reading, checking, and decrypting its payload is saved. Keys in
+    /// the thread keyring aren't cached.
+    ///
+    /// A cached secret is dropped when any of the store's credentials
+    /// writes or deletes a key. With the `watch` feature, on kernels with
+    /// key notifications, the keys that secrets were read from are watched,
+    /// so that a secret is also dropped as soon as its key is updated in
+    /// place by another process. Otherwise, such an update isn't seen until
+    /// the TTL runs out, which [Store::diagnose] reports as a problem. A
+    /// secret is kept in memory (wiped when it's dropped) for as long as
+    /// it's cached.
+    pub fn read_cache(mut self, ttl: Duration) -> Self {
+        self.read_cache = Some(ttl);
+        self
+    }
+
+    /// What credentials search for, add, link, read, and invalidate their
+    /// keys with (default the kernel). Tests can pass a [FakeKeyctl](crate::FakeKeyctl)
+    /// to make these operations fail as they would on a full or hostile keyring.
+    pub fn keyctl(mut self, keyctl: Arc<dyn Keyctl>) -> Self {
+        self.keyctl = keyctl;
+        self
+    }
+
+    /// Create the store.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error for delimiters that make
+    /// descriptions ambiguous (an empty divider, or a prefix or suffix that
+    /// contains the divider), a zero timeout or keepalive timeout, or a description limit that is zero, over the kernel's limit, or (if long
+    /// descriptions are hashed) too short to hold the hash and the suffix.
+    ///
+    /// If the store is to use another user's keyring, that keyring is
+    /// fetched up front, so a store that can't reach it is never built:
+    /// this returns an [Invalid](Error::Invalid) error if the process isn't
+    /// root or the keyring is a process or thread keyring (which can't
+    /// belong to another user), a [NotSupportedByStore](Error::NotSupportedByStore)
+    /// error if the kernel has no persistent keyrings, and a
+    /// [NoStorageAccess](Error::NoStorageAccess) error if the kernel won't
+    /// hand the keyring over.
+    ///
+    /// Likewise, a store that [requires](PersistentPolicy::Require) the
+    /// persistent keyring checks for it: this returns an
+    /// [Invalid](Error::Invalid) error for a process or thread keyring
+    /// (whose keys aren't linked into it), a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error if the kernel
+    /// has no persistent keyrings, and a [NoStorageAccess](Error::NoStorageAccess)
+    /// error if it can't be had.
+    ///
+    /// Unless they are [lazy](Self::lazy_keyrings), the store's keyrings
+    /// are resolved here too: this returns a [NoStorageAccess](Error::NoStorageAccess)
+    /// error if its keyring can't be had.
+    pub fn build(self) -> Result<Arc<Store>> {
+        if let Some((option, ambiguity)) =
+            delimiter_ambiguities(&self.delimiters).into_iter().next()
+        {
+            return Err(Error::Invalid(option.to_string(), ambiguity));
+        }
+        if self.timeout.is_some_and(|t| t.is_zero()) {
+            return Err(Error::Invalid(
+                "timeout".to_string(),
+                "must be at least a second".to_string(),
+            ));
+        }
+        if self.keepalive_timeout.is_some_and(|t| t.is_zero()) {
+            return Err(Error::Invalid(
+                "keepalive_timeout".to_string(),
+                "must be at least a second".to_string(),
+            ));
+        }
+        if !(1..=MAX_DESCRIPTION_LEN).contains(&self.max_description_len) {
+            return Err(Error::Invalid(
+                "max_description_len".to_string(),
+                format!("must be between 1 and {MAX_DESCRIPTION_LEN}"),
+            ));
+        }
+        if self.description_overflow == DescriptionOverflow::Hash
+            && self.max_description_len < 2 * DIGEST_LEN + self.delimiters[2].len()
+        {
+            return Err(Error::Invalid(
+                "max_description_len".to_string(),
+                "must leave room for a hash (64 bytes) and the suffix".to_string(),
+            ));
+        }
+        let keyring_uid = self.keyring_user.other_uid();
+        let mut keyring = self.keyring;
+        if let Some(uid) = keyring_uid {
+            check_other_user(uid, keyring)?;
+            if !self.mirrors.is_empty() {
+                return Err(Error::Invalid(
+                    "mirrors".to_string(),
+                    "cannot be used with another user's keyring".to_string(),
+                ));
+            }
+            keyring = Target::Persistent;
+        } else if self.persistent == PersistentPolicy::Require {
+            check_persistent(keyring)?;
+        }
+        let keyrings = if self.lazy_keyrings {
+            None
+        } else {
+            Some(Keyrings::resolve(keyring, self.persistent)?)
+        };
+        let backup = self.backup_dir.map(|dir| {
+            Arc::new(Backup {
+                dir,
+                master: self.backup_key,
+            })
+        });
+        let mut store = Store {
+            id: String::new(),
+            delimiters: self.delimiters,
+            service_no_divider: self.service_no_divider,
+            user_no_divider: self.user_no_divider,
+            keyring,
+            relink: self.relink,
+            backup,
+            timeout: self.timeout,
+            keepalive_timeout: self.keepalive_timeout,
+            permissions: self.permissions,
+            keyring_uid,
+            envelope: self.envelope
+                || self.integrity.is_some()
+                || self.hash_descriptions
+                || self.record_specifiers,
+            integrity: self.integrity,
+            cipher: self.cipher,
+            hash_descriptions: self.hash_descriptions,
+            record_specifiers: self.record_specifiers,
+            target_modifier: self.target_modifier,
+            mirrors: self.mirrors,
+            audit: self.audit,
+            metrics: self.metrics,
+            retry: self.retry,
+            history: self.history,
+            max_description_len: self.max_description_len,
+            description_overflow: self.description_overflow,
+            control_chars: self.control_chars,
+            case_insensitive: self.case_insensitive,
+            normalization: self.normalization,
+            max_read_len: self.max_read_len,
+            verify_owner: self.verify_owner,
+            read_only: self.read_only,
+            redaction: self.redaction,
+            lsm_hints: self.lsm_hints,
+            persistent: self.persistent,
+            lazy_keyrings: self.lazy_keyrings,
+            keyrings,
+            negative_cache: self.negative_cache,
+            misses: self
+                .negative_cache
+                .map(|ttl| Arc::new(NegativeCache::new(ttl))),
+            read_cache: self.read_cache,
+            secrets: self.read_cache.map(|ttl| Arc::new(ReadCache::new(ttl))),
+            keyctl: self.keyctl,
+        };
+        store.id = store.derive_id();
+        Ok(Arc::new(store))
+    }
+}
+
+/// Check that the persistent keyring a store requires can be had.
+fn check_persistent(keyring: Target) -> Result<()> {
+    if matches!(keyring, Target::Process | Target::Thread) {
+        return Err(Error::Invalid(
+            "persistent".to_string(),
+            format!("cannot be required for the {keyring} keyring, whose keys aren't linked to it"),
+        ));
+    }
+    if !Capabilities::get().persistent_keyrings {
+        return Err(Error::NotSupportedByStore(
+            "the kernel has no persistent keyrings".to_string(),
+        ));
+    }
+    sys::persistent_serial(keyring.identifier())
+        .map_err(|err| Error::NoStorageAccess(err.into()))?;
+    Ok(())
+}
+
+/// Check that the store can keep its keys in user `uid`'s persistent keyring.
+fn check_other_user(uid: u32, keyring: Target) -> Result<()> {
+    let euid = unsafe { libc::geteuid() };
+    if euid != 0 {
+        return Err(Error::Invalid(
+            "keyring_user".to_string(),
+            format!("only root can use the keyrings of user {uid} (running as user {euid})"),
+        ));
+    }
+    if matches!(keyring, Target::Process | Target::Thread) {
+        return Err(Error::Invalid(
+            "keyring".to_string(),
+            format!("the {keyring} keyring can't belong to user {uid}"),
+        ));
+    }
+    if !Capabilities::get().persistent_keyrings {
+        return Err(Error::NotSupportedByStore(format!(
+            "the kernel has no persistent keyrings, so the keyrings of user {uid} can't be reached"
+        )));
+    }
+    user::persistent_serial(uid).map_err(|err| Error::NoStorageAccess(err.into()))?;
+    Ok(())
+}
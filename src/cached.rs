@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use keyring_core::api::{CredentialApi, CredentialPersistence, CredentialStoreApi};
+use keyring_core::{Credential, CredentialStore, Entry, Error, Result};
+
+use super::{SecretBytes, Store};
+
+/// A credential store that caches a slower store's secrets in keyutils.
+///
+/// Reads are served from the keyutils store when it has the secret, and
+/// otherwise fall through to the backing store (e.g. secret-service, or a
+/// file-based store), whose answer is then cached. Writes and deletes go to
+/// the backing store first, and then to the cache. The backing store is
+/// the authority: if the cache can't be updated after a write, the cached
+/// secret is removed, so it never serves a stale one.
+///
+/// Configuring the keyutils store with a `timeout` bounds how long a secret
+/// changed behind the cache's back (e.g. by another program using the
+/// backing store directly) can be served from the cache.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use linux_keyutils_keyring_store::{CachedStore, StoreBuilder};
+///
+/// # let slow_store = linux_keyutils_keyring_store::Store::new().unwrap();
+/// let cache = StoreBuilder::new()
+///     .prefix("my-app-cache:")
+///     .timeout(Duration::from_secs(300))
+///     .build()
+///     .unwrap();
+/// keyring_core::set_default_store(CachedStore::new(cache, slow_store));
+/// ```
+#[derive(Debug)]
+pub struct CachedStore {
+    id: String,
+    cache: Arc<Store>,
+    backing: Arc<CredentialStore>,
+}
+
+impl CachedStore {
+    /// Put the `cache` store in front of the `backing` store.
+    pub fn new(cache: Arc<Store>, backing: Arc<CredentialStore>) -> Arc<Self> {
+        Arc::new(CachedStore {
+            id: Store::new_id(),
+            cache,
+            backing,
+        })
+    }
+
+    /// The keyutils store that does the caching.
+    pub fn cache(&self) -> &Arc<Store> {
+        &self.cache
+    }
+
+    /// The store that is cached.
+    pub fn backing(&self) -> &Arc<CredentialStore> {
+        &self.backing
+    }
+}
+
+impl CredentialStoreApi for CachedStore {
+    /// See the keyring-core API docs.
+    fn vendor(&self) -> String {
+        format!("Linux keyutils cache over {}", self.backing.vendor())
+    }
+
+    /// See the keyring-core API docs.
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// The modifiers are passed to the backing store; the cache entry is
+    /// built from the service and user alone.
+    fn build(
+        &self,
+        service: &str,
+        user: &str,
+        modifiers: Option<&HashMap<&str, &str>>,
+    ) -> Result<Entry> {
+        let cached = CachedCred {
+            cache: self.cache.build(service, user, None)?,
+            backing: self.backing.build(service, user, modifiers)?,
+        };
+        Ok(Entry::new_with_credential(Arc::new(cached)))
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// Searches go to the backing store, and return its entries (which
+    /// bypass the cache).
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Entry>> {
+        self.backing.search(spec)
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// Credentials last as long as they do in the backing store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.backing.persistence()
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A credential of a [CachedStore]: a backing store entry and its cache entry.
+#[derive(Debug)]
+pub struct CachedCred {
+    /// The entry in the keyutils cache
+    pub cache: Entry,
+    /// The entry in the backing store
+    pub backing: Entry,
+}
+
+impl CachedCred {
+    /// Forget the cached secret, if there is one.
+    pub fn evict(&self) -> Result<()> {
+        match self.cache.delete_credential() {
+            Err(Error::NoEntry) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl CredentialApi for CachedCred {
+    /// See the keyring-core API docs.
+    ///
+    /// The secret is written to the backing store, and then cached. If it
+    /// can't be cached, any previously cached secret is evicted instead.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.backing.set_secret(secret)?;
+        if self.cache.set_secret(secret).is_err() {
+            // a stale cached secret is worse than none
+            let _ = self.evict();
+        }
+        Ok(())
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// Any failure to read the cache (not just a missing key) counts as a miss.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        if let Ok(secret) = self.cache.get_secret() {
+            return Ok(secret);
+        }
+        let secret = SecretBytes::new(self.backing.get_secret()?);
+        // failing to cache doesn't fail the read
+        let _ = self.cache.set_secret(&secret);
+        Ok(secret.to_vec())
+    }
+
+    /// See the keyring-core API docs.
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.backing.get_attributes()
+    }
+
+    /// See the keyring-core API docs.
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        self.backing.update_attributes(attributes)
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// The cached secret is evicted even if the backing store has no credential.
+    fn delete_credential(&self) -> Result<()> {
+        let _ = self.evict();
+        self.backing.delete_credential()
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// The credential exists if it exists in the backing store.
+    fn get_credential(&self) -> Result<Option<Arc<Credential>>> {
+        self.backing.get_credential()?;
+        Ok(None)
+    }
+
+    /// See the keyring-core API docs.
+    fn get_specifiers(&self) -> Option<(String, String)> {
+        self.backing.get_specifiers()
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
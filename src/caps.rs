@@ -0,0 +1,114 @@
+use std::sync::OnceLock;
+
+use super::sys;
+
+const KEYCTL_CAPABILITIES: libc::c_int = 31;
+
+const CAPS0_CAPABILITIES: u8 = 0x01;
+const CAPS0_PERSISTENT_KEYRINGS: u8 = 0x02;
+const CAPS0_DIFFIE_HELLMAN: u8 = 0x04;
+const CAPS0_PUBLIC_KEY: u8 = 0x08;
+const CAPS0_BIG_KEY: u8 = 0x10;
+const CAPS0_INVALIDATE: u8 = 0x20;
+const CAPS0_RESTRICT_KEYRING: u8 = 0x40;
+const CAPS0_MOVE: u8 = 0x80;
+const CAPS1_NS_KEYRING_NAME: u8 = 0x01;
+const CAPS1_NS_KEY_TAG: u8 = 0x02;
+const CAPS1_NOTIFICATIONS: u8 = 0x04;
+
+/// The keyutils features supported by the running kernel.
+///
+/// Kernels from 5.3 on report these directly (`KEYCTL_CAPABILITIES`).
+/// On older kernels they are inferred from the kernel version, which
+/// can't account for features compiled out of the kernel, so
+/// [reported](Capabilities::reported) tells which is the case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// Whether the kernel reported its capabilities (rather than them being inferred)
+    pub reported: bool,
+    /// The kernel's major and minor version, if they could be determined
+    pub kernel_version: Option<(u32, u32)>,
+    /// Persistent keyrings (`KEYCTL_GET_PERSISTENT`)
+    pub persistent_keyrings: bool,
+    /// Diffie-Hellman computation (`KEYCTL_DH_COMPUTE`)
+    pub diffie_hellman: bool,
+    /// Public key operations on asymmetric keys (`KEYCTL_PKEY_*`)
+    pub public_key: bool,
+    /// The `big_key` key type
+    pub big_key: bool,
+    /// Key invalidation (`KEYCTL_INVALIDATE`)
+    pub invalidate: bool,
+    /// Keyring restrictions (`KEYCTL_RESTRICT_KEYRING`)
+    pub restrict_keyring: bool,
+    /// Moving keys between keyrings (`KEYCTL_MOVE`)
+    pub move_key: bool,
+    /// Keyring names scoped to user namespaces
+    pub namespaced_keyring_names: bool,
+    /// Keys tagged with network namespaces
+    pub key_tags: bool,
+    /// Key change notifications (`KEYCTL_WATCH_KEY`)
+    pub notifications: bool,
+}
+
+impl Capabilities {
+    /// The capabilities of the running kernel (probed once per process).
+    pub fn get() -> Capabilities {
+        static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+        *CAPABILITIES.get_or_init(Capabilities::probe)
+    }
+
+    fn probe() -> Capabilities {
+        let kernel_version = kernel_version();
+        let mut caps = [0u8; 2];
+        let reported = sys::keyctl(
+            KEYCTL_CAPABILITIES,
+            caps.as_mut_ptr() as libc::c_ulong,
+            caps.len() as libc::c_ulong,
+            0,
+            0,
+        );
+        if reported.is_ok() && caps[0] & CAPS0_CAPABILITIES != 0 {
+            return Capabilities {
+                reported: true,
+                kernel_version,
+                persistent_keyrings: caps[0] & CAPS0_PERSISTENT_KEYRINGS != 0,
+                diffie_hellman: caps[0] & CAPS0_DIFFIE_HELLMAN != 0,
+                public_key: caps[0] & CAPS0_PUBLIC_KEY != 0,
+                big_key: caps[0] & CAPS0_BIG_KEY != 0,
+                invalidate: caps[0] & CAPS0_INVALIDATE != 0,
+                restrict_keyring: caps[0] & CAPS0_RESTRICT_KEYRING != 0,
+                move_key: caps[0] & CAPS0_MOVE != 0,
+                namespaced_keyring_names: caps[1] & CAPS1_NS_KEYRING_NAME != 0,
+                key_tags: caps[1] & CAPS1_NS_KEY_TAG != 0,
+                notifications: caps[1] & CAPS1_NOTIFICATIONS != 0,
+            };
+        }
+        // Kernels without KEYCTL_CAPABILITIES predate move, notifications,
+        // and namespacing, so only the older features need inferring.
+        let at_least = |major, minor| kernel_version.is_some_and(|v| v >= (major, minor));
+        Capabilities {
+            reported: false,
+            kernel_version,
+            persistent_keyrings: at_least(3, 13),
+            diffie_hellman: at_least(4, 7),
+            public_key: at_least(4, 20),
+            big_key: at_least(3, 13),
+            invalidate: at_least(3, 5),
+            restrict_keyring: at_least(4, 12),
+            ..Capabilities::default()
+        }
+    }
+}
+
+/// Parse the major and minor version out of the kernel release (e.g. `6.1.0-13-amd64`).
+fn kernel_version() -> Option<(u32, u32)> {
+    let mut uts = unsafe { std::mem::zeroed::<libc::utsname>() };
+    if unsafe { libc::uname(&mut uts) } < 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) };
+    let mut parts = release.to_str().ok()?.split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
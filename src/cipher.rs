@@ -0,0 +1,57 @@
+//! Client-side encryption of key payloads.
+//!
+//! A store configured with a [Cipher] encrypts every payload before it's handed
+//! to the kernel, and decrypts it after it's read back, so the kernel (and anyone
+//! who can read the key) only ever sees ciphertext. The crate doesn't ship any
+//! ciphers: deployments supply their own, e.g. an AEAD keyed from a TPM, a KMS,
+//! or a passphrase.
+use keyring_core::Result;
+
+/// Encryption and decryption of key payloads.
+///
+/// The whole payload is encrypted, including its [Envelope](crate::Envelope)
+/// and integrity tag (if the store uses them). Each call is given the key's
+/// description, which an AEAD should bind in as associated data, so that
+/// ciphertexts can't be moved between keys.
+///
+/// ```
+/// use linux_keyutils_keyring_store::{Cipher, StoreBuilder};
+/// use std::sync::Arc;
+///
+/// /// Not encryption at all: a real cipher would use an AEAD.
+/// #[derive(Debug)]
+/// struct Rot13;
+///
+/// impl Cipher for Rot13 {
+///     fn encrypt(&self, _description: &str, plaintext: &[u8]) -> keyring_core::Result<Vec<u8>> {
+///         Ok(plaintext.iter().map(|b| b.wrapping_add(13)).collect())
+///     }
+///     fn decrypt(&self, _description: &str, ciphertext: &[u8]) -> keyring_core::Result<Vec<u8>> {
+///         Ok(ciphertext.iter().map(|b| b.wrapping_sub(13)).collect())
+///     }
+/// }
+///
+/// let store = StoreBuilder::new().cipher(Arc::new(Rot13)).build().unwrap();
+/// ```
+pub trait Cipher: std::fmt::Debug + Send + Sync {
+    /// Encrypt the payload of the key with this description.
+    fn encrypt(&self, description: &str, plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypt the payload of the key with this description.
+    ///
+    /// A payload that fails to decrypt (because it was corrupted or tampered
+    /// with, or was never encrypted) should be reported as a
+    /// [BadStoreFormat](keyring_core::Error::BadStoreFormat) error, and a
+    /// decryption key that isn't available as a
+    /// [NoStorageAccess](keyring_core::Error::NoStorageAccess) error.
+    fn decrypt(&self, description: &str, ciphertext: &[u8]) -> Result<Vec<u8>>;
+
+    /// The most bytes encryption adds to a payload (e.g. a nonce and a tag).
+    ///
+    /// This is taken off the largest secret a key can hold, so that
+    /// over-long secrets are reported as [TooLong](keyring_core::Error::TooLong)
+    /// errors up front. The default is none.
+    fn overhead(&self) -> usize {
+        0
+    }
+}
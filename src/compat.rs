@@ -0,0 +1,64 @@
+//! What differs between the keyring-core releases the crate can be built against.
+//!
+//! The crate is built against keyring-core 0.7 by default, or against 0.6
+//! with the `keyring-core-0-6` feature. Their credential APIs are the same,
+//! except that 0.6 has no [BadStoreFormat] error, and its
+//! `parse_attributes` doesn't know the `*` (boolean) and `+` (non-empty)
+//! key prefixes, so code that uses those goes through this module.
+//!
+//! [BadStoreFormat]: https://docs.rs/keyring-core/0.7/keyring_core/error/enum.Error.html#variant.BadStoreFormat
+use keyring_core::Error;
+#[cfg(not(feature = "keyring-core-0-7"))]
+use {keyring_core::Result, std::collections::HashMap};
+
+#[cfg(feature = "keyring-core-0-7")]
+pub(crate) use keyring_core::attributes::parse_attributes;
+
+/// An error for stored data that isn't in the format the store expects.
+///
+/// keyring-core 0.6 has no error for it, so it's reported there as
+/// malformed secret data (with no data attached).
+pub(crate) fn bad_store_format(reason: String) -> Error {
+    #[cfg(feature = "keyring-core-0-7")]
+    return Error::BadStoreFormat(reason);
+    #[cfg(not(feature = "keyring-core-0-7"))]
+    return Error::BadDataFormat(Vec::new(), reason.into());
+}
+
+/// Whether `err` is one made by [bad_store_format].
+#[cfg(test)]
+pub(crate) fn is_bad_store_format(err: &Error) -> bool {
+    #[cfg(feature = "keyring-core-0-7")]
+    return matches!(err, Error::BadStoreFormat(_));
+    #[cfg(not(feature = "keyring-core-0-7"))]
+    return matches!(err, Error::BadDataFormat(data, _) if data.is_empty());
+}
+
+/// Parse an attribute map for the allowed `keys`, as keyring-core 0.7 does:
+/// a key prefixed with `*` takes only `true` or `false`, and one prefixed
+/// with `+` takes only a non-empty value.
+#[cfg(not(feature = "keyring-core-0-7"))]
+pub(crate) fn parse_attributes(
+    keys: &[&str],
+    attrs: Option<&HashMap<&str, &str>>,
+) -> Result<HashMap<String, String>> {
+    let mut result = HashMap::new();
+    for (key, value) in attrs.into_iter().flatten() {
+        let problem = match keys
+            .iter()
+            .find(|k| k.trim_start_matches(['*', '+']) == *key)
+        {
+            None => Some("unknown key"),
+            Some(k) if k.starts_with('*') && *value != "true" && *value != "false" => {
+                Some("must be 'true' or 'false'")
+            }
+            Some(k) if k.starts_with('+') && value.is_empty() => Some("must not be empty"),
+            Some(_) => None,
+        };
+        if let Some(problem) = problem {
+            return Err(Error::Invalid(key.to_string(), problem.to_string()));
+        }
+        result.insert(key.to_string(), value.to_string());
+    }
+    Ok(result)
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::compat::parse_attributes;
+use keyring_core::{Error, Result};
+
+use super::{
+    ControlChars, DescriptionOverflow, HistoryPolicy, Integrity, KeyringUser, Mirror,
+    Normalization, Perm, PersistentPolicy, Redaction, Relink, RetryPolicy, StoreBuilder, Target,
+};
+
+/// The prefix of the environment variables read by [StoreConfig::from_env].
+const ENV_PREFIX: &str = "KEYUTILS_STORE_";
+
+/// Store settings as a plain struct, for use with [Store::from_config](crate::Store::from_config).
+///
+/// Each field corresponds to the [new_with_configuration](crate::Store::new_with_configuration)
+/// option of the same name, and a field left as `None` gets that option's default.
+/// This makes it easy to fill in from an application's own configuration types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoreConfig {
+    /// The prefix of key descriptions
+    pub prefix: Option<String>,
+    /// The divider between user and service in key descriptions
+    pub divider: Option<String>,
+    /// The suffix of key descriptions
+    pub suffix: Option<String>,
+    /// Whether services may not contain the divider
+    pub service_no_divider: Option<bool>,
+    /// Whether users may not contain the divider
+    pub user_no_divider: Option<bool>,
+    /// The keyring keys are added to
+    pub keyring: Option<Target>,
+    /// Whether reads re-link keys into their keyrings
+    pub relink: Option<Relink>,
+    /// The directory for encrypted backups of every credential
+    pub backup_dir: Option<PathBuf>,
+    /// The description of the backup master key
+    pub backup_key: Option<String>,
+    /// How long after each write keys expire
+    pub timeout: Option<Duration>,
+    /// How long after each write or read keys expire
+    pub keepalive_timeout: Option<Duration>,
+    /// The permissions to give keys when they are written
+    pub permissions: Option<Perm>,
+    /// Whose keyrings the store uses
+    pub keyring_user: Option<KeyringUser>,
+    /// Whether key payloads carry an envelope of metadata
+    pub envelope: Option<bool>,
+    /// How key payloads are protected against tampering
+    pub integrity: Option<Integrity>,
+    /// Whether key descriptions are hashes of the service and user
+    pub hash_descriptions: Option<bool>,
+    /// Whether entries with custom descriptions record their service and user
+    pub record_specifiers: Option<bool>,
+    /// Whether the `target` entry modifier is accepted as an alias of `description`
+    pub target_modifier: Option<bool>,
+    /// The extra keyrings keys are mirrored into
+    pub mirrors: Option<Vec<Mirror>>,
+    /// How kernel operations that fail for transient reasons are retried
+    pub retry: Option<RetryPolicy>,
+    /// How many previous versions of each credential are kept
+    pub history: Option<HistoryPolicy>,
+    /// The longest key description the store will use
+    pub max_description_len: Option<usize>,
+    /// What the store does with descriptions that are too long
+    pub description_overflow: Option<DescriptionOverflow>,
+    /// What the store does with services and users that contain control characters
+    pub control_chars: Option<ControlChars>,
+    /// Whether services and users are matched case-insensitively
+    pub case_insensitive: Option<bool>,
+    /// Which Unicode normalization form services and users are put into
+    pub normalization: Option<Normalization>,
+    /// The longest key payload reads will allocate for, in bytes
+    pub max_read_len: Option<usize>,
+    /// Whether reads check the key's owner and permissions first
+    pub verify_owner: Option<bool>,
+    /// Whether the store refuses to change anything
+    pub read_only: Option<bool>,
+    /// How much of their descriptions credentials show in debug output
+    pub redaction: Option<Redaction>,
+    /// Whether access errors say which security module may have caused them
+    pub lsm_hints: Option<bool>,
+    /// What is done when the persistent keyring can't be had or linked to
+    pub persistent: Option<PersistentPolicy>,
+    /// Whether the store's keyrings are resolved per entry rather than when it's built
+    pub lazy_keyrings: Option<bool>,
+    /// How long credentials found missing are remembered as missing
+    pub negative_cache: Option<Duration>,
+    /// How long secrets read are served from memory
+    pub read_cache: Option<Duration>,
+}
+
+impl StoreConfig {
+    /// Parse the string options accepted by
+    /// [new_with_configuration](crate::Store::new_with_configuration).
+    pub fn from_options(config: &HashMap<&str, &str>) -> Result<Self> {
+        let config = parse_attributes(
+            &[
+                "prefix",
+                "divider",
+                "suffix",
+                "*service_no_divider",
+                "*user_no_divider",
+                "keyring",
+                "relink",
+                "backup_dir",
+                "backup_key",
+                "timeout",
+                "keepalive_timeout",
+                "permissions",
+                "keyring_user",
+                "*envelope",
+                "integrity",
+                "integrity_key",
+                "*hash_descriptions",
+                "*record_specifiers",
+                "*target_modifier",
+                "mirrors",
+                "retry",
+                "history",
+                "max_description_len",
+                "description_overflow",
+                "control_chars",
+                "*case_insensitive",
+                "normalization",
+                "max_read_len",
+                "*verify_owner",
+                "*read_only",
+                "redaction",
+                "*lsm_hints",
+                "persistent",
+                "*lazy_keyrings",
+                "negative_cache",
+                "read_cache",
+            ],
+            Some(config),
+        )?;
+        let timeout = match config.get("timeout") {
+            Some(seconds) => Some(Duration::from_secs(seconds.parse().map_err(|_| {
+                Error::Invalid(
+                    "timeout".to_string(),
+                    "must be a number of seconds".to_string(),
+                )
+            })?)),
+            None => None,
+        };
+        let keepalive_timeout = match config.get("keepalive_timeout") {
+            Some(seconds) => Some(Duration::from_secs(seconds.parse().map_err(|_| {
+                Error::Invalid(
+                    "keepalive_timeout".to_string(),
+                    "must be a number of seconds".to_string(),
+                )
+            })?)),
+            None => None,
+        };
+        let negative_cache = match config.get("negative_cache") {
+            Some(millis) => Some(Duration::from_millis(millis.parse().map_err(|_| {
+                Error::Invalid(
+                    "negative_cache".to_string(),
+                    "must be a number of milliseconds".to_string(),
+                )
+            })?)),
+            None => None,
+        };
+        let read_cache = match config.get("read_cache") {
+            Some(millis) => Some(Duration::from_millis(millis.parse().map_err(|_| {
+                Error::Invalid(
+                    "read_cache".to_string(),
+                    "must be a number of milliseconds".to_string(),
+                )
+            })?)),
+            None => None,
+        };
+        let max_description_len = match config.get("max_description_len") {
+            Some(len) => Some(len.parse().map_err(|_| {
+                Error::Invalid(
+                    "max_description_len".to_string(),
+                    "must be a number of bytes".to_string(),
+                )
+            })?),
+            None => None,
+        };
+        let max_read_len = match config.get("max_read_len") {
+            Some(len) => Some(len.parse().map_err(|_| {
+                Error::Invalid(
+                    "max_read_len".to_string(),
+                    "must be a number of bytes".to_string(),
+                )
+            })?),
+            None => None,
+        };
+        let integrity = match (config.get("integrity"), config.get("integrity_key")) {
+            (Some(integrity), None) => Some(integrity.parse()?),
+            (Some(integrity), Some(key)) if integrity == "hmac" => {
+                Some(Integrity::Hmac(key.to_string()))
+            }
+            (_, Some(_)) => {
+                return Err(Error::Invalid(
+                    "integrity_key".to_string(),
+                    "can only be given with integrity=hmac".to_string(),
+                ));
+            }
+            (None, None) => None,
+        };
+        Ok(StoreConfig {
+            prefix: config.get("prefix").cloned(),
+            divider: config.get("divider").cloned(),
+            suffix: config.get("suffix").cloned(),
+            service_no_divider: config.get("service_no_divider").map(|s| s == "true"),
+            user_no_divider: config.get("user_no_divider").map(|s| s == "true"),
+            keyring: config.get("keyring").map(|s| s.parse()).transpose()?,
+            relink: config.get("relink").map(|s| s.parse()).transpose()?,
+            backup_dir: config.get("backup_dir").map(PathBuf::from),
+            backup_key: config.get("backup_key").cloned(),
+            timeout,
+            keepalive_timeout,
+            permissions: config.get("permissions").map(|s| s.parse()).transpose()?,
+            keyring_user: config.get("keyring_user").map(|s| s.parse()).transpose()?,
+            envelope: config.get("envelope").map(|s| s == "true"),
+            integrity,
+            hash_descriptions: config.get("hash_descriptions").map(|s| s == "true"),
+            record_specifiers: config.get("record_specifiers").map(|s| s == "true"),
+            target_modifier: config.get("target_modifier").map(|s| s == "true"),
+            mirrors: config
+                .get("mirrors")
+                .map(|s| Mirror::parse_list(s))
+                .transpose()?,
+            retry: config.get("retry").map(|s| s.parse()).transpose()?,
+            history: config.get("history").map(|s| s.parse()).transpose()?,
+            max_description_len,
+            description_overflow: config
+                .get("description_overflow")
+                .map(|s| s.parse())
+                .transpose()?,
+            control_chars: config.get("control_chars").map(|s| s.parse()).transpose()?,
+            case_insensitive: config.get("case_insensitive").map(|s| s == "true"),
+            normalization: config.get("normalization").map(|s| s.parse()).transpose()?,
+            max_read_len,
+            verify_owner: config.get("verify_owner").map(|s| s == "true"),
+            read_only: config.get("read_only").map(|s| s == "true"),
+            redaction: config.get("redaction").map(|s| s.parse()).transpose()?,
+            lsm_hints: config.get("lsm_hints").map(|s| s == "true"),
+            persistent: config.get("persistent").map(|s| s.parse()).transpose()?,
+            lazy_keyrings: config.get("lazy_keyrings").map(|s| s == "true"),
+            negative_cache,
+            read_cache,
+        })
+    }
+
+    /// Read the options from `KEYUTILS_STORE_*` environment variables.
+    ///
+    /// Each option is read from the variable named after it in upper case,
+    /// e.g. `KEYUTILS_STORE_PREFIX`, `KEYUTILS_STORE_KEYRING`, or
+    /// `KEYUTILS_STORE_TIMEOUT`, and takes the same values as in
+    /// [new_with_configuration](crate::Store::new_with_configuration).
+    /// Any other variable with that prefix is an [Invalid](Error::Invalid)
+    /// error, so typos don't go unnoticed.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    pub(crate) fn from_vars(vars: impl Iterator<Item = (String, String)>) -> Result<Self> {
+        let options: Vec<(String, String)> = vars
+            .filter_map(|(name, value)| {
+                let option = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+                Some((option, value))
+            })
+            .collect();
+        let options = options
+            .iter()
+            .map(|(option, value)| (option.as_str(), value.as_str()))
+            .collect();
+        Self::from_options(&options)
+    }
+}
+
+impl From<StoreConfig> for StoreBuilder {
+    fn from(config: StoreConfig) -> Self {
+        let mut builder = StoreBuilder::new();
+        if let Some(prefix) = config.prefix {
+            builder = builder.prefix(prefix);
+        }
+        if let Some(divider) = config.divider {
+            builder = builder.divider(divider);
+        }
+        if let Some(suffix) = config.suffix {
+            builder = builder.suffix(suffix);
+        }
+        if let Some(service_no_divider) = config.service_no_divider {
+            builder = builder.service_no_divider(service_no_divider);
+        }
+        if let Some(user_no_divider) = config.user_no_divider {
+            builder = builder.user_no_divider(user_no_divider);
+        }
+        if let Some(keyring) = config.keyring {
+            builder = builder.keyring(keyring);
+        }
+        if let Some(relink) = config.relink {
+            builder = builder.relink(relink);
+        }
+        if let Some(dir) = config.backup_dir {
+            builder = builder.backup_dir(dir);
+        }
+        if let Some(description) = config.backup_key {
+            builder = builder.backup_key(description);
+        }
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = config.keepalive_timeout {
+            builder = builder.keepalive_timeout(timeout);
+        }
+        if let Some(permissions) = config.permissions {
+            builder = builder.permissions(permissions);
+        }
+        if let Some(user) = config.keyring_user {
+            builder = builder.keyring_user(user);
+        }
+        if let Some(envelope) = config.envelope {
+            builder = builder.envelope(envelope);
+        }
+        if let Some(integrity) = config.integrity {
+            builder = builder.integrity(integrity);
+        }
+        if let Some(hash_descriptions) = config.hash_descriptions {
+            builder = builder.hash_descriptions(hash_descriptions);
+        }
+        if let Some(record_specifiers) = config.record_specifiers {
+            builder = builder.record_specifiers(record_specifiers);
+        }
+        if let Some(target_modifier) = config.target_modifier {
+            builder = builder.target_modifier(target_modifier);
+        }
+        if let Some(mirrors) = config.mirrors {
+            builder = builder.mirrors(mirrors);
+        }
+        if let Some(retry) = config.retry {
+            builder = builder.retry(retry);
+        }
+        if let Some(history) = config.history {
+            builder = builder.history(history);
+        }
+        if let Some(len) = config.max_description_len {
+            builder = builder.max_description_len(len);
+        }
+        if let Some(overflow) = config.description_overflow {
+            builder = builder.description_overflow(overflow);
+        }
+        if let Some(control_chars) = config.control_chars {
+            builder = builder.control_chars(control_chars);
+        }
+        if let Some(case_insensitive) = config.case_insensitive {
+            builder = builder.case_insensitive(case_insensitive);
+        }
+        if let Some(normalization) = config.normalization {
+            builder = builder.normalization(normalization);
+        }
+        if let Some(len) = config.max_read_len {
+            builder = builder.max_read_len(len);
+        }
+        if let Some(verify_owner) = config.verify_owner {
+            builder = builder.verify_owner(verify_owner);
+        }
+        if let Some(read_only) = config.read_only {
+            builder = builder.read_only(read_only);
+        }
+        if let Some(redaction) = config.redaction {
+            builder = builder.redaction(redaction);
+        }
+        if let Some(lsm_hints) = config.lsm_hints {
+            builder = builder.lsm_hints(lsm_hints);
+        }
+        if let Some(persistent) = config.persistent {
+            builder = builder.persistent(persistent);
+        }
+        if let Some(lazy_keyrings) = config.lazy_keyrings {
+            builder = builder.lazy_keyrings(lazy_keyrings);
+        }
+        if let Some(ttl) = config.negative_cache {
+            builder = builder.negative_cache(ttl);
+        }
+        if let Some(ttl) = config.read_cache {
+            builder = builder.read_cache(ttl);
+        }
+        builder
+    }
+}
@@ -1,9 +1,471 @@
-use super::error::KeyStoreError;
-use keyring_core::Error::NoStorageAccess;
+use super::audit::{AuditEvent, AuditOp, AuditSink};
+use super::backup::Backup;
+use super::crypto::{DIGEST_LEN, sha256, to_hex, wipe};
+use super::error::{ErrorContext, FailureKind, KeyStoreError};
+use super::history::{version_description, version_number};
+use super::keyctl::{FailedCall, Keyctl, KeyctlOp, Traced, kernel, read_payload, take_failed_call};
+use super::metrics::{ErrorCategory, MetricsObserver, Observation};
+use super::negative::NegativeCache;
+use super::procfs::{self, Expiry};
+use super::readcache::ReadCache;
+use super::retry::{is_stale, is_transient};
+use super::store::type_name;
+use super::{
+    Cipher, Envelope, HistoryPolicy, Integrity, LsmHint, Perm, RetryPolicy, SecretBytes,
+    SecretReader, SecretSpec, Target, sys, user,
+};
 use keyring_core::api::CredentialApi;
 use keyring_core::{Credential, Error};
-use linux_keyutils::{KeyRing, KeyRingIdentifier};
+use linux_keyutils::{Key, KeyError, KeyRing, KeySerialId, KeyType};
+use std::io::Read;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The largest payload of a `user` or `logon` key.
+pub(crate) const USER_KEY_MAX_LEN: usize = 32767;
+/// The largest payload of a `big_key` key.
+const BIG_KEY_MAX_LEN: usize = 1024 * 1024;
+
+/// The longest description the kernel accepts.
+pub(crate) const MAX_DESCRIPTION_LEN: usize = 4095;
+
+/// How many times [Cred::swap_secret] retries when it loses a race.
+const SWAP_ATTEMPTS: usize = 5;
+/// How many times a read searches for a key that vanishes before it can be read.
+const READ_ATTEMPTS: usize = 5;
+/// The longest [Cred::delete_credential_sync] sleeps between searches.
+const DELETE_SYNC_MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+/// How [set_secret](CredentialApi::set_secret) treats existing credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    /// Create the credential or overwrite it if it exists (the default).
+    #[default]
+    Upsert,
+    /// Only create the credential: fail if it already exists.
+    CreateNew,
+    /// Only overwrite the credential: fail if it doesn't exist.
+    UpdateOnly,
+}
+
+/// When reads re-link a key into its keyrings.
+///
+/// Re-linking on read makes sure a key that was dropped from one of its
+/// keyrings (e.g. by a logout, or an expiring persistent keyring) is put
+/// back, at the cost of extra syscalls on every read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Relink {
+    /// Re-link on every read, failing the read if a link fails (the default).
+    #[default]
+    Always,
+    /// Re-link on every read, ignoring link failures.
+    BestEffort,
+    /// Never re-link on read.
+    Never,
+    /// Re-link on every `n`th read of a given entry, ignoring link failures.
+    Every(u32),
+}
+
+impl FromStr for Relink {
+    type Err = Error;
+
+    fn from_str(s: &str) -> keyring_core::error::Result<Self> {
+        match s {
+            "always" => Ok(Relink::Always),
+            "best_effort" => Ok(Relink::BestEffort),
+            "never" => Ok(Relink::Never),
+            _ => match s.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(Relink::Every(n)),
+                _ => Err(Error::Invalid(
+                    "relink".to_string(),
+                    "must be always, best_effort, never, or a positive read count".to_string(),
+                )),
+            },
+        }
+    }
+}
+
+/// What a store does when the persistent keyring can't be had or linked to.
+///
+/// Keys in the session, user, and user-session keyrings are also linked
+/// into the user's persistent keyring, so that they survive a logout. On
+/// systems without one (containers often have none), that link can't be
+/// made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PersistentPolicy {
+    /// Fail to build a store without the persistent keyring, and fail
+    /// writes and reads whose persistent links fail.
+    Require,
+    /// Use the persistent keyring when it can be had, and ignore any
+    /// failure to link to it (the default).
+    #[default]
+    BestEffort,
+    /// Never use the persistent keyring: keys live only in their own keyring.
+    Disabled,
+}
+
+impl PersistentPolicy {
+    /// Apply the policy to the outcome of linking a key into the persistent keyring.
+    pub(crate) fn check(self, result: Result<(), KeyError>) -> Result<(), KeyError> {
+        match result {
+            Err(_) if self == PersistentPolicy::BestEffort => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl FromStr for PersistentPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> keyring_core::error::Result<Self> {
+        match s {
+            "require" => Ok(PersistentPolicy::Require),
+            "best_effort" => Ok(PersistentPolicy::BestEffort),
+            "disabled" => Ok(PersistentPolicy::Disabled),
+            _ => Err(Error::Invalid(
+                "persistent".to_string(),
+                "must be require, best_effort, or disabled".to_string(),
+            )),
+        }
+    }
+}
+
+/// Why a credential that verifies its key's owner refused to read the key.
+///
+/// This is returned as the platform error wrapped inside a
+/// [NoStorageAccess](Error::NoStorageAccess) error (see
+/// [StoreBuilder::verify_owner](crate::StoreBuilder::verify_owner)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerMismatch {
+    /// The key's description
+    pub description: String,
+    /// The key's owner
+    pub uid: u32,
+    /// The user who should own the key
+    pub expected_uid: u32,
+    /// The key's permission mask
+    pub permissions: u32,
+    /// The permission mask the key should have
+    pub expected_permissions: u32,
+}
+
+impl std::fmt::Display for OwnerMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key '{}' belongs to user {} with permissions {:08x}, but should belong to user {} with permissions {:08x}",
+            self.description,
+            self.uid,
+            self.permissions,
+            self.expected_uid,
+            self.expected_permissions
+        )
+    }
+}
+
+impl std::error::Error for OwnerMismatch {}
+
+/// Why a credential of a read-only store refused to change its key.
+///
+/// This is returned as the platform error wrapped inside a
+/// [NoStorageAccess](Error::NoStorageAccess) error (see
+/// [StoreBuilder::read_only](crate::StoreBuilder::read_only)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnly {
+    /// What was refused, e.g. `set` or `delete`
+    pub operation: String,
+    /// The description of the key that would have changed, if it was one key
+    pub description: Option<String>,
+}
+
+impl std::fmt::Display for ReadOnly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.description {
+            Some(description) => write!(
+                f,
+                "can't {} key '{}': the store is read-only",
+                self.operation, description
+            ),
+            None => write!(f, "can't {}: the store is read-only", self.operation),
+        }
+    }
+}
+
+impl std::error::Error for ReadOnly {}
+
+/// What a store does with a description longer than its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DescriptionOverflow {
+    /// Refuse to build the entry (the default).
+    #[default]
+    Reject,
+    /// Shorten the description: as much of its start as fits is kept, and
+    /// the rest is replaced by a hash of the whole description (followed by
+    /// the store's suffix, if the description ends with it).
+    Hash,
+}
+
+impl FromStr for DescriptionOverflow {
+    type Err = Error;
+
+    fn from_str(s: &str) -> keyring_core::error::Result<Self> {
+        match s {
+            "reject" => Ok(DescriptionOverflow::Reject),
+            "hash" => Ok(DescriptionOverflow::Hash),
+            _ => Err(Error::Invalid(
+                "description_overflow".to_string(),
+                "must be reject or hash".to_string(),
+            )),
+        }
+    }
+}
+
+/// How much of a credential's description its debug output shows.
+///
+/// Under some naming schemes a description is sensitive in itself (e.g.
+/// when the user is an email address), and debug output tends to end up in
+/// logs. The policy applies to the description and the specifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Redaction {
+    /// Show the description as it is (the default).
+    #[default]
+    Full,
+    /// Show a hash of the description, so that log lines about the same
+    /// credential can still be matched up.
+    Hashed,
+    /// Show nothing of the description.
+    Suppressed,
+}
+
+impl Redaction {
+    /// Redact `text` as the policy says.
+    pub(crate) fn apply(self, text: &str) -> String {
+        match self {
+            Redaction::Full => text.to_string(),
+            Redaction::Hashed => format!("<sha256:{}>", &to_hex(&sha256(text.as_bytes()))[..16]),
+            Redaction::Suppressed => "<redacted>".to_string(),
+        }
+    }
+}
+
+impl FromStr for Redaction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> keyring_core::error::Result<Self> {
+        match s {
+            "full" => Ok(Redaction::Full),
+            "hashed" => Ok(Redaction::Hashed),
+            "suppressed" => Ok(Redaction::Suppressed),
+            _ => Err(Error::Invalid(
+                "redaction".to_string(),
+                "must be full, hashed, or suppressed".to_string(),
+            )),
+        }
+    }
+}
+
+/// Fit a description within `limit` bytes, as `overflow` says.
+///
+/// An overlong description is an [Invalid](Error::Invalid) error that
+/// gives the limit, unless it's to be hashed.
+pub(crate) fn fit_description(
+    description: String,
+    limit: usize,
+    overflow: DescriptionOverflow,
+    suffix: &str,
+) -> keyring_core::error::Result<String> {
+    if description.len() <= limit {
+        return Ok(description);
+    }
+    let suffix = if description.ends_with(suffix) {
+        suffix
+    } else {
+        ""
+    };
+    let keep = limit.checked_sub(2 * DIGEST_LEN + suffix.len());
+    let (DescriptionOverflow::Hash, Some(mut keep)) = (overflow, keep) else {
+        return Err(Error::Invalid(
+            "description".to_string(),
+            format!(
+                "is {} bytes long, but the limit is {limit} bytes",
+                description.len()
+            ),
+        ));
+    };
+    while !description.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    let hash = to_hex(&sha256(description.as_bytes()));
+    Ok(format!("{}{hash}{suffix}", &description[..keep]))
+}
+
+/// The description (and specifiers, if any) of the key for an entry.
+///
+/// An explicit target string is the description. Otherwise it's built from
+/// the delimiters, the user, and the service.
+pub(crate) fn describe<'a>(
+    target: Option<&str>,
+    delimiters: &[String; 3],
+    service_no_dividers: bool,
+    service: &'a str,
+    user: &'a str,
+) -> keyring_core::error::Result<(String, Option<(&'a str, &'a str)>)> {
+    // Construct the description with a URI-style description
+    let (description, specifiers) = match target {
+        Some(value) => (value.to_string(), None),
+        None => {
+            if service_no_dividers {
+                check_no_divider("service", service, &delimiters[1])?;
+            }
+            (
+                format!(
+                    "{}{user}{}{service}{}",
+                    delimiters[0], delimiters[1], delimiters[2]
+                ),
+                Some((service, user)),
+            )
+        }
+    };
+    if description.is_empty() {
+        return Err(Error::Invalid(
+            "description".to_string(),
+            "cannot be empty".to_string(),
+        ));
+    }
+    Ok((description, specifiers))
+}
+
+/// Check that a service or user doesn't contain the divider.
+pub(crate) fn check_no_divider(
+    name: &str,
+    value: &str,
+    divider: &str,
+) -> keyring_core::error::Result<()> {
+    if value.contains(divider) {
+        return Err(Error::Invalid(
+            name.to_string(),
+            "cannot contain delimiter".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The ways a store's delimiters make descriptions ambiguous, with the
+/// option to blame for each.
+///
+/// Descriptions can only be split back into user and service if the divider
+/// is non-empty and appears in neither the prefix nor the suffix.
+pub(crate) fn delimiter_ambiguities(delimiters: &[String; 3]) -> Vec<(&'static str, String)> {
+    let [prefix, divider, suffix] = delimiters;
+    if divider.is_empty() {
+        return vec![(
+            "divider",
+            "the divider is empty, so descriptions are ambiguous".to_string(),
+        )];
+    }
+    [("prefix", prefix), ("suffix", suffix)]
+        .into_iter()
+        .filter(|(_, delimiter)| delimiter.contains(divider.as_str()))
+        .map(|(name, delimiter)| {
+            (
+                name,
+                format!(
+                    "the {name} '{delimiter}' contains the divider '{divider}', so descriptions are ambiguous"
+                ),
+            )
+        })
+        .collect()
+}
+
+/// The persistent keyring (and its serial) that keys in `target` are also
+/// linked into, if they are and it can be had.
+fn persistent_keyring(target: Target) -> (Option<KeyRing>, Option<KeySerialId>) {
+    if !target.links_persistent() {
+        return (None, None);
+    }
+    match KeyRing::get_persistent(target.identifier()) {
+        Ok(keyring) => match sys::persistent_serial(target.identifier()) {
+            Ok(serial) => (Some(keyring), Some(serial)),
+            Err(_) => (None, None),
+        },
+        Err(_) => (None, None),
+    }
+}
+
+/// A target's keyring, and the persistent keyring its keys are also
+/// linked into, resolved once and shared by all the credentials that use them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Keyrings {
+    keyring: KeyRing,
+    keyring_id: KeySerialId,
+    persistent: Option<KeyRing>,
+    persistent_id: Option<KeySerialId>,
+}
+
+impl Keyrings {
+    /// Resolve the keyrings of `target`, leaving out a persistent keyring
+    /// that `policy` disables or (unless it requires one) that can't be had.
+    pub(crate) fn resolve(
+        target: Target,
+        policy: PersistentPolicy,
+    ) -> keyring_core::error::Result<Arc<Self>> {
+        let keyring = target.keyring()?;
+        let keyring_id = target
+            .keyring_id()
+            .map_err(|e| Error::NoStorageAccess(e.into()))?;
+        // Link the persistent keyring to the target, if it should be used
+        let (persistent, persistent_id) = match policy {
+            PersistentPolicy::Disabled => (None, None),
+            _ => persistent_keyring(target),
+        };
+        if policy == PersistentPolicy::Require
+            && target.links_persistent()
+            && persistent_id.is_none()
+        {
+            return Err(persistent_unavailable());
+        }
+        Ok(Arc::new(Keyrings {
+            keyring,
+            keyring_id,
+            persistent,
+            persistent_id,
+        }))
+    }
+
+    /// Link `key` into the persistent keyring, if there is one, failing only
+    /// if `policy` says a failed link should.
+    fn link_persistent(
+        &self,
+        keyctl: &dyn Keyctl,
+        key: KeySerialId,
+        policy: PersistentPolicy,
+    ) -> Result<(), KeyError> {
+        match self.persistent_id {
+            Some(keyring) => policy.check(keyctl.link(key, keyring)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The error for a persistent keyring that's required but can't be had.
+fn persistent_unavailable() -> Error {
+    Error::NoStorageAccess("the persistent keyring is required but isn't available".into())
+}
+
+/// How many times a credential has been read, for [Relink::Every].
+///
+/// A clone starts from the original's count rather than sharing it, so
+/// that keeping count costs a credential no allocation.
+#[derive(Debug, Default)]
+struct ReadCount(AtomicU32);
+
+impl Clone for ReadCount {
+    fn clone(&self) -> Self {
+        ReadCount(AtomicU32::new(self.0.load(Ordering::Relaxed)))
+    }
+}
 
 /// Representation of a keyutils credential.
 ///
@@ -16,16 +478,140 @@ use std::sync::Arc;
 /// is that any call to get_password before set_password is done
 /// will result in a proper error as the key does not exist until
 /// set_password is called.
-#[derive(Debug, Clone)]
+///
+/// Its debug output shows the description and specifiers as its
+/// [Redaction] policy says; [describe_unredacted](Cred::describe_unredacted)
+/// shows them regardless.
+#[derive(Clone)]
 pub struct Cred {
-    /// Host session keyring
-    pub session: KeyRing,
+    /// Which keyring the key lives in
+    pub target: Target,
+    /// Host keyring for the target (the session keyring by default)
+    pub keyring: KeyRing,
     /// Host persistent keyring
     pub persistent: Option<KeyRing>,
     /// Description of the key entry
-    pub description: String,
+    pub description: Arc<str>,
     /// Specifiers for the entry, if any
-    pub specifiers: Option<(String, String)>,
+    pub specifiers: Option<(Arc<str>, Arc<str>)>,
+    /// On-disk backup configuration, if any
+    pub backup: Option<Arc<Backup>>,
+    /// Whether writes may create and/or overwrite the credential
+    pub write_mode: WriteMode,
+    /// When reads re-link the key into its keyrings
+    pub relink: Relink,
+    /// Callout info for `request_key(2)`, if missing keys should be requested
+    pub callout: Option<String>,
+    /// The key this credential is bound to, bypassing the description search
+    pub serial: Option<KeySerialId>,
+    /// How long after each write the key expires, if it should
+    pub timeout: Option<Duration>,
+    /// How long after each write or successful read the key expires, if it should
+    pub keepalive_timeout: Option<Duration>,
+    /// Permissions to give the key on each write, if not the kernel's default
+    pub permissions: Option<Perm>,
+    /// The kernel key type holding the secret (`user`, `big_key`, or `logon`)
+    pub key_type: KeyType,
+    /// The user to give the key to on each write, if not the caller
+    pub uid: Option<u32>,
+    /// The group to give the key to on each write, if not the caller's
+    pub gid: Option<u32>,
+    /// The user whose persistent keyring holds the key, if not the effective user
+    pub keyring_uid: Option<u32>,
+    /// Whether the key's payload carries an [Envelope] of metadata
+    pub envelope: bool,
+    /// The content-type hint to record in the envelope
+    pub content_type: Option<String>,
+    /// How the key's payload is protected against tampering, if it is
+    pub integrity: Option<Integrity>,
+    /// How the key's payload is encrypted, if it is
+    pub cipher: Option<Arc<dyn Cipher>>,
+    /// The keyrings the key is mirrored into, in search order
+    pub mirrors: Vec<KeySerialId>,
+    /// Where reads, writes, and deletes are recorded, if they are
+    pub audit: Option<Arc<dyn AuditSink>>,
+    /// Caller-supplied context to record with each operation
+    pub audit_context: Option<String>,
+    /// What measures the credential's operations, if anything does
+    pub metrics: Option<Arc<dyn MetricsObserver>>,
+    /// How kernel operations that fail for transient reasons are retried
+    pub retry: RetryPolicy,
+    /// How many previous versions of the secret are kept, if any are
+    pub history: Option<HistoryPolicy>,
+    /// The longest payload a read will allocate for, if reads are limited
+    pub max_read_len: Option<usize>,
+    /// Whether reads check the key's owner and permissions first
+    pub verify_owner: bool,
+    /// Whether the credential refuses to change its key (reads neither re-link nor keep it alive)
+    pub read_only: bool,
+    /// How much of the description debug output shows
+    pub redaction: Redaction,
+    /// Whether access errors say which security module may have caused them
+    pub lsm_hints: bool,
+    /// What is done when the persistent keyring can't be had or linked to
+    pub persistent_policy: PersistentPolicy,
+    /// What the key is searched for, added, linked, read, and invalidated with
+    pub keyctl: Arc<dyn Keyctl>,
+    /// The store's recent misses, if it caches them
+    pub(crate) misses: Option<Arc<NegativeCache>>,
+    /// The store's cache of secrets read, if it has one
+    pub(crate) secrets: Option<Arc<ReadCache>>,
+    /// The host and persistent keyrings, shared with the store's other credentials
+    keyrings: Arc<Keyrings>,
+    /// Read count, for [Relink::Every]
+    reads: ReadCount,
+}
+
+impl std::fmt::Debug for Cred {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |text: &str| self.redaction.apply(text);
+        f.debug_struct("Cred")
+            .field("target", &self.target)
+            .field("keyring", &self.keyring)
+            .field("persistent", &self.persistent)
+            .field("description", &redact(&self.description))
+            .field(
+                "specifiers",
+                &self
+                    .specifiers
+                    .as_ref()
+                    .map(|(service, user)| (redact(service), redact(user))),
+            )
+            .field("backup", &self.backup)
+            .field("write_mode", &self.write_mode)
+            .field("relink", &self.relink)
+            .field("callout", &self.callout)
+            .field("serial", &self.serial)
+            .field("timeout", &self.timeout)
+            .field("keepalive_timeout", &self.keepalive_timeout)
+            .field("permissions", &self.permissions)
+            .field("key_type", &self.key_type)
+            .field("uid", &self.uid)
+            .field("gid", &self.gid)
+            .field("keyring_uid", &self.keyring_uid)
+            .field("envelope", &self.envelope)
+            .field("content_type", &self.content_type)
+            .field("integrity", &self.integrity)
+            .field("cipher", &self.cipher)
+            .field("mirrors", &self.mirrors)
+            .field("audit", &self.audit)
+            .field("audit_context", &self.audit_context)
+            .field("metrics", &self.metrics)
+            .field("retry", &self.retry)
+            .field("history", &self.history)
+            .field("max_read_len", &self.max_read_len)
+            .field("verify_owner", &self.verify_owner)
+            .field("read_only", &self.read_only)
+            .field("redaction", &self.redaction)
+            .field("lsm_hints", &self.lsm_hints)
+            .field("persistent_policy", &self.persistent_policy)
+            .field("keyctl", &self.keyctl)
+            .field("misses", &self.misses)
+            .field("secrets", &self.secrets)
+            .field("keyrings", &self.keyrings)
+            .field("reads", &self.reads)
+            .finish()
+    }
 }
 
 impl CredentialApi for Cred {
@@ -36,23 +622,34 @@ impl CredentialApi for Cred {
     ///
     /// Returns an [Invalid](Error::Invalid) error if the password
     /// is empty, because keyutils keys cannot have empty values.
+    ///
+    /// If the store keeps an on-disk backup, the secret is also sealed
+    /// and written to the backup directory. The write fails up front
+    /// if the backup master key is not available.
+    ///
+    /// If the entry was built with the `create_new` modifier, returns an
+    /// [Invalid](Error::Invalid) error if the credential already exists.
+    /// If it was built with the `update_only` modifier, returns a
+    /// [NoEntry](Error::NoEntry) error if the credential doesn't exist.
+    /// (The kernel offers no exclusive create, so these checks are made
+    /// just before the write, not atomically with it.)
     fn set_secret(&self, secret: &[u8]) -> keyring_core::error::Result<()> {
-        if secret.is_empty() {
-            return Err(Error::Invalid(
-                "secret".to_string(),
-                "cannot be empty".to_string(),
-            ));
-        }
-        self.set(secret)?;
-        Ok(())
+        let started = Instant::now();
+        let result = self.set_checked(secret);
+        self.report(AuditOp::Set, started, secret.len(), result)
     }
 
     /// See the keyring-core API docs.
     ///
     /// This requires a call to `Key::read`.
+    ///
+    /// If the key is not in the kernel but the store keeps an on-disk backup,
+    /// the secret is recovered from the backup and re-added to the kernel.
     fn get_secret(&self) -> keyring_core::error::Result<Vec<u8>> {
-        let buffer = self.get()?;
-        Ok(buffer)
+        let started = Instant::now();
+        let result = self.fetch();
+        let size = result.as_ref().map_or(0, Vec::len);
+        self.report(AuditOp::Get, started, size, result)
     }
 
     /// See the keyring-core API docs.
@@ -66,27 +663,41 @@ impl CredentialApi for Cred {
     /// so get_password may find a key that has been invalidated
     /// if it's called within milliseconds of the invalidation
     /// in *the same process* that deleted the key.
+    ///
+    /// Any on-disk backup of the credential is removed as well.
     fn delete_credential(&self) -> keyring_core::error::Result<()> {
-        self.remove()?;
-        Ok(())
+        let started = Instant::now();
+        let result = self.delete();
+        self.report(AuditOp::Delete, started, 0, result)
     }
 
     /// See the keyring-core API docs.
     ///
-    /// Since this store has no ambiguity, entries are wrappers.
+    /// The wrapper is this credential bound to the serial of the key found
+    /// now, as by [pin](Cred::pin) (but without re-linking the key), so it
+    /// operates on that exact key even if another writer later replaces it.
+    /// A credential already bound to a serial is its own wrapper.
     fn get_credential(&self) -> keyring_core::Result<Option<Arc<Credential>>> {
-        self.session
-            .search(&self.description)
-            .map_err(KeyStoreError::from)
-            .map_err(keyring_core::Error::from)?;
-        Ok(None)
+        let key = self.find().map_err(keyring_core::Error::from)?;
+        if self.serial.is_some() {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(Cred {
+            serial: Some(key.get_id()),
+            ..self.clone()
+        })))
     }
 
     /// See the keyring-core API docs.
     ///
     /// Specifiers are remembered at creation time if the description was not custom.
+    /// Otherwise, if the store uses envelopes, they are the specifiers recorded
+    /// in the key's envelope (if any).
     fn get_specifiers(&self) -> Option<(String, String)> {
-        self.specifiers.clone()
+        if self.specifiers.is_some() || !self.envelope {
+            return self.owned_specifiers();
+        }
+        self.get_envelope().ok().flatten()?.specifiers
     }
 
     /// See the keyring-core API docs.
@@ -106,90 +717,1558 @@ impl Cred {
     /// An explicit target string is interpreted as the description to use for the entry.
     /// If none is provided, then we concatenate the user and service in the string
     /// `{delimiters[0]}{user}{delimiters[1]}{service}{delimiters[2]}`.
+    ///
+    /// The key will live in the keyring selected by `keyring`.
     pub fn build_from_specifiers(
         target: Option<&str>,
         delimiters: &[String; 3],
         service_no_dividers: bool,
         service: &str,
         user: &str,
+        keyring: Target,
+    ) -> keyring_core::error::Result<Self> {
+        Self::build_in_keyrings(
+            target,
+            delimiters,
+            service_no_dividers,
+            service,
+            user,
+            keyring,
+            None,
+        )
+    }
+
+    /// As [build_from_specifiers](Cred::build_from_specifiers), but with
+    /// the keyrings of `keyring` already resolved if `keyrings` is given.
+    pub(crate) fn build_in_keyrings(
+        target: Option<&str>,
+        delimiters: &[String; 3],
+        service_no_dividers: bool,
+        service: &str,
+        user: &str,
+        keyring: Target,
+        keyrings: Option<Arc<Keyrings>>,
     ) -> keyring_core::error::Result<Self> {
-        // Construct the description with a URI-style description
-        let (description, specifiers) = match target {
-            Some(value) => (value.to_string(), None),
-            None => {
-                if service_no_dividers && service.contains(delimiters[1].as_str()) {
+        let (description, specifiers) =
+            describe(target, delimiters, service_no_dividers, service, user)?;
+
+        // Get the target keyring (and the persistent keyring)
+        let target = keyring;
+        let keyrings = match keyrings {
+            Some(keyrings) => keyrings,
+            None => Keyrings::resolve(target, PersistentPolicy::BestEffort)?,
+        };
+
+        Ok(Self {
+            target,
+            keyring: keyrings.keyring,
+            persistent: keyrings.persistent,
+            description: description.into(),
+            specifiers: specifiers.map(|(service, user)| (service.into(), user.into())),
+            backup: None,
+            write_mode: WriteMode::Upsert,
+            relink: Relink::Always,
+            callout: None,
+            serial: None,
+            timeout: None,
+            keepalive_timeout: None,
+            permissions: None,
+            key_type: KeyType::User,
+            uid: None,
+            gid: None,
+            keyring_uid: None,
+            envelope: false,
+            content_type: None,
+            integrity: None,
+            cipher: None,
+            mirrors: Vec::new(),
+            audit: None,
+            audit_context: None,
+            metrics: None,
+            retry: RetryPolicy::default(),
+            history: None,
+            max_read_len: None,
+            verify_owner: false,
+            read_only: false,
+            redaction: Redaction::Full,
+            lsm_hints: false,
+            persistent_policy: PersistentPolicy::BestEffort,
+            keyctl: kernel(),
+            misses: None,
+            secrets: None,
+            keyrings,
+            reads: ReadCount::default(),
+        })
+    }
+
+    /// Set the credential's [PersistentPolicy], dropping its persistent
+    /// keyring if the policy is [Disabled](PersistentPolicy::Disabled).
+    ///
+    /// Returns a [NoStorageAccess](Error::NoStorageAccess) error if the
+    /// policy is [Require](PersistentPolicy::Require) and the credential's
+    /// keyring should be, but couldn't be, linked to the persistent keyring.
+    pub(crate) fn set_persistent_policy(
+        &mut self,
+        policy: PersistentPolicy,
+    ) -> keyring_core::error::Result<()> {
+        self.persistent_policy = policy;
+        match policy {
+            // the store's shared keyrings already leave it out
+            PersistentPolicy::Disabled if self.keyrings.persistent_id.is_some() => {
+                let keyrings = Arc::make_mut(&mut self.keyrings);
+                keyrings.persistent = None;
+                keyrings.persistent_id = None;
+                self.persistent = None;
+            }
+            PersistentPolicy::Require
+                if self.target.links_persistent() && self.keyrings.persistent_id.is_none() =>
+            {
+                return Err(persistent_unavailable());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The body of [set_secret](CredentialApi::set_secret).
+    fn set_checked(&self, secret: &[u8]) -> keyring_core::error::Result<()> {
+        self.check_writable("set")?;
+        self.check_secret(secret)?;
+        match self.write_mode {
+            WriteMode::Upsert => {}
+            WriteMode::CreateNew => match self.secret_len() {
+                Ok(_) => {
                     return Err(Error::Invalid(
-                        "service".to_string(),
-                        "cannot contain delimiter".to_string(),
+                        "create_new".to_string(),
+                        "the credential already exists".to_string(),
                     ));
                 }
-                (
-                    format!(
-                        "{}{user}{}{service}{}",
-                        delimiters[0], delimiters[1], delimiters[2]
-                    ),
-                    Some((service.to_string(), user.to_string())),
-                )
+                Err(Error::NoEntry) => {}
+                Err(err) => return Err(err),
+            },
+            WriteMode::UpdateOnly => {
+                self.secret_len()?;
+            }
+        }
+        if let Some(backup) = &self.backup {
+            backup.check(self.keyring)?;
+        }
+        self.set(secret)?;
+        if let Some(backup) = &self.backup {
+            backup.save(self.keyring, &self.description, secret)?;
+        }
+        self.record_version(secret)
+    }
+
+    /// The body of [delete_credential](CredentialApi::delete_credential).
+    fn delete(&self) -> keyring_core::error::Result<()> {
+        self.check_writable("delete")?;
+        let removed = self
+            .retry
+            .run(|err: &KeyStoreError| is_transient(err), || self.remove())
+            .map_err(Error::from);
+        if let Some(backup) = &self.backup {
+            let had_backup = backup.remove(&self.description)?;
+            if had_backup && matches!(removed, Err(Error::NoEntry)) {
+                return Ok(());
             }
+        }
+        removed
+    }
+
+    /// Delete the credential, then wait up to `wait` until its key can't be found.
+    ///
+    /// The kernel invalidates a deleted key at once, but garbage-collects
+    /// it in the background, and until then a search can still find it for
+    /// a few milliseconds. This polls (with backoff) until searching finds
+    /// no key, or a different one, so that tests and provisioning scripts
+    /// can count on a deleted credential being absent.
+    ///
+    /// Returns whether the key was gone within `wait`. Fails as
+    /// [delete_credential](CredentialApi::delete_credential) does.
+    pub fn delete_credential_sync(&self, wait: Duration) -> keyring_core::error::Result<bool> {
+        let deleted = self.find().ok().map(|key| key.get_id());
+        self.delete_credential()?;
+        let Some(deleted) = deleted else {
+            return Ok(true);
         };
-        if description.is_empty() {
+        let deadline = Instant::now() + wait;
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            match self.find().map_err(Error::from) {
+                Ok(key) if key.get_id() == deleted => {}
+                Ok(_) | Err(Error::NoEntry) => return Ok(true),
+                Err(err) => return Err(err),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            std::thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(DELETE_SYNC_MAX_BACKOFF);
+        }
+    }
+
+    /// Revoke the credential's key, as a kill switch.
+    ///
+    /// Unlike [delete_credential](CredentialApi::delete_credential), which
+    /// invalidates the key so that it disappears at once, this marks the key
+    /// revoked but leaves it in its keyrings until it is garbage collected:
+    /// every process that possesses it gets `EKEYREVOKED` on its next read,
+    /// and `/proc/keys` shows it as revoked in the meantime (see
+    /// [Store::prune](crate::Store::prune) to unlink it sooner). Through this
+    /// store, reading a revoked credential fails with [NoEntry](Error::NoEntry),
+    /// and writing it creates a new key.
+    ///
+    /// Any on-disk backup of the credential is removed, so the secret can't
+    /// be restored from it, unless the key couldn't be revoked.
+    pub fn revoke(&self) -> keyring_core::error::Result<()> {
+        let started = Instant::now();
+        let result = self.revoke_key();
+        self.report(AuditOp::Revoke, started, 0, result)
+    }
+
+    /// The body of [revoke](Cred::revoke).
+    fn revoke_key(&self) -> keyring_core::error::Result<()> {
+        self.check_writable("revoke")?;
+        let revoked = self
+            .retry
+            .run(
+                |err: &KeyStoreError| is_transient(err),
+                || Ok(self.traced().revoke(self.find()?.get_id())?),
+            )
+            .map_err(Error::from);
+        let removed = match (&self.backup, &revoked) {
+            // a key that couldn't be revoked keeps its backup
+            (Some(backup), Ok(()) | Err(Error::NoEntry)) => {
+                backup.remove(&self.description).map(drop)
+            }
+            _ => Ok(()),
+        };
+        revoked.and(removed)
+    }
+
+    /// Retrieve the secret into a buffer that is wiped when dropped.
+    ///
+    /// This behaves exactly like [get_secret](CredentialApi::get_secret),
+    /// but the returned [SecretBytes] zeroes its memory on drop so no
+    /// plaintext copy of the secret lingers on the heap.
+    pub fn get_secret_secure(&self) -> keyring_core::error::Result<SecretBytes> {
+        let started = Instant::now();
+        let result = self.fetch().map(SecretBytes::new);
+        let size = result.as_ref().map_or(0, |secret| secret.len());
+        self.report(AuditOp::Get, started, size, result)
+    }
+
+    /// Read the secret into a caller-provided buffer, returning its length.
+    ///
+    /// The secret is read by the kernel directly into `buffer`, so no heap
+    /// allocation is made (unless the secret has to be recovered from the
+    /// on-disk backup). If `buffer` is too small for the secret, it is wiped
+    /// and an [Invalid](Error::Invalid) error giving the secret's length is returned.
+    ///
+    /// With envelopes or encryption, the payload has to be read onto the
+    /// heap to unwrap it, and is wiped afterwards.
+    pub fn get_secret_into(&self, buffer: &mut [u8]) -> keyring_core::error::Result<usize> {
+        let started = Instant::now();
+        let result = self.read_into(buffer);
+        let size = *result.as_ref().unwrap_or(&0);
+        self.report(AuditOp::Get, started, size, result)
+    }
+
+    /// The body of [get_secret_into](Cred::get_secret_into).
+    fn read_into(&self, buffer: &mut [u8]) -> keyring_core::error::Result<usize> {
+        let read = if !self.bare() {
+            self.get().map(|secret| {
+                let secret = SecretBytes::new(secret);
+                if secret.len() <= buffer.len() {
+                    buffer[..secret.len()].copy_from_slice(&secret);
+                }
+                secret.len()
+            })
+        } else {
+            self.read_found(|key| {
+                if let Err(err) = self.verify(key)? {
+                    return Ok(Err(err));
+                }
+                let len = self.traced().read(key.get_id(), buffer)?;
+                self.keep_alive(key)?;
+                Ok(Ok(len))
+            })
+            .map_err(Error::from)
+            .and_then(|read| read)
+        };
+        let len = match read {
+            Err(Error::NoEntry) if self.backup.is_some() => {
+                let secret = SecretBytes::new(self.restore()?);
+                if secret.len() <= buffer.len() {
+                    buffer[..secret.len()].copy_from_slice(&secret);
+                }
+                secret.len()
+            }
+            result => result?,
+        };
+        if len > buffer.len() {
+            wipe(buffer);
             return Err(Error::Invalid(
-                "description".to_string(),
-                "cannot be empty".to_string(),
+                "buffer".to_string(),
+                format!("is too small: the secret is {len} bytes long"),
             ));
         }
+        Ok(len)
+    }
 
-        // Get the session keyring
-        let session = KeyRing::from_special_id(KeyRingIdentifier::Session, false)
-            .map_err(|e| NoStorageAccess(e.into()))?;
+    /// Retrieve the secret as a reader, for consuming big secrets in pieces.
+    ///
+    /// The kernel hands back a key's payload in one piece, so the secret is
+    /// read (as by [get_secret_secure](Cred::get_secret_secure)) into a
+    /// single buffer owned by the reader and wiped when it's dropped. The
+    /// caller can copy it out a piece at a time, e.g. with [std::io::copy],
+    /// without holding a copy of the whole secret itself.
+    pub fn get_secret_reader(&self) -> keyring_core::error::Result<SecretReader> {
+        self.get_secret_secure().map(SecretReader::new)
+    }
 
-        // Link the persistent keyring to the session
-        let persistent = KeyRing::get_persistent(KeyRingIdentifier::Session).ok();
+    /// Store a secret read from `reader` until it's exhausted, returning its length.
+    ///
+    /// The kernel takes a key's payload in one piece, so the secret is read
+    /// into a single buffer, sized up front for the longest secret the
+    /// entry's key type can hold (so it's never reallocated), and wiped
+    /// afterwards. The caller never needs to hold the whole secret itself.
+    /// Otherwise this behaves like [set_secret](CredentialApi::set_secret).
+    ///
+    /// Returns a [TooLong](Error::TooLong) error, without reading more than
+    /// one byte past the limit, if the secret is too long for the key type,
+    /// and a [PlatformFailure](Error::PlatformFailure) error if reading fails.
+    pub fn set_secret_from(&self, reader: impl Read) -> keyring_core::error::Result<usize> {
+        let max = self.max_secret_len();
+        let mut buffer = Vec::with_capacity(max + 1);
+        let read = reader.take(max as u64 + 1).read_to_end(&mut buffer);
+        // wrap the buffer first, so that it's wiped even if reading failed
+        let secret = SecretBytes::new(buffer);
+        read.map_err(|e| Error::PlatformFailure(e.into()))?;
+        if secret.len() > max {
+            return Err(Error::TooLong("secret".to_string(), max as u32));
+        }
+        self.set_secret(&secret)?;
+        Ok(secret.len())
+    }
 
-        Ok(Self {
-            session,
-            persistent,
-            description,
-            specifiers,
+    /// Get the length of the secret without reading it.
+    ///
+    /// This asks the kernel for the payload size (a read into an empty buffer),
+    /// so the secret itself is never copied into process memory. If the key is
+    /// not in the kernel but the store keeps an on-disk backup, the length of
+    /// the backed-up secret is reported (without decrypting it).
+    ///
+    /// With envelopes or encryption, the payload has to be read to unwrap it.
+    pub fn secret_len(&self) -> keyring_core::error::Result<usize> {
+        let read = if !self.bare() {
+            self.get().map(|secret| SecretBytes::new(secret).len())
+        } else {
+            self.read_found(|key| self.traced().read(key.get_id(), &mut []))
+                .map_err(Error::from)
+        };
+        match (read, &self.backup) {
+            (Err(Error::NoEntry), Some(backup)) => {
+                backup.secret_len(&self.description)?.ok_or(Error::NoEntry)
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Generate a random secret to `spec`, store it, and return it.
+    ///
+    /// The secret is stored just as [set_secret](CredentialApi::set_secret)
+    /// would store it, so the entry's write mode applies (e.g. an entry built
+    /// with `create_new` won't replace an existing secret with a generated one).
+    pub fn set_generated_secret(
+        &self,
+        spec: &SecretSpec,
+    ) -> keyring_core::error::Result<SecretBytes> {
+        let secret = spec.generate()?;
+        self.set_secret(&secret)?;
+        Ok(secret)
+    }
+
+    /// Get the secret, or generate, store, and return one if there is none.
+    ///
+    /// `generate` is only called if the credential doesn't exist. If another
+    /// writer creates the credential while the secret is being generated, the
+    /// generated secret is discarded in favor of theirs. After writing, the
+    /// secret is read back, so that concurrent creators converge on whichever
+    /// value the kernel ended up holding.
+    pub fn get_or_create_with<F>(&self, generate: F) -> keyring_core::error::Result<Vec<u8>>
+    where
+        F: FnOnce() -> keyring_core::error::Result<Vec<u8>>,
+    {
+        let started = Instant::now();
+        let result = self.get_or_create(generate);
+        let size = result.as_ref().map_or(0, Vec::len);
+        self.report(AuditOp::Get, started, size, result)
+    }
+
+    /// The body of [get_or_create_with](Cred::get_or_create_with).
+    fn get_or_create(
+        &self,
+        generate: impl FnOnce() -> keyring_core::error::Result<Vec<u8>>,
+    ) -> keyring_core::error::Result<Vec<u8>> {
+        match self.fetch() {
+            Err(Error::NoEntry) => {}
+            result => return result,
+        }
+        self.check_writable("create")?;
+        let secret = SecretBytes::new(generate()?);
+        self.check_secret(&secret)?;
+        match self.secret_len() {
+            // someone else got there first
+            Ok(_) => return self.fetch(),
+            Err(Error::NoEntry) => {}
+            Err(err) => return Err(err),
+        }
+        if let Some(backup) = &self.backup {
+            backup.check(self.keyring)?;
+        }
+        self.set(&secret)?;
+        if let Some(backup) = &self.backup {
+            backup.save(self.keyring, &self.description, &secret)?;
+        }
+        match self.fetch() {
+            // deleted right after creation; report what we stored
+            Err(Error::NoEntry) => Ok(secret.to_vec()),
+            result => result,
+        }
+    }
+
+    /// Set a new secret, returning the previous one (if there was one).
+    ///
+    /// An existing key is read and then updated in place, keeping its serial,
+    /// so there is no window in which the credential is missing. If the key is
+    /// invalidated or replaced between the read and the update, the sequence is
+    /// retried a bounded number of times. If there is no existing key, the new
+    /// secret is written as by [set_secret](CredentialApi::set_secret)
+    /// and `None` is returned. The `create_new` and `update_only` modifiers
+    /// are honored.
+    pub fn swap_secret(&self, secret: &[u8]) -> keyring_core::error::Result<Option<Vec<u8>>> {
+        let started = Instant::now();
+        let result = self.swap(secret);
+        self.report(AuditOp::Set, started, secret.len(), result)
+    }
+
+    /// The body of [swap_secret](Cred::swap_secret).
+    fn swap(&self, secret: &[u8]) -> keyring_core::error::Result<Option<Vec<u8>>> {
+        self.check_writable("set")?;
+        self.check_secret(secret)?;
+        if let Some(backup) = &self.backup {
+            backup.check(self.keyring)?;
+        }
+        for _ in 0..SWAP_ATTEMPTS {
+            let key = match self.locate().map_err(Error::from) {
+                Ok(key) => key,
+                Err(Error::NoEntry) => {
+                    // a backed-up secret counts as existing: restore it and swap against it
+                    if self.backup.is_some() {
+                        match self.restore() {
+                            Ok(restored) => {
+                                drop(SecretBytes::new(restored));
+                                continue;
+                            }
+                            Err(Error::NoEntry) => {}
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    if self.write_mode == WriteMode::UpdateOnly {
+                        return Err(Error::NoEntry);
+                    }
+                    self.set(secret)?;
+                    if let Some(backup) = &self.backup {
+                        backup.save(self.keyring, &self.description, secret)?;
+                    }
+                    return Ok(None);
+                }
+                Err(err) => return Err(err),
+            };
+            if self.write_mode == WriteMode::CreateNew {
+                return Err(Error::Invalid(
+                    "create_new".to_string(),
+                    "the credential already exists".to_string(),
+                ));
+            }
+            let read = self
+                .verify(key)
+                .and_then(|verified| verified.map_or_else(|err| Ok(Err(err)), |()| self.read(key)));
+            let (previous, envelope) = match read.map_err(KeyStoreError) {
+                Ok(payload) => self.unseal(payload?)?,
+                Err(err) => match Error::from(err) {
+                    Error::NoEntry => continue,
+                    err => return Err(err),
+                },
+            };
+            let previous = SecretBytes::new(previous);
+            let sealed = self.seal(secret, envelope)?;
+            match self.traced().update(key.get_id(), &sealed) {
+                Ok(()) => {
+                    self.forget_cached();
+                    self.apply_attributes(key, self.timeout.or(self.keepalive_timeout))?;
+                    if let Some(backup) = &self.backup {
+                        backup.save(self.keyring, &self.description, secret)?;
+                    }
+                    self.record_version(secret)?;
+                    return Ok(Some(previous.to_vec()));
+                }
+                Err(err) => match Error::from(KeyStoreError(err)) {
+                    Error::NoEntry => continue,
+                    err => return Err(err),
+                },
+            }
+        }
+        // every attempt lost a race with a concurrent delete
+        Err(Error::NoEntry)
+    }
+
+    /// Read the metadata in the envelope of the credential's key.
+    ///
+    /// Returns `None` if the key's payload isn't in an envelope (e.g. because
+    /// it was written by a store without the `envelope` option).
+    pub fn get_envelope(&self) -> keyring_core::error::Result<Option<Envelope>> {
+        let key = self.locate()?;
+        let payload = self.decrypt(self.read(key).map_err(KeyStoreError)??)?;
+        Ok(Envelope::open(&payload).map(|(envelope, _)| envelope))
+    }
+
+    /// The numbers of the previous versions of the secret that are still
+    /// kept, oldest first (see [HistoryPolicy]).
+    ///
+    /// The latest version is the one written last, so it holds the current
+    /// secret (unless the credential has since been deleted). This lists
+    /// whatever versions are in the credential's keyring, whether or not
+    /// the store still keeps a history.
+    pub fn versions(&self) -> keyring_core::error::Result<Vec<u64>> {
+        Ok(self
+            .version_keys()?
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect())
+    }
+
+    /// Read version `number` of the secret, as listed by [versions](Cred::versions).
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if that version has
+    /// expired or been dropped. With envelopes, the version's envelope
+    /// records when it was written (see [get_version_envelope](Cred::get_version_envelope)).
+    pub fn get_version(&self, number: u64) -> keyring_core::error::Result<Vec<u8>> {
+        let key = self.version_key(number)?;
+        let payload = self.read(key).map_err(KeyStoreError)??;
+        Ok(self.unseal(payload)?.0)
+    }
+
+    /// Read the metadata in the envelope of version `number` of the secret.
+    ///
+    /// Returns `None` if the version's payload isn't in an envelope.
+    pub fn get_version_envelope(
+        &self,
+        number: u64,
+    ) -> keyring_core::error::Result<Option<Envelope>> {
+        let key = self.version_key(number)?;
+        let payload = self.decrypt(self.read(key).map_err(KeyStoreError)??)?;
+        Ok(Envelope::open(&payload).map(|(envelope, _)| envelope))
+    }
+
+    /// Move the credential's key into the keyring of another target.
+    ///
+    /// The key is moved with `KEYCTL_MOVE`, so (on kernels that have it)
+    /// the move is atomic: the key is never missing from both keyrings,
+    /// nor in both. Its link into the persistent keyring is added or
+    /// removed to suit the new target. This is how a session-scoped secret
+    /// can be promoted to user scope, e.g. after authentication.
+    ///
+    /// Returns a credential for the moved key: this one keeps using its old
+    /// keyring.
+    pub fn move_to(&self, target: Target) -> keyring_core::error::Result<Cred> {
+        self.check_writable("move")?;
+        let key = self.find()?;
+        let keyrings = Keyrings::resolve(target, self.persistent_policy)?;
+        self.move_key(key, keyrings.keyring_id)?;
+        let linked = match (self.keyrings.persistent_id, keyrings.persistent_id) {
+            (None, Some(keyring)) => self.traced().link(key.get_id(), keyring),
+            (Some(keyring), None) => self.traced().unlink(key.get_id(), keyring),
+            _ => Ok(()),
+        };
+        self.persistent_policy
+            .check(linked)
+            .map_err(KeyStoreError)?;
+        self.forget_cached();
+        Ok(Cred {
+            target,
+            keyring: keyrings.keyring,
+            persistent: keyrings.persistent,
+            keyrings,
+            reads: ReadCount::default(),
+            ..self.clone()
         })
     }
 
-    /// Internal method to retrieve the underlying secret
+    /// Move the credential's key into any keyring, given the keyring's serial.
+    ///
+    /// As with [move_to](Cred::move_to), the move is atomic where the kernel
+    /// supports it. Afterwards, this credential only finds the key if the
+    /// destination keyring is reachable from its own keyring.
+    pub fn move_to_keyring(&self, keyring: KeySerialId) -> keyring_core::error::Result<()> {
+        self.check_writable("move")?;
+        let key = self.find()?;
+        self.move_key(key, keyring)?;
+        self.forget_cached();
+        Ok(())
+    }
+
+    /// Link the credential's key into another keyring as well, given the keyring's serial.
+    ///
+    /// The key stays in the credential's own keyrings; `keyring` just gets a
+    /// link to it, so that whatever searches that keyring (e.g. a child
+    /// session given it) finds the key too. Writes to the credential update
+    /// the same key, so the shared link sees them, while a delete takes the
+    /// key away everywhere. Linking a key that's already linked does nothing.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no key.
+    pub fn link_to(&self, keyring: KeySerialId) -> keyring_core::error::Result<()> {
+        self.check_writable("link")?;
+        let key = self.find()?;
+        self.traced()
+            .link(key.get_id(), keyring)
+            .map_err(KeyStoreError)?;
+        self.forget_cached();
+        Ok(())
+    }
+
+    /// Take back a link made with [link_to](Cred::link_to).
+    ///
+    /// The credential's own keyring and the persistent keyring can't be
+    /// unlinked from this way (use [delete_credential](CredentialApi::delete_credential)
+    /// or [move_to](Cred::move_to) instead), which gives an
+    /// [Invalid](Error::Invalid) error. Returns a [NoEntry](Error::NoEntry)
+    /// error if there is no key, or it isn't linked into `keyring`.
+    pub fn unlink_from(&self, keyring: KeySerialId) -> keyring_core::error::Result<()> {
+        self.check_writable("unlink")?;
+        let key = self.find()?;
+        if keyring == self.keyring_serial().map_err(KeyStoreError)?
+            || Some(keyring) == self.keyrings.persistent_id
+        {
+            return Err(Error::Invalid(
+                "keyring".to_string(),
+                "is one of the credential's own keyrings".to_string(),
+            ));
+        }
+        match self.traced().unlink(key.get_id(), keyring) {
+            Ok(()) => Ok(()),
+            // the kernel's answer for a key that isn't in the keyring
+            Err(KeyError::MissingFileOrDirectory) => Err(Error::NoEntry),
+            Err(err) => Err(KeyStoreError(err).into()),
+        }
+    }
+
+    /// Make the credential's key findable under the description `alias` as well.
+    ///
+    /// The alias is a keyring with that description, in the credential's
+    /// keyring, that links the key. Entries whose description is the alias
+    /// find the key through it when there's no key of their own, so during a
+    /// rename a credential can be found under both its old and its new
+    /// description. Entries that find the key through an alias read and
+    /// delete it, but their writes make a key of their own, which then hides
+    /// the alias. The alias goes away with the key, and adding an alias
+    /// that's already there points it at this credential's key.
+    ///
+    /// In a store that uses envelopes, an entry that finds the key through
+    /// an alias must still match the specifiers recorded in it, so there
+    /// aliases are for entries built with a custom description.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no key, an
+    /// [Invalid](Error::Invalid) error if there is already a credential with
+    /// the alias as its description, and a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error for a
+    /// credential with integrity protection or a cipher, since those bind
+    /// the payload to the key's own description.
+    pub fn add_alias(&self, alias: &str) -> keyring_core::error::Result<()> {
+        self.check_writable("alias")?;
+        if self.integrity.is_some() || self.cipher.is_some() {
+            return Err(Error::NotSupportedByStore(
+                "aliases can't be used with integrity protection or encryption".to_string(),
+            ));
+        }
+        if alias.is_empty() || alias == &*self.description {
+            return Err(Error::Invalid(
+                "alias".to_string(),
+                "must be a non-empty description other than the credential's".to_string(),
+            ));
+        }
+        let key = self.find()?;
+        let keyring = self.keyring_serial().map_err(KeyStoreError)?;
+        match self.traced().search(keyring, self.key_type, alias) {
+            Err(KeyError::KeyDoesNotExist) => {}
+            Ok(_) => {
+                return Err(Error::Invalid(
+                    "alias".to_string(),
+                    "is the description of another credential".to_string(),
+                ));
+            }
+            Err(err) => return Err(KeyStoreError(err).into()),
+        }
+        // adding a keyring replaces any with the same description, so an old alias is dropped
+        let holder = self
+            .traced()
+            .add(KeyType::KeyRing, alias, &[], keyring)
+            .map_err(KeyStoreError)?;
+        self.traced()
+            .link(key.get_id(), holder)
+            .map_err(KeyStoreError)?;
+        self.keyrings
+            .link_persistent(&self.traced(), holder, self.persistent_policy)
+            .map_err(KeyStoreError)?;
+        self.forget_cached();
+        Ok(())
+    }
+
+    /// Remove the alias `alias` of the credential's key; see [add_alias](Cred::add_alias).
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no such alias
+    /// of this credential's key.
+    pub fn remove_alias(&self, alias: &str) -> keyring_core::error::Result<()> {
+        self.check_writable("unalias")?;
+        let key = self.find()?;
+        let keyring = self.keyring_serial().map_err(KeyStoreError)?;
+        let holder = self
+            .traced()
+            .search(keyring, KeyType::KeyRing, alias)
+            .map_err(KeyStoreError)?;
+        if !self
+            .traced()
+            .links(holder)
+            .map_err(KeyStoreError)?
+            .contains(&key.get_id())
+        {
+            return Err(Error::NoEntry);
+        }
+        self.traced().invalidate(holder).map_err(KeyStoreError)?;
+        self.forget_cached();
+        Ok(())
+    }
+
+    /// A credential bound to the key this one finds now, for reading it as cheaply as possible.
     ///
-    /// Will search for and re-link the existing key to the session and
+    /// The returned credential uses the key's serial directly, as one from
+    /// [Store::entry_from_serial](crate::Store::entry_from_serial) does: its
+    /// reads skip the search and the re-linking, so reading a secret of up
+    /// to 4 KiB takes a single syscall. Writes update the key in place. A
+    /// key that is deleted or expires is gone for the pinned credential even
+    /// once this one is written again (which makes a new key), so pin again then.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no key to pin.
+    pub fn pin(&self) -> keyring_core::error::Result<Cred> {
+        let key = self.locate()?;
+        Ok(Cred {
+            serial: Some(key.get_id()),
+            ..self.clone()
+        })
+    }
+
+    /// The credential's debug output with its description and specifiers
+    /// in full, whatever its [Redaction] policy, for diagnostics.
+    pub fn describe_unredacted(&self) -> String {
+        format!(
+            "{:?}",
+            Cred {
+                redaction: Redaction::Full,
+                ..self.clone()
+            }
+        )
+    }
+
+    /// How long the credential's key has left before the kernel expires it.
+    ///
+    /// Returns `None` if the key has no timeout. The kernel only reports a
+    /// key's expiry in `/proc/keys`, rounded down to the largest whole unit
+    /// (seconds, minutes, hours, days, or weeks), so a key with 90 minutes
+    /// left is reported as having an hour: refresh credentials well before
+    /// the reported time runs out, rather than just as it does.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no key (or it
+    /// has already expired), and a [PlatformFailure](Error::PlatformFailure)
+    /// error if `/proc/keys` can't be read.
+    pub fn time_remaining(&self) -> keyring_core::error::Result<Option<Duration>> {
+        let id = self.find()?.get_id();
+        let listed = procfs::keys().map_err(|e| Error::PlatformFailure(e.into()))?;
+        match listed.into_iter().find(|key| key.serial == id) {
+            Some(key) => match key.expiry {
+                Expiry::Never => Ok(None),
+                Expiry::In(remaining) => Ok(Some(remaining)),
+                Expiry::Expired => Err(Error::NoEntry),
+            },
+            // it was deleted since it was found
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// Internal method to move the key out of the credential's keyring
+    fn move_key(&self, key: Key, to: KeySerialId) -> keyring_core::error::Result<()> {
+        let from = self.keyring_serial().map_err(KeyStoreError)?;
+        self.traced()
+            .move_key(key.get_id(), from, to)
+            .map_err(KeyStoreError)?;
+        Ok(())
+    }
+
+    /// Report an operation that started at `started` and handled a secret
+    /// of `size` bytes to the metrics observer and audit sink, if there are any.
+    fn report<T>(
+        &self,
+        operation: AuditOp,
+        started: Instant,
+        size: usize,
+        result: keyring_core::error::Result<T>,
+    ) -> keyring_core::error::Result<T> {
+        let failed = take_failed_call();
+        let result = result.map_err(|err| self.with_context(operation, failed, err));
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(&Observation {
+                operation: operation.into(),
+                latency: started.elapsed(),
+                size: result.is_ok().then_some(size),
+                error: result.as_ref().err().map(ErrorCategory::of),
+            });
+        }
+        if let Some(sink) = &self.audit {
+            sink.record(&AuditEvent {
+                operation,
+                description: &self.description,
+                context: self.audit_context.as_deref(),
+                outcome: result.as_ref().map(drop),
+            });
+        }
+        result
+    }
+
+    /// Wrap a kernel error from `operation` in an [ErrorContext], naming the
+    /// keyctl call that failed if it's the one that failed last.
+    fn with_context(&self, operation: AuditOp, failed: Option<FailedCall>, err: Error) -> Error {
+        // a write into a keyring that's gone isn't about the credential's key
+        if let (Error::NoEntry, Some((KeyctlOp::Add, _, KeyError::KeyDoesNotExist))) =
+            (&err, failed)
+        {
+            return Error::NoStorageAccess(Box::new(ErrorContext {
+                operation: operation.to_string(),
+                description: self.redaction.apply(&self.description),
+                keyring: self.keyring_serial().ok(),
+                call: Some(KeyctlOp::Add),
+                serial: self.serial,
+                kind: FailureKind::MissingKeyring,
+                lsm: None,
+                error: KeyError::KeyDoesNotExist,
+            }));
+        }
+        let wrap = |inner: Box<dyn std::error::Error + Send + Sync>| {
+            let Some(error) = inner.downcast_ref::<KeyError>().copied() else {
+                return inner;
+            };
+            let (call, serial) = match failed {
+                Some((call, serial, failed)) if failed == error => match call {
+                    // those calls are made on the keyring
+                    KeyctlOp::Search | KeyctlOp::Add => (Some(call), self.serial),
+                    _ => (Some(call), Some(serial)),
+                },
+                _ => (None, self.serial),
+            };
+            let kind = FailureKind::of(call, error, || self.keyring_denied());
+            let denied = matches!(
+                kind,
+                FailureKind::KeyAccessDenied | FailureKind::KeyringAccessDenied
+            );
+            Box::new(ErrorContext {
+                operation: operation.to_string(),
+                description: self.redaction.apply(&self.description),
+                keyring: self.keyring_serial().ok(),
+                call,
+                serial,
+                kind,
+                lsm: if denied && self.lsm_hints {
+                    LsmHint::detect()
+                } else {
+                    None
+                },
+                error,
+            })
+        };
+        match err {
+            Error::PlatformFailure(inner) => Error::PlatformFailure(wrap(inner)),
+            Error::NoStorageAccess(inner) => Error::NoStorageAccess(wrap(inner)),
+            err => err,
+        }
+    }
+
+    /// Internal method to copy out the specifiers, for the types that own theirs
+    pub(crate) fn owned_specifiers(&self) -> Option<(String, String)> {
+        let (service, user) = self.specifiers.as_ref()?;
+        Some((service.to_string(), user.to_string()))
+    }
+
+    /// Internal method to probe whether the credential's keyring can be used, after an access error
+    ///
+    /// The probe isn't traced, so it doesn't displace the call that failed.
+    fn keyring_denied(&self) -> bool {
+        matches!(
+            self.keyring_serial()
+                .and_then(|keyring| self.keyctl.describe(keyring)),
+            Err(KeyError::AccessDenied | KeyError::PermissionDenied)
+        )
+    }
+
+    /// Internal method to read the secret, falling back to the on-disk backup
+    fn fetch(&self) -> keyring_core::error::Result<Vec<u8>> {
+        match self.get() {
+            Err(Error::NoEntry) if self.backup.is_some() => self.restore(),
+            result => result,
+        }
+    }
+
+    /// Internal method to recover a secret from the on-disk backup
+    ///
+    /// The recovered secret is put back into the kernel so subsequent
+    /// reads don't need to touch the disk (unless the credential is read-only).
+    fn restore(&self) -> keyring_core::error::Result<Vec<u8>> {
+        let Some(backup) = &self.backup else {
+            return Err(Error::NoEntry);
+        };
+        let secret = backup
+            .load(self.keyring, &self.description)?
+            .ok_or(Error::NoEntry)?;
+        // make sure the recovered plaintext is wiped if re-adding it fails
+        let secret = SecretBytes::new(secret);
+        if !self.read_only {
+            self.set(&secret)?;
+        }
+        Ok(secret.to_vec())
+    }
+
+    /// Internal method to write the secret as the next version, if the credential keeps a history
+    ///
+    /// Versions beyond the history's count are invalidated, oldest first.
+    /// (Concurrent writers can pick the same number, in which case the
+    /// later version replaces the earlier one.)
+    fn record_version(&self, secret: &[u8]) -> keyring_core::error::Result<()> {
+        let Some(history) = self.history else {
+            return Ok(());
+        };
+        let mut versions = self.version_keys()?;
+        let number = versions.last().map_or(1, |(number, _)| number + 1);
+        let payload = self.seal(secret, self.previous_envelope())?;
+        let keyring = self.keyring_serial().map_err(KeyStoreError)?;
+        let description = version_description(&self.description, number);
+        let serial = self
+            .traced()
+            .add(self.key_type, &description, &payload, keyring)
+            .map_err(KeyStoreError)?;
+        self.apply_attributes(Key::from_id(serial), Some(history.lifetime))?;
+        versions.push((number, serial));
+        let excess = versions.len().saturating_sub(history.versions);
+        for (_, serial) in &versions[..excess] {
+            // the version may already have expired, which is just as good
+            let _ = self.keyctl.invalidate(*serial);
+        }
+        Ok(())
+    }
+
+    /// Internal method to list the version keys in the credential's keyring, by number
+    fn version_keys(&self) -> keyring_core::error::Result<Vec<(u64, KeySerialId)>> {
+        let keyring = self.keyring_serial().map_err(KeyStoreError)?;
+        let mut versions = Vec::new();
+        for id in self.traced().links(keyring).map_err(KeyStoreError)? {
+            // expired and invalidated versions can't be described, so they're
+            // skipped (untraced, since that isn't a failure)
+            let Ok(described) = self.keyctl.describe(id) else {
+                continue;
+            };
+            if let Some(number) = version_number(&self.description, &described.description) {
+                versions.push((number, id));
+            }
+        }
+        versions.sort_unstable_by_key(|(number, _)| *number);
+        Ok(versions)
+    }
+
+    /// Internal method to find the key of version `number`
+    fn version_key(&self, number: u64) -> keyring_core::error::Result<Key> {
+        self.version_keys()?
+            .into_iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, serial)| Key::from_id(serial))
+            .ok_or(Error::NoEntry)
+    }
+
+    /// Internal method to find the underlying key
+    ///
+    /// Will search for and re-link the existing key to the target and
     /// persistent keyrings to ensure the key doesn't time out.
-    fn get(&self) -> Result<Vec<u8>, KeyStoreError> {
+    ///
+    /// How often (and how strictly) the re-linking is done is controlled
+    /// by the credential's [Relink] policy.
+    ///
+    /// If the credential has callout info, a missing key is requested
+    /// from the kernel (and thus from a `request-key` handler) instead.
+    ///
+    /// A credential bound to a serial just uses that key: it isn't
+    /// one of the store's keys, so it is never re-linked.
+    fn locate(&self) -> Result<Key, KeyStoreError> {
+        if self.serial.is_some() {
+            return self.find();
+        }
+
         // Verify that the key exists and is valid
-        let key = self.session.search(&self.description)?;
+        let key = match &self.callout {
+            Some(callout) => match self.keyring.request_key(&self.description, Some(callout)) {
+                // there is no /sbin/request-key to ask, so nobody can supply the key
+                Err(KeyError::MissingFileOrDirectory) => {
+                    return Err(KeyError::KeyDoesNotExist.into());
+                }
+                result => result?,
+            },
+            None => self.find()?,
+        };
 
-        // Directly re-link to the session keyring
+        // a read-only credential leaves the key's links as they are
+        if self.read_only {
+            return Ok(key);
+        }
+        let strict = match self.relink {
+            Relink::Always => true,
+            Relink::BestEffort => false,
+            Relink::Never => return Ok(key),
+            Relink::Every(n) => {
+                let reads = self.reads.0.fetch_add(1, Ordering::Relaxed);
+                if reads % n.max(1) != 0 {
+                    return Ok(key);
+                }
+                false
+            }
+        };
+        let check = |result: Result<(), KeyError>| match result {
+            Err(err) if strict => Err(KeyStoreError(err)),
+            _ => Ok(()),
+        };
+
+        // Directly re-link to the target keyring
         // If a logout occurred, it will only be linked to the
         // persistent keyring and needs to be added again.
-        self.session.link_key(key)?;
+        // (Process and thread keyrings outlive no logout, and may
+        // be restricted against any further links.)
+        if !matches!(self.target, Target::Process | Target::Thread) {
+            check(
+                self.keyring_serial()
+                    .and_then(|keyring| self.traced().link(key.get_id(), keyring)),
+            )?;
+        }
 
         // Directly re-link to the persistent keyring
         // If it expired, it will only be linked to the
         // session keyring and needs to be added again.
-        if let Some(keyring) = self.persistent {
-            keyring.link_key(key)?;
+        // (Whether a failure fails the read is up to the persistent policy.)
+        self.keyrings
+            .link_persistent(&self.traced(), key.get_id(), self.persistent_policy)
+            .map_err(KeyStoreError)?;
+
+        // Re-link to the mirrors, which may have been cleared
+        for mirror in &self.mirrors {
+            check(self.traced().link(key.get_id(), *mirror))?;
         }
+        Ok(key)
+    }
 
-        // Read in the key (making sure we have enough room)
-        let data = key.read_to_vec()?;
-        Ok(data)
+    /// Internal method to find the underlying key without re-linking it
+    ///
+    /// A key missing from the credential's keyring is looked for in its
+    /// mirrors, and then through an alias (see [Cred::add_alias]). If the
+    /// store caches misses, a recent miss is answered without searching.
+    pub(crate) fn find(&self) -> Result<Key, KeyStoreError> {
+        if let Some(serial) = self.serial {
+            // make sure the key is still there
+            self.traced().describe(serial)?;
+            return Ok(Key::from_id(serial));
+        }
+        // a thread keyring's keys are only for the thread that looks for them
+        let misses = self
+            .misses
+            .as_ref()
+            .filter(|_| self.target != Target::Thread);
+        let key_type = type_name(self.key_type);
+        if misses.is_some_and(|misses| misses.is_missing(self.target, key_type, &self.description))
+        {
+            return Err(KeyError::KeyDoesNotExist.into());
+        }
+        let mut found = self.search();
+        for mirror in &self.mirrors {
+            if !matches!(found, Err(KeyError::KeyDoesNotExist)) {
+                break;
+            }
+            found = self
+                .traced()
+                .search(*mirror, self.key_type, &self.description)
+                .map(Key::from_id);
+        }
+        if matches!(found, Err(KeyError::KeyDoesNotExist)) {
+            found = self.follow_alias();
+        }
+        if let (Some(misses), Err(KeyError::KeyDoesNotExist)) = (misses, &found) {
+            misses.record_miss(self.target, key_type, &self.description);
+        }
+        Ok(found?)
+    }
+
+    /// Internal method to find the key that an alias with the credential's description links
+    fn follow_alias(&self) -> Result<Key, KeyError> {
+        let keyring = self.keyring_serial()?;
+        let holder = self
+            .traced()
+            .search(keyring, KeyType::KeyRing, &self.description)?;
+        // the alias links just the key, and nothing once the key is gone
+        match self.traced().links(holder)?.first() {
+            Some(serial) => Ok(Key::from_id(*serial)),
+            None => Err(KeyError::KeyDoesNotExist),
+        }
+    }
+
+    /// Internal method to find the key and read from it
+    ///
+    /// If the key is invalidated or replaced between the search and the read
+    /// (e.g. by a concurrent write), the search is made again, a bounded
+    /// number of times, so that the read gets the replacement key instead of
+    /// failing as if there were no credential. (A replaced key that's still
+    /// linked elsewhere may be unreadable rather than gone, since it's no
+    /// longer possessed, so access errors also search again, unless the search
+    /// finds the same key.)
+    fn read_found<T>(
+        &self,
+        mut read: impl FnMut(Key) -> Result<T, KeyError>,
+    ) -> Result<T, KeyStoreError> {
+        if let Some(serial) = self.serial {
+            // a bound key is just read: there's nothing to search for again
+            let key = Key::from_id(serial);
+            return match self.retry.run(is_transient, || read(key)) {
+                // a key that's gone is unreadable too, which describing it tells apart
+                Err(KeyError::AccessDenied) => {
+                    self.traced().describe(serial)?;
+                    Err(KeyError::AccessDenied.into())
+                }
+                result => Ok(result?),
+            };
+        }
+        let mut attempts = 1;
+        let mut denied = None;
+        loop {
+            let key = self
+                .retry
+                .run(|err: &KeyStoreError| is_transient(err), || self.locate())?;
+            if denied == Some(key.get_id()) {
+                return Err(KeyError::AccessDenied.into());
+            }
+            match self.retry.run(is_transient, || read(key)) {
+                Err(err) if attempts < READ_ATTEMPTS && is_stale(&err) => attempts += 1,
+                Err(KeyError::AccessDenied) if attempts < READ_ATTEMPTS => {
+                    attempts += 1;
+                    denied = Some(key.get_id());
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Internal method to search the credential's keyring for the key
+    fn search(&self) -> Result<Key, KeyError> {
+        let keyring = self.keyring_serial()?;
+        let serial = self
+            .traced()
+            .search(keyring, self.key_type, &self.description)?;
+        Ok(Key::from_id(serial))
+    }
+
+    /// Internal method to get the credential's keyctl operations, noting failed calls
+    fn traced(&self) -> Traced<'_> {
+        Traced(&*self.keyctl)
+    }
+
+    /// Internal method to resolve the serial of the credential's keyring
+    fn keyring_serial(&self) -> Result<KeySerialId, KeyError> {
+        match self.keyring_uid {
+            Some(uid) => user::persistent_serial(uid),
+            None => Ok(self.keyrings.keyring_id),
+        }
+    }
+
+    /// Internal method to read a key's payload
+    ///
+    /// The payload's length is checked before anything is allocated for it
+    /// (big keys can be far larger than any buffer worth allocating up
+    /// front), and with a read limit, a longer payload gives the inner error.
+    fn read(&self, key: Key) -> Result<keyring_core::error::Result<Vec<u8>>, KeyError> {
+        let max = self.max_read_len.unwrap_or(usize::MAX);
+        Ok(
+            read_payload(&self.traced(), key.get_id(), max)?.ok_or_else(|| {
+                Error::TooLong(
+                    "payload".to_string(),
+                    u32::try_from(max).unwrap_or(u32::MAX),
+                )
+            }),
+        )
+    }
+
+    /// Internal method to wrap a secret in an envelope, if the credential uses them
+    ///
+    /// The creation time (and any specifiers or content type this credential
+    /// doesn't know) are carried over from the previous envelope.
+    ///
+    /// With integrity protection, the envelope also gets the payload's tag.
+    /// With a cipher, the whole payload is then encrypted.
+    fn seal(
+        &self,
+        secret: &[u8],
+        previous: Option<Envelope>,
+    ) -> keyring_core::error::Result<SecretBytes> {
+        if !self.envelope {
+            return self.encrypt(secret);
+        }
+        let now = SystemTime::now();
+        let envelope = match previous {
+            Some(previous) => Envelope {
+                created: previous.created,
+                modified: now,
+                specifiers: self.owned_specifiers().or(previous.specifiers),
+                content_type: self.content_type.clone().or(previous.content_type),
+            },
+            None => Envelope {
+                created: now,
+                modified: now,
+                specifiers: self.owned_specifiers(),
+                content_type: self.content_type.clone(),
+            },
+        };
+        let mut payload = envelope.header(self.integrity.is_some());
+        if let Some(integrity) = &self.integrity {
+            let tag = integrity.tag(self.keyring, &self.description, &payload, secret)?;
+            payload.extend_from_slice(&tag);
+        }
+        payload.extend_from_slice(secret);
+        self.encrypt(&SecretBytes::new(payload))
+    }
+
+    /// Internal method to encrypt a payload, if the credential has a cipher
+    fn encrypt(&self, payload: &[u8]) -> keyring_core::error::Result<SecretBytes> {
+        match &self.cipher {
+            Some(cipher) => Ok(SecretBytes::new(
+                cipher.encrypt(&self.description, payload)?,
+            )),
+            None => Ok(SecretBytes::new(payload.to_vec())),
+        }
+    }
+
+    /// Internal method to decrypt a payload, if the credential has a cipher
+    fn decrypt(&self, payload: Vec<u8>) -> keyring_core::error::Result<SecretBytes> {
+        let payload = SecretBytes::new(payload);
+        match &self.cipher {
+            Some(cipher) => Ok(SecretBytes::new(
+                cipher.decrypt(&self.description, &payload)?,
+            )),
+            None => Ok(payload),
+        }
+    }
+
+    /// Internal method to tell whether the key's payload is just the secret
+    fn bare(&self) -> bool {
+        !self.envelope && self.cipher.is_none()
+    }
+
+    /// Internal method to take the secret out of a payload, if it's in an envelope
+    ///
+    /// A key whose envelope records other specifiers than this credential's
+    /// belongs to another (service, user) pair with the same description,
+    /// so it's treated as missing.
+    ///
+    /// With integrity protection, a payload whose tag is missing or wrong
+    /// is a [BadStoreFormat](Error::BadStoreFormat) error.
+    ///
+    /// With a cipher, the payload is decrypted first.
+    fn unseal(&self, payload: Vec<u8>) -> keyring_core::error::Result<(Vec<u8>, Option<Envelope>)> {
+        let payload = self.decrypt(payload)?;
+        if !self.envelope {
+            return Ok((payload.to_vec(), None));
+        }
+        let sealed = Envelope::parse(&payload);
+        if let Some(integrity) = &self.integrity {
+            let (header, tag, secret) = match &sealed {
+                Some(sealed) => (sealed.header, sealed.tag, sealed.secret),
+                None => (&[][..], None, &payload[..]),
+            };
+            integrity.verify(self.keyring, &self.description, header, tag, secret)?;
+        }
+        let Some(sealed) = sealed else {
+            return Ok((payload.to_vec(), None));
+        };
+        if let (Some((service, user)), Some(theirs)) =
+            (&self.specifiers, &sealed.envelope.specifiers)
+        {
+            if (&**service, &**user) != (theirs.0.as_str(), theirs.1.as_str()) {
+                return Err(Error::NoEntry);
+            }
+        }
+        Ok((sealed.secret.to_vec(), Some(sealed.envelope)))
+    }
+
+    /// Internal method to get the envelope of the existing key, if there is one
+    ///
+    /// The envelope of a key that belongs to other specifiers is ignored,
+    /// since this credential is about to replace that key.
+    fn previous_envelope(&self) -> Option<Envelope> {
+        if !self.envelope {
+            return None;
+        }
+        let key = self.find().ok()?;
+        let (secret, envelope) = self.unseal(self.read(key).ok()?.ok()?).ok()?;
+        drop(SecretBytes::new(secret));
+        envelope
+    }
+
+    /// Internal method to check that a secret can be held by the credential's key type
+    fn check_secret(&self, secret: &[u8]) -> keyring_core::error::Result<()> {
+        if secret.is_empty() {
+            return Err(Error::Invalid(
+                "secret".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        let max = self.max_secret_len();
+        if secret.len() > max {
+            return Err(Error::TooLong("secret".to_string(), max as u32));
+        }
+        Ok(())
+    }
+
+    /// The longest secret the credential's key type can hold, after any envelope and encryption.
+    fn max_secret_len(&self) -> usize {
+        let max = match self.key_type {
+            KeyType::BigKey => BIG_KEY_MAX_LEN,
+            _ => USER_KEY_MAX_LEN,
+        };
+        let max = if self.envelope {
+            let envelope = Envelope {
+                created: UNIX_EPOCH,
+                modified: UNIX_EPOCH,
+                specifiers: self.owned_specifiers(),
+                content_type: self.content_type.clone(),
+            };
+            let tag_len = if self.integrity.is_some() {
+                DIGEST_LEN
+            } else {
+                0
+            };
+            max.saturating_sub(envelope.header_len() + tag_len)
+        } else {
+            max
+        };
+        max.saturating_sub(self.cipher.as_ref().map_or(0, |c| c.overhead()))
+    }
+
+    /// Internal method to retrieve the underlying secret
+    fn get(&self) -> keyring_core::error::Result<Vec<u8>> {
+        // a thread keyring's keys are only for the thread that reads them
+        let cache = self
+            .secrets
+            .as_ref()
+            .filter(|_| self.target != Target::Thread);
+        let generation = cache.map(|cache| cache.generation());
+        let mut read_from = None;
+        // a key that vanishes after it's found was (probably) replaced,
+        // so it's worth searching again
+        let (data, cached) = self.read_found(|key| {
+            if let Err(err) = self.verify(key)? {
+                return Ok(Err(err));
+            }
+            // a cached secret saves reading and checking the payload, but
+            // the key is still found, checked, and kept alive
+            if let Some(secret) =
+                cache.and_then(|cache| cache.get(key.get_id(), &self.description, &self.specifiers))
+            {
+                self.keep_alive(key)?;
+                return Ok(Ok((secret, true)));
+            }
+            let data = self.read(key)?;
+            if data.is_ok() {
+                self.keep_alive(key)?;
+                read_from = Some(key.get_id());
+            }
+            Ok(data.map(|data| (data, false)))
+        })??;
+        if cached {
+            return Ok(data);
+        }
+        let secret = self.unseal(data)?.0;
+        if let (Some(cache), Some(generation), Some(serial)) = (cache, generation, read_from) {
+            cache.insert(
+                generation,
+                serial,
+                &self.description,
+                &self.specifiers,
+                &secret,
+            );
+        }
+        Ok(secret)
     }
 
     /// Internal method to set the underlying secret
     ///
-    /// Will add the key directly to the session and link it to the
+    /// Will add the key directly to the target keyring and link it to the
     /// persistent keyring when available.
-    fn set<T: AsRef<[u8]>>(&self, secret: T) -> Result<(), KeyStoreError> {
-        // Add to the session keyring
-        let key = self.session.add_key(&self.description, &secret)?;
+    ///
+    /// A credential bound to a serial updates that key in place.
+    fn set(&self, secret: &[u8]) -> keyring_core::error::Result<()> {
+        let previous = self.previous_envelope();
+        let payload = self.seal(secret, previous)?;
+        self.write_payload(&payload)
+    }
+
+    /// Internal method to write a payload, reporting one too large for the key type as such
+    ///
+    /// The kernel rejects payloads longer than the key type takes as
+    /// invalid, which would otherwise blame the secret.
+    pub(crate) fn write_payload(&self, payload: &[u8]) -> keyring_core::error::Result<()> {
+        let max = match self.key_type {
+            KeyType::BigKey => BIG_KEY_MAX_LEN,
+            _ => USER_KEY_MAX_LEN,
+        };
+        match self.write(payload) {
+            Err(err)
+                if payload.len() > max
+                    && matches!(
+                        *err,
+                        KeyError::InvalidArguments | KeyError::Unknown(libc::E2BIG)
+                    ) =>
+            {
+                Err(Error::TooLong("payload".to_string(), max as u32))
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Refuse to `operation` the key if the credential is read-only.
+    pub(crate) fn check_writable(&self, operation: &str) -> keyring_core::error::Result<()> {
+        if !self.read_only {
+            return Ok(());
+        }
+        Err(Error::NoStorageAccess(Box::new(ReadOnly {
+            operation: operation.to_string(),
+            description: Some(self.description.to_string()),
+        })))
+    }
+
+    /// Internal method to write a payload to the underlying key
+    pub(crate) fn write(&self, secret: &[u8]) -> Result<(), KeyStoreError> {
+        if let Some(serial) = self.serial {
+            self.traced().update(serial, secret)?;
+            self.forget_cached();
+            return Ok(());
+        }
+
+        // Add to the target keyring
+        let key = match self.retry.run(is_transient, || self.add(secret)) {
+            // a restricted keyring refuses even adds that would update a key in place
+            Err(KeyError::PermissionDenied) => {
+                let key = self
+                    .find()
+                    .map_err(|_| KeyStoreError(KeyError::PermissionDenied))?;
+                self.traced().update(key.get_id(), secret)?;
+                self.forget_cached();
+                key
+            }
+            result => result?,
+        };
 
         // Directly link to the persistent keyring as well
-        if let Some(keyring) = self.persistent {
-            keyring.link_key(key).map_err(KeyStoreError)?;
+        self.keyrings
+            .link_persistent(&self.traced(), key.get_id(), self.persistent_policy)?;
+
+        // And to the mirrors
+        for mirror in &self.mirrors {
+            self.traced().link(key.get_id(), *mirror)?;
+        }
+        self.apply_attributes(key, self.timeout.or(self.keepalive_timeout))
+    }
+
+    /// Internal method to check the key's owner and permissions before a read, if it should
+    ///
+    /// Describing a key that has gone away fails as reading it would, so
+    /// that it's searched for again; a key that's there but isn't as
+    /// expected gives the inner error.
+    fn verify(&self, key: Key) -> Result<keyring_core::error::Result<()>, KeyError> {
+        if !self.verify_owner {
+            return Ok(Ok(()));
+        }
+        let described = self.traced().describe(key.get_id())?;
+        let expected_uid = self
+            .uid
+            .or(self.keyring_uid)
+            .unwrap_or_else(|| unsafe { libc::geteuid() });
+        let expected_permissions = self.permissions.unwrap_or(Perm::kernel_default()).bits();
+        let permissions = described.perm.bits();
+        if described.uid == expected_uid && permissions == expected_permissions {
+            return Ok(Ok(()));
+        }
+        Ok(Err(Error::NoStorageAccess(Box::new(OwnerMismatch {
+            description: self.description.to_string(),
+            uid: described.uid,
+            expected_uid,
+            permissions,
+            expected_permissions,
+        }))))
+    }
+
+    /// Internal method to re-arm the key's timeout after a read, if it has a keepalive timeout
+    fn keep_alive(&self, key: Key) -> Result<(), KeyError> {
+        if self.read_only {
+            return Ok(());
+        }
+        if let Some(timeout) = self.keepalive_timeout {
+            self.traced()
+                .set_timeout(key.get_id(), timeout.as_secs().max(1) as usize)?;
+        }
+        Ok(())
+    }
+
+    /// Internal method to add (or update) the key in the target keyring
+    fn add(&self, secret: &[u8]) -> Result<Key, KeyError> {
+        let keyring = self.keyring_serial()?;
+        let serial = self
+            .traced()
+            .add(self.key_type, &self.description, secret, keyring)?;
+        self.forget_cached();
+        Ok(Key::from_id(serial))
+    }
+
+    /// Internal method to forget the store's cached misses and secrets, once a key is changed
+    fn forget_cached(&self) {
+        if let Some(misses) = &self.misses {
+            misses.clear();
+        }
+        if let Some(secrets) = &self.secrets {
+            secrets.clear();
+        }
+    }
+
+    /// Internal method to give a freshly written key a timeout and the credential's permissions
+    fn apply_attributes(&self, key: Key, timeout: Option<Duration>) -> Result<(), KeyStoreError> {
+        if let Some(timeout) = timeout {
+            self.traced()
+                .set_timeout(key.get_id(), timeout.as_secs().max(1) as usize)?;
+        }
+        if let Some(permissions) = self.permissions {
+            self.traced().set_perm(key.get_id(), permissions)?;
+        }
+        // this goes last, since giving the key away can take away our setattr permission
+        if self.uid.is_some() || self.gid.is_some() {
+            self.traced().chown(key.get_id(), self.uid, self.gid)?;
         }
         Ok(())
     }
@@ -197,12 +2276,14 @@ impl Cred {
     /// Internal method to remove the underlying secret
     ///
     /// Performs a search and invalidates the key when found.
+    /// (Kernels without invalidation get the key revoked instead.)
     fn remove(&self) -> Result<(), KeyStoreError> {
         // Verify that the key exists and is valid
-        let key = self.session.search(&self.description)?;
+        let key = self.find()?;
 
         // Invalidate the key immediately
-        key.invalidate()?;
+        self.traced().invalidate(key.get_id())?;
+        self.forget_cached();
         Ok(())
     }
 }
@@ -0,0 +1,65 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::store::type_name;
+use super::{Cred, Integrity, Target};
+
+/// How a credential's payload is encoded, as recorded in a [CredRef].
+///
+/// A store can only read a credential whose payload it encodes the same way.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Codec {
+    /// The kernel key type holding the secret (`user`, `big_key`, or `logon`)
+    pub key_type: String,
+    /// Whether the payload carries an [Envelope](crate::Envelope) of metadata
+    pub envelope: bool,
+    /// How the payload is protected against tampering, if it is
+    pub integrity: Option<Integrity>,
+    /// Whether the payload is encrypted (with a [Cipher](crate::Cipher))
+    pub encrypted: bool,
+}
+
+/// A reference to a credential, without its secret, that outlives the process.
+///
+/// Applications can keep these in their state files (with the `serde`
+/// feature, they serialize) and turn them back into entries after a restart
+/// with [Store::entry_from_ref](crate::Store::entry_from_ref). A reference
+/// records the key's description and keyring, the specifiers it was built
+/// for (if any), and how its payload is encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CredRef {
+    /// The description of the credential's key
+    pub description: String,
+    /// The service and user the credential was built for, if any
+    pub specifiers: Option<(String, String)>,
+    /// The keyring the key lives in
+    pub keyring: Target,
+    /// How the key's payload is encoded
+    pub codec: Codec,
+}
+
+impl Cred {
+    /// How this credential encodes its key's payload.
+    pub fn codec(&self) -> Codec {
+        Codec {
+            key_type: type_name(self.key_type).to_string(),
+            envelope: self.envelope,
+            integrity: self.integrity.clone(),
+            encrypted: self.cipher.is_some(),
+        }
+    }
+
+    /// A reference to this credential, from which
+    /// [Store::entry_from_ref](crate::Store::entry_from_ref) makes an
+    /// equivalent entry later, even in another process.
+    pub fn to_ref(&self) -> CredRef {
+        CredRef {
+            description: self.description.to_string(),
+            specifiers: self.owned_specifiers(),
+            keyring: self.target,
+            codec: self.codec(),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Thin wrappers over the cryptographic primitives used by the store.
+//!
+//! The primitives themselves (SHA-256, SHA-512, HMAC, PBKDF2, ChaCha20 and
+//! ChaCha20-Poly1305) come from the RustCrypto crates; this module only adapts
+//! them to the fixed-size arrays and byte slices the rest of the store works with.
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::aead::{AeadInPlace, KeyInit as _};
+use hmac::{Hmac, Mac};
+use sha2::Digest;
+use zeroize::Zeroize;
+
+/// Size in bytes of a SHA-256 digest (and of an HMAC-SHA256 tag).
+pub(crate) const DIGEST_LEN: usize = 32;
+
+/// Size in bytes of a ChaCha20 key.
+pub(crate) const KEY_LEN: usize = 32;
+
+/// Size in bytes of a ChaCha20 nonce.
+pub(crate) const NONCE_LEN: usize = 12;
+
+/// Size in bytes of a Poly1305 authentication tag.
+pub(crate) const TAG_LEN: usize = 16;
+
+/// Incremental SHA-256 hasher.
+#[derive(Clone)]
+pub(crate) struct Sha256(sha2::Sha256);
+
+impl Sha256 {
+    pub(crate) fn new() -> Self {
+        Sha256(sha2::Sha256::new())
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub(crate) fn finalize(self) -> [u8; DIGEST_LEN] {
+        self.0.finalize().into()
+    }
+}
+
+/// One-shot SHA-256 digest.
+pub(crate) fn sha256(data: &[u8]) -> [u8; DIGEST_LEN] {
+    sha2::Sha256::digest(data).into()
+}
+
+/// One-shot SHA-512 digest (which eCryptfs derives its keys with).
+pub(crate) fn sha512(data: &[u8]) -> [u8; 64] {
+    sha2::Sha512::digest(data).into()
+}
+
+/// HMAC-SHA512 over the concatenation of `parts` (which fscrypt derives its key identifiers with).
+pub(crate) fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut mac =
+        <Hmac<sha2::Sha512> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// HMAC-SHA256 over the concatenation of `parts`.
+pub(crate) fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; DIGEST_LEN] {
+    let mut mac =
+        <Hmac<sha2::Sha256> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// PBKDF2-HMAC-SHA256, deriving a single block of key material.
+pub(crate) fn pbkdf2_hmac_sha256(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+) -> [u8; DIGEST_LEN] {
+    let mut out = [0u8; DIGEST_LEN];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, salt, iterations, &mut out);
+    out
+}
+
+/// Compare two byte strings without short-circuiting on the first difference.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+/// XOR `data` in place with the ChaCha20 keystream (initial block counter 1).
+///
+/// Encryption and decryption are the same operation.
+pub(crate) fn chacha20(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    let mut cipher = chacha20::ChaCha20::new(key.into(), nonce.into());
+    // block 0 is left unused, as RFC 8439 does when pairing ChaCha20 with Poly1305
+    cipher.seek(64u32);
+    cipher.apply_keystream(data);
+}
+
+/// Encrypt `data` in place with ChaCha20-Poly1305, appending the tag.
+///
+/// The tag covers `aad` as well, which is authenticated but not encrypted.
+pub(crate) fn seal(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut Vec<u8>) {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt_in_place(nonce.into(), aad, data)
+        .expect("in-memory buffers don't run out of room");
+}
+
+/// Check the tag at the end of `data` and decrypt the rest in place,
+/// returning false (with `data` unchanged) if the tag doesn't match.
+pub(crate) fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    data: &mut Vec<u8>,
+) -> bool {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt_in_place(nonce.into(), aad, data)
+        .is_ok()
+}
+
+/// Fill `buf` from the kernel's CSPRNG.
+pub(crate) fn random_bytes(buf: &mut [u8]) -> std::io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let rest = &mut buf[filled..];
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_getrandom,
+                rest.as_mut_ptr() as *mut libc::c_void,
+                rest.len(),
+                0,
+            )
+        };
+        if res < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        filled += res as usize;
+    }
+    Ok(())
+}
+
+/// Overwrite `buf` with zeros in a way the optimizer won't elide.
+pub(crate) fn wipe(buf: &mut [u8]) {
+    buf.zeroize();
+}
+
+/// Lowercase hex encoding.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
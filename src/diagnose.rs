@@ -0,0 +1,251 @@
+use std::fmt;
+
+use linux_keyutils::{Key, KeyError, KeyRingIdentifier, KeySerialId};
+
+use super::cred::delimiter_ambiguities;
+use super::{Capabilities, PersistentPolicy, Store, Target, procfs, sys};
+
+/// What [Store::diagnose] found out about one keyring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyringStatus {
+    /// The keyring's name (`session`, `persistent`, ...)
+    pub name: &'static str,
+    /// The keyring's serial, or why it couldn't be resolved
+    pub serial: Result<KeySerialId, KeyError>,
+    /// The keyring's description, if it could be described
+    pub description: Option<String>,
+    /// The keyring's owner uid, if it could be described
+    pub uid: Option<u32>,
+    /// The keyring's owner gid, if it could be described
+    pub gid: Option<u32>,
+    /// The keyring's permission mask, if it could be described
+    pub perms: Option<u32>,
+    /// Whether the keyring's contents could be listed
+    pub readable: bool,
+}
+
+impl KeyringStatus {
+    fn probe(name: &'static str, serial: Result<KeySerialId, KeyError>) -> Self {
+        let mut status = KeyringStatus {
+            name,
+            serial,
+            description: None,
+            uid: None,
+            gid: None,
+            perms: None,
+            readable: false,
+        };
+        if let Ok(serial) = serial {
+            if let Ok(metadata) = Key::from_id(serial).metadata() {
+                status.description = Some(metadata.get_description().to_string());
+                status.uid = Some(metadata.get_uid());
+                status.gid = Some(metadata.get_gid());
+                status.perms = Some(metadata.get_perms().bits());
+            }
+            status.readable = sys::keyring_links(serial).is_ok();
+        }
+        status
+    }
+}
+
+/// A user's key quota, from `/proc/key-users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// Keys owned by the user
+    pub keys: usize,
+    /// Maximum number of keys the user may own
+    pub max_keys: usize,
+    /// Bytes of payload owned by the user
+    pub bytes: usize,
+    /// Maximum bytes of payload the user may own
+    pub max_bytes: usize,
+}
+
+impl Quota {
+    pub(crate) fn read() -> Option<Quota> {
+        let uid = unsafe { libc::geteuid() };
+        let users = procfs::key_users().ok()?;
+        users
+            .into_iter()
+            .find(|user| user.uid == uid)
+            .map(|user| user.quota)
+    }
+}
+
+/// A report on the environment a store runs in, produced by [Store::diagnose].
+///
+/// Its `Display` output is meant to be pasted into bug reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// The keyring the store keeps its keys in
+    pub target: Target,
+    /// The status of each of the process's keyrings, and the persistent keyring
+    pub keyrings: Vec<KeyringStatus>,
+    /// The current user's key quota, if it could be read
+    pub quota: Option<Quota>,
+    /// The keyutils features supported by the kernel
+    pub capabilities: Capabilities,
+    /// Everything that looks likely to stop the store from working as expected
+    pub problems: Vec<String>,
+}
+
+impl Diagnosis {
+    /// Whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub(crate) fn run(store: &Store) -> Diagnosis {
+        let target = store.keyring;
+        let mut keyrings: Vec<KeyringStatus> = [
+            ("thread", KeyRingIdentifier::Thread),
+            ("process", KeyRingIdentifier::Process),
+            ("session", KeyRingIdentifier::Session),
+            ("user", KeyRingIdentifier::User),
+            ("user_session", KeyRingIdentifier::UserSession),
+        ]
+        .into_iter()
+        .map(|(name, id)| KeyringStatus::probe(name, sys::keyring_serial(id, false)))
+        .collect();
+        // fetching the persistent keyring links it into the target, as building an entry does
+        let persistent = sys::persistent_serial(target.identifier());
+        keyrings.push(KeyringStatus::probe("persistent", persistent));
+        let capabilities = Capabilities::get();
+        let quota = Quota::read();
+        let mut problems = Vec::new();
+
+        for (_, ambiguity) in delimiter_ambiguities(&store.delimiters) {
+            problems.push(ambiguity);
+        }
+
+        let status = |name: &str| keyrings.iter().find(|k| k.name == name);
+        match status(target.as_str()) {
+            Some(KeyringStatus {
+                serial: Err(err), ..
+            }) => problems.push(format!(
+                "the {target} keyring can't be resolved ({err:?}); in a container this usually means no session was set up (e.g. with pam_keyinit or `keyctl session`)"
+            )),
+            Some(KeyringStatus {
+                readable: false, ..
+            }) => problems.push(format!(
+                "the {target} keyring isn't readable; it may belong to another user or not be possessed by this process"
+            )),
+            _ => {}
+        }
+        if target == Target::Session {
+            if let Some(description) = status("session").and_then(|k| k.description.as_deref()) {
+                if description.starts_with("_uid_ses.") {
+                    problems.push(
+                        "the session keyring is the user's default session keyring, shared by all of the user's processes without a session of their own".to_string(),
+                    );
+                }
+            }
+        }
+        if matches!(target, Target::Session | Target::User | Target::UserSession)
+            && store.persistent != PersistentPolicy::Disabled
+        {
+            if !capabilities.persistent_keyrings {
+                problems.push(
+                    "the kernel has no persistent keyrings, so keys won't survive a logout"
+                        .to_string(),
+                );
+            } else if let Err(err) = persistent {
+                problems.push(format!(
+                    "the persistent keyring isn't available ({err:?}), so keys won't survive a logout"
+                ));
+            }
+        }
+
+        if let Some(quota) = quota {
+            if quota.keys >= quota.max_keys {
+                problems.push(format!(
+                    "the key quota is used up ({} of {} keys)",
+                    quota.keys, quota.max_keys
+                ));
+            }
+            if quota.bytes.saturating_mul(10) >= quota.max_bytes.saturating_mul(9) {
+                problems.push(format!(
+                    "the key quota is almost used up ({} of {} bytes)",
+                    quota.bytes, quota.max_bytes
+                ));
+            }
+        }
+
+        if let Some(backup) = &store.backup {
+            let master = target.keyring().and_then(|keyring| backup.check(keyring));
+            if let Err(err) = master {
+                problems.push(format!(
+                    "backups can't be written or recovered: {err}; provision the key '{}'",
+                    backup.master
+                ));
+            }
+        }
+
+        if store
+            .secrets
+            .as_ref()
+            .is_some_and(|cache| !cache.is_watching())
+        {
+            problems.push(
+                "the read cache can't watch keys (this needs the `watch` feature and a kernel with key notifications), so a secret another process changes is served until the cache's TTL runs out"
+                    .to_string(),
+            );
+        }
+
+        Diagnosis {
+            target,
+            keyrings,
+            quota,
+            capabilities,
+            problems,
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "target keyring: {}", self.target)?;
+        match self.capabilities.kernel_version {
+            Some((major, minor)) => writeln!(f, "kernel: {major}.{minor}")?,
+            None => writeln!(f, "kernel: unknown")?,
+        }
+        writeln!(f, "capabilities: {:?}", self.capabilities)?;
+        writeln!(f, "keyrings:")?;
+        for keyring in &self.keyrings {
+            write!(f, "  {}: ", keyring.name)?;
+            match keyring.serial {
+                Ok(serial) => write!(f, "{}", serial.as_raw_id())?,
+                Err(err) => write!(f, "unavailable ({err:?})")?,
+            }
+            if let (Some(description), Some(uid), Some(gid), Some(perms)) = (
+                &keyring.description,
+                keyring.uid,
+                keyring.gid,
+                keyring.perms,
+            ) {
+                write!(f, " '{description}' uid={uid} gid={gid} perms={perms:08x}")?;
+            }
+            if keyring.serial.is_ok() && !keyring.readable {
+                write!(f, " (not readable)")?;
+            }
+            writeln!(f)?;
+        }
+        match &self.quota {
+            Some(quota) => writeln!(
+                f,
+                "quota: {}/{} keys, {}/{} bytes",
+                quota.keys, quota.max_keys, quota.bytes, quota.max_bytes
+            )?,
+            None => writeln!(f, "quota: unknown")?,
+        }
+        if self.problems.is_empty() {
+            writeln!(f, "no problems found")
+        } else {
+            writeln!(f, "problems:")?;
+            for problem in &self.problems {
+                writeln!(f, "  - {problem}")?;
+            }
+            Ok(())
+        }
+    }
+}
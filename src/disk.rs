@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use keyring_core::api::CredentialStoreApi;
+use keyring_core::{Entry, Error, Result};
+
+use super::crypto::{sha512, to_hex, wipe};
+use super::{Cred, SecretBytes, Store};
+
+/// The prefix of the logon keys cryptsetup gives dm-crypt.
+const DM_CRYPT_PREFIX: &str = "cryptsetup:";
+
+/// How many times eCryptfs hashes a passphrase into its key.
+const ECRYPTFS_HASH_ITERATIONS: u32 = 65536;
+/// The salt `ecryptfs-add-passphrase` uses unless it's given one.
+const ECRYPTFS_DEFAULT_SALT: [u8; 8] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77];
+/// The longest passphrase eCryptfs accepts.
+const ECRYPTFS_MAX_PASSPHRASE_LEN: usize = 64;
+/// The size of the kernel's `struct ecryptfs_auth_tok`.
+const ECRYPTFS_AUTH_TOK_LEN: usize = 740;
+/// Where the password token starts in an `ecryptfs_auth_tok`, after the
+/// version, token type, flags, session key, and reserved bytes.
+const ECRYPTFS_PASSWORD_OFFSET: usize = 628;
+
+/// A dm-crypt volume key, held in a logon key for the kernel to read.
+///
+/// cryptsetup hands dm-crypt its volume keys as logon keys described
+/// `cryptsetup:<name>`, which user space can't read back; dm-crypt reads
+/// them when a table names one as `:<size>:logon:<description>` instead of
+/// giving the key in hex. Provisioning one through a store puts it in the
+/// store's keyring (and the persistent keyring, if the store links into
+/// it), with the store's timeout and permissions.
+///
+/// ```no_run
+/// use linux_keyutils_keyring_store::{DmCryptKey, Store};
+///
+/// let store = Store::new().unwrap();
+/// let key = DmCryptKey::provision(&store, "data-volume", &[0x42; 64])?;
+/// // 0 <sectors> crypt aes-xts-plain64 :64:logon:cryptsetup:data-volume 0 /dev/sdb1 0
+/// let table = format!("0 2097152 crypt aes-xts-plain64 {} 0 /dev/sdb1 0", key.table_key());
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmCryptKey {
+    /// The description of the key (`cryptsetup:<name>`)
+    pub description: String,
+    /// The size of the volume key, in bytes
+    pub key_size: usize,
+}
+
+impl DmCryptKey {
+    /// Add the volume key `key` for the volume `name` to `store`.
+    ///
+    /// The key is stored as it is, without the store's envelope or cipher,
+    /// since the kernel reads it. A key for the same name is replaced.
+    pub fn provision(store: &Store, name: &str, key: &[u8]) -> Result<Self> {
+        if name.is_empty() {
+            return Err(Error::Invalid(
+                "name".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        let description = format!("{DM_CRYPT_PREFIX}{name}");
+        write_raw(store, &description, "logon", key)?;
+        Ok(DmCryptKey {
+            description,
+            key_size: key.len(),
+        })
+    }
+
+    /// The entry of the key in `store`, e.g. to delete it once the volume is set up.
+    pub fn entry(&self, store: &Store) -> Result<Entry> {
+        let modifiers = HashMap::from([
+            ("description", self.description.as_str()),
+            ("key_type", "logon"),
+        ]);
+        store.build("", "", Some(&modifiers))
+    }
+
+    /// The key field of a dm-crypt table that reads this key from the keyring.
+    pub fn table_key(&self) -> String {
+        format!(":{}:logon:{}", self.key_size, self.description)
+    }
+}
+
+/// An eCryptfs mount passphrase, held in the authentication token eCryptfs reads.
+///
+/// This does what `ecryptfs-add-passphrase` does: the passphrase is salted
+/// and hashed into eCryptfs's file encryption key encryption key, which is
+/// added to the store's keyring in a `user` key whose description is the
+/// key's signature. Mounting with `ecryptfs_sig=<signature>` then finds it.
+///
+/// ```no_run
+/// use linux_keyutils_keyring_store::{EcryptfsKey, Store};
+///
+/// let store = Store::new().unwrap();
+/// let key = EcryptfsKey::provision(&store, b"correct horse", None)?;
+/// let options = format!("ecryptfs_cipher=aes,ecryptfs_key_bytes=16,{}", key.mount_option());
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcryptfsKey {
+    /// The signature of the key, in hex (and the description of its key)
+    pub signature: String,
+}
+
+impl EcryptfsKey {
+    /// Add the authentication token for `passphrase` to `store`.
+    ///
+    /// Without a `salt`, the default salt of `ecryptfs-add-passphrase` is
+    /// used, so the signature matches the one it would print. Passphrases
+    /// are at most 64 bytes long.
+    pub fn provision(store: &Store, passphrase: &[u8], salt: Option<[u8; 8]>) -> Result<Self> {
+        if passphrase.len() > ECRYPTFS_MAX_PASSPHRASE_LEN {
+            return Err(Error::TooLong(
+                "passphrase".to_string(),
+                ECRYPTFS_MAX_PASSPHRASE_LEN as u32,
+            ));
+        }
+        let salt = salt.unwrap_or(ECRYPTFS_DEFAULT_SALT);
+        let (mut key, signature) = derive_ecryptfs_key(passphrase, &salt);
+        let token = SecretBytes::new(ecryptfs_auth_tok(&key, &signature, &salt));
+        wipe(&mut key);
+        write_raw(store, &signature, "user", &token)?;
+        Ok(EcryptfsKey { signature })
+    }
+
+    /// The entry of the token in `store`, e.g. to delete it after unmounting.
+    pub fn entry(&self, store: &Store) -> Result<Entry> {
+        let modifiers = HashMap::from([("description", self.signature.as_str())]);
+        store.build("", "", Some(&modifiers))
+    }
+
+    /// The mount option that has eCryptfs use this key.
+    pub fn mount_option(&self) -> String {
+        format!("ecryptfs_sig={}", self.signature)
+    }
+}
+
+/// Write `payload` to the key of type `key_type` described `description`, as it is.
+fn write_raw(store: &Store, description: &str, key_type: &str, payload: &[u8]) -> Result<()> {
+    let modifiers = HashMap::from([("description", description), ("key_type", key_type)]);
+    let entry = store.build("", "", Some(&modifiers))?;
+    let cred = entry
+        .as_any()
+        .downcast_ref::<Cred>()
+        .expect("stores build keyutils credentials");
+    cred.check_writable("set")?;
+    cred.write_payload(payload)
+}
+
+/// Derive eCryptfs's key from a passphrase, and the key's signature, as `ecryptfs-utils` does.
+fn derive_ecryptfs_key(passphrase: &[u8], salt: &[u8; 8]) -> ([u8; 64], String) {
+    let mut salted = salt.to_vec();
+    salted.extend_from_slice(passphrase);
+    let mut digest = sha512(&salted);
+    wipe(&mut salted);
+    for _ in 1..ECRYPTFS_HASH_ITERATIONS {
+        digest = sha512(&digest);
+    }
+    let signature = to_hex(&sha512(&digest)[..8]);
+    (digest, signature)
+}
+
+/// The `struct ecryptfs_auth_tok` for a passphrase's key, as `ecryptfs-utils` lays it out.
+fn ecryptfs_auth_tok(key: &[u8; 64], signature: &str, salt: &[u8; 8]) -> Vec<u8> {
+    let mut token = vec![0u8; ECRYPTFS_AUTH_TOK_LEN];
+    // version 0.4, of a password token (type 0)
+    token[0..2].copy_from_slice(&0x0004u16.to_ne_bytes());
+    let password = &mut token[ECRYPTFS_PASSWORD_OFFSET..];
+    // hashed with SHA-512 (OpenPGP algorithm 10)
+    password[4..8].copy_from_slice(&10i32.to_ne_bytes());
+    password[8..12].copy_from_slice(&ECRYPTFS_HASH_ITERATIONS.to_ne_bytes());
+    password[12..16].copy_from_slice(&(key.len() as u32).to_ne_bytes());
+    // the session key encryption key is set
+    password[16..20].copy_from_slice(&2u32.to_ne_bytes());
+    password[20..84].copy_from_slice(key);
+    password[84..100].copy_from_slice(signature.as_bytes());
+    password[101..109].copy_from_slice(salt);
+    token
+}
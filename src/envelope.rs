@@ -0,0 +1,166 @@
+//! Key payloads that carry metadata alongside the secret.
+//!
+//! When a store is configured with `envelope`, each key's payload is a small header
+//! followed by the secret. The header records when the credential was created and
+//! last modified, the (service, user) pair it was created for, and a content-type hint.
+//!
+//! The header starts with the magic bytes `LKKE` and a version byte, followed by a
+//! flags byte, the two timestamps (seconds since the epoch, little-endian `u64`s),
+//! and then, as flagged, the service, the user, and the content type, each prefixed
+//! with its length as a little-endian `u16`. Payloads without the magic are taken to
+//! be bare secrets, so credentials written before the envelope was turned on still read.
+//!
+//! A store with [Integrity](crate::Integrity) protection also puts a 32-byte tag
+//! between the header and the secret, computed over the key's description, the
+//! header, and the secret (see [Integrity](crate::Integrity)).
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::crypto::DIGEST_LEN;
+
+const MAGIC: &[u8] = b"LKKE";
+const VERSION: u8 = 1;
+const HAS_SPECIFIERS: u8 = 0x01;
+const HAS_CONTENT_TYPE: u8 = 0x02;
+const HAS_TAG: u8 = 0x04;
+
+/// A payload split into its parts by [Envelope::parse].
+pub(crate) struct Sealed<'a> {
+    pub(crate) envelope: Envelope,
+    /// The header, as covered by the tag
+    pub(crate) header: &'a [u8],
+    pub(crate) tag: Option<&'a [u8; DIGEST_LEN]>,
+    pub(crate) secret: &'a [u8],
+}
+
+/// The metadata recorded in a credential's payload envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    /// When the credential was first written
+    pub created: SystemTime,
+    /// When the credential was last written
+    pub modified: SystemTime,
+    /// The (service, user) pair the credential was created for, if it wasn't
+    /// created with a custom description
+    pub specifiers: Option<(String, String)>,
+    /// The content-type hint given when the credential was written, if any
+    pub content_type: Option<String>,
+}
+
+impl Envelope {
+    /// The length of this envelope's header.
+    pub(crate) fn header_len(&self) -> usize {
+        let strings = self.specifiers.iter().flat_map(|(s, u)| [s, u]);
+        MAGIC.len()
+            + 2
+            + 16
+            + strings
+                .chain(self.content_type.iter())
+                .map(|s| 2 + s.len())
+                .sum::<usize>()
+    }
+
+    /// This envelope's header, flagged as followed by a tag if `tagged`.
+    ///
+    /// Strings too long for the header are truncated (on a character boundary).
+    pub(crate) fn header(&self, tagged: bool) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.header_len() + DIGEST_LEN);
+        payload.extend_from_slice(MAGIC);
+        payload.push(VERSION);
+        let mut flags = 0;
+        if tagged {
+            flags |= HAS_TAG;
+        }
+        if self.specifiers.is_some() {
+            flags |= HAS_SPECIFIERS;
+        }
+        if self.content_type.is_some() {
+            flags |= HAS_CONTENT_TYPE;
+        }
+        payload.push(flags);
+        payload.extend_from_slice(&seconds(self.created).to_le_bytes());
+        payload.extend_from_slice(&seconds(self.modified).to_le_bytes());
+        if let Some((service, user)) = &self.specifiers {
+            push_str(&mut payload, service);
+            push_str(&mut payload, user);
+        }
+        if let Some(content_type) = &self.content_type {
+            push_str(&mut payload, content_type);
+        }
+        payload
+    }
+
+    /// Split a payload into its envelope and secret.
+    ///
+    /// Returns `None` if the payload isn't in an envelope (or is in one of
+    /// a later version), in which case it's all secret.
+    pub(crate) fn open(payload: &[u8]) -> Option<(Envelope, &[u8])> {
+        Self::parse(payload).map(|sealed| (sealed.envelope, sealed.secret))
+    }
+
+    /// Split a payload into its header, envelope, tag, and secret.
+    pub(crate) fn parse(payload: &[u8]) -> Option<Sealed<'_>> {
+        let rest = payload.strip_prefix(MAGIC)?;
+        let (&[version, flags], rest) = rest.split_first_chunk::<2>()?;
+        if version != VERSION {
+            return None;
+        }
+        let (created, rest) = rest.split_first_chunk::<8>()?;
+        let (modified, mut rest) = rest.split_first_chunk::<8>()?;
+        let specifiers = if flags & HAS_SPECIFIERS != 0 {
+            let service = take_str(&mut rest)?;
+            let user = take_str(&mut rest)?;
+            Some((service, user))
+        } else {
+            None
+        };
+        let content_type = if flags & HAS_CONTENT_TYPE != 0 {
+            Some(take_str(&mut rest)?)
+        } else {
+            None
+        };
+        let header = &payload[..payload.len() - rest.len()];
+        let tag = if flags & HAS_TAG != 0 {
+            let (tag, tail) = rest.split_first_chunk::<DIGEST_LEN>()?;
+            rest = tail;
+            Some(tag)
+        } else {
+            None
+        };
+        let envelope = Envelope {
+            created: UNIX_EPOCH + Duration::from_secs(u64::from_le_bytes(*created)),
+            modified: UNIX_EPOCH + Duration::from_secs(u64::from_le_bytes(*modified)),
+            specifiers,
+            content_type,
+        };
+        Some(Sealed {
+            envelope,
+            header,
+            tag,
+            secret: rest,
+        })
+    }
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn push_str(payload: &mut Vec<u8>, s: &str) {
+    let mut len = s.len().min(u16::MAX as usize);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    payload.extend_from_slice(&(len as u16).to_le_bytes());
+    payload.extend_from_slice(&s.as_bytes()[..len]);
+}
+
+fn take_str(rest: &mut &[u8]) -> Option<String> {
+    let (len, tail) = rest.split_first_chunk::<2>()?;
+    let len = u16::from_le_bytes(*len) as usize;
+    if tail.len() < len {
+        return None;
+    }
+    let (s, tail) = tail.split_at(len);
+    *rest = tail;
+    String::from_utf8(s.to_vec()).ok()
+}
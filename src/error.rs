@@ -1,7 +1,10 @@
 use keyring_core::error::Error as KeyRingError;
 use linux_keyutils::KeyError as KeyUtilsError;
+use linux_keyutils::KeySerialId;
 use std::ops::Deref;
 
+use super::{KeyctlOp, LsmHint};
+
 /// Internal new type to convert linux_keyutils::KeyError to
 /// keyring_core::error::Error implicitly.
 pub(crate) struct KeyStoreError(pub(crate) KeyUtilsError);
@@ -24,11 +27,15 @@ impl From<KeyStoreError> for KeyRingError {
         match err.0 {
             // Experimentation has shown that the keyutils implementation can return a lot of
             // different errors that all mean "no such key", depending on where in the invalidation
-            // processing the [get_password](KeyutilsCredential::get_password) call is made.
+            // processing the `get_password` call is made.
+            // A key rejected by a request-key handler is also "no such key".
             KeyUtilsError::KeyDoesNotExist
             | KeyUtilsError::KeyRevoked
-            | KeyUtilsError::KeyExpired => KeyRingError::NoEntry,
-            KeyUtilsError::AccessDenied => KeyRingError::NoStorageAccess(err.0.into()),
+            | KeyUtilsError::KeyExpired
+            | KeyUtilsError::KeyRejected => KeyRingError::NoEntry,
+            KeyUtilsError::AccessDenied
+            | KeyUtilsError::PermissionDenied
+            | KeyUtilsError::KeyringDoesNotExist => KeyRingError::NoStorageAccess(err.0.into()),
             KeyUtilsError::InvalidDescription => KeyRingError::Invalid(
                 "description".to_string(),
                 "rejected by the platform".to_string(),
@@ -37,7 +44,177 @@ impl From<KeyStoreError> for KeyRingError {
                 "password".to_string(),
                 "rejected by the platform".to_string(),
             ),
+            // e.g. reading the payload of a logon key
+            KeyUtilsError::OperationNotSupported => KeyRingError::NotSupportedByStore(
+                "the kernel doesn't support this operation on this key".to_string(),
+            ),
             other => KeyRingError::PlatformFailure(other.into()),
         }
     }
 }
+
+/// Where a credential operation failed in the kernel.
+///
+/// The kernel's error is wrapped in this and returned as the platform error
+/// inside a [PlatformFailure](KeyRingError::PlatformFailure) or
+/// [NoStorageAccess](KeyRingError::NoStorageAccess) error, so that a failure
+/// deep in a read says which of its calls failed, and on which key. (Its
+/// [source](std::error::Error::source) is the kernel's error.)
+///
+/// Access to the credential's keyring and access to just its key are both
+/// refused with a [NoStorageAccess](KeyRingError::NoStorageAccess) error,
+/// told apart by the [kind](ErrorContext::kind): keyring-core has no error
+/// for a single credential the caller may not use, and the store already
+/// reports a key of the wrong owner and a read-only store that way, which
+/// is what callers (and [FallbackStore](crate::FallbackStore)s) act on to use
+/// other storage. [ErrorContext::of] finds the context of an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The credential operation, e.g. `get` or `set`
+    pub operation: String,
+    /// The description of the credential's key, as its [Redaction](crate::Redaction) shows it
+    pub description: String,
+    /// The credential's keyring, if it could be resolved
+    pub keyring: Option<KeySerialId>,
+    /// The keyctl call that failed, if it was one a [Keyctl](crate::Keyctl) makes
+    pub call: Option<KeyctlOp>,
+    /// The key the failed call was made on, when known
+    pub serial: Option<KeySerialId>,
+    /// What kind of failure it was
+    pub kind: FailureKind,
+    /// The security module that may have refused access, if the store
+    /// looks for one (see [StoreBuilder::lsm_hints](crate::StoreBuilder::lsm_hints))
+    pub lsm: Option<LsmHint>,
+    /// The kernel's error
+    pub error: KeyUtilsError,
+}
+
+impl ErrorContext {
+    /// The context wrapped in `err`, if it has one.
+    ///
+    /// ```
+    /// use keyring_core::Error;
+    /// use linux_keyutils_keyring_store::{ErrorContext, FailureKind};
+    ///
+    /// fn keyring_unusable(err: &Error) -> bool {
+    ///     ErrorContext::of(err).is_some_and(|context| {
+    ///         context.kind == FailureKind::KeyringAccessDenied
+    ///     })
+    /// }
+    /// assert!(!keyring_unusable(&Error::NoEntry));
+    /// ```
+    pub fn of(err: &KeyRingError) -> Option<&ErrorContext> {
+        match err {
+            KeyRingError::PlatformFailure(inner) | KeyRingError::NoStorageAccess(inner) => {
+                inner.downcast_ref()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of key '{}' failed", self.operation, self.description)?;
+        if let Some(call) = self.call {
+            write!(f, " in keyctl {call}")?;
+        }
+        if let Some(serial) = self.serial {
+            write!(f, " on key {}", serial.as_raw_id())?;
+        }
+        if let Some(keyring) = self.keyring {
+            write!(f, " (keyring {})", keyring.as_raw_id())?;
+        }
+        write!(f, ": {}", self.error)?;
+        if let Some(lsm) = &self.lsm {
+            write!(f, " ({lsm})")?;
+        }
+        Ok(())
+    }
+}
+
+/// What kind of failure an [ErrorContext] reports, for the kernel errors
+/// that keyring-core has no error of their own for.
+///
+/// (A payload the kernel finds too large for its key type is reported as a
+/// [TooLong](KeyRingError::TooLong) error for `payload` instead.)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The user's key quota is full (`EDQUOT`): delete keys, or raise
+    /// `/proc/sys/kernel/keys/maxkeys` and `maxbytes`.
+    QuotaExceeded,
+    /// A payload is larger than the kernel takes for the operation (`E2BIG`).
+    PayloadTooLarge,
+    /// The keyring a key was to be added to doesn't exist (anymore), e.g.
+    /// because the session it belonged to has ended.
+    MissingKeyring,
+    /// The kernel doesn't have the key type (`ENODEV`), e.g. `big_key` in
+    /// a kernel built without it.
+    UnknownKeyType,
+    /// The kernel refused access (`EACCES` or `EPERM`) to the credential's
+    /// keyring, so that no credential of the store can be used: the store
+    /// is configured with a keyring the process may not use (or not
+    /// possess, e.g. a session keyring in a service without one).
+    ///
+    /// The kernel refuses searches and adds for the key as well as for the
+    /// keyring, so when one is refused the keyring is probed to tell which.
+    KeyringAccessDenied,
+    /// The kernel refused access to the credential's own key, e.g. one
+    /// that another user put under its description, or whose permissions
+    /// were changed: other credentials of the store can still be used.
+    /// (This is still a [NoStorageAccess](KeyRingError::NoStorageAccess)
+    /// error; see [ErrorContext].)
+    KeyAccessDenied,
+    /// Any other kernel error.
+    Other,
+}
+
+impl FailureKind {
+    /// The kind of `error`, as returned by `call`.
+    ///
+    /// Access errors of calls that could be the key's or the keyring's are
+    /// put down to the keyring if `keyring_denied` says it can't be used.
+    pub(crate) fn of(
+        call: Option<KeyctlOp>,
+        error: KeyUtilsError,
+        keyring_denied: impl FnOnce() -> bool,
+    ) -> Self {
+        match (call, error) {
+            (_, KeyUtilsError::QuotaExceeded) => FailureKind::QuotaExceeded,
+            (_, KeyUtilsError::Unknown(libc::E2BIG)) => FailureKind::PayloadTooLarge,
+            (_, KeyUtilsError::Unknown(libc::ENODEV)) => FailureKind::UnknownKeyType,
+            // adds are made on the keyring, so the missing key is the keyring
+            (_, KeyUtilsError::KeyringDoesNotExist)
+            | (Some(KeyctlOp::Add), KeyUtilsError::KeyDoesNotExist) => FailureKind::MissingKeyring,
+            // reads, updates, attribute changes, revocations, and invalidations are made on
+            // the key; searches and adds are made on the keyring, but are also
+            // refused for a key in it that the caller may not search or update
+            (
+                Some(
+                    KeyctlOp::Read
+                    | KeyctlOp::Update
+                    | KeyctlOp::SetTimeout
+                    | KeyctlOp::SetPerm
+                    | KeyctlOp::Chown
+                    | KeyctlOp::Revoke
+                    | KeyctlOp::Invalidate,
+                ),
+                KeyUtilsError::AccessDenied | KeyUtilsError::PermissionDenied,
+            ) => FailureKind::KeyAccessDenied,
+            (_, KeyUtilsError::AccessDenied | KeyUtilsError::PermissionDenied) => {
+                if keyring_denied() {
+                    FailureKind::KeyringAccessDenied
+                } else {
+                    FailureKind::KeyAccessDenied
+                }
+            }
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+impl std::error::Error for ErrorContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use keyring_core::{Entry, Error};
+
+use super::Cred;
+
+/// The callback run when a monitored entry's credential goes away.
+type Callback = Arc<Mutex<dyn FnMut(&Entry) + Send>>;
+
+/// Calls back when the kernel expires or revokes a credential.
+///
+/// Register each entry to watch with [on_expiry](ExpiryMonitor::on_expiry),
+/// giving a callback to re-provision its credential. The callback runs
+/// once each time the credential goes from present to missing, whether its
+/// key expired, was revoked, or was deleted, so an application can put the
+/// secret back before its next read fails, rather than on it.
+///
+/// The kernel sends no notification when a key expires, so credentials
+/// are polled: create one with [new](ExpiryMonitor::new) and call
+/// [check](ExpiryMonitor::check) from your own scheduler, or with
+/// [spawn](ExpiryMonitor::spawn) to have a background thread check them
+/// every `interval`. The background thread also wakes when the earliest
+/// reported expiry of a keyutils credential (see [Cred::time_remaining])
+/// comes due, so expirations are noticed within about a second, even with
+/// a long interval. Revocations and deletions are noticed at the next check
+/// (with the `watch` feature, a `Watcher` reports those as they happen). The background
+/// thread stops when the monitor is dropped.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use keyring_core::Entry;
+/// use linux_keyutils_keyring_store::ExpiryMonitor;
+///
+/// # keyring_core::set_default_store(linux_keyutils_keyring_store::Store::new().unwrap());
+/// let monitor = ExpiryMonitor::spawn(Duration::from_secs(60));
+/// let entry = Entry::new("my-service", "session")?;
+/// monitor.on_expiry(entry, |entry| {
+///     _ = entry.set_password("a freshly issued token");
+/// });
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+pub struct ExpiryMonitor {
+    watches: Arc<Mutex<Vec<Watch>>>,
+    wake: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// A monitored entry.
+struct Watch {
+    entry: Arc<Entry>,
+    /// Whether the credential was there at the last check
+    present: bool,
+    callback: Callback,
+}
+
+impl ExpiryMonitor {
+    /// Create a monitor whose entries are checked manually.
+    pub fn new() -> Self {
+        ExpiryMonitor {
+            watches: Arc::new(Mutex::new(Vec::new())),
+            wake: None,
+            thread: None,
+        }
+    }
+
+    /// Create a monitor that checks its entries every `interval` (or sooner,
+    /// when a credential is due to expire) on a background thread.
+    pub fn spawn(interval: Duration) -> Self {
+        let (wake, woken) = mpsc::channel::<()>();
+        let watches = Arc::new(Mutex::new(Vec::new()));
+        let shared = watches.clone();
+        let thread = std::thread::spawn(move || {
+            let mut wait = interval;
+            loop {
+                match woken.recv_timeout(wait) {
+                    // a new entry may expire sooner than any other
+                    Ok(()) => {}
+                    Err(RecvTimeoutError::Timeout) => _ = check(&shared),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                let started = Instant::now();
+                wait = next_expiry(&shared).map_or(interval, |due| due.min(interval));
+                wait = wait.saturating_sub(started.elapsed());
+            }
+        });
+        ExpiryMonitor {
+            watches,
+            wake: Some(wake),
+            thread: Some(thread),
+        }
+    }
+
+    /// Run `callback` whenever `entry`'s credential goes away.
+    ///
+    /// If the credential is missing now, the callback first runs once it
+    /// has been provisioned and has then gone away again. The callback runs
+    /// on the thread that checks (the background thread of a spawned
+    /// monitor), and may set the entry's credential, or register other
+    /// entries.
+    pub fn on_expiry(&self, entry: Entry, callback: impl FnMut(&Entry) + Send + 'static) {
+        let present = !matches!(entry.get_credential(), Err(Error::NoEntry));
+        self.watches.lock().unwrap().push(Watch {
+            entry: Arc::new(entry),
+            present,
+            callback: Arc::new(Mutex::new(callback)),
+        });
+        if let Some(wake) = &self.wake {
+            _ = wake.send(());
+        }
+    }
+
+    /// Check every monitored entry, running the callbacks of those whose
+    /// credential has gone away since the last check.
+    ///
+    /// Returns the number of callbacks run.
+    pub fn check(&self) -> usize {
+        check(&self.watches)
+    }
+
+    /// The number of monitored entries.
+    pub fn len(&self) -> usize {
+        self.watches.lock().unwrap().len()
+    }
+
+    /// Whether there are no monitored entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ExpiryMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check the watched entries, and run the callbacks of the ones that went away.
+fn check(watches: &Mutex<Vec<Watch>>) -> usize {
+    let mut gone = Vec::new();
+    let mut list = watches.lock().unwrap();
+    for watch in list.iter_mut() {
+        // errors other than a missing credential say nothing about it, so it's left as it was
+        let present = match watch.entry.get_credential() {
+            Err(Error::NoEntry) => false,
+            Err(_) => continue,
+            Ok(_) => true,
+        };
+        if watch.present && !present {
+            gone.push((watch.entry.clone(), watch.callback.clone()));
+        }
+        watch.present = present;
+    }
+    drop(list);
+    // the list isn't locked while callbacks run, so they can register entries
+    for (entry, callback) in &gone {
+        (callback.lock().unwrap())(entry);
+        // a re-provisioned credential can go away again
+        if entry.get_credential().is_ok() {
+            let mut list = watches.lock().unwrap();
+            for watch in list.iter_mut() {
+                if Arc::ptr_eq(&watch.entry, entry) {
+                    watch.present = true;
+                }
+            }
+        }
+    }
+    gone.len()
+}
+
+/// How long until the first of the watched keyutils credentials expires, if any will.
+fn next_expiry(watches: &Mutex<Vec<Watch>>) -> Option<Duration> {
+    let list = watches.lock().unwrap();
+    list.iter()
+        .filter(|watch| watch.present)
+        .filter_map(|watch| watch.entry.as_any().downcast_ref::<Cred>())
+        .filter_map(|cred| cred.time_remaining().ok().flatten())
+        .min()
+}
+
+impl Drop for ExpiryMonitor {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread
+        self.wake.take();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for ExpiryMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExpiryMonitor")
+            .field("entries", &self.len())
+            .field("background", &self.thread.is_some())
+            .finish()
+    }
+}
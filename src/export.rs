@@ -0,0 +1,145 @@
+//! Passphrase-protected archives of a store's credentials.
+//!
+//! An archive starts with a version header, the PBKDF2 iteration count (a
+//! little-endian `u32`), a random salt, and a random nonce. Then comes the
+//! ChaCha20 ciphertext of the records, and an HMAC-SHA256 tag over everything
+//! before it. The encryption and MAC keys are derived from the passphrase with
+//! PBKDF2-HMAC-SHA256.
+//!
+//! Each record holds a key's type and description (each prefixed with its length
+//! as a little-endian `u16`), its permission mask (a little-endian `u32`), and its
+//! payload (prefixed with its length as a little-endian `u32`). Payloads are kept
+//! exactly as they are in the kernel, envelopes and all.
+use keyring_core::{Error, Result};
+use linux_keyutils::KeyType;
+
+use super::compat::bad_store_format;
+use super::crypto::{self, DIGEST_LEN, KEY_LEN, NONCE_LEN};
+use super::store::type_name;
+use super::{Perm, SecretBytes};
+
+const HEADER: &[u8] = b"LKKA\x01";
+const SALT_LEN: usize = 16;
+const PREAMBLE_LEN: usize = HEADER.len() + 4 + SALT_LEN + NONCE_LEN;
+
+/// The PBKDF2 iteration count of new archives.
+pub(crate) const ITERATIONS: u32 = 200_000;
+
+/// The most PBKDF2 iterations an archive may ask for, so a corrupt one can't stall an import.
+const MAX_ITERATIONS: u32 = 10_000_000;
+
+/// One key in an archive.
+pub(crate) struct Record {
+    pub(crate) key_type: KeyType,
+    pub(crate) description: String,
+    pub(crate) permissions: Perm,
+    pub(crate) payload: SecretBytes,
+}
+
+/// Derive the encryption and MAC keys from the passphrase.
+fn keys(passphrase: &[u8], salt: &[u8], iterations: u32) -> ([u8; KEY_LEN], [u8; DIGEST_LEN]) {
+    let mut master = crypto::pbkdf2_hmac_sha256(passphrase, salt, iterations);
+    let enc = crypto::hmac_sha256(&master, &[b"linux-keyutils-keyring-store export enc"]);
+    let mac = crypto::hmac_sha256(&master, &[b"linux-keyutils-keyring-store export mac"]);
+    crypto::wipe(&mut master);
+    (enc, mac)
+}
+
+/// Seal `records` into an archive.
+pub(crate) fn seal(records: &[Record], passphrase: &[u8], iterations: u32) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    crypto::random_bytes(&mut salt).map_err(|e| Error::PlatformFailure(e.into()))?;
+    crypto::random_bytes(&mut nonce).map_err(|e| Error::PlatformFailure(e.into()))?;
+    let mut archive = Vec::new();
+    archive.extend_from_slice(HEADER);
+    archive.extend_from_slice(&iterations.to_le_bytes());
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&nonce);
+    for record in records {
+        push_str(&mut archive, type_name(record.key_type));
+        push_str(&mut archive, &record.description);
+        archive.extend_from_slice(&record.permissions.bits().to_le_bytes());
+        archive.extend_from_slice(&(record.payload.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&record.payload);
+    }
+    let (mut enc, mut mac) = keys(passphrase, &salt, iterations);
+    crypto::chacha20(&enc, &nonce, &mut archive[PREAMBLE_LEN..]);
+    let tag = crypto::hmac_sha256(&mac, &[&archive]);
+    archive.extend_from_slice(&tag);
+    crypto::wipe(&mut enc);
+    crypto::wipe(&mut mac);
+    Ok(archive)
+}
+
+/// Open an archive, checking its tag.
+///
+/// A wrong passphrase can't be told apart from a corrupt archive: both are
+/// a [BadStoreFormat](Error::BadStoreFormat) error.
+pub(crate) fn open(archive: &[u8], passphrase: &[u8]) -> Result<Vec<Record>> {
+    let corrupt =
+        || bad_store_format("the archive is corrupt, or the passphrase is wrong".to_string());
+    if archive.len() < PREAMBLE_LEN + DIGEST_LEN || !archive.starts_with(HEADER) {
+        return Err(corrupt());
+    }
+    let (body, tag) = archive.split_at(archive.len() - DIGEST_LEN);
+    let rest = &body[HEADER.len()..];
+    let (iterations, rest) = rest.split_first_chunk::<4>().ok_or_else(corrupt)?;
+    let iterations = u32::from_le_bytes(*iterations);
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(corrupt());
+    }
+    let (salt, rest) = rest.split_first_chunk::<SALT_LEN>().ok_or_else(corrupt)?;
+    let (nonce, ciphertext) = rest.split_first_chunk::<NONCE_LEN>().ok_or_else(corrupt)?;
+    let (mut enc, mut mac) = keys(passphrase, salt, iterations);
+    let expected = crypto::hmac_sha256(&mac, &[body]);
+    crypto::wipe(&mut mac);
+    if !crypto::constant_time_eq(&expected, tag) {
+        crypto::wipe(&mut enc);
+        return Err(corrupt());
+    }
+    let plaintext = SecretBytes::new({
+        let mut plaintext = ciphertext.to_vec();
+        crypto::chacha20(&enc, nonce, &mut plaintext);
+        crypto::wipe(&mut enc);
+        plaintext
+    });
+    let mut rest: &[u8] = &plaintext;
+    let mut records = Vec::new();
+    while !rest.is_empty() {
+        let key_type = take_str(&mut rest).ok_or_else(corrupt)?;
+        let key_type = KeyType::try_from(key_type.as_str()).map_err(|_| corrupt())?;
+        let description = take_str(&mut rest).ok_or_else(corrupt)?;
+        let (permissions, tail) = rest.split_first_chunk::<4>().ok_or_else(corrupt)?;
+        let (len, tail) = tail.split_first_chunk::<4>().ok_or_else(corrupt)?;
+        let len = u32::from_le_bytes(*len) as usize;
+        if tail.len() < len {
+            return Err(corrupt());
+        }
+        let (payload, tail) = tail.split_at(len);
+        records.push(Record {
+            key_type,
+            description,
+            permissions: Perm::from_bits(u32::from_le_bytes(*permissions)),
+            payload: SecretBytes::new(payload.to_vec()),
+        });
+        rest = tail;
+    }
+    Ok(records)
+}
+
+fn push_str(archive: &mut Vec<u8>, s: &str) {
+    archive.extend_from_slice(&(s.len() as u16).to_le_bytes());
+    archive.extend_from_slice(s.as_bytes());
+}
+
+fn take_str(rest: &mut &[u8]) -> Option<String> {
+    let (len, tail) = rest.split_first_chunk::<2>()?;
+    let len = u16::from_le_bytes(*len) as usize;
+    if tail.len() < len {
+        return None;
+    }
+    let (s, tail) = tail.split_at(len);
+    *rest = tail;
+    String::from_utf8(s.to_vec()).ok()
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use keyring_core::api::{CredentialApi, CredentialPersistence, CredentialStoreApi};
+use keyring_core::{Credential, CredentialStore, Entry, Error, Result};
+
+use super::Store;
+
+/// Which failures of the keyutils store make a [FallbackStore] use its secondary store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FallbackPolicy {
+    /// Use the secondary store for everything if keyutils is unavailable
+    /// when the fallback store is created (e.g. because `keyctl` is blocked
+    /// by seccomp, or the process has no keyrings), and never otherwise.
+    ///
+    /// This is the default.
+    #[default]
+    Unavailable,
+    /// As for [FallbackPolicy::Unavailable], and also redo any operation
+    /// that fails with a [NoStorageAccess](Error::NoStorageAccess) or
+    /// [PlatformFailure](Error::PlatformFailure) error on the secondary store.
+    ///
+    /// Since a credential may then have been written to either store, reads
+    /// that find nothing in keyutils also look in the secondary store, and
+    /// deletes delete from both.
+    StorageErrors,
+}
+
+impl FallbackPolicy {
+    /// Whether this error from the keyutils store calls for the secondary store.
+    fn falls_back(&self, err: &Error) -> bool {
+        match self {
+            FallbackPolicy::Unavailable => false,
+            FallbackPolicy::StorageErrors => {
+                matches!(err, Error::NoStorageAccess(_) | Error::PlatformFailure(_))
+            }
+        }
+    }
+}
+
+/// A credential store that uses keyutils when it can, and another store when it can't.
+///
+/// Whether keyutils is available is checked (with [Store::check_available])
+/// when the fallback store is created. Entry modifiers are only passed to
+/// the keyutils store, since they are specific to it.
+///
+/// ```
+/// use linux_keyutils_keyring_store::{FallbackPolicy, FallbackStore, Store};
+///
+/// let store = FallbackStore::new(
+///     Store::new().unwrap(),
+///     keyring_core::mock::Store::new().unwrap(),
+///     FallbackPolicy::Unavailable,
+/// );
+/// keyring_core::set_default_store(store);
+/// ```
+#[derive(Debug)]
+pub struct FallbackStore {
+    id: String,
+    primary: Arc<Store>,
+    secondary: Arc<CredentialStore>,
+    policy: FallbackPolicy,
+    available: bool,
+}
+
+impl FallbackStore {
+    /// Use the `secondary` store in place of the `primary` one as `policy` says.
+    pub fn new(
+        primary: Arc<Store>,
+        secondary: Arc<CredentialStore>,
+        policy: FallbackPolicy,
+    ) -> Arc<Self> {
+        let available = primary.check_available().is_ok();
+        Self::new_internal(primary, secondary, policy, available)
+    }
+
+    pub(crate) fn new_internal(
+        primary: Arc<Store>,
+        secondary: Arc<CredentialStore>,
+        policy: FallbackPolicy,
+        available: bool,
+    ) -> Arc<Self> {
+        Arc::new(FallbackStore {
+            id: Store::new_id(),
+            primary,
+            secondary,
+            policy,
+            available,
+        })
+    }
+
+    /// Whether keyutils was found to be unavailable, so everything goes to the secondary store.
+    pub fn fell_back(&self) -> bool {
+        !self.available
+    }
+}
+
+impl CredentialStoreApi for FallbackStore {
+    /// See the keyring-core API docs.
+    fn vendor(&self) -> String {
+        format!(
+            "Linux keyutils with fallback to {}",
+            self.secondary.vendor()
+        )
+    }
+
+    /// See the keyring-core API docs.
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// If the store fell back, this is an entry of the secondary store
+    /// (built without the modifiers).
+    fn build(
+        &self,
+        service: &str,
+        user: &str,
+        modifiers: Option<&HashMap<&str, &str>>,
+    ) -> Result<Entry> {
+        if !self.available {
+            return self.secondary.build(service, user, None);
+        }
+        let primary = self.primary.build(service, user, modifiers)?;
+        if self.policy == FallbackPolicy::Unavailable {
+            return Ok(primary);
+        }
+        let cred = FallbackCred {
+            primary,
+            secondary: self.secondary.build(service, user, None)?,
+            policy: self.policy,
+        };
+        Ok(Entry::new_with_credential(Arc::new(cred)))
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// Only a store that fell back can search (if its secondary store can).
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Entry>> {
+        if self.available {
+            self.primary.search(spec)
+        } else {
+            self.secondary.search(spec)
+        }
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    fn persistence(&self) -> CredentialPersistence {
+        if self.available {
+            self.primary.persistence()
+        } else {
+            self.secondary.persistence()
+        }
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A credential of a [FallbackStore] that falls back operation by operation.
+#[derive(Debug)]
+pub struct FallbackCred {
+    /// The entry in the keyutils store
+    pub primary: Entry,
+    /// The entry in the secondary store
+    pub secondary: Entry,
+    policy: FallbackPolicy,
+}
+
+impl FallbackCred {
+    /// Run `op` on the keyutils entry, and on the secondary entry if that calls for it.
+    ///
+    /// For reads, a missing credential also calls for the secondary entry.
+    fn attempt<T>(&self, read: bool, op: impl Fn(&Entry) -> Result<T>) -> Result<T> {
+        match op(&self.primary) {
+            Err(Error::NoEntry) if read => op(&self.secondary),
+            Err(err) if self.policy.falls_back(&err) => op(&self.secondary),
+            result => result,
+        }
+    }
+}
+
+impl CredentialApi for FallbackCred {
+    /// See the keyring-core API docs.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.attempt(false, |entry| entry.set_secret(secret))
+    }
+
+    /// See the keyring-core API docs.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.attempt(true, |entry| entry.get_secret())
+    }
+
+    /// See the keyring-core API docs.
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.attempt(true, |entry| entry.get_attributes())
+    }
+
+    /// See the keyring-core API docs.
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        self.attempt(false, |entry| entry.update_attributes(attributes))
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// The credential is deleted from both stores, and this only fails if
+    /// it couldn't be deleted from either.
+    fn delete_credential(&self) -> Result<()> {
+        let primary = self.primary.delete_credential();
+        let secondary = self.secondary.delete_credential();
+        match (primary, secondary) {
+            (Ok(()), _) | (_, Ok(())) => Ok(()),
+            (Err(Error::NoEntry), Err(err)) | (Err(err), _) => Err(err),
+        }
+    }
+
+    /// See the keyring-core API docs.
+    fn get_credential(&self) -> Result<Option<Arc<Credential>>> {
+        self.attempt(true, |entry| entry.get_credential())?;
+        Ok(None)
+    }
+
+    /// See the keyring-core API docs.
+    fn get_specifiers(&self) -> Option<(String, String)> {
+        self.primary.get_specifiers()
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
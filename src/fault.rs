@@ -0,0 +1,268 @@
+//! Deterministic fault injection for the keyctl operations of a store.
+//!
+//! A [FaultInjector] wraps a [Keyctl] (the kernel, or a [FakeKeyctl](crate::FakeKeyctl))
+//! and passes each operation through to it, except for the ones it has
+//! been told to fail. Failures are scheduled by call count rather than
+//! chance, so a test that provokes one fails the same way every run.
+//!
+//! This module is only available with the `fault_injection` feature.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use linux_keyutils::{KeyError, KeySerialId, KeyType};
+
+use super::keyctl::kernel;
+use super::{KeyDescription, Keyctl, KeyctlOp, Perm};
+
+/// A [Keyctl] that fails chosen calls of another.
+///
+/// Calls of each operation are counted from 1, and every call counts,
+/// including failed ones. A single credential operation can make several
+/// calls of one kind (a read of a big payload first asks for its length,
+/// for instance), so tests usually schedule faults relative to
+/// [calls](FaultInjector::calls), or with [fail_next](FaultInjector::fail_next):
+///
+/// ```
+/// use std::sync::Arc;
+/// use linux_keyutils::KeyError;
+/// use linux_keyutils_keyring_store::{FaultInjector, KeyctlOp, StoreBuilder};
+///
+/// let faults = Arc::new(FaultInjector::kernel());
+/// let store = StoreBuilder::new().keyctl(faults.clone()).build().unwrap();
+/// // the next write finds the user's key quota full (EDQUOT)
+/// faults.fail_next(KeyctlOp::Add, KeyError::QuotaExceeded);
+/// ```
+#[derive(Debug)]
+pub struct FaultInjector {
+    inner: Arc<dyn Keyctl>,
+    state: Mutex<InjectorState>,
+}
+
+#[derive(Debug, Default)]
+struct InjectorState {
+    /// How many calls of each operation have been made
+    calls: HashMap<KeyctlOp, usize>,
+    /// The calls to fail, by operation and number, and what with
+    faults: Vec<(KeyctlOp, usize, KeyError)>,
+    /// The searches whose keys are revoked once found, by number
+    revocations: Vec<usize>,
+    /// The keys that appear revoked
+    revoked: Vec<KeySerialId>,
+}
+
+impl InjectorState {
+    /// Count a call of `op`, failing it if it's scheduled to fail.
+    fn call(&mut self, op: KeyctlOp) -> Result<usize, KeyError> {
+        let count = self.calls.entry(op).or_insert(0);
+        *count += 1;
+        let n = *count;
+        match self
+            .faults
+            .iter()
+            .position(|(failing, nth, _)| *failing == op && *nth == n)
+        {
+            Some(index) => Err(self.faults.remove(index).2),
+            None => Ok(n),
+        }
+    }
+
+    /// Fail with [KeyRevoked](KeyError::KeyRevoked) if `key` appears revoked.
+    fn check(&self, key: KeySerialId) -> Result<(), KeyError> {
+        if self.revoked.contains(&key) {
+            return Err(KeyError::KeyRevoked);
+        }
+        Ok(())
+    }
+}
+
+impl FaultInjector {
+    /// Inject faults into the operations of `inner`.
+    pub fn new(inner: Arc<dyn Keyctl>) -> Self {
+        FaultInjector {
+            inner,
+            state: Mutex::new(InjectorState::default()),
+        }
+    }
+
+    /// Inject faults into the kernel's operations.
+    pub fn kernel() -> Self {
+        Self::new(kernel())
+    }
+
+    /// How many calls of `op` have been made so far.
+    pub fn calls(&self, op: KeyctlOp) -> usize {
+        let state = self.state.lock().unwrap();
+        state.calls.get(&op).copied().unwrap_or(0)
+    }
+
+    /// Fail the `nth` call of `op` (counting from 1) with `err`, e.g.
+    /// [AccessDenied](KeyError::AccessDenied) on the third read.
+    pub fn fail_nth(&self, op: KeyctlOp, nth: usize, err: KeyError) {
+        let mut state = self.state.lock().unwrap();
+        state.faults.push((op, nth, err));
+    }
+
+    /// Fail the next call of `op` with `err`.
+    pub fn fail_next(&self, op: KeyctlOp, err: KeyError) {
+        let mut state = self.state.lock().unwrap();
+        let nth = state.calls.get(&op).copied().unwrap_or(0) + 1;
+        state.faults.push((op, nth, err));
+    }
+
+    /// Revoke the key found by the `nth` search (counting from 1) as soon as
+    /// it's found, as if another process had revoked it between the search
+    /// and whatever the caller does with the key next.
+    ///
+    /// The key is only revoked as seen through this injector: its reads,
+    /// links, and searches fail with [KeyRevoked](KeyError::KeyRevoked), as
+    /// a revoked key's do, until a write replaces it or it's invalidated.
+    pub fn revoke_after_search(&self, nth: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.revocations.push(nth);
+    }
+
+    /// Revoke the key found by the next search, as [revoke_after_search](FaultInjector::revoke_after_search) does.
+    pub fn revoke_next_found(&self) {
+        let mut state = self.state.lock().unwrap();
+        let nth = state.calls.get(&KeyctlOp::Search).copied().unwrap_or(0) + 1;
+        state.revocations.push(nth);
+    }
+
+    /// Cancel every scheduled fault, and un-revoke every key.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.faults.clear();
+        state.revocations.clear();
+        state.revoked.clear();
+    }
+}
+
+impl Keyctl for FaultInjector {
+    fn search(
+        &self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+    ) -> Result<KeySerialId, KeyError> {
+        let n = self.state.lock().unwrap().call(KeyctlOp::Search)?;
+        let key = self.inner.search(keyring, key_type, description)?;
+        let mut state = self.state.lock().unwrap();
+        state.check(key)?;
+        if let Some(index) = state.revocations.iter().position(|nth| *nth == n) {
+            state.revocations.remove(index);
+            state.revoked.push(key);
+        }
+        Ok(key)
+    }
+
+    fn add(
+        &self,
+        key_type: KeyType,
+        description: &str,
+        payload: &[u8],
+        keyring: KeySerialId,
+    ) -> Result<KeySerialId, KeyError> {
+        self.state.lock().unwrap().call(KeyctlOp::Add)?;
+        let key = self.inner.add(key_type, description, payload, keyring)?;
+        // a write replaces a revoked key (in the kernel, with a new one)
+        let mut state = self.state.lock().unwrap();
+        state.revoked.retain(|revoked| *revoked != key);
+        Ok(key)
+    }
+
+    fn update(&self, key: KeySerialId, payload: &[u8]) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Update)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.update(key, payload)
+    }
+
+    fn link(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Link)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.link(key, keyring)
+    }
+
+    fn unlink(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        // a revoked key can still be unlinked
+        self.state.lock().unwrap().call(KeyctlOp::Unlink)?;
+        self.inner.unlink(key, keyring)
+    }
+
+    fn read(&self, key: KeySerialId, buffer: &mut [u8]) -> Result<usize, KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Read)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.read(key, buffer)
+    }
+
+    fn set_timeout(&self, key: KeySerialId, seconds: usize) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::SetTimeout)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.set_timeout(key, seconds)
+    }
+
+    fn set_perm(&self, key: KeySerialId, perm: Perm) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::SetPerm)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.set_perm(key, perm)
+    }
+
+    fn chown(&self, key: KeySerialId, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Chown)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.chown(key, uid, gid)
+    }
+
+    fn revoke(&self, key: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Revoke)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.revoke(key)
+    }
+
+    fn invalidate(&self, key: KeySerialId) -> Result<(), KeyError> {
+        self.state.lock().unwrap().call(KeyctlOp::Invalidate)?;
+        self.inner.invalidate(key)?;
+        let mut state = self.state.lock().unwrap();
+        state.revoked.retain(|revoked| *revoked != key);
+        Ok(())
+    }
+
+    fn describe(&self, key: KeySerialId) -> Result<KeyDescription, KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Describe)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.describe(key)
+    }
+
+    fn links(&self, keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError> {
+        self.state.lock().unwrap().call(KeyctlOp::Links)?;
+        self.inner.links(keyring)
+    }
+
+    fn move_key(
+        &self,
+        key: KeySerialId,
+        from: KeySerialId,
+        to: KeySerialId,
+    ) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(KeyctlOp::Move)?;
+        state.check(key)?;
+        drop(state);
+        self.inner.move_key(key, from, to)
+    }
+}
@@ -0,0 +1,362 @@
+//! A C interface to the store, for services on the same host that aren't written in Rust.
+//!
+//! The functions here create a [Store] from config options and get, set,
+//! delete, and list credentials in it, so that C, C++, Go (via cgo), and
+//! other programs describe their keys exactly as Rust applications using the
+//! same configuration do, and so can share credentials with them.
+//!
+//! Build the shared library with the `ffi` feature, e.g.
+//! `cargo rustc --release --features ffi --lib --crate-type cdylib`, and
+//! declare the functions with `include/keyutils_store.h`.
+//!
+//! Functions that can fail return one of the `KEYUTILS_STORE_*` status
+//! codes (zero for success), and leave a message describing the failure
+//! for [keyutils_store_last_error]. Secrets and lists returned by the
+//! library must be freed with its own free functions.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+use std::sync::Arc;
+
+use keyring_core::Error;
+use keyring_core::api::CredentialStoreApi;
+
+use super::crypto::wipe;
+use super::{SecretBytes, Store};
+
+/// Success.
+pub const KEYUTILS_STORE_OK: i32 = 0;
+/// There is no credential for the service and user.
+pub const KEYUTILS_STORE_NO_ENTRY: i32 = 1;
+/// The caller may not access the keyring or key.
+pub const KEYUTILS_STORE_NO_ACCESS: i32 = 2;
+/// A service, user, config option, or secret is invalid or too long.
+pub const KEYUTILS_STORE_INVALID: i32 = 3;
+/// The kernel doesn't support what was asked.
+pub const KEYUTILS_STORE_NOT_SUPPORTED: i32 = 4;
+/// Any other failure, e.g. of a system call.
+pub const KEYUTILS_STORE_FAILURE: i32 = 5;
+/// A required pointer argument was null, or a string wasn't UTF-8.
+pub const KEYUTILS_STORE_BAD_ARGUMENT: i32 = 6;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Remember `message` as the calling thread's last error, and return `code`.
+fn fail(code: i32, message: String) -> i32 {
+    let message = CString::new(message.replace('\0', "?")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// The status code for `err`, which becomes the last error.
+fn status(err: Error) -> i32 {
+    let code = match &err {
+        Error::NoEntry => KEYUTILS_STORE_NO_ENTRY,
+        Error::NoStorageAccess(_) => KEYUTILS_STORE_NO_ACCESS,
+        Error::Invalid(_, _) | Error::TooLong(_, _) | Error::Ambiguous(_) => KEYUTILS_STORE_INVALID,
+        Error::NotSupportedByStore(_) => KEYUTILS_STORE_NOT_SUPPORTED,
+        _ => KEYUTILS_STORE_FAILURE,
+    };
+    fail(code, err.to_string())
+}
+
+/// Read a string argument, which must be non-null, NUL-terminated UTF-8.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn string_arg<'a>(name: &str, ptr: *const c_char) -> Result<&'a str, i32> {
+    if ptr.is_null() {
+        return Err(fail(KEYUTILS_STORE_BAD_ARGUMENT, format!("{name} is null")));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| fail(KEYUTILS_STORE_BAD_ARGUMENT, format!("{name} is not UTF-8")))
+}
+
+/// The store behind a handle.
+///
+/// # Safety
+///
+/// `store` must be null or a handle from [keyutils_store_new] that hasn't been freed.
+unsafe fn store_arg<'a>(store: *const Store) -> Result<&'a Store, i32> {
+    unsafe { store.as_ref() }
+        .ok_or_else(|| fail(KEYUTILS_STORE_BAD_ARGUMENT, "store is null".to_string()))
+}
+
+/// The store, service, and user arguments that name a credential.
+///
+/// # Safety
+///
+/// As for [store_arg] and [string_arg].
+unsafe fn entry_args<'a>(
+    store: *const Store,
+    service: *const c_char,
+    user: *const c_char,
+) -> Result<(&'a Store, &'a str, &'a str), i32> {
+    unsafe {
+        Ok((
+            store_arg(store)?,
+            string_arg("service", service)?,
+            string_arg("user", user)?,
+        ))
+    }
+}
+
+/// Create a store with `count` config options, given as parallel arrays of
+/// names and values (see [Store::new_with_configuration]).
+///
+/// Returns null on failure; see [keyutils_store_last_error] for why. The
+/// store must be freed with [keyutils_store_free].
+///
+/// # Safety
+///
+/// `names` and `values` must each point to `count` NUL-terminated strings
+/// (or may be null if `count` is zero).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_new(
+    names: *const *const c_char,
+    values: *const *const c_char,
+    count: usize,
+) -> *mut Store {
+    let mut config = HashMap::new();
+    if count > 0 && (names.is_null() || values.is_null()) {
+        fail(
+            KEYUTILS_STORE_BAD_ARGUMENT,
+            "names or values is null".to_string(),
+        );
+        return ptr::null_mut();
+    }
+    for i in 0..count {
+        let (Ok(name), Ok(value)) = (unsafe {
+            (
+                string_arg("name", *names.add(i)),
+                string_arg("value", *values.add(i)),
+            )
+        }) else {
+            return ptr::null_mut();
+        };
+        config.insert(name, value);
+    }
+    match Store::new_with_configuration(&config) {
+        Ok(store) => Arc::into_raw(store).cast_mut(),
+        Err(err) => {
+            status(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a store created by [keyutils_store_new]. Does nothing if `store` is null.
+///
+/// # Safety
+///
+/// `store` must be null or a handle from [keyutils_store_new] that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_free(store: *mut Store) {
+    if !store.is_null() {
+        drop(unsafe { Arc::from_raw(store.cast_const()) });
+    }
+}
+
+/// Set the secret of the credential for `service` and `user` to the `len`
+/// bytes at `secret`.
+///
+/// # Safety
+///
+/// `store` must be a live handle, `service` and `user` NUL-terminated
+/// strings, and `secret` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_set(
+    store: *const Store,
+    service: *const c_char,
+    user: *const c_char,
+    secret: *const u8,
+    len: usize,
+) -> i32 {
+    let (store, service, user) = match unsafe { entry_args(store, service, user) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    if secret.is_null() {
+        return fail(KEYUTILS_STORE_BAD_ARGUMENT, "secret is null".to_string());
+    }
+    let secret = unsafe { std::slice::from_raw_parts(secret, len) };
+    match store
+        .build(service, user, None)
+        .and_then(|entry| entry.set_secret(secret))
+    {
+        Ok(()) => KEYUTILS_STORE_OK,
+        Err(err) => status(err),
+    }
+}
+
+/// Get the secret of the credential for `service` and `user`.
+///
+/// On success, `*secret` points to the secret and `*len` is its length.
+/// The secret must be freed (which wipes it) with [keyutils_store_free_secret].
+///
+/// # Safety
+///
+/// `store` must be a live handle, `service` and `user` NUL-terminated
+/// strings, and `secret` and `len` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_get(
+    store: *const Store,
+    service: *const c_char,
+    user: *const c_char,
+    secret: *mut *mut u8,
+    len: *mut usize,
+) -> i32 {
+    let (store, service, user) = match unsafe { entry_args(store, service, user) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    if secret.is_null() || len.is_null() {
+        return fail(
+            KEYUTILS_STORE_BAD_ARGUMENT,
+            "secret or len is null".to_string(),
+        );
+    }
+    match store
+        .build(service, user, None)
+        .and_then(|entry| entry.get_secret())
+    {
+        Ok(value) => {
+            // a boxed slice's allocation is exactly its length, so it can be rebuilt to free
+            // it; shrinking the Vec into one may reallocate without wiping, so it's copied
+            let value = SecretBytes::new(value);
+            let value = Box::into_raw(Box::<[u8]>::from(value.expose_secret()));
+            unsafe {
+                *len = value.len();
+                *secret = value.cast();
+            }
+            KEYUTILS_STORE_OK
+        }
+        Err(err) => status(err),
+    }
+}
+
+/// Wipe and free a secret returned by [keyutils_store_get]. Does nothing if `secret` is null.
+///
+/// # Safety
+///
+/// `secret` and `len` must be exactly as returned by `keyutils_store_get`,
+/// and the secret must not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_free_secret(secret: *mut u8, len: usize) {
+    if !secret.is_null() {
+        let mut secret = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(secret, len)) };
+        wipe(&mut secret);
+    }
+}
+
+/// Delete the credential for `service` and `user`.
+///
+/// # Safety
+///
+/// `store` must be a live handle, and `service` and `user` NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_delete(
+    store: *const Store,
+    service: *const c_char,
+    user: *const c_char,
+) -> i32 {
+    let (store, service, user) = match unsafe { entry_args(store, service, user) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+    match store
+        .build(service, user, None)
+        .and_then(|entry| entry.delete_credential())
+    {
+        Ok(()) => KEYUTILS_STORE_OK,
+        Err(err) => status(err),
+    }
+}
+
+/// List the services and users of the credentials in the store (see [Store::entries]).
+///
+/// On success, `*services` and `*users` point to parallel arrays of
+/// `*count` strings, which must be freed with [keyutils_store_free_list].
+/// Keys whose descriptions can't be read back into a service and user
+/// (e.g. hashed ones) aren't listed.
+///
+/// # Safety
+///
+/// `store` must be a live handle, and `services`, `users`, and `count`
+/// must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_list(
+    store: *const Store,
+    services: *mut *mut *mut c_char,
+    users: *mut *mut *mut c_char,
+    count: *mut usize,
+) -> i32 {
+    let store = match unsafe { store_arg(store) } {
+        Ok(store) => store,
+        Err(code) => return code,
+    };
+    if services.is_null() || users.is_null() || count.is_null() {
+        return fail(
+            KEYUTILS_STORE_BAD_ARGUMENT,
+            "services, users, or count is null".to_string(),
+        );
+    }
+    let entries = match store.entries() {
+        Ok(entries) => entries,
+        Err(err) => return status(err),
+    };
+    let (found_services, found_users): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .filter_map(|entry| entry.get_specifiers())
+        .filter_map(|(service, user)| Some((CString::new(service).ok()?, CString::new(user).ok()?)))
+        .map(|(service, user)| (service.into_raw(), user.into_raw()))
+        .unzip();
+    unsafe {
+        *count = found_services.len();
+        *services = Box::into_raw(found_services.into_boxed_slice()).cast();
+        *users = Box::into_raw(found_users.into_boxed_slice()).cast();
+    }
+    KEYUTILS_STORE_OK
+}
+
+/// Free the lists returned by [keyutils_store_list].
+///
+/// # Safety
+///
+/// The arguments must be exactly as returned by `keyutils_store_list`,
+/// and the lists must not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn keyutils_store_free_list(
+    services: *mut *mut c_char,
+    users: *mut *mut c_char,
+    count: usize,
+) {
+    for list in [services, users] {
+        if list.is_null() {
+            continue;
+        }
+        let list = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(list, count)) };
+        for &string in list.iter() {
+            drop(unsafe { CString::from_raw(string) });
+        }
+    }
+}
+
+/// A description of the calling thread's last failure, or null if there
+/// hasn't been one.
+///
+/// The string is owned by the library, and is valid until the thread's
+/// next failing call.
+#[unsafe(no_mangle)]
+pub extern "C" fn keyutils_store_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -0,0 +1,254 @@
+use std::ffi::CStr;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{Key, KeyError, KeySerialId};
+
+use super::crypto::{hmac_sha512, to_hex};
+use super::error::KeyStoreError;
+use super::keyctl::{KernelKeyctl, Keyctl};
+use super::{SecretBytes, Store, sys};
+
+/// The key type that hands fscrypt its keys without user space reading them back.
+const FSCRYPT_PROVISIONING: &CStr = c"fscrypt-provisioning";
+/// The prefix of the descriptions of a store's provisioning keys.
+const FSCRYPT_PREFIX: &str = "fscrypt:";
+/// The shortest and longest master keys fscrypt accepts.
+const FSCRYPT_MIN_KEY_SIZE: usize = 16;
+const FSCRYPT_MAX_KEY_SIZE: usize = 64;
+/// The key specifier type of v2 policies, which name keys by identifier.
+const FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER: u32 = 2;
+/// The HKDF context fscrypt derives key identifiers in.
+const HKDF_CONTEXT_KEY_IDENTIFIER: u8 = 1;
+/// `_IOWR('f', 23, struct fscrypt_add_key_arg)`
+const FS_IOC_ADD_ENCRYPTION_KEY: u32 = 0xc050_6617;
+/// `_IOWR('f', 24, struct fscrypt_remove_key_arg)`
+const FS_IOC_REMOVE_ENCRYPTION_KEY: u32 = 0xc040_6618;
+
+/// The kernel's `struct fscrypt_key_specifier`.
+#[repr(C)]
+struct KeySpecifier {
+    spec_type: u32,
+    reserved: u32,
+    identifier: [u8; 32],
+}
+
+/// The kernel's `struct fscrypt_add_key_arg`, without a raw key, since it names a key instead.
+#[repr(C)]
+struct AddKeyArg {
+    key_spec: KeySpecifier,
+    raw_size: u32,
+    key_id: u32,
+    reserved: [u32; 8],
+}
+
+/// The kernel's `struct fscrypt_remove_key_arg`.
+#[repr(C)]
+struct RemoveKeyArg {
+    key_spec: KeySpecifier,
+    removal_status_flags: u32,
+    reserved: [u32; 5],
+}
+
+/// An fscrypt v2 master key, held in an `fscrypt-provisioning` key.
+///
+/// Keys of that type can't be read by user space; instead,
+/// `FS_IOC_ADD_ENCRYPTION_KEY` names one to add its key to a filesystem, so
+/// that the directories whose v2 policy names the key's identifier can be
+/// unlocked. Provisioning one through a store puts it in the store's
+/// keyring (and the persistent keyring, if the store links into it), with
+/// the store's timeout and permissions, described `fscrypt:<name>`, so a
+/// file-encryption key can live next to the application's credentials.
+///
+/// ```no_run
+/// use linux_keyutils_keyring_store::{FscryptKey, Store};
+///
+/// let store = Store::new().unwrap();
+/// let key = FscryptKey::provision(&store, "home", &[0x42; 64])?;
+/// // the identifier to set in the directory's policy
+/// println!("{}", key.identifier);
+/// key.add_to_filesystem("/home/alice".as_ref())?;
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FscryptKey {
+    /// The description of the provisioning key (`fscrypt:<name>`)
+    pub description: String,
+    /// The serial number of the provisioning key
+    pub serial: KeySerialId,
+    /// The identifier of the master key, in hex, as v2 policies name it
+    pub identifier: String,
+}
+
+impl FscryptKey {
+    /// Add the master key `key` for `name` to `store`.
+    ///
+    /// Master keys are 16 to 64 bytes long (fscrypt wants 64 random bytes).
+    /// A key for the same name is replaced. Kernels built without fscrypt
+    /// get a [NotSupportedByStore](Error::NotSupportedByStore) error.
+    pub fn provision(store: &Store, name: &str, key: &[u8]) -> Result<Self> {
+        if name.is_empty() {
+            return Err(Error::Invalid(
+                "name".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        if key.len() < FSCRYPT_MIN_KEY_SIZE {
+            return Err(Error::Invalid(
+                "key".to_string(),
+                format!("must be at least {FSCRYPT_MIN_KEY_SIZE} bytes"),
+            ));
+        }
+        if key.len() > FSCRYPT_MAX_KEY_SIZE {
+            return Err(Error::TooLong(
+                "key".to_string(),
+                FSCRYPT_MAX_KEY_SIZE as u32,
+            ));
+        }
+        store.check_writable("provision")?;
+        let description = format!("{FSCRYPT_PREFIX}{name}");
+        // struct fscrypt_provisioning_key_payload
+        let mut payload = Vec::with_capacity(8 + key.len());
+        payload.extend_from_slice(&FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER.to_ne_bytes());
+        payload.extend_from_slice(&0u32.to_ne_bytes());
+        payload.extend_from_slice(key);
+        let payload = SecretBytes::new(payload);
+        let (keyring, persistent) = store.keyring_serials()?;
+        let serial = sys::add_key_of_type(FSCRYPT_PROVISIONING, &description, &payload, keyring)
+            .map_err(|err| match err {
+                // the kernel doesn't know the key type
+                KeyError::Unknown(libc::ENODEV) => {
+                    Error::NotSupportedByStore("the kernel doesn't support fscrypt".to_string())
+                }
+                err => KeyStoreError(err).into(),
+            })?;
+        let attributes = || -> std::result::Result<(), KeyError> {
+            if let Some(persistent) = persistent {
+                KernelKeyctl.link(serial, persistent)?;
+            }
+            let key = Key::from_id(serial);
+            if let Some(timeout) = store.timeout {
+                key.set_timeout(timeout.as_secs().max(1) as usize)?;
+            }
+            if let Some(permissions) = store.permissions {
+                key.set_perms(permissions.into())?;
+            }
+            Ok(())
+        };
+        attributes().map_err(KeyStoreError)?;
+        Ok(FscryptKey {
+            description,
+            serial,
+            identifier: to_hex(&key_identifier(key)),
+        })
+    }
+
+    /// Add the master key to the filesystem that holds `path`.
+    ///
+    /// Filesystems without encryption support get a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error. Adding a key
+    /// that the filesystem already has only counts another user of it.
+    pub fn add_to_filesystem(&self, path: &Path) -> Result<()> {
+        let mut arg = AddKeyArg {
+            key_spec: KeySpecifier {
+                spec_type: FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER,
+                reserved: 0,
+                identifier: [0; 32],
+            },
+            raw_size: 0,
+            key_id: self.serial.as_raw_id() as u32,
+            reserved: [0; 8],
+        };
+        ioctl(path, FS_IOC_ADD_ENCRYPTION_KEY, &mut arg)?;
+        if to_hex(&arg.key_spec.identifier[..16]) != self.identifier {
+            return Err(Error::Invalid(
+                "identifier".to_string(),
+                "the kernel derived a different one".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Remove the master key from the filesystem that holds `path`.
+    ///
+    /// Files that are still open stay unlocked until they're closed. A key
+    /// the filesystem doesn't have gets a [NoEntry](Error::NoEntry) error,
+    /// and an identifier that isn't 32 hex digits an [Invalid](Error::Invalid) one.
+    pub fn remove_from_filesystem(&self, path: &Path) -> Result<()> {
+        let identifier = self.identifier_bytes()?;
+        let mut arg = RemoveKeyArg {
+            key_spec: KeySpecifier {
+                spec_type: FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER,
+                reserved: 0,
+                identifier: [0; 32],
+            },
+            removal_status_flags: 0,
+            reserved: [0; 5],
+        };
+        arg.key_spec.identifier[..16].copy_from_slice(&identifier);
+        ioctl(path, FS_IOC_REMOVE_ENCRYPTION_KEY, &mut arg)
+    }
+
+    /// Delete the provisioning key; filesystems the key was added to keep it.
+    pub fn delete(&self) -> Result<()> {
+        Ok(KernelKeyctl
+            .invalidate(self.serial)
+            .map_err(KeyStoreError)?)
+    }
+
+    /// The identifier, back in binary.
+    fn identifier_bytes(&self) -> Result<[u8; 16]> {
+        let invalid = || {
+            Error::Invalid(
+                "identifier".to_string(),
+                format!("{} isn't 32 hex digits", self.identifier),
+            )
+        };
+        let digits = self.identifier.as_bytes();
+        if digits.len() != 32 {
+            return Err(invalid());
+        }
+        let digit = |d: u8| char::from(d).to_digit(16);
+        let mut identifier = [0u8; 16];
+        for (byte, pair) in identifier.iter_mut().zip(digits.chunks(2)) {
+            let (Some(high), Some(low)) = (digit(pair[0]), digit(pair[1])) else {
+                return Err(invalid());
+            };
+            *byte = (high << 4 | low) as u8;
+        }
+        Ok(identifier)
+    }
+}
+
+/// The identifier fscrypt derives for a master key: HKDF-SHA512 of the key,
+/// without a salt, in the key identifier context.
+fn key_identifier(key: &[u8]) -> [u8; 16] {
+    let prk = SecretBytes::new(hmac_sha512(&[0; 64], &[key]).to_vec());
+    let okm = hmac_sha512(&prk, &[b"fscrypt\0", &[HKDF_CONTEXT_KEY_IDENTIFIER], &[1]]);
+    okm[..16]
+        .try_into()
+        .expect("digests are longer than identifiers")
+}
+
+/// Issue an fscrypt ioctl on the filesystem that holds `path`.
+fn ioctl<T>(path: &Path, request: u32, arg: &mut T) -> Result<()> {
+    let file = std::fs::File::open(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => Error::Invalid(
+            "path".to_string(),
+            format!("{} doesn't exist", path.display()),
+        ),
+        std::io::ErrorKind::PermissionDenied => Error::NoStorageAccess(err.into()),
+        _ => Error::PlatformFailure(err.into()),
+    })?;
+    let res = unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg as *mut T) };
+    if res < 0 {
+        return Err(match std::io::Error::last_os_error().raw_os_error() {
+            Some(libc::ENOTTY | libc::EOPNOTSUPP) => {
+                Error::NotSupportedByStore("the filesystem doesn't support encryption".to_string())
+            }
+            _ => KeyStoreError(KeyError::from_errno()).into(),
+        });
+    }
+    Ok(())
+}
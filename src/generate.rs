@@ -0,0 +1,118 @@
+use keyring_core::{Error, Result};
+
+use super::SecretBytes;
+use super::crypto::random_bytes;
+
+/// The characters a generated text secret is drawn from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Charset {
+    /// `A-Z`, `a-z`, and `0-9`.
+    Alphanumeric,
+    /// `0-9` and `a-f`.
+    Hex,
+    /// Every printable ASCII character other than space.
+    Printable,
+    /// The characters of the given string (which may be any Unicode).
+    Custom(String),
+}
+
+impl Charset {
+    /// The characters of the set.
+    fn chars(&self) -> Vec<char> {
+        match self {
+            Charset::Alphanumeric => ('A'..='Z').chain('a'..='z').chain('0'..='9').collect(),
+            Charset::Hex => ('0'..='9').chain('a'..='f').collect(),
+            Charset::Printable => ('!'..='~').collect(),
+            Charset::Custom(chars) => chars.chars().collect(),
+        }
+    }
+}
+
+/// What kind of secret [Cred::set_generated_secret](crate::Cred::set_generated_secret) generates.
+///
+/// Secrets are drawn from the kernel's CSPRNG (`getrandom(2)`), and text
+/// secrets pick each character uniformly from their charset.
+///
+/// ```
+/// use linux_keyutils_keyring_store::{Charset, SecretSpec};
+///
+/// let key = SecretSpec::Bytes(32);
+/// let password = SecretSpec::Text { length: 24, charset: Charset::Alphanumeric };
+/// assert_eq!(password.generate().unwrap().len(), 24);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSpec {
+    /// This many random bytes.
+    Bytes(usize),
+    /// This many characters from a charset.
+    Text {
+        /// How many characters
+        length: usize,
+        /// Which characters
+        charset: Charset,
+    },
+}
+
+impl SecretSpec {
+    /// Generate a secret to this spec.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error for a zero length or an
+    /// empty charset.
+    pub fn generate(&self) -> Result<SecretBytes> {
+        let length = match self {
+            SecretSpec::Bytes(length) | SecretSpec::Text { length, .. } => *length,
+        };
+        if length == 0 {
+            return Err(Error::Invalid(
+                "length".to_string(),
+                "cannot be zero".to_string(),
+            ));
+        }
+        let mut secret = Vec::new();
+        let filled = match self {
+            SecretSpec::Bytes(_) => {
+                secret.resize(length, 0);
+                random_bytes(&mut secret).map_err(|e| Error::PlatformFailure(e.into()))
+            }
+            SecretSpec::Text { charset, .. } => {
+                let chars = charset.chars();
+                if chars.is_empty() {
+                    return Err(Error::Invalid(
+                        "charset".to_string(),
+                        "cannot be empty".to_string(),
+                    ));
+                }
+                // reserve enough up front that the secret is never reallocated (and so copied)
+                let width = chars.iter().map(|c| c.len_utf8()).max().unwrap_or(1);
+                secret.reserve_exact(length * width);
+                (0..length).try_for_each(|_| {
+                    let c = chars[uniform(chars.len())?];
+                    secret.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    Ok(())
+                })
+            }
+        };
+        // wrap the secret first, so that it's wiped even if generating it failed
+        let secret = SecretBytes::new(secret);
+        filled.map(|()| secret)
+    }
+}
+
+/// A uniformly random index below `n`.
+///
+/// Random values from the top of the range that would make some indices
+/// more likely than others are drawn again.
+fn uniform(n: usize) -> Result<usize> {
+    let n = u32::try_from(n).map_err(|_| {
+        Error::Invalid("charset".to_string(), "has too many characters".to_string())
+    })?;
+    let zone = u32::MAX - u32::MAX % n;
+    loop {
+        let mut bytes = [0; 4];
+        random_bytes(&mut bytes).map_err(|e| Error::PlatformFailure(e.into()))?;
+        let value = u32::from_ne_bytes(bytes);
+        if value < zone {
+            return Ok((value % n) as usize);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+
+/// What marks a key as a previous version of a credential: `desc@v{n}`.
+const VERSION_MARKER: &str = "@v";
+
+/// How long versions last unless configured otherwise (a day).
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many previous versions of each credential a store keeps, and for how long.
+///
+/// With a history policy, each write of a credential whose key has
+/// description `desc` also writes the secret into a key with description
+/// `desc@v{n}` in the same keyring, where `n` counts up from 1. Only the
+/// latest `versions` of these keys are kept (older ones are invalidated),
+/// and each expires `lifetime` after it was written, so a credential's
+/// history is always bounded. Versions outlive deletes of the credential
+/// (that's when they're most useful) until they expire.
+///
+/// See [Cred::versions](crate::Cred::versions) and
+/// [Cred::get_version](crate::Cred::get_version) for reading them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryPolicy {
+    /// How many versions of each credential are kept (at least one)
+    pub versions: usize,
+    /// How long after it was written each version expires
+    pub lifetime: Duration,
+}
+
+impl HistoryPolicy {
+    /// Keep the latest `versions` versions, each for `lifetime` (rounded down to whole seconds, but at least one).
+    pub fn new(versions: usize, lifetime: Duration) -> Self {
+        HistoryPolicy {
+            versions: versions.max(1),
+            lifetime: lifetime.max(Duration::from_secs(1)),
+        }
+    }
+}
+
+impl FromStr for HistoryPolicy {
+    type Err = Error;
+
+    /// Parse `versions` or `versions/lifetime_secs`, e.g. `5/3600`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(
+                "history".to_string(),
+                "must be a version count, optionally followed by /lifetime in seconds".to_string(),
+            )
+        };
+        let (versions, lifetime) = match s.split_once('/') {
+            Some((versions, lifetime)) => match lifetime.parse() {
+                Ok(seconds) if seconds > 0 => (versions, Duration::from_secs(seconds)),
+                _ => return Err(invalid()),
+            },
+            None => (s, DEFAULT_LIFETIME),
+        };
+        match versions.parse() {
+            Ok(versions) if versions > 0 => Ok(HistoryPolicy::new(versions, lifetime)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The description of version `number` of the credential with description `description`.
+pub(crate) fn version_description(description: &str, number: u64) -> String {
+    format!("{description}{VERSION_MARKER}{number}")
+}
+
+/// The version number in `candidate`, if it's a version of the credential with description `description`.
+pub(crate) fn version_number(description: &str, candidate: &str) -> Option<u64> {
+    let number = candidate
+        .strip_prefix(description)?
+        .strip_prefix(VERSION_MARKER)?;
+    if number.starts_with('0') || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Whether `candidate` looks like a version of some credential, and if so,
+/// the description of that credential.
+pub(crate) fn versioned_description(candidate: &str) -> Option<&str> {
+    let (description, _) = candidate.rsplit_once(VERSION_MARKER)?;
+    version_number(description, candidate).map(|_| description)
+}
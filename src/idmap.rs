@@ -0,0 +1,235 @@
+use std::ffi::CStr;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{Key, KeyError, KeySerialId};
+
+use super::error::KeyStoreError;
+use super::keyctl::{KernelKeyctl, Keyctl};
+use super::{procfs, sys};
+
+/// The key type the NFS client caches id mappings in.
+const ID_RESOLVER: &CStr = c"id_resolver";
+/// The kernel's keyring of id mapping keys.
+const ID_RESOLVER_KEYRING: &str = ".id_resolver";
+/// How long `nfsidmap` keeps a mapping, unless it's run with `-t`.
+const NFSIDMAP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// An NFSv4 id mapping request, as the description of an `id_resolver` key.
+///
+/// The NFS client asks `nfsidmap(5)` to map the names on the wire to ids
+/// with keys described `uid:<name>` or `gid:<name>`, and ids back to names
+/// with keys described `user:<uid>` or `group:<gid>`. The key's payload is
+/// the answer: the id or the name, in text.
+///
+/// ```
+/// use linux_keyutils_keyring_store::IdMapping;
+///
+/// let mapping: IdMapping = "uid:alice@example.com".parse().unwrap();
+/// assert_eq!(mapping, IdMapping::Uid("alice@example.com".to_string()));
+/// assert_eq!(IdMapping::Group(100).to_string(), "group:100");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IdMapping {
+    /// The uid of an NFSv4 user name (`uid:<name>`)
+    Uid(String),
+    /// The gid of an NFSv4 group name (`gid:<name>`)
+    Gid(String),
+    /// The NFSv4 user name of a uid (`user:<uid>`)
+    User(u32),
+    /// The NFSv4 group name of a gid (`group:<gid>`)
+    Group(u32),
+}
+
+impl IdMapping {
+    /// Check that `answer` is a valid payload for this mapping: an id for
+    /// name lookups, and a name for id lookups.
+    fn check_answer(&self, answer: &str) -> Result<()> {
+        match self {
+            IdMapping::Uid(_) | IdMapping::Gid(_) if answer.parse::<u32>().is_err() => Err(
+                Error::Invalid("answer".to_string(), "must be a numeric id".to_string()),
+            ),
+            IdMapping::User(_) | IdMapping::Group(_) if answer.is_empty() => Err(Error::Invalid(
+                "answer".to_string(),
+                "cannot be empty".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for IdMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdMapping::Uid(name) => write!(f, "uid:{name}"),
+            IdMapping::Gid(name) => write!(f, "gid:{name}"),
+            IdMapping::User(uid) => write!(f, "user:{uid}"),
+            IdMapping::Group(gid) => write!(f, "group:{gid}"),
+        }
+    }
+}
+
+impl FromStr for IdMapping {
+    type Err = Error;
+
+    /// Parse the description of an `id_resolver` key.
+    fn from_str(description: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(
+                "description".to_string(),
+                format!("{description:?} isn't an id mapping"),
+            )
+        };
+        let (kind, subject) = description.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "uid" | "gid" if subject.is_empty() => Err(invalid()),
+            "uid" => Ok(IdMapping::Uid(subject.to_string())),
+            "gid" => Ok(IdMapping::Gid(subject.to_string())),
+            "user" => Ok(IdMapping::User(subject.parse().map_err(|_| invalid())?)),
+            "group" => Ok(IdMapping::Group(subject.parse().map_err(|_| invalid())?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Inspects and pre-seeds the `id_resolver` keys of the NFS client.
+///
+/// [nfsidmap](IdResolver::nfsidmap) is the preset that works on the keys
+/// `nfsidmap` itself would add: in the kernel's `.id_resolver` keyring, with
+/// `nfsidmap`'s default timeout. Seeding a mapping there answers the NFS
+/// client's next request for it without an upcall; clearing one makes the
+/// client ask again, like `nfsidmap -r` does. The kernel's keyring belongs
+/// to root, so both need root.
+///
+/// ```no_run
+/// use linux_keyutils_keyring_store::{IdMapping, IdResolver};
+///
+/// let resolver = IdResolver::nfsidmap()?;
+/// resolver.seed(&IdMapping::Uid("alice@example.com".to_string()), "1000")?;
+/// for (mapping, answer) in resolver.mappings()? {
+///     println!("{mapping} -> {answer}");
+/// }
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdResolver {
+    keyring: KeySerialId,
+    timeout: Option<Duration>,
+}
+
+impl IdResolver {
+    /// Work on the `id_resolver` keys in `keyring`, without a timeout.
+    pub fn new(keyring: KeySerialId) -> Self {
+        IdResolver {
+            keyring,
+            timeout: None,
+        }
+    }
+
+    /// Work on the kernel's `.id_resolver` keyring, as `nfsidmap` does.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if the keyring can't be
+    /// seen, which is the case until the NFS client has made its first
+    /// request, and for users other than root.
+    pub fn nfsidmap() -> Result<Self> {
+        let keys = procfs::keys().map_err(|err| Error::PlatformFailure(err.into()))?;
+        let keyring = keys
+            .into_iter()
+            .find(|key| key.key_type == "keyring" && key.description == ID_RESOLVER_KEYRING)
+            .ok_or(Error::NoEntry)?;
+        Ok(IdResolver::new(keyring.serial).timeout(Some(NFSIDMAP_TIMEOUT)))
+    }
+
+    /// Set the timeout of seeded mappings (`None` for none).
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The keyring the mappings are in.
+    pub fn keyring(&self) -> KeySerialId {
+        self.keyring
+    }
+
+    /// The answer cached for `mapping`.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there's none.
+    pub fn get(&self, mapping: &IdMapping) -> Result<String> {
+        let key = sys::search_of_type(self.keyring, ID_RESOLVER, &mapping.to_string())
+            .map_err(not_supported)?;
+        read_answer(key)
+    }
+
+    /// Cache `answer` for `mapping`, replacing any answer already there.
+    ///
+    /// Name lookups (`uid:` and `gid:`) are answered with an id, and id
+    /// lookups (`user:` and `group:`) with a name.
+    pub fn seed(&self, mapping: &IdMapping, answer: &str) -> Result<()> {
+        mapping.check_answer(answer)?;
+        // nfsidmap includes the terminating NUL
+        let mut payload = answer.as_bytes().to_vec();
+        payload.push(0);
+        let key = sys::add_key_of_type(ID_RESOLVER, &mapping.to_string(), &payload, self.keyring)
+            .map_err(not_supported)?;
+        if let Some(timeout) = self.timeout {
+            Key::from_id(key)
+                .set_timeout(timeout.as_secs().max(1) as usize)
+                .map_err(KeyStoreError)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the answer cached for `mapping`, so that it's looked up again.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there's none.
+    pub fn clear(&self, mapping: &IdMapping) -> Result<()> {
+        let key = sys::search_of_type(self.keyring, ID_RESOLVER, &mapping.to_string())
+            .map_err(not_supported)?;
+        Ok(KernelKeyctl.invalidate(key).map_err(KeyStoreError)?)
+    }
+
+    /// The cached mappings and their answers.
+    ///
+    /// Keys that expire or go away during the walk, and keys whose
+    /// descriptions aren't mappings, are skipped.
+    pub fn mappings(&self) -> Result<Vec<(IdMapping, String)>> {
+        let mut mappings = Vec::new();
+        for key in sys::keyring_links(self.keyring).map_err(KeyStoreError)? {
+            let Ok(described) = sys::describe(key) else {
+                continue;
+            };
+            if described.key_type.as_bytes() != ID_RESOLVER.to_bytes() {
+                continue;
+            }
+            let (Ok(mapping), Ok(answer)) = (described.description.parse(), read_answer(key))
+            else {
+                continue;
+            };
+            mappings.push((mapping, answer));
+        }
+        Ok(mappings)
+    }
+}
+
+/// Read the answer in an `id_resolver` key's payload.
+fn read_answer(key: KeySerialId) -> Result<String> {
+    let mut payload = sys::read(key, usize::MAX)
+        .map_err(KeyStoreError)?
+        .unwrap_or_default();
+    if payload.last() == Some(&0) {
+        payload.pop();
+    }
+    String::from_utf8(payload).map_err(|err| Error::BadEncoding(err.into_bytes()))
+}
+
+/// Report a kernel without the `id_resolver` key type (NFS isn't loaded) as unsupported.
+fn not_supported(err: KeyError) -> Error {
+    match err {
+        KeyError::Unknown(libc::ENODEV) => Error::NotSupportedByStore(
+            "the kernel has no id_resolver keys (is NFS loaded?)".to_string(),
+        ),
+        err => KeyStoreError(err).into(),
+    }
+}
@@ -0,0 +1,152 @@
+//! Integrity protection of key payloads.
+//!
+//! A store configured with integrity protection tags every payload it writes, and
+//! checks the tag on every read, so a payload that was corrupted, or swapped in by
+//! someone with write access to the key (or to the keyring), is reported as a
+//! [BadStoreFormat](keyring_core::Error::BadStoreFormat) error rather than returned.
+//!
+//! The tag covers the key's description as well as its payload, so payloads can't
+//! be moved between keys either.
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeyRing};
+
+use super::compat::bad_store_format;
+use super::crypto::{self, DIGEST_LEN};
+
+/// The description of the integrity master key, unless configured otherwise.
+pub(crate) const DEFAULT_INTEGRITY_KEY: &str = "keyring-store:integrity-master";
+
+/// How the payloads of a store's keys are protected against tampering.
+///
+/// Integrity protection keeps its tag in the payload's
+/// [Envelope](crate::Envelope), so it turns envelopes on. Payloads without
+/// a tag fail the check, since stripping the tag would otherwise be an easy
+/// way around it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Integrity {
+    /// A SHA-256 checksum, which catches corruption and mix-ups, but not
+    /// deliberate tampering (anyone can compute a checksum).
+    Checksum,
+    /// An HMAC-SHA256 keyed from a master key held in the keyring under
+    /// this description, which catches tampering by anyone who can't read
+    /// the master key.
+    ///
+    /// The master key must be provisioned (see
+    /// [provision_integrity_key](crate::Store::provision_integrity_key))
+    /// before credentials can be written or read.
+    Hmac(String),
+}
+
+/// Errors specific to integrity protection.
+///
+/// These are returned as the platform error wrapped inside a keyring-core error
+/// (or, for a failed check, as the message of a
+/// [BadStoreFormat](keyring_core::Error::BadStoreFormat) error).
+#[derive(Debug)]
+pub enum IntegrityError {
+    /// The master key could not be found in (or read from) the keyring.
+    MasterKeyUnavailable(String, KeyError),
+    /// The payload of the key with this description failed its integrity check.
+    Mismatch(String),
+}
+
+impl std::fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityError::MasterKeyUnavailable(description, err) => write!(
+                f,
+                "integrity master key '{description}' is not available: {err}"
+            ),
+            IntegrityError::Mismatch(description) => {
+                write!(f, "key '{description}' failed its integrity check")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+impl Integrity {
+    /// The tag for a payload with this header and secret, of the key with this description.
+    pub(crate) fn tag(
+        &self,
+        keyring: KeyRing,
+        description: &str,
+        header: &[u8],
+        secret: &[u8],
+    ) -> Result<[u8; DIGEST_LEN]> {
+        match self {
+            Integrity::Checksum => {
+                let mut hasher = crypto::Sha256::new();
+                for part in [description.as_bytes(), header, secret] {
+                    hasher.update(&(part.len() as u64).to_le_bytes());
+                    hasher.update(part);
+                }
+                Ok(hasher.finalize())
+            }
+            Integrity::Hmac(master) => {
+                let mut key = Self::mac_key(keyring, master)?;
+                let tag =
+                    crypto::hmac_sha256(&key, &[description.as_bytes(), b"\0", header, secret]);
+                crypto::wipe(&mut key);
+                Ok(tag)
+            }
+        }
+    }
+
+    /// Check a payload's tag, failing if it's missing or wrong.
+    pub(crate) fn verify(
+        &self,
+        keyring: KeyRing,
+        description: &str,
+        header: &[u8],
+        tag: Option<&[u8; DIGEST_LEN]>,
+        secret: &[u8],
+    ) -> Result<()> {
+        let mismatch =
+            || bad_store_format(IntegrityError::Mismatch(description.to_string()).to_string());
+        let tag = tag.ok_or_else(mismatch)?;
+        let expected = self.tag(keyring, description, header, secret)?;
+        if !crypto::constant_time_eq(&expected, tag) {
+            return Err(mismatch());
+        }
+        Ok(())
+    }
+
+    /// Derive the MAC key from the master key in the keyring.
+    fn mac_key(keyring: KeyRing, master: &str) -> Result<[u8; DIGEST_LEN]> {
+        let unavailable = |err| {
+            Error::NoStorageAccess(
+                IntegrityError::MasterKeyUnavailable(master.to_string(), err).into(),
+            )
+        };
+        let key = keyring.search(master).map_err(unavailable)?;
+        let mut material = key.read_to_vec().map_err(unavailable)?;
+        let mac = crypto::hmac_sha256(&material, &[b"linux-keyutils-keyring-store integrity"]);
+        crypto::wipe(&mut material);
+        Ok(mac)
+    }
+}
+
+impl FromStr for Integrity {
+    type Err = Error;
+
+    /// Parse `checksum` or `hmac` (with the default master key description).
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "checksum" => Ok(Integrity::Checksum),
+            "hmac" => Ok(Integrity::Hmac(DEFAULT_INTEGRITY_KEY.to_string())),
+            _ => Err(Error::Invalid(
+                "integrity".to_string(),
+                format!("'{s}' is not one of checksum or hmac"),
+            )),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use keyring_core::Result;
+use linux_keyutils::KeySerialId;
+
+use super::Store;
+use super::sys;
+
+/// Keeps a store's keys from expiring along with the persistent keyring.
+///
+/// The persistent keyring expires after a period of inactivity (see
+/// `/proc/sys/kernel/keys/persistent_keyring_expiry`), and keys that were
+/// only reachable through it go with it. Each [refresh](KeepAlive::refresh)
+/// re-fetches the persistent keyring (resetting its expiry timer) and re-links
+/// every key belonging to the store into both the store's keyring and the
+/// persistent keyring.
+///
+/// Create one with [new](KeepAlive::new) and call `refresh` from your own
+/// scheduler, or with [spawn](KeepAlive::spawn) to have a background thread
+/// do it periodically. The background thread stops when the handle is dropped.
+pub struct KeepAlive {
+    store: Arc<Store>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    /// Create a keep-alive for `store` that is refreshed manually.
+    pub fn new(store: Arc<Store>) -> Self {
+        KeepAlive {
+            store,
+            stop: None,
+            thread: None,
+        }
+    }
+
+    /// Create a keep-alive for `store` that refreshes every `interval` on a background thread.
+    ///
+    /// Errors during background refreshes are ignored; the next refresh tries again.
+    pub fn spawn(store: Arc<Store>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let worker = KeepAlive::new(store.clone());
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                _ = worker.refresh();
+            }
+        });
+        KeepAlive {
+            store,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Re-fetch the persistent keyring and re-link the store's keys.
+    ///
+    /// Returns the number of keys that were re-linked.
+    pub fn refresh(&self) -> Result<usize> {
+        let (keyring, persistent) = self.store.keyring_serials()?;
+        let keyrings: Vec<KeySerialId> =
+            [Some(keyring), persistent].into_iter().flatten().collect();
+        let mut count = 0;
+        for (id, _, _) in self.store.managed_keys(&keyrings)? {
+            let linked = sys::link(id, keyring)
+                .and_then(|_| persistent.map_or(Ok(()), |p| sys::link(id, p)));
+            if linked.is_ok() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for KeepAlive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeepAlive")
+            .field("store", &self.store)
+            .field("background", &self.thread.is_some())
+            .finish()
+    }
+}
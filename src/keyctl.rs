@@ -0,0 +1,749 @@
+//! The keyctl operations a credential makes on its key, behind a trait.
+//!
+//! A [Cred] makes every call on its key (searching for it, adding,
+//! reading, and updating it, linking it, setting its attributes, and so on)
+//! and on its keyring through a [Keyctl], which is the kernel
+//! ([KernelKeyctl]) unless the store was built with another (see
+//! [StoreBuilder::keyctl](crate::StoreBuilder::keyctl)).
+//! Substituting a [FakeKeyctl] lets tests drive a store into the error
+//! paths that are hard to reach with a real keyring, such as a full quota,
+//! a revoked key, or a key the caller may not read.
+use std::cell::Cell;
+use std::fmt::Debug;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use linux_keyutils::{Key, KeyError, KeySerialId, KeyType};
+
+use super::crypto::wipe;
+use super::store::type_name;
+use super::{Capabilities, Perm, sys};
+
+/// One of the operations of a [Keyctl].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyctlOp {
+    /// [search](Keyctl::search)
+    Search,
+    /// [add](Keyctl::add)
+    Add,
+    /// [update](Keyctl::update)
+    Update,
+    /// [link](Keyctl::link)
+    Link,
+    /// [unlink](Keyctl::unlink)
+    Unlink,
+    /// [read](Keyctl::read)
+    Read,
+    /// [set_timeout](Keyctl::set_timeout)
+    SetTimeout,
+    /// [set_perm](Keyctl::set_perm)
+    SetPerm,
+    /// [chown](Keyctl::chown)
+    Chown,
+    /// [revoke](Keyctl::revoke)
+    Revoke,
+    /// [invalidate](Keyctl::invalidate)
+    Invalidate,
+    /// [describe](Keyctl::describe)
+    Describe,
+    /// [links](Keyctl::links)
+    Links,
+    /// [move_key](Keyctl::move_key)
+    Move,
+}
+
+impl std::fmt::Display for KeyctlOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KeyctlOp::Search => "search",
+            KeyctlOp::Add => "add",
+            KeyctlOp::Update => "update",
+            KeyctlOp::Link => "link",
+            KeyctlOp::Unlink => "unlink",
+            KeyctlOp::Read => "read",
+            KeyctlOp::SetTimeout => "set_timeout",
+            KeyctlOp::SetPerm => "set_perm",
+            KeyctlOp::Chown => "chown",
+            KeyctlOp::Revoke => "revoke",
+            KeyctlOp::Invalidate => "invalidate",
+            KeyctlOp::Describe => "describe",
+            KeyctlOp::Links => "links",
+            KeyctlOp::Move => "move",
+        })
+    }
+}
+
+/// What [describe](Keyctl::describe) tells of a key or keyring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDescription {
+    /// The name of the key's type, e.g. `user` or `keyring`
+    pub key_type: String,
+    /// The key's owner
+    pub uid: u32,
+    /// The key's group
+    pub gid: u32,
+    /// The key's permissions
+    pub perm: Perm,
+    /// The key's description
+    pub description: String,
+}
+
+/// The keyctl operations credentials use to get at their keys.
+///
+/// Keys and keyrings are named by serial, and failures are reported as
+/// the kernel reports them, so an implementation can stand in for the
+/// kernel under any credential.
+pub trait Keyctl: Debug + Send + Sync {
+    /// Search `keyring` (and the keyrings linked into it) for a key of
+    /// `key_type` with `description`.
+    fn search(
+        &self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+    ) -> Result<KeySerialId, KeyError>;
+
+    /// Add a key of `key_type` with `description` to `keyring`, or update
+    /// the key of that type and description already in it.
+    fn add(
+        &self,
+        key_type: KeyType,
+        description: &str,
+        payload: &[u8],
+        keyring: KeySerialId,
+    ) -> Result<KeySerialId, KeyError>;
+
+    /// Replace `key`'s payload.
+    fn update(&self, key: KeySerialId, payload: &[u8]) -> Result<(), KeyError>;
+
+    /// Link `key` into `keyring`.
+    fn link(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError>;
+
+    /// Unlink `key` from `keyring`.
+    fn unlink(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError>;
+
+    /// Read as much of `key`'s payload as fits into `buffer`, returning the
+    /// payload's full length (which may be more than was read).
+    fn read(&self, key: KeySerialId, buffer: &mut [u8]) -> Result<usize, KeyError>;
+
+    /// Make `key` expire `seconds` from now (or never, if `seconds` is 0).
+    fn set_timeout(&self, key: KeySerialId, seconds: usize) -> Result<(), KeyError>;
+
+    /// Set `key`'s permissions.
+    fn set_perm(&self, key: KeySerialId, perm: Perm) -> Result<(), KeyError>;
+
+    /// Give `key` to the user `uid` and the group `gid`, leaving whichever
+    /// is `None` as it is.
+    fn chown(&self, key: KeySerialId, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError>;
+
+    /// Revoke `key`, so that it can no longer be read or written.
+    fn revoke(&self, key: KeySerialId) -> Result<(), KeyError>;
+
+    /// Invalidate `key`, so that it disappears from every keyring at once.
+    fn invalidate(&self, key: KeySerialId) -> Result<(), KeyError>;
+
+    /// The type, owner, permissions, and description of `key` (or
+    /// keyring), which fails as access to it would.
+    fn describe(&self, key: KeySerialId) -> Result<KeyDescription, KeyError>;
+
+    /// The serials of the keys (and keyrings) linked into `keyring`.
+    fn links(&self, keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError>;
+
+    /// Move `key` from the keyring `from` to the keyring `to`.
+    fn move_key(
+        &self,
+        key: KeySerialId,
+        from: KeySerialId,
+        to: KeySerialId,
+    ) -> Result<(), KeyError>;
+}
+
+/// The kernel's keyctl operations.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelKeyctl;
+
+impl Keyctl for KernelKeyctl {
+    fn search(
+        &self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+    ) -> Result<KeySerialId, KeyError> {
+        sys::search(keyring, key_type, description)
+    }
+
+    fn add(
+        &self,
+        key_type: KeyType,
+        description: &str,
+        payload: &[u8],
+        keyring: KeySerialId,
+    ) -> Result<KeySerialId, KeyError> {
+        sys::add_key(key_type, description, payload, keyring)
+    }
+
+    fn update(&self, key: KeySerialId, payload: &[u8]) -> Result<(), KeyError> {
+        Key::from_id(key).update(&payload)
+    }
+
+    fn link(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        sys::link(key, keyring)
+    }
+
+    fn unlink(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        sys::unlink(key, keyring)
+    }
+
+    fn read(&self, key: KeySerialId, mut buffer: &mut [u8]) -> Result<usize, KeyError> {
+        Key::from_id(key).read(&mut buffer)
+    }
+
+    fn set_timeout(&self, key: KeySerialId, seconds: usize) -> Result<(), KeyError> {
+        Key::from_id(key).set_timeout(seconds)
+    }
+
+    fn set_perm(&self, key: KeySerialId, perm: Perm) -> Result<(), KeyError> {
+        Key::from_id(key).set_perms(perm.into())
+    }
+
+    fn chown(&self, key: KeySerialId, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError> {
+        Key::from_id(key).chown(uid, gid)
+    }
+
+    fn revoke(&self, key: KeySerialId) -> Result<(), KeyError> {
+        Key::from_id(key).revoke()
+    }
+
+    /// Kernels without invalidation (before 3.5) get the key revoked instead.
+    fn invalidate(&self, key: KeySerialId) -> Result<(), KeyError> {
+        let key = Key::from_id(key);
+        if Capabilities::get().invalidate {
+            key.invalidate()
+        } else {
+            key.revoke()
+        }
+    }
+
+    fn describe(&self, key: KeySerialId) -> Result<KeyDescription, KeyError> {
+        sys::describe(key)
+    }
+
+    fn links(&self, keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError> {
+        sys::keyring_links(keyring)
+    }
+
+    /// Kernels without `KEYCTL_MOVE` (before 5.3) get the key linked and then unlinked.
+    fn move_key(
+        &self,
+        key: KeySerialId,
+        from: KeySerialId,
+        to: KeySerialId,
+    ) -> Result<(), KeyError> {
+        sys::move_key(key, from, to)
+    }
+}
+
+/// The kernel, shared by every store and credential that uses it (so that
+/// building a credential doesn't allocate for it).
+static KERNEL: LazyLock<Arc<dyn Keyctl>> = LazyLock::new(|| Arc::new(KernelKeyctl));
+
+/// The kernel's keyctl operations, as a shared [Keyctl].
+pub(crate) fn kernel() -> Arc<dyn Keyctl> {
+    KERNEL.clone()
+}
+
+/// A failed call of a [Keyctl]: the operation, the key or keyring it was
+/// made on, and the error.
+pub(crate) type FailedCall = (KeyctlOp, KeySerialId, KeyError);
+
+thread_local! {
+    /// The last call of a [Traced] keyctl on this thread, if it failed.
+    static FAILED_CALL: Cell<Option<FailedCall>> = const { Cell::new(None) };
+}
+
+/// Take the last call of a [Traced] keyctl on this thread, if it failed.
+pub(crate) fn take_failed_call() -> Option<FailedCall> {
+    FAILED_CALL.take()
+}
+
+/// A [Keyctl] that notes its failed calls, so that a credential's errors
+/// can say which call failed (see [ErrorContext](crate::ErrorContext)).
+#[derive(Debug)]
+pub(crate) struct Traced<'a>(pub(crate) &'a dyn Keyctl);
+
+impl Traced<'_> {
+    fn note<T>(
+        op: KeyctlOp,
+        serial: KeySerialId,
+        result: Result<T, KeyError>,
+    ) -> Result<T, KeyError> {
+        FAILED_CALL.set(result.as_ref().err().map(|err| (op, serial, *err)));
+        result
+    }
+}
+
+impl Keyctl for Traced<'_> {
+    fn search(
+        &self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+    ) -> Result<KeySerialId, KeyError> {
+        let result = self.0.search(keyring, key_type, description);
+        Self::note(KeyctlOp::Search, keyring, result)
+    }
+
+    fn add(
+        &self,
+        key_type: KeyType,
+        description: &str,
+        payload: &[u8],
+        keyring: KeySerialId,
+    ) -> Result<KeySerialId, KeyError> {
+        let result = self.0.add(key_type, description, payload, keyring);
+        Self::note(KeyctlOp::Add, keyring, result)
+    }
+
+    fn update(&self, key: KeySerialId, payload: &[u8]) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Update, key, self.0.update(key, payload))
+    }
+
+    fn link(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Link, key, self.0.link(key, keyring))
+    }
+
+    fn unlink(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Unlink, key, self.0.unlink(key, keyring))
+    }
+
+    fn read(&self, key: KeySerialId, buffer: &mut [u8]) -> Result<usize, KeyError> {
+        Self::note(KeyctlOp::Read, key, self.0.read(key, buffer))
+    }
+
+    fn set_timeout(&self, key: KeySerialId, seconds: usize) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::SetTimeout, key, self.0.set_timeout(key, seconds))
+    }
+
+    fn set_perm(&self, key: KeySerialId, perm: Perm) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::SetPerm, key, self.0.set_perm(key, perm))
+    }
+
+    fn chown(&self, key: KeySerialId, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Chown, key, self.0.chown(key, uid, gid))
+    }
+
+    fn revoke(&self, key: KeySerialId) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Revoke, key, self.0.revoke(key))
+    }
+
+    fn invalidate(&self, key: KeySerialId) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Invalidate, key, self.0.invalidate(key))
+    }
+
+    fn describe(&self, key: KeySerialId) -> Result<KeyDescription, KeyError> {
+        Self::note(KeyctlOp::Describe, key, self.0.describe(key))
+    }
+
+    fn links(&self, keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError> {
+        Self::note(KeyctlOp::Links, keyring, self.0.links(keyring))
+    }
+
+    fn move_key(
+        &self,
+        key: KeySerialId,
+        from: KeySerialId,
+        to: KeySerialId,
+    ) -> Result<(), KeyError> {
+        Self::note(KeyctlOp::Move, key, self.0.move_key(key, from, to))
+    }
+}
+
+/// The size of the stack buffer payloads are first read into.
+const SMALL_PAYLOAD: usize = 4096;
+
+/// Read the whole payload of `key`, unless it's longer than `max` bytes.
+///
+/// Most payloads are small, so the first read goes into a buffer on the
+/// stack (which is wiped afterwards), and a payload that fits is read with
+/// that one call. Otherwise the heap buffer is sized from the payload's
+/// reported length, and the read is retried (after wiping the buffer) if
+/// the payload grows in between. Returns `None`, without allocating, if the
+/// payload is too long.
+pub(crate) fn read_payload(
+    keyctl: &dyn Keyctl,
+    key: KeySerialId,
+    max: usize,
+) -> Result<Option<Vec<u8>>, KeyError> {
+    let mut small = [0u8; SMALL_PAYLOAD];
+    let mut len = keyctl.read(key, &mut small)?;
+    if len <= SMALL_PAYLOAD {
+        let payload = (len <= max).then(|| small[..len].to_vec());
+        wipe(&mut small[..len]);
+        return Ok(payload);
+    }
+    loop {
+        if len > max {
+            return Ok(None);
+        }
+        let mut buffer = vec![0u8; len];
+        let actual = keyctl.read(key, &mut buffer)?;
+        if actual <= len {
+            buffer.truncate(actual);
+            return Ok(Some(buffer));
+        }
+        // the buffer holds the start of the payload, which is just as secret
+        wipe(&mut buffer);
+        len = actual;
+    }
+}
+
+/// An in-memory stand-in for the kernel, for testing code that uses a store.
+///
+/// Keys live in a table in the fake, linked into keyrings by serial
+/// (which needn't exist in the kernel), and behave as kernel keys do:
+/// adding a key with the description of one in the keyring updates it,
+/// and reading or linking a revoked key fails with
+/// [KeyRevoked](KeyError::KeyRevoked). Any operation can be made to fail
+/// with an error of the test's choosing:
+///
+/// ```
+/// use std::sync::Arc;
+/// use linux_keyutils::KeyError;
+/// use linux_keyutils_keyring_store::{FakeKeyctl, KeyctlOp, StoreBuilder};
+///
+/// let keyctl = Arc::new(FakeKeyctl::new());
+/// let store = StoreBuilder::new().keyctl(keyctl.clone()).build().unwrap();
+/// keyctl.fail(KeyctlOp::Add, KeyError::QuotaExceeded);
+/// // every write to the store now fails as if the user's key quota were full
+/// ```
+///
+/// The fake doesn't search keyrings linked into the searched keyring, and
+/// keeps no expiry times (so setting a timeout only checks that the key is
+/// live). It records each key's owners and permissions, which start as the
+/// kernel's (the caller's, and [Perm::kernel_default]), but doesn't check
+/// them. A serial that isn't one of its keys is described as an empty
+/// keyring owned by the caller, since any serial can be one.
+#[derive(Default)]
+pub struct FakeKeyctl {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    last_serial: i32,
+    keys: Vec<FakeKey>,
+    failures: Vec<(KeyctlOp, KeyError)>,
+}
+
+struct FakeKey {
+    serial: KeySerialId,
+    key_type: KeyType,
+    description: String,
+    payload: Vec<u8>,
+    keyrings: Vec<KeySerialId>,
+    uid: u32,
+    gid: u32,
+    perm: Perm,
+    revoked: bool,
+}
+
+impl FakeKey {
+    fn matches(&self, keyring: KeySerialId, key_type: KeyType, description: &str) -> bool {
+        self.keyrings.contains(&keyring)
+            && self.key_type == key_type
+            && self.description == description
+    }
+}
+
+impl FakeState {
+    /// Fail with the error set for `op`, if there is one.
+    fn check(&self, op: KeyctlOp) -> Result<(), KeyError> {
+        match self.failures.iter().find(|(failing, _)| *failing == op) {
+            Some((_, err)) => Err(*err),
+            None => Ok(()),
+        }
+    }
+
+    /// The key with serial `key`, which must not be revoked.
+    fn live_key(&mut self, key: KeySerialId) -> Result<&mut FakeKey, KeyError> {
+        match self.keys.iter_mut().find(|k| k.serial == key) {
+            Some(k) if k.revoked => Err(KeyError::KeyRevoked),
+            Some(k) => Ok(k),
+            None => Err(KeyError::KeyDoesNotExist),
+        }
+    }
+
+    /// Unlink every key but `keep` with `key_type` and `description` from
+    /// `keyring`, as the kernel does when a link would displace them.
+    fn displace(
+        &mut self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+        keep: KeySerialId,
+    ) {
+        for key in &mut self.keys {
+            if key.serial != keep && key.matches(keyring, key_type, description) {
+                key.keyrings.retain(|k| *k != keyring);
+            }
+        }
+    }
+}
+
+impl FakeKeyctl {
+    /// An empty fake, with no keys and no failures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every later `op` fail with `err`, until [recover](FakeKeyctl::recover) is called.
+    pub fn fail(&self, op: KeyctlOp, err: KeyError) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.retain(|(failing, _)| *failing != op);
+        state.failures.push((op, err));
+    }
+
+    /// Let `op` succeed again.
+    pub fn recover(&self, op: KeyctlOp) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.retain(|(failing, _)| *failing != op);
+    }
+
+    /// Revoke the keys with `description`, returning whether there were any.
+    pub fn revoke(&self, description: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut revoked = false;
+        for key in state
+            .keys
+            .iter_mut()
+            .filter(|k| k.description == description)
+        {
+            key.revoked = true;
+            revoked = true;
+        }
+        revoked
+    }
+
+    /// How many keys the fake holds (including revoked ones).
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().keys.len()
+    }
+
+    /// Whether the fake holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Debug for FakeKeyctl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the keys' payloads are secrets, so only their number is shown
+        let state = self.state.lock().unwrap();
+        f.debug_struct("FakeKeyctl")
+            .field("keys", &state.keys.len())
+            .field("failures", &state.failures)
+            .finish()
+    }
+}
+
+impl Keyctl for FakeKeyctl {
+    fn search(
+        &self,
+        keyring: KeySerialId,
+        key_type: KeyType,
+        description: &str,
+    ) -> Result<KeySerialId, KeyError> {
+        let state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Search)?;
+        match state
+            .keys
+            .iter()
+            .find(|k| k.matches(keyring, key_type, description))
+        {
+            Some(key) if key.revoked => Err(KeyError::KeyRevoked),
+            Some(key) => Ok(key.serial),
+            None => Err(KeyError::KeyDoesNotExist),
+        }
+    }
+
+    fn add(
+        &self,
+        key_type: KeyType,
+        description: &str,
+        payload: &[u8],
+        keyring: KeySerialId,
+    ) -> Result<KeySerialId, KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Add)?;
+        if (payload.is_empty() && key_type != KeyType::KeyRing) || description.is_empty() {
+            return Err(KeyError::InvalidArguments);
+        }
+        if let Some(key) = state
+            .keys
+            .iter_mut()
+            .find(|k| !k.revoked && k.matches(keyring, key_type, description))
+        {
+            key.payload = payload.to_vec();
+            return Ok(key.serial);
+        }
+        state.last_serial += 1;
+        let serial = KeySerialId::new(state.last_serial);
+        state.displace(keyring, key_type, description, serial);
+        state.keys.push(FakeKey {
+            serial,
+            key_type,
+            description: description.to_string(),
+            payload: payload.to_vec(),
+            keyrings: vec![keyring],
+            uid: unsafe { libc::geteuid() },
+            gid: unsafe { libc::getegid() },
+            perm: Perm::kernel_default(),
+            revoked: false,
+        });
+        Ok(serial)
+    }
+
+    fn update(&self, key: KeySerialId, payload: &[u8]) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Update)?;
+        if payload.is_empty() {
+            return Err(KeyError::InvalidArguments);
+        }
+        state.live_key(key)?.payload = payload.to_vec();
+        Ok(())
+    }
+
+    fn link(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Link)?;
+        let found = state.live_key(key)?;
+        if found.keyrings.contains(&keyring) {
+            return Ok(());
+        }
+        found.keyrings.push(keyring);
+        let (key_type, description) = (found.key_type, found.description.clone());
+        state.displace(keyring, key_type, &description, key);
+        Ok(())
+    }
+
+    fn unlink(&self, key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Unlink)?;
+        let Some(found) = state.keys.iter_mut().find(|k| k.serial == key) else {
+            return Err(KeyError::KeyDoesNotExist);
+        };
+        match found.keyrings.iter().position(|k| *k == keyring) {
+            Some(index) => {
+                found.keyrings.remove(index);
+                Ok(())
+            }
+            // the kernel's answer for a key that isn't in the keyring
+            None => Err(KeyError::MissingFileOrDirectory),
+        }
+    }
+
+    fn read(&self, key: KeySerialId, buffer: &mut [u8]) -> Result<usize, KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Read)?;
+        let payload = &state.live_key(key)?.payload;
+        let len = payload.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&payload[..len]);
+        Ok(payload.len())
+    }
+
+    fn set_timeout(&self, key: KeySerialId, _seconds: usize) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::SetTimeout)?;
+        state.live_key(key)?;
+        Ok(())
+    }
+
+    fn set_perm(&self, key: KeySerialId, perm: Perm) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::SetPerm)?;
+        state.live_key(key)?.perm = perm;
+        Ok(())
+    }
+
+    fn chown(&self, key: KeySerialId, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Chown)?;
+        let found = state.live_key(key)?;
+        found.uid = uid.unwrap_or(found.uid);
+        found.gid = gid.unwrap_or(found.gid);
+        Ok(())
+    }
+
+    fn revoke(&self, key: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Revoke)?;
+        state.live_key(key)?.revoked = true;
+        Ok(())
+    }
+
+    fn invalidate(&self, key: KeySerialId) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Invalidate)?;
+        match state.keys.iter().position(|k| k.serial == key) {
+            Some(index) => {
+                state.keys.remove(index);
+                Ok(())
+            }
+            None => Err(KeyError::KeyDoesNotExist),
+        }
+    }
+
+    fn describe(&self, key: KeySerialId) -> Result<KeyDescription, KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Describe)?;
+        if !state.keys.iter().any(|k| k.serial == key) {
+            return Ok(KeyDescription {
+                key_type: "keyring".to_string(),
+                uid: unsafe { libc::geteuid() },
+                gid: unsafe { libc::getegid() },
+                perm: Perm::kernel_default(),
+                description: String::new(),
+            });
+        }
+        let found = state.live_key(key)?;
+        Ok(KeyDescription {
+            key_type: type_name(found.key_type).to_string(),
+            uid: found.uid,
+            gid: found.gid,
+            perm: found.perm,
+            description: found.description.clone(),
+        })
+    }
+
+    fn links(&self, keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError> {
+        let state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Links)?;
+        Ok(state
+            .keys
+            .iter()
+            .filter(|k| k.keyrings.contains(&keyring))
+            .map(|k| k.serial)
+            .collect())
+    }
+
+    fn move_key(
+        &self,
+        key: KeySerialId,
+        from: KeySerialId,
+        to: KeySerialId,
+    ) -> Result<(), KeyError> {
+        let mut state = self.state.lock().unwrap();
+        state.check(KeyctlOp::Move)?;
+        let found = state.live_key(key)?;
+        let Some(index) = found.keyrings.iter().position(|k| *k == from) else {
+            return Err(KeyError::MissingFileOrDirectory);
+        };
+        found.keyrings.remove(index);
+        if found.keyrings.contains(&to) {
+            return Ok(());
+        }
+        found.keyrings.push(to);
+        let (key_type, description) = (found.key_type, found.description.clone());
+        state.displace(to, key_type, &description, key);
+        Ok(())
+    }
+}
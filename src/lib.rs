@@ -17,7 +17,8 @@ keyring_core::set_default_store(linux_keyutils_keyring_store::Store::new().unwra
 # Mapping service and user values to credentials
 
 Entries in keyutils are identified by a string `description`.  If a keyring entry is created with
-an explicit `description` modifier, that value is used as the keyutils description.
+an explicit `description` modifier (or, as in keyring v3, a `target` modifier), that value
+is used as the keyutils description.
 Otherwise, a description is generated by concatenating a prefix string, the `service`,
 a delimiter string, the `user`, and a suffix string.  The prefix, delimiter, and suffix strings
 are part of the store configuration.  Their default values are: `keyring:` for the prefix,
@@ -29,6 +30,14 @@ map to the same description (and thus the same credential in the store). If you
 are worried about this, you can avoid it by configuring your store to forbid the
 delimiter string in the service string.
 
+Applications that host plugins (or other independent parts) can give each one a
+namespace of its own in the same keyring with [Store::scoped], which makes a child store
+whose prefix has the namespace appended.
+
+Descriptions are visible to anyone who can read `/proc/keys`. If service and user names
+shouldn't be, configure your store with `hash_descriptions`: descriptions are then made
+from a hash of the service and user, which are kept in the key's payload instead.
+
 # Attributes
 
 There is no notion of attribute other than the description supported by keyutils,
@@ -36,6 +45,13 @@ so the [get_attributes](keyring_core::Entry::get_attributes)
 and [update_attributes](keyring_core::Entry::update_attributes)
 calls are both no-ops for this credential store.
 
+The exception is the `asymmetric` keys (certificates and public keys parsed by the
+kernel) that [Store::asymmetric_keys] finds in the store's keyrings: these are
+read-only [AsymmetricKey] credentials whose attributes give the key's description,
+subtype, and fingerprint, so trusted keys can be audited alongside credentials.
+With the `pkey` feature, they can also be used to sign, verify, encrypt, and decrypt
+without the key material leaving the kernel (see `AsymmetricKey::sign`).
+
 # Persistence
 
 The key management facility provided by the kernel is completely in-memory and will not persist
@@ -97,7 +113,8 @@ this ensures that the credential will persist as long as the user session exists
 logs out the credential will persist as long as the persistent keyring doesn't expire while the user is
 logged out.
 
-Each time the `Entry::new()` operation is performed, the persistent keyring's expiration timer
+Each time a store is created (or, if its keyrings are resolved lazily, each time the
+`Entry::new()` operation is performed), the persistent keyring's expiration timer
 is reset to the value configured in:
 
 ```no_run,no_test,ignore
@@ -112,14 +129,361 @@ proc/sys/kernel/keys/persistent_keyring_expiry
 | Expired                  | Logged Out            | Expired        |
 
 **Note**: As mentioned above, a reboot clears all keyrings.
+
+Long-running services that read their credentials rarely can use a [KeepAlive] to
+periodically reset the persistent keyring's expiration timer and re-link the store's keys,
+so they don't expire while the user is logged out.
+
+Applications whose credentials are meant to expire (or can be revoked) can register
+them with an [ExpiryMonitor], which calls back as soon as one goes away, so it can be
+re-provisioned before the next read fails.
+
+Stores can also be configured (with the `keyring` config option) to keep their keys in the
+user keyring (also linked into the persistent keyring), directly in the persistent keyring,
+or in the process or thread keyring, whose keys vanish as soon as the process (or thread)
+exits. In containers whose processes have no session keyring of their own, the
+`user_session` keyring (the user's default session keyring) is the one they actually
+share. The store's `persistence` reflects this choice. Individual entries can be put in a
+different keyring than their store's with the `keyring` modifier.
+
+Programs run under `sudo` can use the `keyring_user` config option to keep their keys
+in the persistent keyring of the user who invoked them, rather than root's (see
+[KeyringUser]).
+
+## On-disk backup
+
+If you need credentials to survive a reboot, you can configure the store with a
+`backup_dir`. Every secret is then also sealed with a master key and written to that
+directory. The master key is itself a key in the keyring, so it vanishes at reboot
+along with everything else: once it has been re-provisioned (typically from a
+passphrase or a hardware-held secret at login or service start), reading an entry
+that is missing from the kernel transparently recovers it from the backup.
+
+```no_run
+use std::collections::HashMap;
+use linux_keyutils_keyring_store::Store;
+
+let config = HashMap::from([("backup_dir", "/var/lib/my-app/keyring-backup")]);
+let store = Store::new_with_configuration(&config).unwrap();
+store.provision_backup_key(b"master key material from somewhere safe").unwrap();
+keyring_core::set_default_store(store);
+```
+
+Alternatively, [Store::export] writes the store's credentials to a single
+passphrase-protected archive, and [Store::import] restores them from one, e.g. at
+shutdown and start-up, or to move them to another host.
+
+## Auditing and metrics
+
+A store built with an [audit](StoreBuilder::audit) sink records every read, write,
+and delete of a credential: the key's description, any context given with the
+`audit_context` entry modifier, and the outcome (but never the secret). The
+[SyslogSink] sends these records to syslog (and so to journald); implement
+[AuditSink] to send them anywhere else.
+
+Similarly, a store built with a [metrics](StoreBuilder::metrics) observer reports
+the latency, size, and outcome of each operation to it, so that an application
+can export keyring health to its monitoring system.
+
+## Credentials from systemd
+
+Services that get their credentials from systemd (with `LoadCredential=` and
+friends) can move them into the keyring at start-up with one call to
+[SystemdCredentials::load], which stores each credential in an entry whose user is
+the credential's name.
+
+## Keys supplied on demand
+
+Entries built with the `callout` modifier are read with `request_key(2)`: if their key is
+missing, the kernel asks a `request-key(8)` handler to supply it. Handlers can be written
+against this crate using [KeyRequest].
+
+## Secrets for the kernel
+
+Some secrets are read by the kernel itself, from keys with descriptions and payloads
+of its choosing. A [DmCryptKey] provisions a dm-crypt volume key in the logon key
+cryptsetup would give it, and an [EcryptfsKey] provisions an eCryptfs mount passphrase
+in the authentication token `ecryptfs-add-passphrase` would add. An [FscryptKey]
+provisions an fscrypt v2 master key, and adds it to the filesystems it unlocks.
+
+## Inspecting the kernel's key lists
+
+The [procfs] module parses `/proc/keys` and `/proc/key-users`, which are the only places
+the kernel describes keys that have expired or been revoked, and reports each user's
+quota. The store uses it to [prune](Store::prune) dead keys and gather [Stats]; tooling
+can use it directly.
+
+The NFS client caches its id mappings in `id_resolver` keys, which `nfsidmap(5)` fills.
+An [IdResolver] lists, pre-seeds, and clears those keys, with [IdMapping] as the codec
+of their descriptions.
+
+## High-throughput reads
+
+By default, each get of a credential searches for its key, re-links the key into the
+store's keyrings (so it doesn't expire while in use), and reads it: three syscalls at
+least. Services that read credentials thousands of times a second can do with fewer:
+
+- A store configured with `relink` set to `never` skips the re-link, so a get is a
+  search and a read.
+- A credential [pinned](Cred::pin) to its current key skips the search as well, so a
+  get of a secret up to 4 KiB is a single read. A pinned credential keeps reading the
+  same key: once the key is replaced, deleted, or expires, its gets fail with `NoEntry`,
+  and it must be pinned again.
+
+The kernel has no call that reads several keys at once, so there is no batched read.
+The `store` benchmark (`cargo bench`) measures each of these paths.
+
+## Credential references
+
+A [CredRef] refers to a credential without holding its secret: its key's description,
+keyring, specifiers, and payload [Codec]. Applications can keep one in their state
+files (with the `serde` feature enabled, it serializes) and get an equivalent entry back
+after a restart, from a store configured the same way, with [Store::entry_from_ref].
+
+## Coordinating processes
+
+A [StoreLock] is an advisory lock held in the store's keyring, so processes that share
+the keyring can take turns at, say, rotating a credential, without any shared files. Its
+lease expires on its own if the holder dies.
+
+## Caching an external secret provider
+
+Deployments whose secrets live in Vault, a cloud KMS, or a similar service can use the
+kernel keyring as a hot cache of them: implement [Provider] for the service, and a
+[SyncEngine] fills the store from it, on a schedule (or when asked), and whenever a read
+finds a credential missing.
+
+## Login sessions
+
+A [PamSession] is the body of a PAM session hook (such as the `keyutils-store pam`
+command, run by `pam_exec`): at login it sets up the store's keyrings and can stage
+credentials from a [Provider], and at logout it revokes the keys of a store kept in the
+session keyring.
+
+## Testing without keyutils
+
+With the `mock` feature enabled, a `MockStore` gives entries the same descriptions as a
+[Store] with the same configuration, but keeps their secrets in process memory, so
+applications can run their tests where keyutils is unavailable (e.g. in unprivileged
+containers whose seccomp filters block `keyctl`).
+
+## Testing applications
+
+With the `test_util` feature enabled, the `test_util` module offers the round-trip and
+threading checks this crate runs against itself, for applications to run against their
+own store configurations (and other keyring-core stores against themselves).
+
+To test how an application handles the kernel's failures, build its store with a
+[FakeKeyctl] as its [keyctl](StoreBuilder::keyctl): credentials then keep their keys
+in the fake instead of the kernel, and the fake can be told to fail any search, add,
+link, read, or invalidation, e.g. with a full quota or a revoked key.
+With the `fault_injection` feature enabled, a `FaultInjector` does the same for the
+real kernel (or any other [Keyctl]), failing exactly the calls it's told to, such as
+the third read, or the read of a key that's revoked just after it's found.
+
+## Async applications
+
+With the `async` feature enabled, an `AsyncStore` wraps a [Store] so that each keyutils
+call runs on a thread of its own and is awaited, keeping executor threads free. It
+works with any async runtime.
+
+## Change notifications
+
+With the `watch` feature enabled, and on kernels with key notification support (5.8 and
+later), a `Watcher` delivers events when a watched credential's key is updated, revoked,
+invalidated, or unlinked, so long-running services can notice when another process
+rotates or deletes one of their secrets. A store built with a
+[read cache](StoreBuilder::read_cache) uses one to drop the secrets it serves from memory
+as soon as their keys change.
+
+## keyring-core releases
+
+The crate is built against keyring-core 0.7 by default. Applications still on
+keyring-core 0.6 can turn off default features and enable `keyring-core-0-6` to get
+a store for their release instead; the store works the same against either, except
+that errors for malformed stored data (such as a corrupt backup file) are
+`BadDataFormat` errors with no data in 0.6, which has no `BadStoreFormat` error.
+(If both features are enabled, 0.7 is used.) The release the crate was built against
+is re-exported as [keyring_core].
 */
+
+#[cfg(not(any(feature = "keyring-core-0-6", feature = "keyring-core-0-7")))]
+compile_error!("one of the keyring-core-0-7 (default) and keyring-core-0-6 features is needed");
+
+/// The keyring-core release the crate was built against, whose entries use its stores.
+#[cfg(feature = "keyring-core-0-7")]
+pub use keyring_core;
+/// The keyring-core release the crate was built against, whose entries use its stores.
+#[cfg(all(feature = "keyring-core-0-6", not(feature = "keyring-core-0-7")))]
+pub extern crate keyring_core_0_6 as keyring_core;
+
+mod asymmetric;
+pub use asymmetric::AsymmetricKey;
+
+mod audit;
+pub use audit::{AuditEvent, AuditOp, AuditSink, SyslogSink};
+
+mod cipher;
+pub use cipher::Cipher;
+
+mod envelope;
+pub use envelope::Envelope;
+
 mod error;
+pub use error::{ErrorContext, FailureKind};
+
+mod expiry;
+pub use expiry::ExpiryMonitor;
+
+mod export;
+
+mod fallback;
+pub use fallback::{FallbackCred, FallbackPolicy, FallbackStore};
+
+mod fscrypt;
+pub use fscrypt::FscryptKey;
+
+mod generate;
+pub use generate::{Charset, SecretSpec};
+
+mod history;
+pub use history::HistoryPolicy;
+
+mod idmap;
+pub use idmap::{IdMapping, IdResolver};
+
+mod integrity;
+pub use integrity::{Integrity, IntegrityError};
+
+mod diagnose;
+pub use diagnose::{Diagnosis, KeyringStatus, Quota};
+
+mod disk;
+pub use disk::{DmCryptKey, EcryptfsKey};
+
+mod compat;
+
+mod crypto;
+
+mod credref;
+pub use credref::{Codec, CredRef};
+
+mod cached;
+pub use cached::{CachedCred, CachedStore};
+
+mod caps;
+pub use caps::Capabilities;
+
+mod backup;
+pub use backup::{Backup, BackupError};
+
+mod keepalive;
+pub use keepalive::KeepAlive;
+
+mod keyctl;
+pub use keyctl::{FakeKeyctl, KernelKeyctl, KeyDescription, Keyctl, KeyctlOp};
+
+mod builder;
+pub use builder::StoreBuilder;
+
+mod config;
+pub use config::StoreConfig;
 
 mod cred;
-pub use cred::Cred;
+pub use cred::{
+    Cred, DescriptionOverflow, OwnerMismatch, PersistentPolicy, ReadOnly, Redaction, Relink,
+    WriteMode,
+};
+
+mod lock;
+pub use lock::StoreLock;
+
+mod lsm;
+pub use lsm::LsmHint;
+
+mod metrics;
+pub use metrics::{ErrorCategory, MetricsObserver, Observation, Operation};
+
+mod mirror;
+pub use mirror::Mirror;
+
+mod negative;
+
+mod readcache;
+
+mod normalize;
+pub use normalize::Normalization;
+
+mod pam;
+pub use pam::{PamSession, PamStage};
+
+mod perm;
+pub use perm::Perm;
+
+mod provider;
+pub use provider::{Provider, SyncEngine};
+
+mod request;
+pub use request::KeyRequest;
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod secret;
+pub use secret::{SecretBytes, SecretReader};
+
+pub mod procfs;
+
+mod specifiers;
+pub use specifiers::ControlChars;
+
+mod stats;
+pub use stats::{KeyringStats, Stats};
 
 mod store;
-pub use store::Store;
+pub use store::{MigrateOptions, MigratedKey, PrunedKey, Store};
+
+mod sys;
+
+mod systemd;
+pub use systemd::SystemdCredentials;
+
+mod target;
+pub use target::Target;
+
+#[cfg(any(test, feature = "test_util"))]
+pub mod test_util;
+
+mod user;
+pub use user::{KeyringUser, invoking_uid};
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "fault_injection")]
+mod fault;
+#[cfg(feature = "fault_injection")]
+pub use fault::FaultInjector;
+
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(feature = "mock")]
+pub use mock::{MockCred, MockStore};
+
+#[cfg(feature = "pkey")]
+mod pkey;
+#[cfg(feature = "pkey")]
+pub use pkey::PkeyQuery;
+
+#[cfg(feature = "async")]
+mod nonblocking;
+#[cfg(feature = "async")]
+pub use nonblocking::{AsyncEntry, AsyncStore};
+
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "watch")]
+pub use watch::{KeyEvent, Notification, Watcher};
 
 #[cfg(test)]
 mod tests;
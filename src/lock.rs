@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{Key, KeyError, KeyRingIdentifier, KeySerialId, KeyType};
+
+use super::Store;
+use super::error::KeyStoreError;
+use super::{procfs, sys};
+
+/// What lock keys' descriptions start with.
+const LOCK_PREFIX: &str = "keyring-store:lock:";
+
+/// The longest wait between attempts to take a held lock.
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+/// An advisory lock held in a store's keyring.
+///
+/// A lock is a `user` key with description `keyring-store:lock:{name}`,
+/// which is created in the calling thread's keyring and then moved into
+/// the store's keyring with `KEYCTL_MOVE_EXCL`, which fails if the keyring
+/// already holds the lock key. Since the check and the move are one atomic
+/// step, at most one holder (in any process that shares the keyring) has
+/// the lock at a time. The key's timeout is the lock's lease: if the holder
+/// dies without releasing the lock, it's free again when the lease runs out
+/// (the expired key is cleared from the keyring by the next taker, since the
+/// kernel only garbage-collects it some minutes later).
+/// A holder that needs longer can [renew](StoreLock::renew) the lease.
+///
+/// The lock is released when it's dropped, or by [release](StoreLock::release).
+/// Locks need `KEYCTL_MOVE` (Linux 5.3); on older kernels taking one gives
+/// a [NotSupportedByStore](Error::NotSupportedByStore) error.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use linux_keyutils_keyring_store::{Store, StoreLock};
+///
+/// let store = Store::new().unwrap();
+/// let lease = Duration::from_secs(30);
+/// if let Some(lock) = StoreLock::acquire(&store, "rotate", lease, Duration::from_secs(5)).unwrap() {
+///     // ... rotate the credential ...
+///     lock.release().unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StoreLock {
+    name: String,
+    serial: KeySerialId,
+    /// The keyring the lock key was moved into
+    keyring: KeySerialId,
+    released: bool,
+}
+
+impl StoreLock {
+    /// Take the lock called `name` in `store`'s keyring for `lease`
+    /// (rounded up to whole seconds), if no one holds it.
+    ///
+    /// Returns `None` if the lock is held.
+    pub fn try_acquire(store: &Store, name: &str, lease: Duration) -> Result<Option<StoreLock>> {
+        let description = lock_description(name);
+        let keyring = store.keyring_serials()?.0;
+        let staging =
+            sys::keyring_serial(KeyRingIdentifier::Thread, true).map_err(KeyStoreError::from)?;
+        let payload = format!("pid {}", std::process::id());
+        let serial = sys::add_key(KeyType::User, &description, payload.as_bytes(), staging)
+            .map_err(KeyStoreError::from)?;
+        let key = Key::from_id(serial);
+        let mut moved = key
+            .set_timeout(lease_secs(lease))
+            .and_then(|_| sys::move_key_exclusive(serial, staging, keyring));
+        if matches!(moved, Err(KeyError::Unknown(libc::EEXIST)))
+            && clear_dead(keyring, &description)
+        {
+            moved = sys::move_key_exclusive(serial, staging, keyring);
+        }
+        match moved {
+            Ok(()) => Ok(Some(StoreLock {
+                name: name.to_string(),
+                serial,
+                keyring,
+                released: false,
+            })),
+            Err(err) => {
+                _ = key.invalidate();
+                match err {
+                    KeyError::Unknown(libc::EEXIST) => Ok(None),
+                    KeyError::OperationNotSupported => Err(Error::NotSupportedByStore(
+                        "the kernel doesn't support exclusive moves (KEYCTL_MOVE)".to_string(),
+                    )),
+                    err => Err(KeyStoreError(err).into()),
+                }
+            }
+        }
+    }
+
+    /// Take the lock called `name` in `store`'s keyring for `lease`,
+    /// waiting up to `wait` for its holder to release it.
+    ///
+    /// Returns `None` if the lock is still held after `wait`.
+    pub fn acquire(
+        store: &Store,
+        name: &str,
+        lease: Duration,
+        wait: Duration,
+    ) -> Result<Option<StoreLock>> {
+        let deadline = Instant::now() + wait;
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            if let Some(lock) = StoreLock::try_acquire(store, name, lease)? {
+                return Ok(Some(lock));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// The lock's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the lock is still held, i.e. its lease hasn't run out.
+    pub fn is_held(&self) -> bool {
+        self.key().is_ok()
+    }
+
+    /// Extend the lease to `lease` from now (rounded up to whole seconds).
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if the lease has already
+    /// run out, since someone else may hold the lock by now.
+    pub fn renew(&self, lease: Duration) -> Result<()> {
+        let key = self.key()?;
+        key.set_timeout(lease_secs(lease))
+            .map_err(|err| KeyStoreError(err).into())
+    }
+
+    /// Release the lock.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if the lease had already
+    /// run out (and so the lock wasn't held any more).
+    pub fn release(mut self) -> Result<()> {
+        self.released = true;
+        self.unlock()
+    }
+
+    /// The lock's key, if it's still the one this holder created.
+    fn key(&self) -> Result<Key> {
+        let key = Key::from_id(self.serial);
+        let metadata = key.metadata().map_err(KeyStoreError::from)?;
+        // serials are reused after keys are gone
+        if metadata.get_description() != lock_description(&self.name) {
+            return Err(Error::NoEntry);
+        }
+        Ok(key)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.key()?
+            .invalidate()
+            .map_err(|err| Error::from(KeyStoreError(err)))?;
+        // an invalidated key stays linked until the kernel garbage-collects
+        // it, which would keep the lock from being taken again until then
+        _ = sys::unlink(self.serial, self.keyring);
+        Ok(())
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if !self.released {
+            _ = self.unlock();
+        }
+    }
+}
+
+/// Unlink any lock key with `description` from `keyring` whose lease has
+/// run out (or which was revoked or invalidated), returning whether there was one.
+///
+/// Such keys can only be found in `/proc/keys`, since the kernel won't
+/// describe them.
+fn clear_dead(keyring: KeySerialId, description: &str) -> bool {
+    let Ok(links) = sys::keyring_links(keyring) else {
+        return false;
+    };
+    let mut cleared = false;
+    for key in procfs::keys().unwrap_or_default() {
+        let dead = key.is_expired() || key.is_revoked() || key.is_invalidated();
+        if dead
+            && key.key_type == "user"
+            && key.description == description
+            && links.contains(&key.serial)
+            && sys::unlink(key.serial, keyring).is_ok()
+        {
+            cleared = true;
+        }
+    }
+    cleared
+}
+
+/// The description of the lock key for the lock called `name`.
+fn lock_description(name: &str) -> String {
+    format!("{LOCK_PREFIX}{name}")
+}
+
+/// A lease in whole seconds, rounded up (a zero timeout would never expire).
+fn lease_secs(lease: Duration) -> usize {
+    let secs = lease.as_secs() + u64::from(lease.subsec_nanos() > 0);
+    secs.max(1) as usize
+}
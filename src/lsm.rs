@@ -0,0 +1,66 @@
+use std::fmt;
+use std::path::Path;
+
+/// A security module enforcing a policy on the process, which may be what
+/// refused it access to a key.
+///
+/// In confined services, `EACCES` from keyctl is almost always the policy
+/// of SELinux or AppArmor rather than the key's permissions, and the fix
+/// is in the policy. Credentials of a store built with
+/// [lsm_hints](crate::StoreBuilder::lsm_hints) put one of these in the
+/// [ErrorContext](crate::ErrorContext) of their access errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsmHint {
+    /// The module: `SELinux` or `AppArmor`
+    pub module: &'static str,
+    /// The process's security context (SELinux) or profile (AppArmor),
+    /// which is what the module's audit records of the denial name
+    pub context: Option<String>,
+}
+
+impl LsmHint {
+    /// The enforcing security module confining this process, if there is one.
+    pub fn detect() -> Option<Self> {
+        Self::detect_in(Path::new("/"))
+    }
+
+    /// Detect the module from the `sys` and `proc` files under `root`.
+    pub(crate) fn detect_in(root: &Path) -> Option<Self> {
+        let read = |path: &str| {
+            std::fs::read_to_string(root.join(path))
+                .ok()
+                .map(|s| s.trim_end_matches(['\0', '\n']).to_string())
+        };
+        if read("sys/fs/selinux/enforce").as_deref() == Some("1") {
+            return Some(LsmHint {
+                module: "SELinux",
+                context: read("proc/self/attr/current"),
+            });
+        }
+        if read("sys/module/apparmor/parameters/enabled").as_deref() == Some("Y") {
+            let profile = read("proc/self/attr/apparmor/current")
+                .or_else(|| read("proc/self/attr/current"))?;
+            // complain-mode profiles only log, and unconfined processes have none
+            if profile.ends_with("(enforce)") {
+                return Some(LsmHint {
+                    module: "AppArmor",
+                    context: Some(profile),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for LsmHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is enforcing", self.module)?;
+        if let Some(context) = &self.context {
+            write!(f, " on this process ({context})")?;
+        }
+        write!(
+            f,
+            ", so its policy may have refused the access; look for a denial in the audit log"
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use keyring_core::Error;
+
+use super::AuditOp;
+
+/// An operation counted by a [MetricsObserver].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// A read of a credential's secret.
+    Get,
+    /// A write of a credential's secret.
+    Set,
+    /// A delete of a credential.
+    Delete,
+    /// A revocation of a credential.
+    Revoke,
+    /// A search of the store's keyrings (see [Store::entries](crate::Store::entries)).
+    Search,
+}
+
+impl Operation {
+    /// The operation's name, for use as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Get => "get",
+            Operation::Set => "set",
+            Operation::Delete => "delete",
+            Operation::Revoke => "revoke",
+            Operation::Search => "search",
+        }
+    }
+}
+
+impl From<AuditOp> for Operation {
+    fn from(op: AuditOp) -> Self {
+        match op {
+            AuditOp::Get => Operation::Get,
+            AuditOp::Set => Operation::Set,
+            AuditOp::Delete => Operation::Delete,
+            AuditOp::Revoke => Operation::Revoke,
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The kind of error an operation failed with, for use as a metric label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// [NoEntry](Error::NoEntry): the credential doesn't exist.
+    NoEntry,
+    /// [NoStorageAccess](Error::NoStorageAccess): the keyring couldn't be reached.
+    NoStorageAccess,
+    /// [PlatformFailure](Error::PlatformFailure): the kernel failed the operation.
+    PlatformFailure,
+    /// [Invalid](Error::Invalid) or [TooLong](Error::TooLong): the caller's input was rejected.
+    Invalid,
+    /// [BadEncoding](Error::BadEncoding), [BadDataFormat](Error::BadDataFormat),
+    /// or [BadStoreFormat](Error::BadStoreFormat): the stored data couldn't be decoded.
+    BadData,
+    /// [NotSupportedByStore](Error::NotSupportedByStore): the operation can't be done here.
+    NotSupported,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorCategory {
+    /// The category of `err`.
+    pub fn of(err: &Error) -> Self {
+        match err {
+            Error::NoEntry => ErrorCategory::NoEntry,
+            Error::NoStorageAccess(_) => ErrorCategory::NoStorageAccess,
+            Error::PlatformFailure(_) => ErrorCategory::PlatformFailure,
+            Error::Invalid(_, _) | Error::TooLong(_, _) => ErrorCategory::Invalid,
+            Error::BadEncoding(_) | Error::BadDataFormat(_, _) => ErrorCategory::BadData,
+            #[cfg(feature = "keyring-core-0-7")]
+            Error::BadStoreFormat(_) => ErrorCategory::BadData,
+            Error::NotSupportedByStore(_) => ErrorCategory::NotSupported,
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    /// The category's name, for use as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::NoEntry => "no_entry",
+            ErrorCategory::NoStorageAccess => "no_storage_access",
+            ErrorCategory::PlatformFailure => "platform_failure",
+            ErrorCategory::Invalid => "invalid",
+            ErrorCategory::BadData => "bad_data",
+            ErrorCategory::NotSupported => "not_supported",
+            ErrorCategory::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The measurements of one operation, as passed to a [MetricsObserver].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+    /// What was done
+    pub operation: Operation,
+    /// How long it took
+    pub latency: Duration,
+    /// The size of the secret read or written (zero for deletes and
+    /// revocations, and the number of entries found for searches), if the
+    /// operation succeeded
+    pub size: Option<usize>,
+    /// Why the operation failed, if it did
+    pub error: Option<ErrorCategory>,
+}
+
+/// Receives measurements of a store's operations, e.g. to feed Prometheus counters.
+///
+/// An observer is called after every read, write, delete, and revocation of
+/// a credential, and every search of the store, on the thread that made it,
+/// so it should only update counters and histograms (which are cheap), not do I/O.
+pub trait MetricsObserver: Debug + Send + Sync {
+    /// Record `observation`.
+    fn observe(&self, observation: &Observation);
+}
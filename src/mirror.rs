@@ -0,0 +1,83 @@
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeyRingIdentifier, KeySerialId, KeyType};
+
+use super::{Target, sys};
+
+/// An extra keyring that a store's keys are mirrored into.
+///
+/// Every key the store writes is also linked into each of its mirrors, and
+/// a key that's missing from the store's own keyring is searched for in the
+/// mirrors, in the order they were given. Since mirroring links the same key
+/// rather than copying it, an update made through any of its keyrings shows
+/// up in all of them, and deleting it removes it from all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Mirror {
+    /// The keyring of this target.
+    Target(Target),
+    /// The keyring with this description in the user keyring, which is
+    /// created there if it doesn't exist (e.g. an application's own keyring).
+    Named(String),
+}
+
+impl Mirror {
+    /// Resolve the serial of this mirror's keyring, creating it if necessary.
+    pub(crate) fn serial(&self) -> std::result::Result<KeySerialId, KeyError> {
+        match self {
+            Mirror::Target(target) => target.serial(),
+            Mirror::Named(name) => {
+                let user = sys::keyring_serial(KeyRingIdentifier::User, false)?;
+                match sys::search(user, KeyType::KeyRing, name) {
+                    // adding a keyring displaces any keyring of the same name,
+                    // so it's only done if there isn't one already
+                    Err(KeyError::KeyDoesNotExist) => {
+                        sys::add_key(KeyType::KeyRing, name, &[], user)
+                    }
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Parse a comma-separated list of mirrors.
+    pub(crate) fn parse_list(s: &str) -> Result<Vec<Mirror>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for Mirror {
+    type Err = Error;
+
+    /// Parse a target name, or `%:name` for a named keyring (as in `keyctl`).
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix("%:") {
+            Some("") => Err(Error::Invalid(
+                "mirrors".to_string(),
+                "keyring names cannot be empty".to_string(),
+            )),
+            Some(name) => Ok(Mirror::Named(name.to_string())),
+            None => s.parse().map(Mirror::Target).map_err(|_| {
+                Error::Invalid(
+                    "mirrors".to_string(),
+                    format!(
+                        "'{s}' is not one of session, process, user, thread, persistent, user_session, or %:name"
+                    ),
+                )
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Mirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mirror::Target(target) => f.write_str(target.as_str()),
+            Mirror::Named(name) => write!(f, "%:{name}"),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::compat::parse_attributes;
+use keyring_core::api::{CredentialApi, CredentialPersistence, CredentialStoreApi};
+use keyring_core::{Credential, Entry, Error, Result};
+
+use super::cred::{
+    MAX_DESCRIPTION_LEN, USER_KEY_MAX_LEN, check_no_divider, delimiter_ambiguities, describe,
+    fit_description,
+};
+use super::specifiers::Canonicalizer;
+use super::store::{MODIFIERS, custom_description};
+use super::{DescriptionOverflow, SecretBytes, Store, StoreConfig, WriteMode};
+
+type Secrets = Arc<Mutex<HashMap<String, SecretBytes>>>;
+
+/// An in-memory stand-in for [Store], for tests and sandboxes without keyutils.
+///
+/// Entries get the same descriptions as they would in a [Store] with the same
+/// delimiters, and accept the same modifiers, but their secrets are kept in a
+/// map in process memory, so they work where `keyctl` is blocked (e.g. in
+/// unprivileged containers). Of the modifiers, only `description`,
+/// `create_new`, and `update_only` have any effect. Each mock store has
+/// its own map, so mock stores don't see each other's credentials.
+///
+/// ```
+/// use linux_keyutils_keyring_store::MockStore;
+///
+/// keyring_core::set_default_store(MockStore::new().unwrap());
+/// let entry = keyring_core::Entry::new("service", "user").unwrap();
+/// entry.set_password("in memory").unwrap();
+/// assert_eq!(entry.get_password().unwrap(), "in memory");
+/// ```
+#[derive(Debug)]
+pub struct MockStore {
+    id: String,
+    delimiters: [String; 3],
+    service_no_divider: bool,
+    user_no_divider: bool,
+    max_description_len: usize,
+    description_overflow: DescriptionOverflow,
+    canonicalizer: Canonicalizer,
+    secrets: Secrets,
+}
+
+impl MockStore {
+    /// Create a mock of the default store.
+    pub fn new() -> Result<Arc<Self>> {
+        Self::from_config(StoreConfig::default())
+    }
+
+    /// Create a mock of a custom-configured store.
+    ///
+    /// This takes the same options as [Store::new_with_configuration], but only
+    /// `prefix`, `divider`, `suffix`, `service_no_divider`, `user_no_divider`,
+    /// `max_description_len`, `description_overflow`, `control_chars`,
+    /// `case_insensitive`, and `normalization` have any effect.
+    /// Delimiters that a [Store] would reject are rejected here too.
+    pub fn new_with_configuration(config: &HashMap<&str, &str>) -> Result<Arc<Self>> {
+        Self::from_config(StoreConfig::from_options(config)?)
+    }
+
+    /// Create a mock of a store created from a [StoreConfig].
+    pub fn from_config(config: StoreConfig) -> Result<Arc<Self>> {
+        let delimiters = [
+            config.prefix.unwrap_or_else(|| "keyring:".to_string()),
+            config.divider.unwrap_or_else(|| "@".to_string()),
+            config.suffix.unwrap_or_default(),
+        ];
+        if let Some((option, ambiguity)) = delimiter_ambiguities(&delimiters).into_iter().next() {
+            return Err(Error::Invalid(option.to_string(), ambiguity));
+        }
+        Ok(Arc::new(MockStore {
+            id: Store::new_id(),
+            delimiters,
+            service_no_divider: config.service_no_divider.unwrap_or(false),
+            user_no_divider: config.user_no_divider.unwrap_or(false),
+            max_description_len: config.max_description_len.unwrap_or(MAX_DESCRIPTION_LEN),
+            description_overflow: config.description_overflow.unwrap_or_default(),
+            canonicalizer: Canonicalizer {
+                control_chars: config.control_chars.unwrap_or_default(),
+                case_insensitive: config.case_insensitive.unwrap_or(false),
+                normalization: config.normalization,
+            },
+            secrets: Default::default(),
+        }))
+    }
+
+    /// The descriptions of the credentials in the store.
+    pub fn descriptions(&self) -> Vec<String> {
+        let mut descriptions: Vec<String> = lock(&self.secrets).keys().cloned().collect();
+        descriptions.sort();
+        descriptions
+    }
+}
+
+impl CredentialStoreApi for MockStore {
+    /// See the keyring-core API docs.
+    fn vendor(&self) -> String {
+        "Linux keyutils mock, https://crates.io/crates/linux-keyutils-keyring-store".to_string()
+    }
+
+    /// See the keyring-core API docs.
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    /// See the keyring-core API docs.
+    fn build(
+        &self,
+        service: &str,
+        user: &str,
+        modifiers: Option<&HashMap<&str, &str>>,
+    ) -> Result<Entry> {
+        let mods = parse_attributes(MODIFIERS, modifiers)?;
+        let flag = |key: &str| mods.get(key).is_some_and(|v| v == "true");
+        let write_mode = match (flag("create_new"), flag("update_only")) {
+            (false, false) => WriteMode::Upsert,
+            (true, false) => WriteMode::CreateNew,
+            (false, true) => WriteMode::UpdateOnly,
+            (true, true) => {
+                return Err(Error::Invalid(
+                    "create_new".to_string(),
+                    "cannot be combined with update_only".to_string(),
+                ));
+            }
+        };
+        let description = custom_description(&mods, true)?;
+        let (service, user) = match description {
+            Some(_) => (Cow::Borrowed(service), Cow::Borrowed(user)),
+            None => (
+                self.canonicalizer.apply("service", service)?,
+                self.canonicalizer.apply("user", user)?,
+            ),
+        };
+        if self.user_no_divider && description.is_none() {
+            check_no_divider("user", &user, &self.delimiters[1])?;
+        }
+        let (description, specifiers) = describe(
+            description,
+            &self.delimiters,
+            self.service_no_divider,
+            &service,
+            &user,
+        )?;
+        // mock credentials keep their specifiers in memory, so there's no envelope to need
+        let specifiers = match specifiers {
+            None if flag("record_specifiers") => Some((service.to_string(), user.to_string())),
+            specifiers => specifiers.map(|(service, user)| (service.to_string(), user.to_string())),
+        };
+        let description = fit_description(
+            description,
+            self.max_description_len,
+            self.description_overflow,
+            &self.delimiters[2],
+        )?;
+        let cred = MockCred {
+            description,
+            specifiers,
+            write_mode,
+            secrets: self.secrets.clone(),
+        };
+        Ok(Entry::new_with_credential(Arc::new(cred)))
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    ///
+    /// Mock credentials vanish when the process exits.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::ProcessOnly
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A credential of a [MockStore].
+#[derive(Debug)]
+pub struct MockCred {
+    /// The description the key would have in keyutils
+    pub description: String,
+    /// Specifiers for the entry, if any
+    pub specifiers: Option<(String, String)>,
+    /// Whether writes may create and/or overwrite the credential
+    pub write_mode: WriteMode,
+    secrets: Secrets,
+}
+
+impl CredentialApi for MockCred {
+    /// See the keyring-core API docs.
+    ///
+    /// As in keyutils, secrets can't be empty or longer than 32767 bytes.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        if secret.is_empty() {
+            return Err(Error::Invalid(
+                "secret".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        if secret.len() > USER_KEY_MAX_LEN {
+            return Err(Error::TooLong(
+                "secret".to_string(),
+                USER_KEY_MAX_LEN as u32,
+            ));
+        }
+        let mut secrets = lock(&self.secrets);
+        match (self.write_mode, secrets.contains_key(&self.description)) {
+            (WriteMode::CreateNew, true) => {
+                return Err(Error::Invalid(
+                    "create_new".to_string(),
+                    "the credential already exists".to_string(),
+                ));
+            }
+            (WriteMode::UpdateOnly, false) => return Err(Error::NoEntry),
+            _ => {}
+        }
+        secrets.insert(self.description.clone(), SecretBytes::new(secret.to_vec()));
+        Ok(())
+    }
+
+    /// See the keyring-core API docs.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        lock(&self.secrets)
+            .get(&self.description)
+            .map(|secret| secret.to_vec())
+            .ok_or(Error::NoEntry)
+    }
+
+    /// See the keyring-core API docs.
+    fn delete_credential(&self) -> Result<()> {
+        lock(&self.secrets)
+            .remove(&self.description)
+            .map(drop)
+            .ok_or(Error::NoEntry)
+    }
+
+    /// See the keyring-core API docs.
+    fn get_credential(&self) -> Result<Option<Arc<Credential>>> {
+        if !lock(&self.secrets).contains_key(&self.description) {
+            return Err(Error::NoEntry);
+        }
+        Ok(None)
+    }
+
+    /// See the keyring-core API docs.
+    fn get_specifiers(&self) -> Option<(String, String)> {
+        self.specifiers.clone()
+    }
+
+    /// See the keyring-core API docs.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// See the keyring-core API docs.
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Lock the map of secrets, which can't be left inconsistent by a panic.
+fn lock(secrets: &Secrets) -> std::sync::MutexGuard<'_, HashMap<String, SecretBytes>> {
+    secrets
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::Target;
+
+/// How many misses are held before expired ones are swept out.
+const SWEEP_LEN: usize = 1024;
+
+/// Which credential was missing: its keyring, key type name, and description.
+type Miss = (Target, &'static str, String);
+
+/// A store's record of the credentials it recently found missing.
+///
+/// A miss is remembered for the cache's TTL, and forgotten as soon as any
+/// of the store's credentials adds or links a key, since that may be the
+/// missing one. Keys added by other processes (or through other stores)
+/// aren't seen until the TTL runs out.
+#[derive(Debug)]
+pub(crate) struct NegativeCache {
+    ttl: Duration,
+    misses: Mutex<HashMap<Miss, Instant>>,
+}
+
+impl NegativeCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        NegativeCache {
+            ttl,
+            misses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the credential was found missing less than the TTL ago.
+    pub(crate) fn is_missing(
+        &self,
+        target: Target,
+        key_type: &'static str,
+        description: &str,
+    ) -> bool {
+        let mut misses = self.misses.lock().unwrap();
+        let miss = (target, key_type, description.to_string());
+        match misses.get(&miss) {
+            Some(expires) if *expires > Instant::now() => true,
+            Some(_) => {
+                misses.remove(&miss);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remember that the credential is missing.
+    pub(crate) fn record_miss(&self, target: Target, key_type: &'static str, description: &str) {
+        let mut misses = self.misses.lock().unwrap();
+        let now = Instant::now();
+        // probes for ever-new descriptions shouldn't grow the map without bound
+        if misses.len() >= SWEEP_LEN {
+            misses.retain(|_, expires| *expires > now);
+        }
+        misses.insert((target, key_type, description.to_string()), now + self.ttl);
+    }
+
+    /// Forget every miss, because a key was added or linked.
+    pub(crate) fn clear(&self) {
+        self.misses.lock().unwrap().clear();
+    }
+}
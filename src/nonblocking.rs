@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use keyring_core::api::CredentialStoreApi;
+use keyring_core::{Entry, Result};
+
+use super::{SecretBytes, Store};
+
+/// An async front for a [Store], for applications that mustn't block their executor.
+///
+/// Every keyutils operation is a system call that can take a while (reads
+/// of `big_key` keys go through tmpfs files, and listing entries walks
+/// whole keyrings), so each one is run on a thread of its own (much like
+/// tokio's `spawn_blocking`) and awaited. This doesn't depend on any async
+/// runtime, so it works with all of them.
+///
+/// ```no_run
+/// # async fn example() -> keyring_core::Result<()> {
+/// use linux_keyutils_keyring_store::{AsyncStore, Store};
+///
+/// let store = AsyncStore::new(Store::new()?);
+/// let entry = store.entry("my-service", "my-user")?;
+/// entry.set_password("topsecret").await?;
+/// assert_eq!(entry.get_password().await?, "topsecret");
+/// entry.delete_credential().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncStore {
+    store: Arc<Store>,
+}
+
+impl AsyncStore {
+    /// Wrap `store`.
+    pub fn new(store: Arc<Store>) -> Self {
+        AsyncStore { store }
+    }
+
+    /// The wrapped store.
+    pub fn store(&self) -> &Arc<Store> {
+        &self.store
+    }
+
+    /// An entry for `service` and `user`.
+    ///
+    /// Building an entry makes no keyutils calls, so this doesn't block.
+    pub fn entry(&self, service: &str, user: &str) -> Result<AsyncEntry> {
+        self.store.build(service, user, None).map(AsyncEntry::new)
+    }
+
+    /// An entry for `service` and `user` with the given modifiers (see [Store::build]).
+    pub fn entry_with_modifiers(
+        &self,
+        service: &str,
+        user: &str,
+        modifiers: &HashMap<&str, &str>,
+    ) -> Result<AsyncEntry> {
+        self.store
+            .build(service, user, Some(modifiers))
+            .map(AsyncEntry::new)
+    }
+
+    /// Entries for all the credentials in the store's keyrings (see [Store::entries]).
+    pub async fn entries(&self) -> Result<Vec<AsyncEntry>> {
+        let entries = self.run(|store| store.entries()).await?;
+        Ok(entries.into_iter().map(AsyncEntry::new).collect())
+    }
+
+    /// Run `op` on the store without blocking the caller, e.g. for bulk
+    /// operations such as [export](Store::export) or [prune](Store::prune).
+    pub async fn run<T, F>(&self, op: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&Store) -> T + Send + 'static,
+    {
+        let store = self.store.clone();
+        spawn_blocking(move || op(&store)).await
+    }
+}
+
+/// An entry whose operations are awaited rather than blocking (see [AsyncStore]).
+#[derive(Debug, Clone)]
+pub struct AsyncEntry {
+    entry: Arc<Entry>,
+}
+
+impl AsyncEntry {
+    fn new(entry: Entry) -> Self {
+        AsyncEntry {
+            entry: Arc::new(entry),
+        }
+    }
+
+    /// The underlying (blocking) entry.
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    /// Set the password (see [Entry::set_password]).
+    pub async fn set_password(&self, password: &str) -> Result<()> {
+        let password = SecretBytes::new(password.as_bytes().to_vec());
+        self.run(move |entry| entry.set_secret(&password)).await
+    }
+
+    /// Set the secret (see [Entry::set_secret]).
+    ///
+    /// The secret is copied so the operation can outlive the borrow, and
+    /// the copy is wiped afterwards.
+    pub async fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let secret = SecretBytes::new(secret.to_vec());
+        self.run(move |entry| entry.set_secret(&secret)).await
+    }
+
+    /// Get the password (see [Entry::get_password]).
+    pub async fn get_password(&self) -> Result<String> {
+        self.run(|entry| entry.get_password()).await
+    }
+
+    /// Get the secret (see [Entry::get_secret]).
+    pub async fn get_secret(&self) -> Result<Vec<u8>> {
+        self.run(|entry| entry.get_secret()).await
+    }
+
+    /// Delete the credential (see [Entry::delete_credential]).
+    pub async fn delete_credential(&self) -> Result<()> {
+        self.run(|entry| entry.delete_credential()).await
+    }
+
+    /// Run `op` on the underlying entry without blocking the caller.
+    pub async fn run<T, F>(&self, op: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&Entry) -> T + Send + 'static,
+    {
+        let entry = self.entry.clone();
+        spawn_blocking(move || op(&entry)).await
+    }
+}
+
+/// The result of a blocking operation, and who to wake when it's ready.
+struct Slot<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A future for the result of an operation running on its own thread.
+struct Blocking<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+/// Run `op` on a new thread, returning a future for its result.
+fn spawn_blocking<T, F>(op: F) -> Blocking<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(Slot {
+        result: None,
+        waker: None,
+    }));
+    let filled = slot.clone();
+    std::thread::spawn(move || {
+        let result = op();
+        let mut slot = filled.lock().unwrap_or_else(|e| e.into_inner());
+        slot.result = Some(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    });
+    Blocking { slot }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
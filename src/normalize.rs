@@ -0,0 +1,173 @@
+//! Unicode normalization (NFC and NFKC) of services and users.
+//!
+//! Stores only need to normalize the short names they compose descriptions
+//! from, so the algorithm of UAX #15 is implemented here directly over
+//! tables generated from the Unicode database (see `scripts/unicode_tables.py`)
+//! rather than pulling in a Unicode stack for it.
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+
+mod tables;
+
+use tables::{CANONICAL, COMBINING_CLASSES, COMPATIBILITY, COMPOSITIONS};
+
+/// Which Unicode normalization form a store puts services and users into.
+///
+/// Names that look the same can be encoded differently, e.g. `é` as one
+/// precomposed character or as `e` followed by a combining accent, depending
+/// on where they were typed or which OS they came from. Normalizing them
+/// makes such names describe the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Canonical composition: only encodings of the same character are unified.
+    Nfc,
+    /// Compatibility composition: also unifies compatibility variants, such
+    /// as ligatures, full-width forms, and superscripts (`ﬁ` becomes `fi`).
+    Nfkc,
+}
+
+impl FromStr for Normalization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nfc" => Ok(Normalization::Nfc),
+            "nfkc" => Ok(Normalization::Nfkc),
+            _ => Err(Error::Invalid(
+                "normalization".to_string(),
+                "must be nfc or nfkc".to_string(),
+            )),
+        }
+    }
+}
+
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+impl Normalization {
+    /// Put `value` into this normalization form.
+    pub(crate) fn apply(&self, value: &str) -> String {
+        // decompose, then put each run of combining marks in canonical order
+        let mut chars: Vec<(char, u8)> = Vec::with_capacity(value.len());
+        for c in value.chars() {
+            self.decompose(c, &mut chars);
+        }
+        let mut start = 0;
+        while start < chars.len() {
+            if chars[start].1 == 0 {
+                start += 1;
+                continue;
+            }
+            let end = chars[start..]
+                .iter()
+                .position(|&(_, class)| class == 0)
+                .map_or(chars.len(), |n| start + n);
+            chars[start..end].sort_by_key(|&(_, class)| class);
+            start = end;
+        }
+        compose(&chars)
+    }
+
+    /// Append the full decomposition of `c`, with combining classes, to `out`.
+    fn decompose(&self, c: char, out: &mut Vec<(char, u8)>) {
+        let code = c as u32;
+        if (S_BASE..S_BASE + S_COUNT).contains(&code) {
+            let index = code - S_BASE;
+            out.push((char_from(L_BASE + index / N_COUNT), 0));
+            out.push((char_from(V_BASE + index % N_COUNT / T_COUNT), 0));
+            if index % T_COUNT != 0 {
+                out.push((char_from(T_BASE + index % T_COUNT), 0));
+            }
+            return;
+        }
+        let mapping = lookup(CANONICAL, c).or_else(|| match self {
+            Normalization::Nfkc => lookup(COMPATIBILITY, c),
+            Normalization::Nfc => None,
+        });
+        match mapping {
+            Some(mapping) => mapping.chars().for_each(|c| self.decompose(c, out)),
+            None => out.push((c, combining_class(c))),
+        }
+    }
+}
+
+/// Canonically compose a decomposed, canonically ordered string.
+fn compose(chars: &[(char, u8)]) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    // the last starter, and the class of the last character since it
+    let mut starter: Option<usize> = None;
+    let mut last_class = 0;
+    for &(c, class) in chars {
+        if let Some(index) = starter {
+            // a character is blocked from the starter by an intervening one
+            // of the same or a higher class (or any starter)
+            let blocked = out.len() > index + 1 && (last_class == 0 || last_class >= class);
+            if !blocked {
+                if let Some(composite) = compose_pair(out[index], c) {
+                    out[index] = composite;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+        }
+        last_class = class;
+        out.push(c);
+    }
+    out.into_iter().collect()
+}
+
+/// The primary composite of `first` and `second`, if there is one.
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+        let index = (a - L_BASE) * N_COUNT + (b - V_BASE) * T_COUNT;
+        return Some(char_from(S_BASE + index));
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&a)
+        && (a - S_BASE) % T_COUNT == 0
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&b)
+    {
+        return Some(char_from(a + b - T_BASE));
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|index| COMPOSITIONS[index].2)
+}
+
+/// The canonical combining class of `c`.
+fn combining_class(c: char) -> u8 {
+    COMBINING_CLASSES
+        .binary_search_by(|&(first, last, _)| {
+            if last < c {
+                std::cmp::Ordering::Less
+            } else if first > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .map_or(0, |index| COMBINING_CLASSES[index].2)
+}
+
+fn lookup(table: &'static [(char, &'static str)], c: char) -> Option<&'static str> {
+    table
+        .binary_search_by_key(&c, |&(key, _)| key)
+        .ok()
+        .map(|index| table[index].1)
+}
+
+/// A character computed by the Hangul algorithm, which is always valid.
+fn char_from(code: u32) -> char {
+    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use keyring_core::Result;
+use linux_keyutils::{Key, KeyError};
+
+use super::error::KeyStoreError;
+use super::{Provider, Store, SyncEngine, Target, sys};
+
+/// The stage of a PAM session that a hook is called for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PamStage {
+    /// The session is being opened (`open_session`).
+    Open,
+    /// The session is being closed (`close_session`).
+    Close,
+}
+
+impl PamStage {
+    /// The session stage `pam_exec(8)` is running its program for, from `PAM_TYPE`.
+    ///
+    /// Returns `None` outside of `pam_exec`, and for the account, auth, and
+    /// password hooks, which have nothing to do with a store's keyrings.
+    pub fn from_env() -> Option<Self> {
+        match std::env::var("PAM_TYPE").ok()?.as_str() {
+            "open_session" => Some(PamStage::Open),
+            "close_session" => Some(PamStage::Close),
+            _ => None,
+        }
+    }
+}
+
+/// Stages a store's keyrings for a login session, from a PAM session hook.
+///
+/// At session [open](PamSession::open), the store's keyring is created if
+/// it's missing and the persistent keyring is linked into the session (as
+/// every store operation would do on demand), so that the user's first
+/// read doesn't find an empty keyring, and the credentials of a
+/// [Provider], if one is given, are written to the store. At session
+/// [close](PamSession::close), the keys of a store whose keyring is the
+/// session keyring are revoked, so the session's secrets don't outlive it
+/// in the persistent keyring.
+///
+/// The hook works on the keyrings of the process that calls it, which
+/// share the session keyring of the PAM application that runs it (so
+/// `pam_keyinit(8)` should come first in the session stack). Run it as the
+/// session's user (e.g. `pam_exec.so seteuid`), so that it works on that
+/// user's keyrings. The `keyutils-store pam` command is such a hook:
+///
+/// ```text
+/// session  optional  pam_keyinit.so force revoke
+/// session  optional  pam_exec.so seteuid /usr/bin/keyutils-store pam
+/// ```
+#[derive(Debug, Clone)]
+pub struct PamSession {
+    store: Arc<Store>,
+    provider: Option<Arc<dyn Provider>>,
+}
+
+impl PamSession {
+    /// Create a session hook for `store`.
+    pub fn new(store: Arc<Store>) -> Self {
+        PamSession {
+            store,
+            provider: None,
+        }
+    }
+
+    /// Pre-populate the store from `provider` at session open.
+    pub fn provider(mut self, provider: Arc<dyn Provider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Do what the session hook does at `stage`; see [open](PamSession::open) and [close](PamSession::close).
+    pub fn run(&self, stage: PamStage) -> Result<usize> {
+        match stage {
+            PamStage::Open => self.open(),
+            PamStage::Close => self.close(),
+        }
+    }
+
+    /// Create the store's keyring, link the persistent keyring, and fill
+    /// the store from the provider (if there is one).
+    ///
+    /// Returns the number of credentials written from the provider.
+    pub fn open(&self) -> Result<usize> {
+        // only a store that uses the caller's own keyrings can create one
+        if self.store.keyring_uid.is_none() && self.store.keyring != Target::Persistent {
+            sys::keyring_serial(self.store.keyring.identifier(), true)
+                .map_err(KeyStoreError::from)?;
+        }
+        self.store.keyring_serials()?;
+        match &self.provider {
+            Some(provider) => SyncEngine::new(self.store.clone(), provider.clone()).refresh(),
+            None => Ok(0),
+        }
+    }
+
+    /// Revoke the keys of the store's credentials, if its keyring is the session keyring.
+    ///
+    /// The keys of stores in other keyrings are meant to outlive the
+    /// session, so they're left alone. Returns the number of keys revoked.
+    pub fn close(&self) -> Result<usize> {
+        if self.store.keyring != Target::Session || self.store.keyring_uid.is_some() {
+            return Ok(0);
+        }
+        let (keyring, _) = self.store.keyring_serials()?;
+        let mut count = 0;
+        for (id, _, _) in self.store.managed_keys(&[keyring])? {
+            // keys can expire or be deleted during the walk
+            match Key::from_id(id).revoke() {
+                Ok(()) => count += 1,
+                Err(KeyError::KeyDoesNotExist | KeyError::KeyExpired | KeyError::KeyRevoked) => {}
+                Err(err) => return Err(KeyStoreError(err).into()),
+            }
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyPermissions, Permission};
+
+/// The permissions given to the keys a store writes.
+///
+/// This is a keyctl permission mask, with one byte each (from most to least
+/// significant) for whoever possesses the key, the key's owner, its group,
+/// and everyone else, as shown by `keyctl describe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Perm(u32);
+
+impl Perm {
+    /// The permissions with the given mask, e.g. `0x3f010000`.
+    pub const fn from_bits(bits: u32) -> Perm {
+        Perm(bits)
+    }
+
+    /// The permission mask.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// All permissions for possessors of the key, and none for anyone else.
+    pub const fn possessor_all() -> Perm {
+        Perm(0x3f00_0000)
+    }
+
+    /// The kernel's default for new keys: all permissions for possessors,
+    /// and view permission for the owner.
+    pub const fn kernel_default() -> Perm {
+        Perm(0x3f01_0000)
+    }
+
+    /// Add permissions for possessors of the key.
+    pub const fn possessor(self, perm: Permission) -> Perm {
+        Perm(self.0 | (perm.bits() as u32) << 24)
+    }
+
+    /// Add permissions for the key's owner.
+    pub const fn user(self, perm: Permission) -> Perm {
+        Perm(self.0 | (perm.bits() as u32) << 16)
+    }
+
+    /// Add permissions for the key's group.
+    pub const fn group(self, perm: Permission) -> Perm {
+        Perm(self.0 | (perm.bits() as u32) << 8)
+    }
+
+    /// Add permissions for everyone else.
+    pub const fn world(self, perm: Permission) -> Perm {
+        Perm(self.0 | perm.bits() as u32)
+    }
+}
+
+impl From<Perm> for KeyPermissions {
+    fn from(perm: Perm) -> Self {
+        KeyPermissions::from_u32(perm.0)
+    }
+}
+
+impl FromStr for Perm {
+    type Err = Error;
+
+    /// Parse a hex mask, with or without a leading `0x`.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        u32::from_str_radix(hex, 16).map(Perm).map_err(|_| {
+            Error::Invalid(
+                "permissions".to_string(),
+                format!("'{s}' is not a hex permission mask"),
+            )
+        })
+    }
+}
@@ -0,0 +1,204 @@
+use std::ffi::CString;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::KeyError;
+
+use super::error::KeyStoreError;
+use super::{AsymmetricKey, Capabilities, sys};
+
+const KEYCTL_PKEY_QUERY: libc::c_int = 24;
+const KEYCTL_PKEY_ENCRYPT: libc::c_int = 25;
+const KEYCTL_PKEY_DECRYPT: libc::c_int = 26;
+const KEYCTL_PKEY_SIGN: libc::c_int = 27;
+const KEYCTL_PKEY_VERIFY: libc::c_int = 28;
+
+const KEYCTL_SUPPORTS_ENCRYPT: u32 = 0x01;
+const KEYCTL_SUPPORTS_DECRYPT: u32 = 0x02;
+const KEYCTL_SUPPORTS_SIGN: u32 = 0x04;
+const KEYCTL_SUPPORTS_VERIFY: u32 = 0x08;
+
+/// The kernel's `struct keyctl_pkey_query`.
+#[repr(C)]
+#[derive(Default)]
+struct QueryResult {
+    supported_ops: u32,
+    key_size: u32,
+    max_data_size: u16,
+    max_sig_size: u16,
+    max_enc_size: u16,
+    max_dec_size: u16,
+    spare: [u32; 10],
+}
+
+/// The kernel's `struct keyctl_pkey_params`.
+#[repr(C)]
+struct Params {
+    key_id: i32,
+    in_len: u32,
+    // `in2_len` when verifying
+    out_len: u32,
+    spare: [u32; 7],
+}
+
+/// What an asymmetric key can do, and with how much data, as the kernel reports it.
+///
+/// The sizes are in bytes, and depend on the `info` the key was queried with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PkeyQuery {
+    /// The size of the key, in bits
+    pub key_size: u32,
+    /// Whether the key can [encrypt](AsymmetricKey::encrypt)
+    pub encrypt: bool,
+    /// Whether the key can [decrypt](AsymmetricKey::decrypt) (it has a private part)
+    pub decrypt: bool,
+    /// Whether the key can [sign](AsymmetricKey::sign) (it has a private part)
+    pub sign: bool,
+    /// Whether the key can [verify](AsymmetricKey::verify)
+    pub verify: bool,
+    /// The largest data (or digest) that can be signed or encrypted
+    pub max_data_size: usize,
+    /// The size of a signature
+    pub max_sig_size: usize,
+    /// The size of encrypted data
+    pub max_enc_size: usize,
+    /// The largest decrypted data
+    pub max_dec_size: usize,
+}
+
+/// Public key operations, done by the kernel with the key it holds.
+///
+/// Each operation takes the `info` string of `keyctl_pkey_query(3)`: the
+/// encoding and hash, e.g. `enc=pkcs1 hash=sha256`. Signing and verifying
+/// work on a digest that the caller has already made with that hash.
+/// Operations the kernel doesn't support, and ones the key can't do (such
+/// as signing with a certificate, which has no private key), fail with a
+/// [NotSupportedByStore](Error::NotSupportedByStore) error.
+impl AsymmetricKey {
+    /// Ask the kernel what the key can do with `info`.
+    pub fn query(&self, info: &str) -> Result<PkeyQuery> {
+        let info = pkey_info(info)?;
+        let mut result = QueryResult::default();
+        sys::keyctl(
+            KEYCTL_PKEY_QUERY,
+            self.serial.as_raw_id() as libc::c_ulong,
+            0,
+            info.as_ptr() as libc::c_ulong,
+            &mut result as *mut QueryResult as libc::c_ulong,
+        )
+        .map_err(pkey_error)?;
+        Ok(PkeyQuery {
+            key_size: result.key_size,
+            encrypt: result.supported_ops & KEYCTL_SUPPORTS_ENCRYPT != 0,
+            decrypt: result.supported_ops & KEYCTL_SUPPORTS_DECRYPT != 0,
+            sign: result.supported_ops & KEYCTL_SUPPORTS_SIGN != 0,
+            verify: result.supported_ops & KEYCTL_SUPPORTS_VERIFY != 0,
+            max_data_size: result.max_data_size as usize,
+            max_sig_size: result.max_sig_size as usize,
+            max_enc_size: result.max_enc_size as usize,
+            max_dec_size: result.max_dec_size as usize,
+        })
+    }
+
+    /// Encrypt `data` with the key.
+    pub fn encrypt(&self, info: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let query = self.query(info)?;
+        supports(query.encrypt, "encrypt")?;
+        self.transform(KEYCTL_PKEY_ENCRYPT, info, data, query.max_enc_size)
+    }
+
+    /// Decrypt `data` with the key's private part.
+    pub fn decrypt(&self, info: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let query = self.query(info)?;
+        supports(query.decrypt, "decrypt")?;
+        self.transform(KEYCTL_PKEY_DECRYPT, info, data, query.max_dec_size)
+    }
+
+    /// Sign `digest` with the key's private part.
+    pub fn sign(&self, info: &str, digest: &[u8]) -> Result<Vec<u8>> {
+        let query = self.query(info)?;
+        supports(query.sign, "sign")?;
+        self.transform(KEYCTL_PKEY_SIGN, info, digest, query.max_sig_size)
+    }
+
+    /// Check `signature` of `digest` with the key.
+    ///
+    /// Returns `false` if the signature doesn't match.
+    pub fn verify(&self, info: &str, digest: &[u8], signature: &[u8]) -> Result<bool> {
+        let info = pkey_info(info)?;
+        let params = Params {
+            key_id: self.serial.as_raw_id(),
+            in_len: digest.len() as u32,
+            out_len: signature.len() as u32,
+            spare: [0; 7],
+        };
+        let result = sys::keyctl(
+            KEYCTL_PKEY_VERIFY,
+            &params as *const Params as libc::c_ulong,
+            info.as_ptr() as libc::c_ulong,
+            digest.as_ptr() as libc::c_ulong,
+            signature.as_ptr() as libc::c_ulong,
+        );
+        match result {
+            Ok(_) => Ok(true),
+            Err(KeyError::KeyRejected | KeyError::Unknown(libc::EBADMSG)) => Ok(false),
+            Err(err) => Err(pkey_error(err)),
+        }
+    }
+
+    /// Run an encrypt, decrypt, or sign operation, into an output of `size` bytes.
+    fn transform(&self, op: libc::c_int, info: &str, data: &[u8], size: usize) -> Result<Vec<u8>> {
+        let info = pkey_info(info)?;
+        let mut out = vec![0u8; size];
+        let params = Params {
+            key_id: self.serial.as_raw_id(),
+            in_len: data.len() as u32,
+            out_len: out.len() as u32,
+            spare: [0; 7],
+        };
+        let len = sys::keyctl(
+            op,
+            &params as *const Params as libc::c_ulong,
+            info.as_ptr() as libc::c_ulong,
+            data.as_ptr() as libc::c_ulong,
+            out.as_mut_ptr() as libc::c_ulong,
+        )
+        .map_err(pkey_error)?;
+        out.truncate(len as usize);
+        Ok(out)
+    }
+}
+
+/// Check that the kernel does public key operations, and make `info` a C string.
+fn pkey_info(info: &str) -> Result<CString> {
+    if !Capabilities::get().public_key {
+        return Err(Error::NotSupportedByStore(
+            "the kernel doesn't do public key operations".to_string(),
+        ));
+    }
+    CString::new(info)
+        .map_err(|_| Error::Invalid("info".to_string(), "cannot contain NUL".to_string()))
+}
+
+/// Refuse an operation the kernel says the key can't do, rather than have it fail as invalid.
+fn supports(supported: bool, op: &str) -> Result<()> {
+    if supported {
+        return Ok(());
+    }
+    Err(Error::NotSupportedByStore(format!(
+        "the key can't {op} (it may have no private part)"
+    )))
+}
+
+/// Translate a failed public key operation, whose invalid arguments are usually its `info`.
+fn pkey_error(err: KeyError) -> Error {
+    match err {
+        KeyError::InvalidArguments => Error::Invalid(
+            "info".to_string(),
+            "rejected by the kernel for this key and data".to_string(),
+        ),
+        KeyError::OperationNotSupported => {
+            Error::NotSupportedByStore("the key doesn't support this operation".to_string())
+        }
+        err => KeyStoreError(err).into(),
+    }
+}
@@ -0,0 +1,245 @@
+//! Parsers for the kernel's key listings in `/proc/keys` and `/proc/key-users`.
+//!
+//! `/proc/keys` lists every key the caller can view, one per line, including
+//! keys that have expired or been revoked (which `keyctl(2)` won't describe),
+//! and `/proc/key-users` gives each user's key counts and quota. These are
+//! the only places the kernel reports either, so tooling that needs them can
+//! use these parsers rather than write its own.
+//!
+//! ```
+//! use linux_keyutils_keyring_store::procfs;
+//!
+//! for key in procfs::keys().unwrap_or_default() {
+//!     if key.key_type == "user" && key.is_expired() {
+//!         println!("{} expired", key.description);
+//!     }
+//! }
+//! ```
+use std::time::Duration;
+
+use linux_keyutils::KeySerialId;
+
+use super::{Perm, Quota};
+
+/// The `asymmetric` key type, as truncated in `/proc/keys`.
+const ASYMMETRIC_TYPE: &str = "asymmetri";
+
+/// When a key expires, as given in `/proc/keys`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// The key has no timeout.
+    Never,
+    /// The key has expired.
+    Expired,
+    /// The key expires in (about) this long. The kernel rounds down to the
+    /// largest whole unit of seconds, minutes, hours, days, or weeks.
+    In(Duration),
+}
+
+/// A key as listed in `/proc/keys`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcKey {
+    /// The key's serial
+    pub serial: KeySerialId,
+    /// The key's state flags, e.g. `I--Q---` (see the `is_*` methods)
+    pub flags: String,
+    /// How many references there are to the key
+    pub usage: u32,
+    /// When the key expires
+    pub expiry: Expiry,
+    /// The key's permission mask
+    pub permissions: Perm,
+    /// The key's owner
+    pub uid: u32,
+    /// The key's group
+    pub gid: u32,
+    /// The key's type (truncated to 9 characters by the kernel)
+    pub key_type: String,
+    /// The key's description
+    pub description: String,
+    /// The type's summary of the payload, if it gave one (e.g. a user key's
+    /// length, or a keyring's link count)
+    pub summary: Option<String>,
+    /// The payload's length, if the summary gives it
+    pub payload_len: Option<usize>,
+}
+
+impl ProcKey {
+    fn flag(&self, index: usize, flag: u8) -> bool {
+        self.flags.as_bytes().get(index) == Some(&flag)
+    }
+
+    /// Whether the key has been instantiated (`I`).
+    pub fn is_instantiated(&self) -> bool {
+        self.flag(0, b'I')
+    }
+
+    /// Whether the key has been revoked (`R`).
+    pub fn is_revoked(&self) -> bool {
+        self.flag(1, b'R')
+    }
+
+    /// Whether the key's type has been unregistered (`D`).
+    pub fn is_dead(&self) -> bool {
+        self.flag(2, b'D')
+    }
+
+    /// Whether the key counts against its owner's quota (`Q`).
+    pub fn in_quota(&self) -> bool {
+        self.flag(3, b'Q')
+    }
+
+    /// Whether the key is negatively instantiated (`N`).
+    pub fn is_negative(&self) -> bool {
+        self.flag(5, b'N')
+    }
+
+    /// Whether the key has been invalidated (`i`).
+    pub fn is_invalidated(&self) -> bool {
+        self.flag(6, b'i')
+    }
+
+    /// Whether the key has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expiry == Expiry::Expired
+    }
+}
+
+/// A user's key counts and quota, as listed in `/proc/key-users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUser {
+    /// The user's id
+    pub uid: u32,
+    /// How many references there are to the user's key accounting record
+    pub usage: u32,
+    /// How many keys the user owns
+    pub keys: usize,
+    /// How many of those keys have been instantiated
+    pub instantiated: usize,
+    /// The user's quota of keys and payload bytes
+    pub quota: Quota,
+}
+
+/// Read the keys the caller can view from `/proc/keys`.
+pub fn keys() -> std::io::Result<Vec<ProcKey>> {
+    Ok(parse_keys(&std::fs::read_to_string("/proc/keys")?))
+}
+
+/// Read the users' key counts and quotas from `/proc/key-users`.
+///
+/// Unprivileged callers are usually only shown their own line.
+pub fn key_users() -> std::io::Result<Vec<KeyUser>> {
+    Ok(parse_key_users(&std::fs::read_to_string(
+        "/proc/key-users",
+    )?))
+}
+
+/// Parse the contents of `/proc/keys`, skipping any line that can't be parsed.
+pub fn parse_keys(contents: &str) -> Vec<ProcKey> {
+    contents.lines().filter_map(parse_key).collect()
+}
+
+/// Parse the contents of `/proc/key-users`, skipping any line that can't be parsed.
+pub fn parse_key_users(contents: &str) -> Vec<KeyUser> {
+    contents.lines().filter_map(parse_key_user).collect()
+}
+
+/// Parse a line of `/proc/keys`: serial, flags, usage, timeout,
+/// permissions, uid, gid, type, and then the description (with the
+/// type's summary of the payload, if any, after a final `: `).
+pub fn parse_key(line: &str) -> Option<ProcKey> {
+    let mut rest = line;
+    let mut fields = [""; 8];
+    for field in &mut fields {
+        let (value, tail) = rest.trim_start().split_once(' ')?;
+        *field = value;
+        rest = tail;
+    }
+    let [serial, flags, usage, timeout, perm, uid, gid, key_type] = fields;
+    let key_type = key_type.to_string();
+    let description = rest.trim_start();
+    let (description, summary) = match description.rsplit_once(": ") {
+        Some((description, summary)) if is_summary(&key_type, summary) => {
+            (description, Some(summary))
+        }
+        _ => (description, None),
+    };
+    // a keyring's summary is its link count, not a length
+    let payload_len = summary
+        .filter(|_| key_type != "keyring")
+        .and_then(|summary| summary.split(' ').next()?.parse().ok());
+    Some(ProcKey {
+        serial: KeySerialId::new(i32::from_str_radix(serial, 16).ok()?),
+        flags: flags.to_string(),
+        usage: usage.parse().ok()?,
+        expiry: parse_expiry(timeout)?,
+        permissions: Perm::from_bits(u32::from_str_radix(perm, 16).ok()?),
+        uid: uid.parse().ok()?,
+        gid: gid.parse().ok()?,
+        key_type,
+        description: description.to_string(),
+        summary: summary.map(str::to_string),
+        payload_len,
+    })
+}
+
+/// Parse a line of `/proc/key-users`, like `1000:     5 5/5 4/200 101/20000`.
+pub fn parse_key_user(line: &str) -> Option<KeyUser> {
+    let (uid, rest) = line.split_once(':')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let pair = |field: &str| -> Option<(usize, usize)> {
+        let (first, second) = field.split_once('/')?;
+        Some((first.parse().ok()?, second.parse().ok()?))
+    };
+    let (keys, instantiated) = pair(fields.get(1)?)?;
+    let (quota_keys, max_keys) = pair(fields.get(2)?)?;
+    let (bytes, max_bytes) = pair(fields.get(3)?)?;
+    Some(KeyUser {
+        uid: uid.trim().parse().ok()?,
+        usage: fields.first()?.parse().ok()?,
+        keys,
+        instantiated,
+        quota: Quota {
+            keys: quota_keys,
+            max_keys,
+            bytes,
+            max_bytes,
+        },
+    })
+}
+
+/// Parse the timeout column: `perm`, `expd`, or a count of `s`, `m`, `h`, `d`, or `w`.
+fn parse_expiry(timeout: &str) -> Option<Expiry> {
+    match timeout {
+        "perm" => Some(Expiry::Never),
+        "expd" => Some(Expiry::Expired),
+        _ => {
+            let unit = match timeout.as_bytes().last()? {
+                b's' => 1,
+                b'm' => 60,
+                b'h' => 60 * 60,
+                b'd' => 60 * 60 * 24,
+                b'w' => 60 * 60 * 24 * 7,
+                _ => return None,
+            };
+            let count: u64 = timeout[..timeout.len() - 1].parse().ok()?;
+            Some(Expiry::In(Duration::from_secs(count * unit)))
+        }
+    }
+}
+
+/// Whether this is a summary of a payload: a keyring's link count (or
+/// `empty`), an asymmetric key's subtype and key id (e.g. `X509.rsa
+/// 3db7e65a []`), or another key's payload length (with a storage note for big keys).
+fn is_summary(key_type: &str, summary: &str) -> bool {
+    let note = summary.split_once(" [");
+    if key_type == ASYMMETRIC_TYPE {
+        return note.is_some_and(|(subtype, note)| !subtype.is_empty() && note.ends_with(']'));
+    }
+    let length = match note {
+        Some((length, note)) => note.ends_with(']').then_some(length),
+        None => Some(summary),
+    };
+    summary == "empty"
+        || length.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
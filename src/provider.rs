@@ -0,0 +1,171 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use keyring_core::api::CredentialStoreApi;
+use keyring_core::{Error, Result};
+
+use super::{SecretBytes, Store};
+
+/// An external source of secrets, such as Vault or a cloud KMS.
+///
+/// A [SyncEngine] copies a provider's secrets into keyutils, so that reads
+/// are served from the kernel keyring, and the provider is only asked for
+/// secrets that are missing or due for a refresh. Providers are called from
+/// whichever thread reads or refreshes, so calls can block on the network.
+pub trait Provider: Debug + Send + Sync {
+    /// Fetch the secret of the credential for `service` and `user`.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if the provider has no such credential.
+    fn fetch(&self, service: &str, user: &str) -> Result<Vec<u8>>;
+
+    /// The services and users of the provider's credentials.
+    fn list(&self) -> Result<Vec<(String, String)>>;
+}
+
+/// Keeps a store's keys filled from a [Provider].
+///
+/// Each [refresh](SyncEngine::refresh) fetches every credential the
+/// provider lists and writes it to the store, so the kernel keyring acts as
+/// a hot cache of the provider's secrets. Reads through
+/// [get_secret](SyncEngine::get_secret) are served from the store, and fall
+/// through to the provider (writing what it returns to the store) when the
+/// store has no credential, e.g. because its key expired.
+///
+/// Create one with [new](SyncEngine::new) and call `refresh` from your own
+/// scheduler, or with [spawn](SyncEngine::spawn) to have a background thread
+/// do it periodically. The background thread stops when the engine is dropped.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use linux_keyutils_keyring_store::{Provider, Store, SyncEngine};
+///
+/// #[derive(Debug)]
+/// struct Vault;
+///
+/// impl Provider for Vault {
+///     fn fetch(&self, service: &str, user: &str) -> keyring_core::Result<Vec<u8>> {
+///         // ask the vault for the secret
+/// #       unimplemented!()
+///     }
+///     fn list(&self) -> keyring_core::Result<Vec<(String, String)>> {
+///         Ok(vec![("db".to_string(), "app".to_string())])
+///     }
+/// }
+///
+/// let engine = SyncEngine::spawn(Store::new().unwrap(), Arc::new(Vault), Duration::from_secs(300));
+/// let password = engine.get_password("db", "app")?;
+/// # Ok::<(), keyring_core::Error>(())
+/// ```
+pub struct SyncEngine {
+    store: Arc<Store>,
+    provider: Arc<dyn Provider>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SyncEngine {
+    /// Create an engine that fills `store` from `provider`, and is refreshed manually.
+    pub fn new(store: Arc<Store>, provider: Arc<dyn Provider>) -> Self {
+        SyncEngine {
+            store,
+            provider,
+            stop: None,
+            thread: None,
+        }
+    }
+
+    /// Create an engine that refreshes `store` from `provider` right away,
+    /// and then every `interval`, on a background thread.
+    ///
+    /// Errors during background refreshes are ignored; the next refresh tries again.
+    pub fn spawn(store: Arc<Store>, provider: Arc<dyn Provider>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let worker = SyncEngine::new(store.clone(), provider.clone());
+        let thread = std::thread::spawn(move || {
+            _ = worker.refresh();
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                _ = worker.refresh();
+            }
+        });
+        SyncEngine {
+            store,
+            provider,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Fetch every credential the provider lists, and write it to the store.
+    ///
+    /// Credentials the provider lists but then has no secret for are
+    /// skipped. Other failures stop the refresh, leaving the credentials
+    /// refreshed before it in the store.
+    ///
+    /// Returns the number of credentials written.
+    pub fn refresh(&self) -> Result<usize> {
+        let mut count = 0;
+        for (service, user) in self.provider.list()? {
+            match self.sync(&service, &user) {
+                Ok(_) => count += 1,
+                // it went away since it was listed
+                Err(Error::NoEntry) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Get the secret of the credential for `service` and `user`.
+    ///
+    /// The secret is read from the store if it's there, and otherwise
+    /// fetched from the provider and written to the store for next time.
+    /// Returns a [NoEntry](Error::NoEntry) error if neither has it.
+    pub fn get_secret(&self, service: &str, user: &str) -> Result<Vec<u8>> {
+        match self.store.build(service, user, None)?.get_secret() {
+            Err(Error::NoEntry) => Ok(self.sync(service, user)?.to_vec()),
+            result => result,
+        }
+    }
+
+    /// Get the password of the credential for `service` and `user`; see [get_secret](SyncEngine::get_secret).
+    pub fn get_password(&self, service: &str, user: &str) -> Result<String> {
+        let secret = self.get_secret(service, user)?;
+        String::from_utf8(secret).map_err(|e| Error::BadEncoding(e.into_bytes()))
+    }
+
+    /// The store the engine fills.
+    pub fn store(&self) -> &Arc<Store> {
+        &self.store
+    }
+
+    /// Fetch one credential from the provider and write it to the store.
+    fn sync(&self, service: &str, user: &str) -> Result<SecretBytes> {
+        let secret = SecretBytes::new(self.provider.fetch(service, user)?);
+        self.store.build(service, user, None)?.set_secret(&secret)?;
+        Ok(secret)
+    }
+}
+
+impl Drop for SyncEngine {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for SyncEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncEngine")
+            .field("store", &self.store)
+            .field("provider", &self.provider)
+            .field("background", &self.thread.is_some())
+            .finish()
+    }
+}
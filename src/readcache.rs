@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+#[cfg(feature = "watch")]
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use linux_keyutils::KeySerialId;
+
+use super::SecretBytes;
+#[cfg(feature = "watch")]
+use super::{KeyEvent, Notification, Watcher};
+
+/// What a secret is cached under: the key it was read from, and the
+/// description and specifiers its payload was checked against.
+type Read = (i32, Arc<str>, Option<(Arc<str>, Arc<str>)>);
+
+/// A secret read from a key, served until `expires`.
+struct Cached {
+    secret: SecretBytes,
+    expires: Instant,
+}
+
+struct State {
+    secrets: HashMap<Read, Cached>,
+    /// Bumped on every [clear](ReadCache::clear), so that a read that
+    /// raced a write doesn't cache what it read
+    generation: u64,
+    #[cfg(feature = "watch")]
+    watcher: Option<Watcher>,
+    #[cfg(feature = "watch")]
+    watched: HashSet<i32>,
+}
+
+/// A store's in-process cache of the secrets its credentials read.
+///
+/// Secrets are cached by the serial of the key they were read from, so a
+/// credential still finds its key on every read (which is when the key is
+/// re-linked, its owner checked, and its timeout re-armed); only reading
+/// and checking the payload is saved. The payload's checks depend on the
+/// credential's description and specifiers, so these are part of what a
+/// secret is cached under.
+///
+/// A secret is served for the cache's TTL at most. With the `watch`
+/// feature, on kernels with key notifications, the key it was read from is
+/// watched, and the secret is dropped as soon as the key is updated,
+/// revoked, invalidated, or has its attributes changed, by this process or
+/// any other. Writes and deletes through any of the store's credentials
+/// empty the cache.
+pub(crate) struct ReadCache {
+    ttl: Duration,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for ReadCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the cached secrets stay out of debug output
+        let state = self.state.lock().unwrap();
+        f.debug_struct("ReadCache")
+            .field("ttl", &self.ttl)
+            .field("cached", &state.secrets.len())
+            .field("watching", &state.is_watching())
+            .finish()
+    }
+}
+
+impl ReadCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        ReadCache {
+            ttl,
+            state: Mutex::new(State {
+                secrets: HashMap::new(),
+                generation: 0,
+                // without key notifications, the TTL is all there is
+                #[cfg(feature = "watch")]
+                watcher: Watcher::new().ok(),
+                #[cfg(feature = "watch")]
+                watched: HashSet::new(),
+            }),
+        }
+    }
+
+    /// Whether changes to cached keys are noticed as they happen, rather
+    /// than when the TTL runs out.
+    pub(crate) fn is_watching(&self) -> bool {
+        self.state.lock().unwrap().is_watching()
+    }
+
+    /// The secret cached for the key `serial`, if it's still good.
+    pub(crate) fn get(
+        &self,
+        serial: KeySerialId,
+        description: &Arc<str>,
+        specifiers: &Option<(Arc<str>, Arc<str>)>,
+    ) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        state.sync();
+        let read = (serial.as_raw_id(), description.clone(), specifiers.clone());
+        match state.secrets.get(&read) {
+            Some(cached) if cached.expires > Instant::now() => Some(cached.secret.to_vec()),
+            Some(_) => {
+                state.secrets.remove(&read);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// The current generation, to be passed to [insert](ReadCache::insert)
+    /// with a secret read after getting it.
+    pub(crate) fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    /// Cache the secret read from the key `serial`.
+    ///
+    /// Nothing is cached if the cache was cleared since `generation` (the
+    /// secret may be one a write replaced), or if the cache watches keys
+    /// and can't watch this one.
+    pub(crate) fn insert(
+        &self,
+        generation: u64,
+        serial: KeySerialId,
+        description: &Arc<str>,
+        specifiers: &Option<(Arc<str>, Arc<str>)>,
+        secret: &[u8],
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation || !state.watch(serial, description) {
+            return;
+        }
+        let now = Instant::now();
+        state.secrets.retain(|_, cached| cached.expires > now);
+        state.secrets.insert(
+            (serial.as_raw_id(), description.clone(), specifiers.clone()),
+            Cached {
+                secret: SecretBytes::new(secret.to_vec()),
+                expires: now + self.ttl,
+            },
+        );
+    }
+
+    /// Drop every cached secret, because a key was written or deleted.
+    pub(crate) fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.secrets.clear();
+        state.generation += 1;
+    }
+}
+
+impl State {
+    #[cfg(feature = "watch")]
+    fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    #[cfg(not(feature = "watch"))]
+    fn is_watching(&self) -> bool {
+        false
+    }
+
+    /// Make sure changes to `serial` will be noticed, returning whether they
+    /// will be (or there's no watching them).
+    #[cfg(feature = "watch")]
+    fn watch(&mut self, serial: KeySerialId, description: &str) -> bool {
+        let Some(watcher) = &mut self.watcher else {
+            return true;
+        };
+        if self.watched.contains(&serial.as_raw_id()) {
+            return true;
+        }
+        if watcher.watch_serial(serial, description).is_err() {
+            return false;
+        }
+        self.watched.insert(serial.as_raw_id());
+        true
+    }
+
+    #[cfg(not(feature = "watch"))]
+    fn watch(&mut self, _: KeySerialId, _: &str) -> bool {
+        true
+    }
+
+    /// Drop the secrets of keys that notifications say have changed.
+    #[cfg(feature = "watch")]
+    fn sync(&mut self) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+        let notifications = match watcher.recv(Some(Duration::ZERO)) {
+            Ok(notifications) => notifications,
+            // changes can't be seen any more, so nothing cached can be trusted
+            Err(_) => vec![Notification::Overrun],
+        };
+        for notification in notifications {
+            match notification {
+                Notification::Key { key, event, .. } => {
+                    if event == KeyEvent::WatchRemoved {
+                        self.watched.remove(&key.as_raw_id());
+                    }
+                    self.secrets
+                        .retain(|(serial, _, _), _| *serial != key.as_raw_id());
+                }
+                Notification::Overrun => {
+                    self.secrets.clear();
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "watch"))]
+    fn sync(&mut self) {}
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{Key, KeySerialId};
+
+use super::error::KeyStoreError;
+
+/// The special serial of the authorization key held by a `request-key` handler.
+const KEY_SPEC_REQKEY_AUTH_KEY: i32 = -7;
+
+/// The handler side of a `request_key(2)` upcall.
+///
+/// When an entry built with the `callout` modifier is read and its key is
+/// missing, the kernel creates an uninstantiated key and runs
+/// `/sbin/request-key`, which dispatches to the handler configured in
+/// `/etc/request-key.conf` (or `/etc/request-key.d/`). A handler written
+/// against this crate should be passed the key's serial (`%k`), e.g.:
+///
+/// ```text
+/// create user keyring:* * /usr/libexec/my-handler %k
+/// ```
+///
+/// The handler then calls [assume](KeyRequest::assume) with that serial,
+/// and either [instantiate](KeyRequest::instantiate)s the key with the
+/// secret or [negate](KeyRequest::negate)s it. (If it exits without doing
+/// either, `request-key` negates the key.)
+#[derive(Debug)]
+pub struct KeyRequest {
+    /// The key being constructed
+    pub key: KeySerialId,
+    /// The description of the key being constructed
+    pub description: String,
+    /// The callout info passed by the requester
+    pub callout: String,
+}
+
+impl KeyRequest {
+    /// Take over the authority to instantiate the key with the given serial.
+    ///
+    /// This only works in a process started by `request-key`, which holds
+    /// the authorization key for the request.
+    pub fn assume(key: KeySerialId) -> Result<Self> {
+        let pending = Key::from_id(key);
+        pending.assume_authority().map_err(KeyStoreError)?;
+        let metadata = pending.metadata().map_err(KeyStoreError)?;
+        let callout = Key::from_id(KeySerialId::new(KEY_SPEC_REQKEY_AUTH_KEY))
+            .read_to_vec()
+            .map_err(KeyStoreError)?;
+        let callout = String::from_utf8(callout).map_err(|e| Error::BadEncoding(e.into_bytes()))?;
+        Ok(KeyRequest {
+            key,
+            description: metadata.get_description().to_string(),
+            callout,
+        })
+    }
+
+    /// Supply the secret for the requested key.
+    ///
+    /// The key is already linked into the requester's keyring, so the
+    /// requester's read completes with this secret.
+    pub fn instantiate(self, secret: &[u8]) -> Result<()> {
+        if secret.is_empty() {
+            return Err(Error::Invalid(
+                "secret".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        Key::from_id(self.key)
+            .instantiate(secret, KeySerialId::new(0))
+            .map_err(KeyStoreError)?;
+        Ok(())
+    }
+
+    /// Refuse the request.
+    ///
+    /// The requester's read fails with [NoEntry](Error::NoEntry), as do
+    /// further reads of the key until `timeout` elapses.
+    pub fn negate(self, timeout: Duration) -> Result<()> {
+        Key::from_id(self.key)
+            .reject(timeout.as_secs() as usize)
+            .map_err(KeyStoreError)?;
+        Ok(())
+    }
+}
+
+impl Drop for KeyRequest {
+    fn drop(&mut self) {
+        // relinquish the authority, so later key operations use our own credentials
+        _ = Key::from_id(KeySerialId::new(0)).assume_authority();
+    }
+}
@@ -0,0 +1,99 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::KeyError;
+
+/// How a store retries kernel operations that fail for transient reasons.
+///
+/// An operation is retried if a system call was interrupted (`EINTR`) or
+/// the kernel asked to try again (`EAGAIN`). The wait between attempts
+/// starts at `backoff` and doubles after each retry.
+///
+/// (Whatever the policy, a read that finds a key which is invalidated,
+/// revoked, or expired before it can be read, e.g. because a concurrent write
+/// replaced it, searches again: the key's replacement is found, if it has one.)
+///
+/// The default is not to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times an operation is attempted in all (at least once)
+    pub attempts: u32,
+    /// How long to wait before the first retry
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(1, Duration::ZERO)
+    }
+}
+
+impl RetryPolicy {
+    /// Attempt operations up to `attempts` times, waiting `backoff` (doubling each time) in between.
+    pub fn new(attempts: u32, backoff: Duration) -> Self {
+        RetryPolicy {
+            attempts: attempts.max(1),
+            backoff,
+        }
+    }
+
+    /// Run `op` until it succeeds, fails for good, or runs out of attempts.
+    ///
+    /// `retry` says whether an error is worth retrying.
+    pub(crate) fn run<T, E>(
+        &self,
+        retry: impl Fn(&E) -> bool,
+        mut op: impl FnMut() -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let mut wait = self.backoff;
+        for _ in 1..self.attempts {
+            match op() {
+                Err(err) if retry(&err) => {
+                    std::thread::sleep(wait);
+                    wait = wait.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        op()
+    }
+}
+
+impl FromStr for RetryPolicy {
+    type Err = Error;
+
+    /// Parse `attempts` or `attempts/backoff_ms`, e.g. `3/10`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(
+                "retry".to_string(),
+                "must be an attempt count, optionally followed by /backoff in milliseconds"
+                    .to_string(),
+            )
+        };
+        let (attempts, backoff) = match s.split_once('/') {
+            Some((attempts, backoff)) => (attempts, backoff.parse().map_err(|_| invalid())?),
+            None => (s, 0),
+        };
+        match attempts.parse() {
+            Ok(attempts) if attempts > 0 => {
+                Ok(RetryPolicy::new(attempts, Duration::from_millis(backoff)))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Whether an error is a transient failure of the system call.
+pub(crate) fn is_transient(err: &KeyError) -> bool {
+    matches!(err, KeyError::Unknown(libc::EINTR | libc::EAGAIN))
+}
+
+/// Whether an error reading a key means it went away after it was found.
+pub(crate) fn is_stale(err: &KeyError) -> bool {
+    matches!(
+        err,
+        KeyError::KeyDoesNotExist | KeyError::KeyRevoked | KeyError::KeyExpired
+    )
+}
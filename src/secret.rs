@@ -0,0 +1,130 @@
+use std::io::Read;
+use std::ops::Deref;
+
+use zeroize::Zeroize;
+
+/// A secret read from the store that is wiped from memory when dropped.
+///
+/// The buffer's entire allocation (not just its contents) is overwritten
+/// with zeros on drop, by [zeroize], so no plaintext copy lingers in freed
+/// heap memory. Its `Debug` output never includes the secret.
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    /// Take ownership of `bytes`, which will be wiped on drop.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+
+    /// The secret bytes.
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Read a secret from `reader` until it ends.
+    ///
+    /// Unlike [Read::read_to_end], this wipes each buffer the secret
+    /// outgrows, so no partial copy of it is left behind in freed memory.
+    pub fn read_from(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut secret = SecretBytes::new(Vec::with_capacity(64));
+        loop {
+            let len = secret.0.len();
+            if len == secret.0.capacity() {
+                let mut grown = Vec::with_capacity(2 * len);
+                grown.extend_from_slice(&secret.0);
+                // the outgrown buffer is wiped as it's dropped
+                secret = SecretBytes::new(grown);
+            }
+            let capacity = secret.0.capacity();
+            secret.0.resize(capacity, 0);
+            let read = reader.read(&mut secret.0[len..]);
+            secret.0.truncate(len + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => return Ok(secret),
+                Ok(_) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes::new(bytes)
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        // this zeroes the spare capacity as well as the contents
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.0.len())
+    }
+}
+
+/// A reader over a secret read from the store, which wipes the secret when dropped.
+///
+/// See [Cred::get_secret_reader](crate::Cred::get_secret_reader).
+pub struct SecretReader {
+    secret: SecretBytes,
+    position: usize,
+}
+
+impl SecretReader {
+    pub(crate) fn new(secret: SecretBytes) -> Self {
+        SecretReader {
+            secret,
+            position: 0,
+        }
+    }
+
+    /// The length of the whole secret.
+    pub fn len(&self) -> usize {
+        self.secret.len()
+    }
+
+    /// Whether the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.secret.is_empty()
+    }
+}
+
+impl Read for SecretReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let rest = &self.secret[self.position..];
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl std::fmt::Debug for SecretReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SecretReader([REDACTED; {}], position: {})",
+            self.secret.len(),
+            self.position
+        )
+    }
+}
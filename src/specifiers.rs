@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+
+use super::Normalization;
+
+/// What a store does with services and users that contain control characters.
+///
+/// Control characters (including NUL) in a description make the key hard
+/// to display or address with `keyctl` and `/proc/keys`, and a NUL cuts
+/// the description short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlChars {
+    /// Refuse to build the entry (the default).
+    #[default]
+    Reject,
+    /// Escape each control character as `\u{XX}` (its hex code point), and
+    /// each backslash as `\\` (even in names without control characters),
+    /// so that distinct names stay distinct.
+    Escape,
+}
+
+impl FromStr for ControlChars {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "reject" => Ok(ControlChars::Reject),
+            "escape" => Ok(ControlChars::Escape),
+            _ => Err(Error::Invalid(
+                "control_chars".to_string(),
+                "must be reject or escape".to_string(),
+            )),
+        }
+    }
+}
+
+/// How a store turns the services and users it's given into the ones it describes keys with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Canonicalizer {
+    pub(crate) control_chars: ControlChars,
+    /// Whether services and users are lower-cased
+    pub(crate) case_insensitive: bool,
+    /// Which normalization form services and users are put into, if any
+    pub(crate) normalization: Option<Normalization>,
+}
+
+impl Canonicalizer {
+    /// The canonical form of `value`, the service or user named `name`.
+    ///
+    /// Case is folded before normalizing (which folding can undo), and
+    /// control characters are escaped last (normalizing can make backslashes).
+    pub(crate) fn apply<'a>(&self, name: &str, value: &'a str) -> Result<Cow<'a, str>> {
+        let mut value = Cow::Borrowed(value);
+        if self.case_insensitive && value.chars().any(|c| c.is_uppercase()) {
+            value = Cow::Owned(value.to_lowercase());
+        }
+        if let Some(normalization) = self.normalization {
+            // ASCII is already in every normalization form
+            if !value.is_ascii() {
+                value = Cow::Owned(normalization.apply(&value));
+            }
+        }
+        self.escape(name, value)
+    }
+
+    /// Reject or escape the control characters in `value`.
+    fn escape<'a>(&self, name: &str, value: Cow<'a, str>) -> Result<Cow<'a, str>> {
+        match self.control_chars {
+            ControlChars::Reject => match value.chars().find(|c| c.is_control()) {
+                Some(c) => Err(Error::Invalid(
+                    name.to_string(),
+                    format!("contains the control character U+{:04X}", c as u32),
+                )),
+                None => Ok(value),
+            },
+            ControlChars::Escape if value.contains(|c: char| c == '\\' || c.is_control()) => {
+                Ok(Cow::Owned(escape(&value)))
+            }
+            ControlChars::Escape => Ok(value),
+        }
+    }
+}
+
+/// Escape the control characters and backslashes in `value`.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:02x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
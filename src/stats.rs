@@ -0,0 +1,44 @@
+use super::Quota;
+
+/// The store's keys in one of its keyrings, as counted by [Store::stats](crate::Store::stats).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyringStats {
+    /// The keyring's name (`session`, `persistent`, a mirror, ...)
+    pub name: String,
+    /// How many of the store's keys are linked into the keyring
+    pub keys: usize,
+    /// The total payload size of those keys, in bytes
+    pub bytes: usize,
+}
+
+/// Counts of a store's keys and their payload sizes, produced by [Store::stats](crate::Store::stats).
+///
+/// Keys are only counted if their descriptions follow the store's naming
+/// scheme, and a key linked into several of the store's keyrings is counted
+/// once in the totals. Payload sizes are as the kernel reports them, so they
+/// include any envelope or encryption overhead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// The counts for each of the store's keyrings
+    pub keyrings: Vec<KeyringStats>,
+    /// How many distinct keys the store has
+    pub keys: usize,
+    /// The total payload size of those keys, in bytes
+    pub bytes: usize,
+    /// The current user's key quota, if it could be read
+    pub quota: Option<Quota>,
+}
+
+impl Stats {
+    /// How many more keys the current user can own, if the quota could be read.
+    pub fn keys_headroom(&self) -> Option<usize> {
+        self.quota
+            .map(|quota| quota.max_keys.saturating_sub(quota.keys))
+    }
+
+    /// How many more bytes of payload the current user can own, if the quota could be read.
+    pub fn bytes_headroom(&self) -> Option<usize> {
+        self.quota
+            .map(|quota| quota.max_bytes.saturating_sub(quota.bytes))
+    }
+}
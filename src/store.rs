@@ -1,12 +1,104 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use keyring_core::api::{CredentialPersistence, CredentialStoreApi};
-use keyring_core::attributes::parse_attributes;
-use keyring_core::{Entry, Result};
+use super::compat::parse_attributes;
+use keyring_core::api::{CredentialApi, CredentialPersistence, CredentialStoreApi};
+use keyring_core::{Entry, Error, Result};
+use linux_keyutils::{Key, KeyError, KeyRing, KeySerialId, KeyType};
 
-use super::Cred;
+use super::asymmetric;
+use super::backup::Backup;
+use super::cred::{Keyrings, ReadOnly, check_no_divider, fit_description};
+use super::crypto::{DIGEST_LEN, hmac_sha256, sha256, to_hex};
+use super::error::KeyStoreError;
+use super::export;
+use super::history::versioned_description;
+use super::metrics::{ErrorCategory, MetricsObserver, Observation, Operation};
+use super::negative::NegativeCache;
+use super::procfs;
+use super::readcache::ReadCache;
+use super::specifiers::Canonicalizer;
+use super::stats::{KeyringStats, Stats};
+use super::{
+    AuditSink, Capabilities, Cipher, ControlChars, Cred, CredRef, DescriptionOverflow, Diagnosis,
+    HistoryPolicy, Integrity, Keyctl, Mirror, Normalization, Perm, PersistentPolicy, Quota,
+    Redaction, Relink, RetryPolicy, SecretBytes, StoreBuilder, StoreConfig, Target, WriteMode, sys,
+    user,
+};
+
+/// The entry modifiers accepted by [build](CredentialStoreApi::build).
+pub(crate) const MODIFIERS: &[&str] = &[
+    "description",
+    "target",
+    "*create_new",
+    "*update_only",
+    "callout",
+    "keyring",
+    "key_type",
+    "uid",
+    "gid",
+    "content_type",
+    "audit_context",
+    "*record_specifiers",
+];
+
+/// The custom description given by an entry's `description` modifier, or
+/// by its `target` modifier if `target` is accepted as an alias.
+pub(crate) fn custom_description(
+    mods: &HashMap<String, String>,
+    target: bool,
+) -> Result<Option<&str>> {
+    match (mods.get("description"), mods.get("target")) {
+        (description, None) => Ok(description.map(|s| s.as_str())),
+        (_, Some(_)) if !target => Err(Error::Invalid(
+            "target".to_string(),
+            "is not accepted by this store (use description)".to_string(),
+        )),
+        (None, Some(target)) => Ok(Some(target.as_str())),
+        (Some(_), Some(_)) => Err(Error::Invalid(
+            "target".to_string(),
+            "cannot be combined with description".to_string(),
+        )),
+    }
+}
+
+/// A dead key unlinked by [Store::prune].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedKey {
+    /// The serial of the key
+    pub serial: KeySerialId,
+    /// The keyring it was unlinked from
+    pub keyring: KeySerialId,
+    /// The description of the key
+    pub description: String,
+    /// Whether the key was revoked (rather than expired)
+    pub revoked: bool,
+}
+
+/// How [Store::migrate_to] treats credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MigrateOptions {
+    /// Delete each original once it has been rewritten
+    pub delete_originals: bool,
+    /// Overwrite credentials the other store already has (otherwise they're kept, and skipped)
+    pub overwrite: bool,
+}
+
+/// A credential rewritten by [Store::migrate_to].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigratedKey {
+    /// The service of the credential
+    pub service: String,
+    /// The user of the credential
+    pub user: String,
+    /// The description of its key in this store
+    pub from: String,
+    /// The description of its key in the other store
+    pub to: String,
+}
 
 /// The builder for keyutils credentials
 #[derive(Clone)]
@@ -14,6 +106,75 @@ pub struct Store {
     pub id: String,
     pub delimiters: [String; 3],
     pub service_no_divider: bool,
+    /// Whether users may not contain the divider
+    pub user_no_divider: bool,
+    pub keyring: Target,
+    pub relink: Relink,
+    pub backup: Option<Arc<Backup>>,
+    pub timeout: Option<Duration>,
+    /// How long after each write or read keys expire, if they should
+    pub keepalive_timeout: Option<Duration>,
+    pub permissions: Option<Perm>,
+    /// The user whose persistent keyring holds the keys, if not the effective user
+    pub keyring_uid: Option<u32>,
+    /// Whether key payloads carry an [Envelope](crate::Envelope) of metadata
+    pub envelope: bool,
+    /// How key payloads are protected against tampering, if they are
+    pub integrity: Option<Integrity>,
+    /// How key payloads are encrypted, if they are
+    pub cipher: Option<Arc<dyn Cipher>>,
+    /// Whether key descriptions are hashes of the service and user
+    pub hash_descriptions: bool,
+    /// Whether entries with custom descriptions record their service and user
+    pub record_specifiers: bool,
+    /// Whether the `target` entry modifier is accepted as an alias of `description`
+    pub target_modifier: bool,
+    /// The extra keyrings keys are mirrored into, in search order
+    pub mirrors: Vec<Mirror>,
+    /// Where reads, writes, and deletes of credentials are recorded, if they are
+    pub audit: Option<Arc<dyn AuditSink>>,
+    /// What measures the store's operations, if anything does
+    pub metrics: Option<Arc<dyn MetricsObserver>>,
+    /// How kernel operations that fail for transient reasons are retried
+    pub retry: RetryPolicy,
+    /// How many previous versions of each credential are kept, if any are
+    pub history: Option<HistoryPolicy>,
+    /// The longest key description the store will use
+    pub max_description_len: usize,
+    /// What the store does with descriptions that are too long
+    pub description_overflow: DescriptionOverflow,
+    /// What the store does with services and users that contain control characters
+    pub control_chars: ControlChars,
+    /// Whether services and users are matched case-insensitively
+    pub case_insensitive: bool,
+    /// Which Unicode normalization form services and users are put into, if any
+    pub normalization: Option<Normalization>,
+    /// The longest key payload reads will allocate for, if reads are limited
+    pub max_read_len: Option<usize>,
+    /// Whether reads check the key's owner and permissions first
+    pub verify_owner: bool,
+    /// Whether the store and its credentials refuse to change anything
+    pub read_only: bool,
+    /// How much of their descriptions credentials show in debug output
+    pub redaction: Redaction,
+    /// Whether credentials' access errors say which security module may have caused them
+    pub lsm_hints: bool,
+    /// What is done when the persistent keyring can't be had or linked to
+    pub persistent: PersistentPolicy,
+    /// Whether the store's keyrings are resolved per credential rather than when it's built
+    pub lazy_keyrings: bool,
+    /// The store's keyrings, unless they are resolved lazily
+    pub(crate) keyrings: Option<Arc<Keyrings>>,
+    /// How long credentials found missing are remembered as missing, if they are
+    pub negative_cache: Option<Duration>,
+    /// The credentials recently found missing, if misses are remembered
+    pub(crate) misses: Option<Arc<NegativeCache>>,
+    /// How long secrets read are served from memory, if they are
+    pub read_cache: Option<Duration>,
+    /// The secrets recently read, if they are cached
+    pub(crate) secrets: Option<Arc<ReadCache>>,
+    /// What credentials search for, add, link, read, and invalidate their keys with
+    pub keyctl: Arc<dyn Keyctl>,
 }
 
 impl std::fmt::Debug for Store {
@@ -23,6 +184,43 @@ impl std::fmt::Debug for Store {
             .field("id", &self.id())
             .field("delimiters", &self.delimiters)
             .field("service_no_divider", &self.service_no_divider)
+            .field("user_no_divider", &self.user_no_divider)
+            .field("keyring", &self.keyring)
+            .field("relink", &self.relink)
+            .field("backup", &self.backup)
+            .field("timeout", &self.timeout)
+            .field("keepalive_timeout", &self.keepalive_timeout)
+            .field("permissions", &self.permissions)
+            .field("keyring_uid", &self.keyring_uid)
+            .field("envelope", &self.envelope)
+            .field("integrity", &self.integrity)
+            .field("cipher", &self.cipher)
+            .field("hash_descriptions", &self.hash_descriptions)
+            .field("record_specifiers", &self.record_specifiers)
+            .field("target_modifier", &self.target_modifier)
+            .field("mirrors", &self.mirrors)
+            .field("audit", &self.audit)
+            .field("metrics", &self.metrics)
+            .field("retry", &self.retry)
+            .field("history", &self.history)
+            .field("max_description_len", &self.max_description_len)
+            .field("description_overflow", &self.description_overflow)
+            .field("control_chars", &self.control_chars)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("normalization", &self.normalization)
+            .field("max_read_len", &self.max_read_len)
+            .field("verify_owner", &self.verify_owner)
+            .field("read_only", &self.read_only)
+            .field("redaction", &self.redaction)
+            .field("lsm_hints", &self.lsm_hints)
+            .field("persistent", &self.persistent)
+            .field("lazy_keyrings", &self.lazy_keyrings)
+            .field("keyrings", &self.keyrings)
+            .field("negative_cache", &self.negative_cache)
+            .field("misses", &self.misses)
+            .field("read_cache", &self.read_cache)
+            .field("secrets", &self.secrets)
+            .field("keyctl", &self.keyctl)
             .finish()
     }
 }
@@ -32,10 +230,7 @@ impl Store {
     ///
     /// This is the configuration that matches the legacy keyring for this store.
     pub fn new() -> Result<Arc<Self>> {
-        Ok(Self::new_internal(
-            ["keyring:".to_string(), "@".to_string(), "".to_string()],
-            false,
-        ))
+        StoreBuilder::new().build()
     }
 
     /// Create a custom-configured store.
@@ -43,55 +238,1236 @@ impl Store {
     /// The delimiter config options are `prefix`, `divider`, and `suffix`. They
     /// default to `keyring:`, `@`, and the empty string, respectively.
     ///
-    /// If you want to be sure that key descriptions cannot be ambiguous, specify
-    /// the config option `service_no_divider` to `true`.
+    /// The delimiters must keep descriptions parseable: the divider can't be
+    /// empty, and neither the prefix nor the suffix can contain it. If you
+    /// want to be sure that key descriptions cannot be ambiguous, specify the
+    /// config option `service_no_divider` (or `user_no_divider`) to `true`.
+    ///
+    /// The config option `keyring` selects the keyring that keys are added to:
+    /// `session` (the default), `process`, `user`, `thread`, `persistent`, or
+    /// `user_session` (the user's default session keyring, which is what many
+    /// container runtimes leave processes with). Session, user, and
+    /// user-session keys are also linked into the user's persistent keyring.
+    /// (See [Target].)
+    ///
+    /// The config option `relink` controls whether reads re-link keys into
+    /// their keyrings: `always` (the default), `best_effort` (ignore link
+    /// failures), `never`, or a number `n` to re-link (best effort) on every
+    /// `n`th read of an entry. See [Relink].
+    ///
+    /// To keep an encrypted on-disk backup of every credential, specify the
+    /// config option `backup_dir` as the directory to hold the backup files.
+    /// The backup is sealed by a master key held in the keyring under the
+    /// description given by `backup_key` (default `keyring-store:backup-master`),
+    /// which must be provisioned with [provision_backup_key](Store::provision_backup_key)
+    /// (or `keyctl`) before credentials can be written or recovered.
+    ///
+    /// The config option `timeout` makes keys expire the given number of
+    /// seconds after they were last written, and `permissions` gives the
+    /// permission mask (in hex, as shown by `keyctl describe`) to set on
+    /// keys when they are written.
+    ///
+    /// The config option `keepalive_timeout` gives keys a sliding expiry:
+    /// they expire the given number of seconds after they were last written
+    /// or successfully read, since each read re-arms the timeout.
+    ///
+    /// The config option `keyring_user` picks whose keyrings the store uses:
+    /// `current` (the default) for the effective user's, `invoking` for
+    /// those of the user who ran the program under `sudo`, or a numeric uid.
+    /// (See [KeyringUser](crate::KeyringUser).)
+    ///
+    /// Specifying the config option `envelope` as `true` makes each key's
+    /// payload carry an [Envelope](crate::Envelope) recording when the
+    /// credential was created and modified, the service and user it was
+    /// created for, and a content-type hint. Reads strip the envelope (and
+    /// still accept bare payloads written without one).
+    ///
+    /// The config option `integrity` protects payloads against corruption
+    /// and tampering: `checksum` tags them with a SHA-256 checksum, and `hmac`
+    /// with an HMAC keyed from a master key held in the keyring under the
+    /// description given by `integrity_key` (default
+    /// `keyring-store:integrity-master`), which must be provisioned with
+    /// [provision_integrity_key](Store::provision_integrity_key). Either turns
+    /// on envelopes. See [Integrity].
+    ///
+    /// Specifying the config option `hash_descriptions` as `true` keeps service
+    /// and user names out of `/proc/keys`: key descriptions are the prefix, an
+    /// HMAC-SHA256 (keyed by the prefix) of the service and user, and the suffix,
+    /// while the service and user themselves are recorded in the key's envelope
+    /// (which this turns on). Since the prefix isn't secret, a reader who can
+    /// guess a service and user can still confirm the guess.
+    ///
+    /// Specifying the config option `record_specifiers` as `true` gives
+    /// entries built with a custom description the service and user they
+    /// were built with as their specifiers, recorded in the key's envelope
+    /// (which this turns on). See [StoreBuilder::record_specifiers].
+    ///
+    /// Specifying the config option `target_modifier` as `false` rejects the
+    /// `target` entry modifier, which is otherwise an alias of `description`.
+    ///
+    /// The config option `mirrors` gives a comma-separated list of extra
+    /// keyrings to link every key into when it's written: targets (as for
+    /// `keyring`), or `%:name` for the keyring named `name` in the user keyring
+    /// (which is created if needed). Keys missing from the store's keyring are
+    /// searched for in the mirrors, in the order given. See [Mirror].
+    ///
+    /// The config option `retry` gives how many times to attempt kernel
+    /// operations that fail for transient reasons, optionally followed by
+    /// `/` and the milliseconds to wait before the first retry (e.g. `3/10`).
+    /// The default is `1` (no retries). See [RetryPolicy].
+    ///
+    /// The config option `history` keeps previous versions of each credential:
+    /// how many to keep, optionally followed by `/` and how many seconds each
+    /// lasts (e.g. `5/3600`; the default is a day). See [HistoryPolicy].
+    ///
+    /// The config option `max_description_len` limits the length of key
+    /// descriptions, in bytes: the default is the kernel's limit of 4095,
+    /// but some tools that read `/proc/keys` choke on much less. What happens
+    /// to longer descriptions is given by `description_overflow`: `reject`
+    /// (the default) fails to build the entry, and `hash` shortens the
+    /// description by hashing its end (see [DescriptionOverflow]).
+    ///
+    /// Services and users that contain control characters (such as NUL) are
+    /// rejected with an [Invalid](Error::Invalid) error naming the character,
+    /// unless the config option `control_chars` is `escape`, which escapes
+    /// them instead (see [ControlChars]).
+    ///
+    /// Specifying the config option `case_insensitive` as `true` matches
+    /// services and users regardless of case, by lower-casing them in
+    /// descriptions, so that an entry for `GitHub` finds the credential
+    /// written for `github`. (Entries' specifiers are then lower-cased too.)
+    ///
+    /// Specifying the config option `normalization` as `nfc` or `nfkc` puts
+    /// services and users into that Unicode normalization form, so that
+    /// names which look the same but are encoded differently describe the
+    /// same key (see [Normalization]). By default they are used as given.
+    ///
+    /// The config option `max_read_len` limits how long a key payload reads
+    /// will allocate for, in bytes. A read of a longer payload (e.g. one put
+    /// in a shared keyring by another process) checks its length first and
+    /// returns a [TooLong](Error::TooLong) error for `payload`, rather than
+    /// reading it. By default, reads take payloads of any length the key
+    /// type allows (up to 1MiB for `big_key`).
+    ///
+    /// Specifying the config option `verify_owner` as `true` makes reads
+    /// describe each key before reading it, and refuse (with a
+    /// [NoStorageAccess](Error::NoStorageAccess) error) a key that doesn't
+    /// belong to the expected user or has other than the expected
+    /// permissions, as one planted by another process sharing the keyring
+    /// would (see [StoreBuilder::verify_owner]).
+    ///
+    /// Specifying the config option `read_only` as `true` makes every write,
+    /// delete, and other change fail with a
+    /// [NoStorageAccess](Error::NoStorageAccess) error wrapping a
+    /// [ReadOnly](crate::ReadOnly), while reads still work (see
+    /// [StoreBuilder::read_only]).
+    ///
+    /// The config option `redaction` says how much of their descriptions
+    /// (and specifiers) credentials show in debug output: `full` (the
+    /// default), `hashed`, or `suppressed` (see [Redaction]).
+    ///
+    /// Specifying the config option `lsm_hints` as `true` makes access
+    /// errors say whether SELinux or AppArmor is enforcing a policy on the
+    /// process, which is the usual cause in confined services (see
+    /// [StoreBuilder::lsm_hints]).
+    ///
+    /// The config option `persistent` says what happens when the persistent
+    /// keyring can't be had or linked to: `require` makes building the
+    /// store fail without it, and makes failed links fail writes and reads;
+    /// `best_effort` (the default) ignores any failure; `disabled` never
+    /// uses it (see [PersistentPolicy]).
+    ///
+    /// The store's keyrings are resolved when it's created, so one that
+    /// can't reach them fails then with a [NoStorageAccess](Error::NoStorageAccess)
+    /// error. Specifying the config option `lazy_keyrings` as `true` defers
+    /// this to each entry's creation, for environments where the keyrings
+    /// are set up after the store (see [StoreBuilder::lazy_keyrings]).
+    ///
+    /// The config option `negative_cache`, a number of milliseconds, has
+    /// credentials found missing remembered as missing for that long, so
+    /// that probing for them again doesn't search the keyrings (see
+    /// [StoreBuilder::negative_cache]).
+    ///
+    /// The config option `read_cache`, a number of milliseconds, has the
+    /// secrets read from keys kept in memory for up to that long, or (with
+    /// the `watch` feature) until their keys change, so that reading the
+    /// same key again doesn't read its payload (see [StoreBuilder::read_cache]).
+    ///
+    /// See [StoreBuilder] for a typed way of setting these options. (Payload
+    /// encryption with a [Cipher], audit sinks, and metrics observers can only
+    /// be set up with the builder.)
     pub fn new_with_configuration(config: &HashMap<&str, &str>) -> Result<Arc<Self>> {
-        let config = parse_attributes(
-            &["prefix", "divider", "suffix", "*service_no_divider"],
-            Some(config),
-        )?;
-        let prefix = config
-            .get("prefix")
-            .map(|s| s.as_str())
-            .unwrap_or("keyring:")
-            .to_string();
-        let divider = config
-            .get("divider")
-            .map(|s| s.as_str())
-            .unwrap_or("@")
-            .to_string();
-        let suffix = config
-            .get("suffix")
-            .map(|s| s.as_str())
-            .unwrap_or("")
-            .to_string();
-        let service_no_divider = config
-            .get("service_no_divider")
-            .map(|s| s.as_str())
-            .unwrap_or("false")
-            .eq("true");
-        Ok(Self::new_internal(
-            [prefix, divider, suffix],
-            service_no_divider,
-        ))
-    }
-
-    fn new_internal(delimiters: [String; 3], service_no_divider: bool) -> Arc<Self> {
+        Self::from_config(StoreConfig::from_options(config)?)
+    }
+
+    /// Create a store that keeps its keys in the persistent keyring of user `uid`.
+    ///
+    /// This is for privileged provisioning agents that stage credentials for
+    /// service accounts (e.g. before those accounts first log in): each key is
+    /// given to that user when it's written, and the user finds it in their
+    /// persistent keyring, which their sessions get linked to. Only root can
+    /// use another user's keyring; see [StoreBuilder::build] for the errors.
+    pub fn new_for_user(uid: u32) -> Result<Arc<Self>> {
+        StoreBuilder::new()
+            .keyring(Target::Persistent)
+            .keyring_user(crate::KeyringUser::Uid(uid))
+            .build()
+    }
+
+    /// Create a store configured by `KEYUTILS_STORE_*` environment variables.
+    ///
+    /// This lets the store be re-pointed (e.g. to another keyring, or to
+    /// keys with a timeout) without code changes. See [StoreConfig::from_env]
+    /// for the variables, e.g. `KEYUTILS_STORE_KEYRING=user`.
+    pub fn from_env() -> Result<Arc<Self>> {
+        Self::from_config(StoreConfig::from_env()?)
+    }
+
+    /// Create a store from typed settings.
+    ///
+    /// Options left unset get the same defaults as in [new_with_configuration](Store::new_with_configuration).
+    pub fn from_config(config: StoreConfig) -> Result<Arc<Self>> {
+        StoreBuilder::from(config).build()
+    }
+
+    /// Create a child store whose descriptions are namespaced by `namespace`.
+    ///
+    /// The child has this store's configuration (and so its keyring), but
+    /// `namespace` is appended to its prefix: with the default prefix, a
+    /// child scoped to `plugin-a:` describes its keys as
+    /// `keyring:plugin-a:user@service`. This gives each part of an
+    /// application (e.g. each plugin) a credential namespace of its own in
+    /// one kernel keyring. Note that the parent store still sees its
+    /// children's keys, since their descriptions start with its prefix.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if `namespace` is empty or
+    /// contains the divider (which would make descriptions ambiguous).
+    pub fn scoped(&self, namespace: &str) -> Result<Arc<Self>> {
+        if namespace.is_empty() {
+            return Err(Error::Invalid(
+                "namespace".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        if namespace.contains(self.delimiters[1].as_str()) {
+            return Err(Error::Invalid(
+                "namespace".to_string(),
+                format!(
+                    "contains the divider '{}', so descriptions are ambiguous",
+                    self.delimiters[1]
+                ),
+            ));
+        }
+        let mut delimiters = self.delimiters.clone();
+        delimiters[0].push_str(namespace);
+        let mut store = Store {
+            delimiters,
+            ..self.clone()
+        };
+        store.id = store.derive_id();
+        Ok(Arc::new(store))
+    }
+
+    /// The id of this store: its keyring's serial and a hash of its configuration.
+    ///
+    /// The configuration hashed is what decides which key an entry names
+    /// and how its payload is read, so stores (even in different processes)
+    /// with the same id have interchangeable entries. A lazy store whose
+    /// keyring can't be resolved yet uses its target's name instead.
+    pub(crate) fn derive_id(&self) -> String {
+        let keyring = match self.keyring_serial() {
+            Ok(serial) => serial.as_raw_id().to_string(),
+            Err(_) => self.keyring.to_string(),
+        };
+        let config = format!(
+            "{:?} {} {} {} {:?} {} {:?} {} {} {} {:?} {:?} {} {:?} {:?} {} {:?}",
+            self.delimiters,
+            self.service_no_divider,
+            self.user_no_divider,
+            self.keyring,
+            self.keyring_uid,
+            self.envelope,
+            self.integrity,
+            self.cipher.is_some(),
+            self.hash_descriptions,
+            self.record_specifiers,
+            self.mirrors,
+            self.history,
+            self.max_description_len,
+            self.description_overflow,
+            self.control_chars,
+            self.case_insensitive,
+            self.normalization,
+        );
+        format!(
+            "Crate version {}, Keyring {keyring}, Configuration {}",
+            env!("CARGO_PKG_VERSION"),
+            &to_hex(&sha256(config.as_bytes()))[..16]
+        )
+    }
+
+    /// The id of a store instantiated now.
+    pub(crate) fn new_id() -> String {
         let now = SystemTime::now();
         let elapsed = if now.lt(&UNIX_EPOCH) {
             UNIX_EPOCH.duration_since(now).unwrap()
         } else {
             now.duration_since(UNIX_EPOCH).unwrap()
         };
-        Arc::new(Store {
-            id: format!(
-                "Crate version {}, Instantiated at {}",
-                env!("CARGO_PKG_VERSION"),
-                elapsed.as_secs_f64()
+        format!(
+            "Crate version {}, Instantiated at {}",
+            env!("CARGO_PKG_VERSION"),
+            elapsed.as_secs_f64()
+        )
+    }
+
+    /// The serial of the keyring the store keeps its keys in.
+    fn keyring_serial(&self) -> std::result::Result<KeySerialId, linux_keyutils::KeyError> {
+        match self.keyring_uid {
+            Some(uid) => user::persistent_serial(uid),
+            None => self.keyring.serial(),
+        }
+    }
+
+    /// Whether a key with the given description belongs to this store.
+    ///
+    /// That's any description generated by the store's delimiter scheme
+    /// (or, with hashed descriptions, its hashing scheme), plus the backup
+    /// master key (if there is one). Keys with custom descriptions can't be
+    /// recognized.
+    pub(crate) fn manages(&self, description: &str) -> bool {
+        if let Some(backup) = &self.backup {
+            if backup.master == description {
+                return true;
+            }
+        }
+        let [prefix, divider, suffix] = &self.delimiters;
+        if self.hash_descriptions {
+            return description
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|hash| {
+                    hash.len() == 2 * DIGEST_LEN
+                        && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+                });
+        }
+        description.len() >= prefix.len() + divider.len() + suffix.len()
+            && description.starts_with(prefix.as_str())
+            && description.ends_with(suffix.as_str())
+            && description[prefix.len()..description.len() - suffix.len()]
+                .contains(divider.as_str())
+    }
+
+    /// The service and user of the entry whose key has `description`, if
+    /// the store's delimiters can be read back out of it.
+    ///
+    /// This undoes the store's `{prefix}{user}{divider}{service}{suffix}`
+    /// scheme. If the divider appears more than once, the store has to say
+    /// where the split goes: after the user if users can't contain the
+    /// divider (`user_no_divider`), before the service if services can't
+    /// (`service_no_divider`); otherwise the description is ambiguous. Hashed
+    /// descriptions can't be reversed (their specifiers are in the keys'
+    /// envelopes instead), nor can descriptions that were shortened or don't
+    /// follow the scheme; for all of these this returns `None`.
+    pub fn parse_description(&self, description: &str) -> Option<(String, String)> {
+        let [prefix, divider, suffix] = &self.delimiters;
+        if self.hash_descriptions || divider.is_empty() {
+            return None;
+        }
+        if self.history.is_some()
+            && versioned_description(description).is_some_and(|base| self.manages(base))
+        {
+            return None;
+        }
+        if description.len() < prefix.len() + suffix.len() {
+            return None;
+        }
+        let inner = description
+            .strip_prefix(prefix.as_str())?
+            .strip_suffix(suffix.as_str())?;
+        let (first, last) = (
+            inner.find(divider.as_str())?,
+            inner.rfind(divider.as_str())?,
+        );
+        let split = match (self.user_no_divider, self.service_no_divider) {
+            _ if first == last => first,
+            (true, false) => first,
+            (false, true) => last,
+            _ => return None,
+        };
+        let (user, service) = (&inner[..split], &inner[split + divider.len()..]);
+        if self.user_no_divider && user.contains(divider.as_str())
+            || self.service_no_divider && service.contains(divider.as_str())
+        {
+            return None;
+        }
+        Some((service.to_string(), user.to_string()))
+    }
+
+    /// The serials of the store's keyring and (if it's used) the persistent keyring.
+    ///
+    /// Fetching the persistent keyring resets its expiry timer. Unless the
+    /// store [requires](PersistentPolicy::Require) it, a persistent keyring
+    /// that can't be had is left out.
+    pub(crate) fn keyring_serials(&self) -> Result<(KeySerialId, Option<KeySerialId>)> {
+        let keyring = self.keyring_serial().map_err(KeyStoreError::from)?;
+        let persistent = match self.persistent {
+            _ if !self.keyring.links_persistent() => None,
+            PersistentPolicy::Disabled => None,
+            PersistentPolicy::BestEffort => sys::persistent_serial(self.keyring.identifier()).ok(),
+            PersistentPolicy::Require => Some(
+                sys::persistent_serial(self.keyring.identifier()).map_err(KeyStoreError::from)?,
             ),
-            delimiters,
-            service_no_divider,
-        })
+        };
+        Ok((keyring, persistent))
+    }
+
+    /// The serials, types, and descriptions of the store's keys that are linked into `keyrings`.
+    pub(crate) fn managed_keys(
+        &self,
+        keyrings: &[KeySerialId],
+    ) -> Result<Vec<(KeySerialId, KeyType, String)>> {
+        let mut seen = HashSet::new();
+        let mut keys = Vec::new();
+        for ring in keyrings {
+            for id in sys::keyring_links(*ring).map_err(KeyStoreError::from)? {
+                if !seen.insert(id.as_raw_id()) {
+                    continue;
+                }
+                // keys can vanish at any point during the walk, so failures here are skipped
+                let Ok(metadata) = Key::from_id(id).metadata() else {
+                    continue;
+                };
+                let key_type = metadata.get_type();
+                if key_type != KeyType::KeyRing && self.manages(metadata.get_description()) {
+                    keys.push((id, key_type, metadata.get_description().to_string()));
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Entries for the `asymmetric` keys (certificates and public keys the
+    /// kernel has parsed) linked into the store's keyrings.
+    ///
+    /// These are read-only [AsymmetricKey](crate::AsymmetricKey) credentials, whatever their
+    /// descriptions, which report what the kernel knows of them as their
+    /// attributes, so that trusted keys can be audited alongside credentials.
+    pub fn asymmetric_keys(&self) -> Result<Vec<Entry>> {
+        asymmetric::entries(&self.all_keyring_serials()?)
+    }
+
+    /// Entries for all the credentials in the store's keyrings.
+    ///
+    /// Only keys whose descriptions follow the store's delimiter scheme are
+    /// found. The entries are built from the service and user the
+    /// descriptions [parse](Store::parse_description) into, so they have
+    /// specifiers, or else with the descriptions as their `description`
+    /// modifier (and, for keys other than `user` keys, their type as the
+    /// `key_type` modifier), and never record specifiers of their own, so
+    /// any recorded in their envelopes are theirs. The backup master key is not
+    /// included, nor (in a store that keeps a [history](HistoryPolicy)) are
+    /// the previous versions of credentials.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        let started = Instant::now();
+        let result = self.find_entries();
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(&Observation {
+                operation: Operation::Search,
+                latency: started.elapsed(),
+                size: result.as_ref().ok().map(Vec::len),
+                error: result.as_ref().err().map(ErrorCategory::of),
+            });
+        }
+        result
+    }
+
+    /// The body of [entries](Store::entries).
+    fn find_entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for (_, key_type, description) in self.credential_keys()? {
+            let mut modifiers = HashMap::new();
+            if key_type != KeyType::User {
+                modifiers.insert("key_type", type_name(key_type));
+            }
+            // a description that parses is only used if it describes the same key again
+            let parsed = self
+                .parse_description(&description)
+                .and_then(|(service, user)| self.build_cred(&service, &user, Some(&modifiers)).ok())
+                .filter(|cred| *cred.description == description);
+            if let Some(cred) = parsed {
+                entries.push(Entry::new_with_credential(Arc::new(cred)));
+                continue;
+            }
+            modifiers.insert("description", description.as_str());
+            // there are no specifiers to record, just the envelope's to read
+            modifiers.insert("record_specifiers", "false");
+            entries.push(self.build("", "", Some(&modifiers))?);
+        }
+        Ok(entries)
+    }
+
+    /// The serials, types, and descriptions of the keys of the store's
+    /// credentials, in all its keyrings (but not the backup master key, or
+    /// previous versions if the store keeps a history).
+    fn credential_keys(&self) -> Result<Vec<(KeySerialId, KeyType, String)>> {
+        let master = self.backup.as_ref().map(|backup| backup.master.as_str());
+        let is_version = |description: &str| {
+            self.history.is_some()
+                && versioned_description(description).is_some_and(|base| self.manages(base))
+        };
+        let mut keys = self.managed_keys(&self.all_keyring_serials()?)?;
+        keys.retain(|(_, _, description)| {
+            Some(description.as_str()) != master && !is_version(description)
+        });
+        Ok(keys)
+    }
+
+    /// The serials of all the store's keyrings: its own, the persistent
+    /// keyring (if it's used), and its mirrors.
+    fn all_keyring_serials(&self) -> Result<Vec<KeySerialId>> {
+        Ok(self
+            .named_keyring_serials()?
+            .into_iter()
+            .map(|(_, serial)| serial)
+            .collect())
+    }
+
+    /// The names and serials of all the store's keyrings.
+    fn named_keyring_serials(&self) -> Result<Vec<(String, KeySerialId)>> {
+        let (keyring, persistent) = self.keyring_serials()?;
+        let mut keyrings = vec![(self.keyring.to_string(), keyring)];
+        if let Some(persistent) = persistent {
+            keyrings.push(("persistent".to_string(), persistent));
+        }
+        for mirror in &self.mirrors {
+            let serial = mirror.serial().map_err(KeyStoreError::from)?;
+            keyrings.push((mirror.to_string(), serial));
+        }
+        Ok(keyrings)
+    }
+
+    /// Count the store's keys and their payload bytes, in each of its keyrings.
+    ///
+    /// This is for capacity planning: it reports, without reading any secrets,
+    /// how much of the current user's key quota the store is using, and how
+    /// much quota is left. Payload sizes are taken from `/proc/keys` if it's
+    /// readable, and otherwise asked of the kernel (which can't tell the size
+    /// of a logon key).
+    pub fn stats(&self) -> Result<Stats> {
+        let listed: HashMap<i32, usize> = procfs::keys()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|key| Some((key.serial.as_raw_id(), key.payload_len?)))
+            .collect();
+        let payload_len = |id: KeySerialId| {
+            listed
+                .get(&id.as_raw_id())
+                .copied()
+                .or_else(|| Key::from_id(id).read(&mut [0u8; 0]).ok())
+                .unwrap_or(0)
+        };
+        let mut stats = Stats {
+            keyrings: Vec::new(),
+            keys: 0,
+            bytes: 0,
+            quota: Quota::read(),
+        };
+        let mut seen = HashSet::new();
+        for (name, keyring) in self.named_keyring_serials()? {
+            let mut keyring_stats = KeyringStats {
+                name,
+                keys: 0,
+                bytes: 0,
+            };
+            for (id, _, _) in self.managed_keys(&[keyring])? {
+                let len = payload_len(id);
+                keyring_stats.keys += 1;
+                keyring_stats.bytes += len;
+                if seen.insert(id.as_raw_id()) {
+                    stats.keys += 1;
+                    stats.bytes += len;
+                }
+            }
+            stats.keyrings.push(keyring_stats);
+        }
+        Ok(stats)
+    }
+
+    /// Unlink the store's expired and revoked keys from its keyrings.
+    ///
+    /// The kernel leaves dead keys linked into their keyrings (where they
+    /// count against the owner's quota) until its garbage collector gets to
+    /// them, which can take minutes (see `/proc/sys/kernel/keys/gc_delay`).
+    /// Long-lived processes that write keys with a timeout can call this
+    /// periodically to reclaim that quota sooner. Since the descriptions of
+    /// dead keys can only be found in `/proc/keys`, that has to be readable.
+    ///
+    /// Returns the keys that were unlinked, one report per link removed.
+    pub fn prune(&self) -> Result<Vec<PrunedKey>> {
+        self.check_writable("prune")?;
+        let listed: HashMap<i32, procfs::ProcKey> = procfs::keys()
+            .map_err(|e| Error::PlatformFailure(e.into()))?
+            .into_iter()
+            .map(|key| (key.serial.as_raw_id(), key))
+            .collect();
+        let mut pruned = Vec::new();
+        for keyring in self.all_keyring_serials()? {
+            for id in sys::keyring_links(keyring).map_err(KeyStoreError::from)? {
+                match Key::from_id(id).metadata() {
+                    Err(KeyError::KeyExpired | KeyError::KeyRevoked) => {}
+                    _ => continue,
+                }
+                let Some(key) = listed.get(&id.as_raw_id()) else {
+                    continue;
+                };
+                if key.key_type == "keyring" || !self.manages(&key.description) {
+                    continue;
+                }
+                // the garbage collector may have beaten us to it
+                match sys::unlink(id, keyring) {
+                    Ok(()) => pruned.push(PrunedKey {
+                        serial: id,
+                        keyring,
+                        description: key.description.clone(),
+                        revoked: key.is_revoked(),
+                    }),
+                    Err(KeyError::KeyDoesNotExist) => {}
+                    Err(err) => return Err(KeyStoreError(err).into()),
+                }
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Rewrite the store's credentials under the description scheme of `other`.
+    ///
+    /// Every credential that [entries](Store::entries) finds with a service
+    /// and user is read, and written to the entry `other` builds for the same
+    /// service and user (and key type), so a fleet can move its credentials
+    /// to a new prefix, divider, or suffix (or keyring) in one pass.
+    /// Credentials with custom descriptions have no service and user to
+    /// rewrite them under, and logon keys can't be read, so they're left
+    /// alone, as are credentials that `other` already has, unless `options`
+    /// says to overwrite them. With `delete_originals`, each original is
+    /// deleted once it's been rewritten.
+    ///
+    /// A failure stops the migration, leaving the credentials migrated
+    /// before it in both stores (or just the other, with `delete_originals`).
+    /// Unless it overwrites, running the migration again picks up where it
+    /// stopped, since the credentials already migrated are skipped.
+    ///
+    /// Returns the credentials that were rewritten.
+    pub fn migrate_to(&self, other: &Store, options: MigrateOptions) -> Result<Vec<MigratedKey>> {
+        if options.delete_originals {
+            self.check_writable("migrate")?;
+        }
+        let mut migrated = Vec::new();
+        for entry in self.entries()? {
+            let Some((service, user)) = entry.get_specifiers() else {
+                continue;
+            };
+            let cred = entry
+                .as_any()
+                .downcast_ref::<Cred>()
+                .expect("entries are keyutils credentials");
+            let mut modifiers = HashMap::new();
+            if cred.key_type != KeyType::User {
+                modifiers.insert("key_type", type_name(cred.key_type));
+            }
+            let target = other.build_cred(&service, &user, Some(&modifiers))?;
+            // a store that puts the key in the same place has nothing to move
+            if target.description == cred.description
+                && target.target == cred.target
+                && target.keyring_uid == cred.keyring_uid
+            {
+                continue;
+            }
+            if !options.overwrite && target.get_credential().is_ok() {
+                continue;
+            }
+            let secret = match cred.get_secret_secure() {
+                Ok(secret) => secret,
+                // it went away, or is a logon key
+                Err(Error::NoEntry | Error::NotSupportedByStore(_)) => continue,
+                Err(err) => return Err(err),
+            };
+            target.set_secret(&secret)?;
+            if options.delete_originals {
+                cred.delete_credential()?;
+            }
+            migrated.push(MigratedKey {
+                service,
+                user,
+                from: cred.description.to_string(),
+                to: target.description.to_string(),
+            });
+        }
+        Ok(migrated)
+    }
+
+    /// Copy the store's credentials into any other keyring-core store.
+    ///
+    /// Every credential that [entries](Store::entries) finds with a service
+    /// and user for which `filter` returns `true` is read, and written to the
+    /// entry `other` builds for the same service and user, replacing any
+    /// credential already there. This is how credentials staged in keyutils
+    /// at boot (where reads are fast) are saved to a store that persists,
+    /// such as the Secret Service. Credentials with custom descriptions, and
+    /// logon keys, are left out. A failure stops the copy.
+    ///
+    /// Returns the number of credentials copied.
+    pub fn copy_to(
+        &self,
+        other: &dyn CredentialStoreApi,
+        filter: impl Fn(&str, &str) -> bool,
+    ) -> Result<usize> {
+        let mut copied = 0;
+        for entry in self.entries()? {
+            let Some((service, user)) = entry.get_specifiers() else {
+                continue;
+            };
+            if !filter(&service, &user) {
+                continue;
+            }
+            let cred = entry
+                .as_any()
+                .downcast_ref::<Cred>()
+                .expect("entries are keyutils credentials");
+            let secret = match cred.get_secret_secure() {
+                Ok(secret) => secret,
+                // it went away, or is a logon key
+                Err(Error::NoEntry | Error::NotSupportedByStore(_)) => continue,
+                Err(err) => return Err(err),
+            };
+            other.build(&service, &user, None)?.set_secret(&secret)?;
+            copied += 1;
+        }
+        Ok(copied)
+    }
+
+    /// Copy the credentials another keyring-core store finds into this store.
+    ///
+    /// The reverse of [copy_to](Store::copy_to): every entry that `other`'s
+    /// [search](CredentialStoreApi::search) finds for `spec` (whose form is
+    /// up to that store) is read, and written to the entry this store builds
+    /// for the same service and user, replacing any credential already
+    /// there. This is how a persistent store's credentials are staged into
+    /// keyutils at boot. Entries without a service and user are left out.
+    /// A failure stops the copy.
+    ///
+    /// Returns the number of credentials copied.
+    pub fn copy_from(
+        &self,
+        other: &dyn CredentialStoreApi,
+        spec: &HashMap<&str, &str>,
+    ) -> Result<usize> {
+        self.check_writable("copy")?;
+        let mut copied = 0;
+        for entry in other.search(spec)? {
+            let Some((service, user)) = entry.get_specifiers() else {
+                continue;
+            };
+            let secret = match entry.get_secret() {
+                Ok(secret) => SecretBytes::new(secret),
+                // it went away since the search
+                Err(Error::NoEntry) => continue,
+                Err(err) => return Err(err),
+            };
+            self.build_cred(&service, &user, None)?
+                .set_secret(&secret)?;
+            copied += 1;
+        }
+        Ok(copied)
+    }
+
+    /// Write an encrypted archive of the store's credentials to `writer`.
+    ///
+    /// The archive holds the description, type, permissions, and payload of
+    /// every key that [entries](Store::entries) would find, sealed with keys
+    /// derived from `passphrase`, so it can be kept across a reboot or moved
+    /// to another host and restored with [import](Store::import). Logon keys
+    /// can't be read, so they are left out, as is the backup master key.
+    ///
+    /// Returns the number of credentials archived.
+    pub fn export(&self, writer: impl Write, passphrase: &[u8]) -> Result<usize> {
+        self.export_with_iterations(writer, passphrase, export::ITERATIONS)
+    }
+
+    pub(crate) fn export_with_iterations(
+        &self,
+        mut writer: impl Write,
+        passphrase: &[u8],
+        iterations: u32,
+    ) -> Result<usize> {
+        let mut records = Vec::new();
+        for (id, key_type, description) in self.credential_keys()? {
+            // keys can vanish during the walk, and logon keys can't be read
+            let Ok(metadata) = Key::from_id(id).metadata() else {
+                continue;
+            };
+            let Ok(Some(payload)) = sys::read(id, usize::MAX) else {
+                continue;
+            };
+            records.push(export::Record {
+                key_type,
+                description,
+                permissions: Perm::from_bits(metadata.get_perms().bits()),
+                payload: SecretBytes::new(payload),
+            });
+        }
+        let archive = SecretBytes::new(export::seal(&records, passphrase, iterations)?);
+        writer
+            .write_all(&archive)
+            .and_then(|_| writer.flush())
+            .map_err(|e| Error::PlatformFailure(e.into()))?;
+        Ok(records.len())
+    }
+
+    /// Restore the credentials in an archive written by [export](Store::export).
+    ///
+    /// Each credential's key is written to the store's keyrings under its
+    /// archived description, replacing any key already there. Keys get the
+    /// store's `permissions` if it has them, and their archived permissions
+    /// otherwise. Payloads are restored as they were, so a store that
+    /// archived enveloped or encrypted payloads should import them into a
+    /// store configured the same way. The store's backup is not written.
+    ///
+    /// A wrong passphrase or a corrupt archive is a
+    /// [BadStoreFormat](Error::BadStoreFormat) error, and nothing is
+    /// restored. Returns the number of credentials restored.
+    pub fn import(&self, mut reader: impl Read, passphrase: &[u8]) -> Result<usize> {
+        self.check_writable("import")?;
+        let mut archive = Vec::new();
+        reader
+            .read_to_end(&mut archive)
+            .map_err(|e| Error::PlatformFailure(e.into()))?;
+        let archive = SecretBytes::new(archive);
+        let records = export::open(&archive, passphrase)?;
+        for record in &records {
+            let modifiers = HashMap::from([
+                ("description", record.description.as_str()),
+                ("key_type", type_name(record.key_type)),
+            ]);
+            let mut cred = self.build_cred("", "", Some(&modifiers))?;
+            cred.permissions = self.permissions.or(Some(record.permissions));
+            cred.write_payload(&record.payload)?;
+        }
+        Ok(records.len())
+    }
+
+    /// Add the backup master key to the keyring.
+    ///
+    /// The key is added to the store's keyring and linked to the persistent
+    /// keyring (when appropriate), just like a credential. After a reboot, the
+    /// same material must be provisioned again before backed-up entries can
+    /// be recovered.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if this store has no backup configured.
+    pub fn provision_backup_key(&self, material: &[u8]) -> Result<()> {
+        let Some(backup) = &self.backup else {
+            return Err(Error::Invalid(
+                "backup_dir".to_string(),
+                "no backup is configured for this store".to_string(),
+            ));
+        };
+        self.provision_key(&backup.master, material)
+    }
+
+    /// Add the integrity master key to the keyring.
+    ///
+    /// As with [provision_backup_key](Store::provision_backup_key), the key is
+    /// added to the store's keyring and linked to the persistent keyring (when
+    /// appropriate), and must be provisioned again after a reboot.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if this store doesn't
+    /// protect its payloads with an HMAC.
+    pub fn provision_integrity_key(&self, material: &[u8]) -> Result<()> {
+        let Some(Integrity::Hmac(master)) = &self.integrity else {
+            return Err(Error::Invalid(
+                "integrity".to_string(),
+                "this store doesn't protect its payloads with an HMAC".to_string(),
+            ));
+        };
+        self.provision_key(master, material)
+    }
+
+    /// Add a master key to the store's keyring (and the persistent keyring).
+    fn provision_key(&self, description: &str, material: &[u8]) -> Result<()> {
+        self.check_writable("provision")?;
+        if material.is_empty() {
+            return Err(Error::Invalid(
+                "material".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        let keyring = self.keyring.keyring()?;
+        let key = keyring
+            .add_key(description, material)
+            .map_err(KeyStoreError::from)?;
+        if self.keyring.links_persistent() && self.persistent != PersistentPolicy::Disabled {
+            match KeyRing::get_persistent(self.keyring.identifier()) {
+                Ok(persistent) => self
+                    .persistent
+                    .check(persistent.link_key(key))
+                    .map_err(KeyStoreError::from)?,
+                Err(err) if self.persistent == PersistentPolicy::Require => {
+                    return Err(KeyStoreError::from(err).into());
+                }
+                Err(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adopt an existing key (e.g. one created with `keyctl` or by another
+    /// program) as the credential for `service` and `user`.
+    ///
+    /// If the key's description already follows the store's scheme, the key
+    /// itself is linked into the store's keyrings. Otherwise, since the kernel
+    /// cannot rename keys, its secret is copied into a key under the store's
+    /// description, and the original key is left as it was. Either way the
+    /// secret is also written to the store's backup, if there is one.
+    ///
+    /// The key must be a `user` key that the caller can read.
+    pub fn adopt_key(&self, serial: KeySerialId, service: &str, user: &str) -> Result<Entry> {
+        let cred = self.build_cred(service, user, None)?;
+        cred.check_writable("adopt")?;
+        let key = Key::from_id(serial);
+        let metadata = key.metadata().map_err(KeyStoreError::from)?;
+        if metadata.get_type() != KeyType::User {
+            return Err(Error::Invalid(
+                "serial".to_string(),
+                format!("is a {:?} key, not a user key", metadata.get_type()),
+            ));
+        }
+        let secret = SecretBytes::new(key.read_to_vec().map_err(KeyStoreError::from)?);
+        if metadata.get_description() == &*cred.description {
+            cred.keyring.link_key(key).map_err(KeyStoreError::from)?;
+        }
+        // for an already-linked key, this updates it in place (and links it persistently)
+        cred.set_secret(&secret)?;
+        Ok(Entry::new_with_credential(Arc::new(cred)))
+    }
+
+    /// Create an entry for the credential that `cred_ref` refers to (see
+    /// [Cred::to_ref]).
+    ///
+    /// The entry names the same key, in the same keyring, with the same
+    /// specifiers, as the credential the reference was made from. Returns
+    /// an [Invalid](Error::Invalid) error if this store encodes payloads
+    /// differently than the reference records (e.g. it doesn't encrypt
+    /// them, or uses another integrity check), since the entry couldn't
+    /// read the secret.
+    pub fn entry_from_ref(&self, cred_ref: &CredRef) -> Result<Entry> {
+        let modifiers = HashMap::from([
+            ("description", cred_ref.description.as_str()),
+            ("keyring", cred_ref.keyring.as_str()),
+            ("key_type", cred_ref.codec.key_type.as_str()),
+        ]);
+        let mut cred = self.build_cred("", "", Some(&modifiers))?;
+        if cred.codec() != cred_ref.codec {
+            return Err(Error::Invalid(
+                "codec".to_string(),
+                format!(
+                    "is {:?}, but this store's is {:?}",
+                    cred_ref.codec,
+                    cred.codec()
+                ),
+            ));
+        }
+        cred.specifiers = cred_ref
+            .specifiers
+            .as_ref()
+            .map(|(service, user)| (service.as_str().into(), user.as_str().into()));
+        Ok(Entry::new_with_credential(Arc::new(cred)))
+    }
+
+    /// Create an entry for the key with the given serial.
+    ///
+    /// The entry's credential operates directly on that key (e.g. one whose
+    /// serial was handed over by systemd or the kernel) rather than searching
+    /// for its description, and the key is neither re-linked into the store's
+    /// keyrings nor backed up. Setting the secret updates the key in place,
+    /// and deleting the credential invalidates the key.
+    ///
+    /// The key must be a `user` key.
+    pub fn entry_from_serial(&self, serial: KeySerialId) -> Result<Entry> {
+        let metadata = Key::from_id(serial)
+            .metadata()
+            .map_err(KeyStoreError::from)?;
+        if metadata.get_type() != KeyType::User {
+            return Err(Error::Invalid(
+                "serial".to_string(),
+                format!("is a {:?} key, not a user key", metadata.get_type()),
+            ));
+        }
+        let mut cred = Cred::build_in_keyrings(
+            Some(metadata.get_description()),
+            &self.delimiters,
+            self.service_no_divider,
+            "",
+            "",
+            self.keyring,
+            self.keyrings.clone(),
+        )?;
+        cred.serial = Some(serial);
+        cred.read_only = self.read_only;
+        cred.redaction = self.redaction;
+        cred.lsm_hints = self.lsm_hints;
+        cred.set_persistent_policy(self.persistent)?;
+        Ok(Entry::new_with_credential(Arc::new(cred)))
+    }
+
+    /// Lock down the store's keyring so that nothing more can be added or linked to it.
+    ///
+    /// This makes the keyring a tamper-resistant namespace: once the store's
+    /// credentials have been provisioned, other code running in the same
+    /// process can neither plant keys in the keyring nor link foreign keys
+    /// into it. The credentials already there can still be read, updated,
+    /// and deleted, but new ones can't be created (writes of them fail with a
+    /// [NoStorageAccess](Error::NoStorageAccess) error). The restriction can't
+    /// be lifted, and lasts as long as the keyring does.
+    ///
+    /// Only stores that keep their keys in the `process` or `thread` keyring
+    /// can do this, since restricting a keyring shared with other processes
+    /// would break them; other stores get an [Invalid](Error::Invalid) error.
+    /// Kernels without keyring restrictions (before 4.12) give a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error.
+    pub fn restrict_keyring(&self) -> Result<()> {
+        self.check_writable("restrict")?;
+        if !matches!(self.keyring, Target::Process | Target::Thread) {
+            return Err(Error::Invalid(
+                "keyring".to_string(),
+                format!(
+                    "the {} keyring is shared with other processes, so it can't be restricted",
+                    self.keyring
+                ),
+            ));
+        }
+        if !Capabilities::get().restrict_keyring {
+            return Err(Error::NotSupportedByStore(
+                "the kernel doesn't support keyring restrictions".to_string(),
+            ));
+        }
+        let keyring = self.keyring.serial().map_err(KeyStoreError::from)?;
+        sys::restrict_keyring(keyring).map_err(KeyStoreError::from)?;
+        Ok(())
+    }
+
+    /// Refuse to `operation` if the store is read-only.
+    pub(crate) fn check_writable(&self, operation: &str) -> Result<()> {
+        if !self.read_only {
+            return Ok(());
+        }
+        Err(Error::NoStorageAccess(Box::new(ReadOnly {
+            operation: operation.to_string(),
+            description: None,
+        })))
+    }
+
+    /// Check that keyutils can be used by this store.
+    ///
+    /// This resolves the store's keyring, which fails with a
+    /// [NoStorageAccess](Error::NoStorageAccess) error if the kernel has no
+    /// keyutils, `keyctl` is blocked (e.g. by a seccomp filter), or the
+    /// process has no such keyring.
+    pub fn check_available(&self) -> Result<()> {
+        self.keyring_serial()
+            .map_err(|err| Error::NoStorageAccess(err.into()))?;
+        Ok(())
+    }
+
+    /// The keyutils features supported by the running kernel.
+    ///
+    /// The store adapts to these by itself (e.g. it doesn't link keys into
+    /// the persistent keyring on kernels without one); they are exposed so
+    /// that applications can do the same.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::get()
+    }
+
+    /// Produce a report on the environment this store runs in.
+    ///
+    /// The report covers which keyrings are reachable (with their serials,
+    /// owners, and permissions), whether the persistent keyring is available,
+    /// the user's key quota, the kernel's keyutils features, and anything that
+    /// looks likely to stop the store from working (e.g. a prefix containing
+    /// the divider). Its `Display` output is meant for bug reports.
+    pub fn diagnose(&self) -> Diagnosis {
+        Diagnosis::run(self)
+    }
+
+    /// How the store turns the services and users it's given into those in its descriptions.
+    fn canonicalizer(&self) -> Canonicalizer {
+        Canonicalizer {
+            control_chars: self.control_chars,
+            case_insensitive: self.case_insensitive,
+            normalization: self.normalization,
+        }
+    }
+
+    /// The hashed description of the key for `service` and `user`.
+    fn hashed_description(&self, service: &str, user: &str) -> String {
+        let [prefix, _, suffix] = &self.delimiters;
+        let hash = hmac_sha256(
+            prefix.as_bytes(),
+            &[
+                &(service.len() as u64).to_le_bytes(),
+                service.as_bytes(),
+                user.as_bytes(),
+            ],
+        );
+        format!("{prefix}{}{suffix}", to_hex(&hash))
+    }
+
+    /// Build the credential for an entry; see [build](CredentialStoreApi::build).
+    fn build_cred(
+        &self,
+        service: &str,
+        user: &str,
+        modifiers: Option<&HashMap<&str, &str>>,
+    ) -> Result<Cred> {
+        let mods = parse_attributes(MODIFIERS, modifiers)?;
+        let description = custom_description(&mods, self.target_modifier)?;
+        let flag = |key: &str| mods.get(key).is_some_and(|v| v == "true");
+        let write_mode = match (flag("create_new"), flag("update_only")) {
+            (false, false) => WriteMode::Upsert,
+            (true, false) => WriteMode::CreateNew,
+            (false, true) => WriteMode::UpdateOnly,
+            (true, true) => {
+                return Err(Error::Invalid(
+                    "create_new".to_string(),
+                    "cannot be combined with update_only".to_string(),
+                ));
+            }
+        };
+        let keyring = match mods.get("keyring") {
+            Some(value) => value.parse()?,
+            None => self.keyring,
+        };
+        // custom descriptions are used as they are, and ignore the service and user
+        let (service, user) = match description {
+            Some(_) => (Cow::Borrowed(service), Cow::Borrowed(user)),
+            None => {
+                let canonicalizer = self.canonicalizer();
+                (
+                    canonicalizer.apply("service", service)?,
+                    canonicalizer.apply("user", user)?,
+                )
+            }
+        };
+        let (service, user) = (service.as_ref(), user.as_ref());
+        if self.user_no_divider && description.is_none() {
+            check_no_divider("user", user, &self.delimiters[1])?;
+        }
+        // the store's own keyrings were resolved when it was built
+        let keyrings = self.keyrings.clone().filter(|_| keyring == self.keyring);
+        let mut cred = Cred::build_in_keyrings(
+            description,
+            &self.delimiters,
+            self.service_no_divider,
+            service,
+            user,
+            keyring,
+            keyrings,
+        )?;
+        cred.backup = self.backup.clone();
+        cred.write_mode = write_mode;
+        cred.relink = self.relink;
+        cred.callout = mods.get("callout").cloned();
+        if let Some(value) = mods.get("key_type") {
+            cred.key_type = parse_key_type(value)?;
+        }
+        if cred.key_type == KeyType::Logon && cred.description.find(':').is_none_or(|i| i == 0) {
+            return Err(Error::Invalid(
+                "description".to_string(),
+                "must have a ':' after a non-empty prefix for a logon key".to_string(),
+            ));
+        }
+        if cred.key_type != KeyType::User && cred.callout.is_some() {
+            return Err(Error::Invalid(
+                "callout".to_string(),
+                "can only be used with user keys".to_string(),
+            ));
+        }
+        cred.uid = parse_owner(&mods, "uid")?;
+        cred.gid = parse_owner(&mods, "gid")?;
+        if let Some(uid) = self.keyring_uid {
+            if keyring != Target::Persistent {
+                return Err(Error::Invalid(
+                    "keyring".to_string(),
+                    "only the persistent keyring of another user can be used".to_string(),
+                ));
+            }
+            if cred.callout.is_some() {
+                return Err(Error::Invalid(
+                    "callout".to_string(),
+                    "cannot be used with another user's keyring".to_string(),
+                ));
+            }
+            cred.keyring_uid = Some(uid);
+            cred.uid = cred.uid.or(Some(uid));
+        }
+        if self.hash_descriptions && cred.specifiers.is_some() {
+            cred.description = self.hashed_description(service, user).into();
+        }
+        if cred.description.len() > self.max_description_len {
+            cred.description = fit_description(
+                cred.description.to_string(),
+                self.max_description_len,
+                self.description_overflow,
+                &self.delimiters[2],
+            )?
+            .into();
+        }
+        cred.mirrors = self
+            .mirrors
+            .iter()
+            .map(|mirror| mirror.serial())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|err| Error::NoStorageAccess(err.into()))?;
+        cred.envelope = self.envelope;
+        cred.integrity = self.integrity.clone();
+        cred.cipher = self.cipher.clone();
+        cred.audit = self.audit.clone();
+        cred.metrics = self.metrics.clone();
+        cred.retry = self.retry;
+        cred.history = self.history;
+        cred.max_read_len = self.max_read_len;
+        cred.verify_owner = self.verify_owner;
+        cred.read_only = self.read_only;
+        cred.redaction = self.redaction;
+        cred.lsm_hints = self.lsm_hints;
+        cred.set_persistent_policy(self.persistent)?;
+        cred.keyctl = self.keyctl.clone();
+        cred.misses = self.misses.clone();
+        cred.secrets = self.secrets.clone();
+        cred.audit_context = mods.get("audit_context").cloned();
+        cred.content_type = mods.get("content_type").cloned();
+        if cred.content_type.is_some() && !cred.envelope {
+            return Err(Error::Invalid(
+                "content_type".to_string(),
+                "can only be recorded by a store that uses envelopes".to_string(),
+            ));
+        }
+        let record_specifiers = match mods.get("record_specifiers") {
+            Some(value) => value == "true",
+            None => self.record_specifiers,
+        };
+        if record_specifiers && cred.specifiers.is_none() {
+            if !cred.envelope {
+                return Err(Error::Invalid(
+                    "record_specifiers".to_string(),
+                    "can only be recorded by a store that uses envelopes".to_string(),
+                ));
+            }
+            cred.specifiers = Some((service.into(), user.into()));
+        }
+        cred.timeout = self.timeout;
+        cred.keepalive_timeout = self.keepalive_timeout;
+        cred.permissions = self.permissions;
+        Ok(cred)
     }
 }
 
@@ -110,21 +1486,57 @@ impl CredentialStoreApi for Store {
     ///
     /// Building a credential does not create a key in the store.
     /// It's setting a password that does that.
+    ///
+    /// The `description` modifier gives an explicit description for the key.
+    /// The `target` modifier is an alias of `description`, for code written
+    /// for keyring v3, unless the store's `target_modifier` option is off.
+    /// The boolean modifiers `create_new` and `update_only` restrict
+    /// writes to only creating or only updating the credential (they
+    /// cannot both be `true`).
+    ///
+    /// The `callout` modifier makes reads of a missing key go through
+    /// `request_key(2)`, passing the modifier's value as the callout info,
+    /// so that a `request-key(8)` handler can supply the secret on demand.
+    /// (See [KeyRequest](crate::KeyRequest) for writing such a handler.)
+    ///
+    /// The `keyring` modifier puts the entry's key in a different keyring
+    /// than the store's (it takes the same values as the `keyring` config
+    /// option), e.g. `thread` for a per-worker ephemeral secret.
+    ///
+    /// The `key_type` modifier selects the kernel key type that holds the
+    /// secret: `user` (the default, up to 32767 bytes), `big_key` (up to
+    /// 1 MiB, on kernels that support it), or `logon` (up to 32767 bytes,
+    /// and never readable from user space, so reading its secret gives a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error). Logon keys
+    /// need a description with a `:` after a non-empty prefix, which the
+    /// default `keyring:` prefix provides.
+    ///
+    /// The `uid` and `gid` modifiers (numeric ids) give the key to another
+    /// user and/or group each time it's written, so that a privileged
+    /// provisioning service can create credentials owned by the service
+    /// that uses them. Only root can use them. Unless the store's
+    /// `permissions` grant that user or group access, the key stays
+    /// readable only by its possessors.
+    ///
+    /// In a store that uses envelopes, the `content_type` modifier gives a
+    /// content-type hint (e.g. `application/json`) to record in the entry's
+    /// envelope when it's written. Similarly, the boolean `record_specifiers`
+    /// modifier overrides the store's `record_specifiers` option for an entry
+    /// with a custom description: with it, the entry's specifiers are the
+    /// service and user it's built with, and are recorded in its envelope.
+    /// As with generated descriptions, a key recorded for other specifiers
+    /// is then treated as missing.
+    ///
+    /// In a store with an [audit](crate::StoreBuilder::audit) sink, the
+    /// `audit_context` modifier gives context (e.g. the requesting client or
+    /// a request id) to record with each of the entry's operations.
     fn build(
         &self,
         service: &str,
         user: &str,
         modifiers: Option<&HashMap<&str, &str>>,
     ) -> Result<Entry> {
-        let mods = parse_attributes(&["description"], modifiers)?;
-        let description = mods.get("description").map(|s| s.as_str());
-        let cred = Cred::build_from_specifiers(
-            description,
-            &self.delimiters,
-            self.service_no_divider,
-            service,
-            user,
-        )?;
+        let cred = self.build_cred(service, user, modifiers)?;
         Ok(Entry::new_with_credential(Arc::new(cred)))
     }
 
@@ -136,8 +1548,14 @@ impl CredentialStoreApi for Store {
     /// See the keyring-core API docs.
     ///
     /// Since this keystore keeps credentials in kernel memory, they vanish on reboot
+    /// (or sooner, depending on the configured keyring: process keys vanish when
+    /// the process exits). If the store keeps an on-disk backup, they last until deleted.
     fn persistence(&self) -> CredentialPersistence {
-        CredentialPersistence::UntilReboot
+        if self.backup.is_some() {
+            CredentialPersistence::UntilDelete
+        } else {
+            self.keyring.persistence()
+        }
     }
 
     /// See the keychain-core API docs.
@@ -145,3 +1563,41 @@ impl CredentialStoreApi for Store {
         std::fmt::Debug::fmt(self, f)
     }
 }
+
+/// Parse the value of the `key_type` modifier.
+fn parse_key_type(value: &str) -> Result<KeyType> {
+    match KeyType::try_from(value) {
+        Ok(KeyType::KeyRing) | Err(_) => Err(Error::Invalid(
+            "key_type".to_string(),
+            format!("'{value}' is not one of user, big_key, or logon"),
+        )),
+        Ok(KeyType::BigKey) if !Capabilities::get().big_key => Err(Error::NotSupportedByStore(
+            "the kernel doesn't support big_key keys".to_string(),
+        )),
+        Ok(key_type) => Ok(key_type),
+    }
+}
+
+/// Parse the value of the `uid` or `gid` modifier, which only root may give.
+fn parse_owner(mods: &HashMap<String, String>, key: &str) -> Result<Option<u32>> {
+    let Some(value) = mods.get(key) else {
+        return Ok(None);
+    };
+    let id = value
+        .parse()
+        .map_err(|_| Error::Invalid(key.to_string(), format!("'{value}' is not a numeric id")))?;
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(Error::Invalid(
+            key.to_string(),
+            "can only be given by root".to_string(),
+        ));
+    }
+    Ok(Some(id))
+}
+
+/// The name of a key type, as taken by the `key_type` modifier.
+pub(crate) fn type_name(key_type: KeyType) -> &'static str {
+    <&std::ffi::CStr>::from(key_type)
+        .to_str()
+        .expect("key type names are ASCII")
+}
@@ -0,0 +1,277 @@
+//! Raw keyctl calls that `linux_keyutils` doesn't expose.
+//!
+//! These work directly on key serials, which `linux_keyutils::KeyRing`
+//! keeps private.
+use std::ffi::{CStr, CString};
+
+use linux_keyutils::{KeyError, KeyRingIdentifier, KeySerialId, KeyType};
+
+use super::keyctl::{KernelKeyctl, KeyDescription, read_payload};
+use super::{Capabilities, Perm};
+
+const KEYCTL_GET_KEYRING_ID: libc::c_int = 0;
+const KEYCTL_DESCRIBE: libc::c_int = 6;
+const KEYCTL_LINK: libc::c_int = 8;
+const KEYCTL_UNLINK: libc::c_int = 9;
+const KEYCTL_SEARCH: libc::c_int = 10;
+const KEYCTL_GET_PERSISTENT: libc::c_int = 22;
+const KEYCTL_RESTRICT_KEYRING: libc::c_int = 29;
+const KEYCTL_MOVE: libc::c_int = 30;
+const KEYCTL_MOVE_EXCL: libc::c_ulong = 1;
+
+/// Perform a keyctl(2) call, translating failures into a [KeyError].
+pub(crate) fn keyctl(
+    op: libc::c_int,
+    arg2: libc::c_ulong,
+    arg3: libc::c_ulong,
+    arg4: libc::c_ulong,
+    arg5: libc::c_ulong,
+) -> Result<libc::c_long, KeyError> {
+    let res = unsafe { libc::syscall(libc::SYS_keyctl, op, arg2, arg3, arg4, arg5) };
+    if res < 0 {
+        return Err(KeyError::from_errno());
+    }
+    Ok(res)
+}
+
+/// Resolve a special keyring identifier to the keyring's serial.
+pub(crate) fn keyring_serial(id: KeyRingIdentifier, create: bool) -> Result<KeySerialId, KeyError> {
+    let serial = keyctl(
+        KEYCTL_GET_KEYRING_ID,
+        id as i32 as libc::c_ulong,
+        create as libc::c_ulong,
+        0,
+        0,
+    )?;
+    Ok(KeySerialId::new(serial as i32))
+}
+
+/// Get the serial of the current user's persistent keyring, linking it into `link_with`.
+///
+/// As with every fetch of the persistent keyring, this resets its expiry timer.
+pub(crate) fn persistent_serial(link_with: KeyRingIdentifier) -> Result<KeySerialId, KeyError> {
+    // a uid of -1 is the current user
+    persistent_serial_of(u32::MAX, link_with)
+}
+
+/// Get the serial of user `uid`'s persistent keyring, linking it into `link_with`.
+///
+/// Getting another user's persistent keyring takes `CAP_SETUID`.
+pub(crate) fn persistent_serial_of(
+    uid: u32,
+    link_with: KeyRingIdentifier,
+) -> Result<KeySerialId, KeyError> {
+    let serial = keyctl(
+        KEYCTL_GET_PERSISTENT,
+        uid as libc::c_ulong,
+        link_with as i32 as libc::c_ulong,
+        0,
+        0,
+    )?;
+    Ok(KeySerialId::new(serial as i32))
+}
+
+/// Link the key `key` into the keyring `keyring`.
+pub(crate) fn link(key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+    keyctl(
+        KEYCTL_LINK,
+        key.as_raw_id() as libc::c_ulong,
+        keyring.as_raw_id() as libc::c_ulong,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Unlink the key `key` from the keyring `keyring`.
+pub(crate) fn unlink(key: KeySerialId, keyring: KeySerialId) -> Result<(), KeyError> {
+    keyctl(
+        KEYCTL_UNLINK,
+        key.as_raw_id() as libc::c_ulong,
+        keyring.as_raw_id() as libc::c_ulong,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Move the key `key` from the keyring `from` to the keyring `to`.
+///
+/// Kernels before 5.3 lack `KEYCTL_MOVE`; on those the key is linked into
+/// `to` and then unlinked from `from`, so for a moment it is in both.
+pub(crate) fn move_key(
+    key: KeySerialId,
+    from: KeySerialId,
+    to: KeySerialId,
+) -> Result<(), KeyError> {
+    if !Capabilities::get().move_key {
+        link(key, to)?;
+        return unlink(key, from);
+    }
+    keyctl(
+        KEYCTL_MOVE,
+        key.as_raw_id() as libc::c_ulong,
+        from.as_raw_id() as libc::c_ulong,
+        to.as_raw_id() as libc::c_ulong,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Move the key `key` from the keyring `from` to the keyring `to`, unless
+/// `to` already holds a key of the same type and description.
+///
+/// That check and the move are one atomic step, which makes this the
+/// kernel's only create-exclusive operation. It fails with `EEXIST` if
+/// there's already such a key, and needs `KEYCTL_MOVE` (Linux 5.3).
+pub(crate) fn move_key_exclusive(
+    key: KeySerialId,
+    from: KeySerialId,
+    to: KeySerialId,
+) -> Result<(), KeyError> {
+    if !Capabilities::get().move_key {
+        return Err(KeyError::OperationNotSupported);
+    }
+    keyctl(
+        KEYCTL_MOVE,
+        key.as_raw_id() as libc::c_ulong,
+        from.as_raw_id() as libc::c_ulong,
+        to.as_raw_id() as libc::c_ulong,
+        KEYCTL_MOVE_EXCL,
+    )?;
+    Ok(())
+}
+
+/// Add a key of any type to `keyring`, or update the key of that type and
+/// description already in it.
+pub(crate) fn add_key(
+    key_type: KeyType,
+    description: &str,
+    payload: &[u8],
+    keyring: KeySerialId,
+) -> Result<KeySerialId, KeyError> {
+    add_key_of_type(key_type.into(), description, payload, keyring)
+}
+
+/// Add a key of a type `linux_keyutils` doesn't know, e.g. `fscrypt-provisioning`.
+pub(crate) fn add_key_of_type(
+    key_type: &CStr,
+    description: &str,
+    payload: &[u8],
+    keyring: KeySerialId,
+) -> Result<KeySerialId, KeyError> {
+    let description = CString::new(description).map_err(|_| KeyError::InvalidDescription)?;
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_add_key,
+            key_type.as_ptr(),
+            description.as_ptr(),
+            payload.as_ptr(),
+            payload.len(),
+            keyring.as_raw_id(),
+        )
+    };
+    if res < 0 {
+        return Err(KeyError::from_errno());
+    }
+    Ok(KeySerialId::new(res as i32))
+}
+
+/// Search `keyring` (and the keyrings linked into it) for a key of any type.
+pub(crate) fn search(
+    keyring: KeySerialId,
+    key_type: KeyType,
+    description: &str,
+) -> Result<KeySerialId, KeyError> {
+    search_of_type(keyring, key_type.into(), description)
+}
+
+/// Search `keyring` for a key of a type `linux_keyutils` doesn't know.
+pub(crate) fn search_of_type(
+    keyring: KeySerialId,
+    key_type: &CStr,
+    description: &str,
+) -> Result<KeySerialId, KeyError> {
+    let description = CString::new(description).map_err(|_| KeyError::InvalidDescription)?;
+    let serial = keyctl(
+        KEYCTL_SEARCH,
+        keyring.as_raw_id() as libc::c_ulong,
+        key_type.as_ptr() as libc::c_ulong,
+        description.as_ptr() as libc::c_ulong,
+        0,
+    )?;
+    Ok(KeySerialId::new(serial as i32))
+}
+
+/// Read the whole payload of `key`, unless it's longer than `max` bytes.
+///
+/// Returns `None`, without allocating, if the payload is too long.
+pub(crate) fn read(key: KeySerialId, max: usize) -> Result<Option<Vec<u8>>, KeyError> {
+    read_payload(&KernelKeyctl, key, max)
+}
+
+/// Stop anything else from being added or linked to `keyring`.
+///
+/// The restriction can't be lifted, and lasts as long as the keyring does.
+pub(crate) fn restrict_keyring(keyring: KeySerialId) -> Result<(), KeyError> {
+    // a null key type rejects every link
+    keyctl(
+        KEYCTL_RESTRICT_KEYRING,
+        keyring.as_raw_id() as libc::c_ulong,
+        0,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+/// List the serials of everything linked into `keyring`, of any key type.
+pub(crate) fn keyring_links(keyring: KeySerialId) -> Result<Vec<KeySerialId>, KeyError> {
+    Ok(read(keyring, usize::MAX)?
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|c| KeySerialId::new(i32::from_ne_bytes([c[0], c[1], c[2], c[3]])))
+        .collect())
+}
+
+/// The type and description of `key`, of any key type.
+///
+/// `linux_keyutils` only describes the key types it knows.
+pub(crate) fn describe(key: KeySerialId) -> Result<KeyDescription, KeyError> {
+    let mut buffer = vec![0u8; 256];
+    loop {
+        let len = keyctl(
+            KEYCTL_DESCRIBE,
+            key.as_raw_id() as libc::c_ulong,
+            buffer.as_mut_ptr() as libc::c_ulong,
+            buffer.len() as libc::c_ulong,
+            0,
+        )? as usize;
+        if len > buffer.len() {
+            // the description grew past the buffer
+            buffer.resize(len, 0);
+            continue;
+        }
+        // "type;uid;gid;perm;description", NUL-terminated
+        let text = String::from_utf8_lossy(&buffer[..len.saturating_sub(1)]);
+        let mut fields = text.splitn(5, ';');
+        let key_type = fields.next().unwrap_or_default().to_string();
+        // ids are printed signed, so an unmapped one (-1) comes back as u32::MAX
+        let mut id = || fields.next()?.parse::<i64>().ok().map(|id| id as u32);
+        let (uid, gid) = (id(), id());
+        let perm = fields
+            .next()
+            .and_then(|perm| u32::from_str_radix(perm, 16).ok());
+        let (Some(uid), Some(gid), Some(perm), Some(description)) = (uid, gid, perm, fields.next())
+        else {
+            return Err(KeyError::InvalidDescription);
+        };
+        return Ok(KeyDescription {
+            key_type,
+            uid,
+            gid,
+            perm: Perm::from_bits(perm),
+            description: description.to_string(),
+        });
+    }
+}
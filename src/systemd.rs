@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use keyring_core::api::CredentialStoreApi;
+use keyring_core::{Entry, Error, Result};
+
+use super::compat::bad_store_format;
+use super::{SecretBytes, Store};
+
+/// The credentials systemd passes to a service in `$CREDENTIALS_DIRECTORY`.
+///
+/// Units configured with `LoadCredential=`, `LoadCredentialEncrypted=`,
+/// `SetCredential=`, or `SetCredentialEncrypted=` get each credential as a
+/// file in the credentials directory, named after the credential (systemd
+/// decrypts encrypted credentials before the service starts). Loading them
+/// into a store moves each secret into a kernel key, in an entry whose user
+/// is the credential's name, so the rest of the service can read them through
+/// keyring-core like any other entry.
+///
+/// ```no_run
+/// use linux_keyutils_keyring_store::{Store, SystemdCredentials};
+///
+/// let store = Store::new().unwrap();
+/// SystemdCredentials::load(&store, "my-service").unwrap();
+/// keyring_core::set_default_store(store);
+/// let entry = keyring_core::Entry::new("my-service", "db-password").unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemdCredentials {
+    dir: PathBuf,
+}
+
+impl SystemdCredentials {
+    /// The credentials in `$CREDENTIALS_DIRECTORY`, if systemd set it.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os("CREDENTIALS_DIRECTORY")
+            .filter(|dir| !dir.is_empty())
+            .map(SystemdCredentials::new)
+    }
+
+    /// The credentials in `dir`, laid out as systemd lays out `$CREDENTIALS_DIRECTORY`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SystemdCredentials { dir: dir.into() }
+    }
+
+    /// Load the credentials in `$CREDENTIALS_DIRECTORY` into `store` under `service`.
+    ///
+    /// A process that wasn't given any credentials by systemd has nothing to
+    /// load, so this returns no entries if `$CREDENTIALS_DIRECTORY` isn't set.
+    pub fn load(store: &Store, service: &str) -> Result<Vec<Entry>> {
+        match SystemdCredentials::from_env() {
+            Some(credentials) => credentials.load_into(store, service),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The directory holding the credentials.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The names of the credentials, in sorted order.
+    ///
+    /// Only regular files are credentials; anything else in the directory is ignored.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(|e| Error::NoStorageAccess(e.into()))? {
+            let entry = entry.map_err(|e| Error::NoStorageAccess(e.into()))?;
+            let file_type = entry
+                .file_type()
+                .map_err(|e| Error::NoStorageAccess(e.into()))?;
+            if !file_type.is_file() {
+                continue;
+            }
+            let name = entry.file_name().into_string().map_err(|name| {
+                bad_store_format(format!("credential name {name:?} is not UTF-8"))
+            })?;
+            names.push(name);
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Load every credential into `store`, in an entry for `service` and the credential's name.
+    ///
+    /// Each credential's secret replaces the secret of its entry, if it has
+    /// one. Loading stops at the first credential that can't be read or
+    /// stored (e.g. because its name contains the store's divider). Returns
+    /// the entries of the loaded credentials.
+    pub fn load_into(&self, store: &Store, service: &str) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for name in self.names()? {
+            let secret = std::fs::read(self.dir.join(&name))
+                .map(SecretBytes::new)
+                .map_err(|e| Error::NoStorageAccess(e.into()))?;
+            let entry = store.build(service, &name, None)?;
+            entry.set_secret(&secret)?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
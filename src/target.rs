@@ -0,0 +1,175 @@
+use std::str::FromStr;
+
+use keyring_core::api::CredentialPersistence;
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeyRing, KeyRingIdentifier, KeySerialId};
+
+use super::{Capabilities, sys};
+
+/// The keyring that credentials are added to and searched for in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Target {
+    /// The session keyring, with keys also linked into the persistent keyring.
+    ///
+    /// This is the default, and matches the legacy keyring behavior.
+    #[default]
+    Session,
+    /// The process keyring: keys vanish when the process exits.
+    Process,
+    /// The user keyring, with keys also linked into the persistent keyring.
+    User,
+    /// The calling thread's keyring: keys vanish when the thread exits.
+    ///
+    /// Each thread has a keyring of its own, and an entry in this target
+    /// uses the keyring of whichever thread it's used on, so one entry shared
+    /// by a pool of workers gives each worker a secret of its own, which no
+    /// other thread (not even the one that built the entry) can see. Keys
+    /// only become visible to other threads when moved to another keyring
+    /// (see [Cred::move_to](crate::Cred::move_to)). The entry's on-disk
+    /// backup, integrity keys, and `request_key` callouts are still looked
+    /// up from the keyring of the thread that built it.
+    Thread,
+    /// The user's persistent keyring itself, so keys survive logouts (but
+    /// not the persistent keyring's expiry) without being in any session.
+    Persistent,
+    /// The user's default session keyring, with keys also linked into the
+    /// persistent keyring.
+    ///
+    /// This is the keyring a process gets as its session keyring when it
+    /// isn't given one of its own, which is how many container runtimes run
+    /// their processes. It's shared by all of the user's processes that have
+    /// no session keyring, and lasts as long as any of them is running. A
+    /// process that has a session keyring of its own is given possession of
+    /// this one through its process keyring, when an entry is built.
+    UserSession,
+}
+
+impl Target {
+    /// The config/modifier value that selects this target.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Target::Session => "session",
+            Target::Process => "process",
+            Target::User => "user",
+            Target::Thread => "thread",
+            Target::Persistent => "persistent",
+            Target::UserSession => "user_session",
+        }
+    }
+
+    /// The kernel's special identifier for this target's keyring.
+    ///
+    /// The persistent keyring has no special identifier of its own; for
+    /// [Target::Persistent] this is the session keyring it's attached to.
+    pub fn identifier(&self) -> KeyRingIdentifier {
+        match self {
+            Target::Session | Target::Persistent => KeyRingIdentifier::Session,
+            Target::Process => KeyRingIdentifier::Process,
+            Target::User => KeyRingIdentifier::User,
+            Target::Thread => KeyRingIdentifier::Thread,
+            Target::UserSession => KeyRingIdentifier::UserSession,
+        }
+    }
+
+    /// Whether keys in this target are also linked into the persistent keyring.
+    ///
+    /// Linking a process-scoped key into the persistent keyring would make it
+    /// outlive its process, so only session and user keys are linked (and
+    /// only if the kernel supports persistent keyrings).
+    pub fn links_persistent(&self) -> bool {
+        matches!(self, Target::Session | Target::User | Target::UserSession)
+            && Capabilities::get().persistent_keyrings
+    }
+
+    /// How long credentials in this target survive.
+    pub fn persistence(&self) -> CredentialPersistence {
+        match self {
+            Target::Session | Target::User | Target::Persistent | Target::UserSession => {
+                CredentialPersistence::UntilReboot
+            }
+            Target::Process | Target::Thread => CredentialPersistence::ProcessOnly,
+        }
+    }
+
+    /// Resolve the keyring for this target, creating it if necessary.
+    pub(crate) fn keyring(&self) -> Result<KeyRing> {
+        let keyring = match self {
+            Target::Persistent => KeyRing::get_persistent(self.identifier()),
+            _ => KeyRing::from_special_id(self.identifier(), self.creates()),
+        };
+        keyring.map_err(|e| Error::NoStorageAccess(e.into()))
+    }
+
+    /// Resolve the serial of this target's keyring, creating it if necessary.
+    ///
+    /// For [Target::Persistent], this resets the persistent keyring's expiry timer.
+    ///
+    /// For [Target::UserSession], when the process has a session keyring of
+    /// its own, this links the user-session keyring into the process keyring:
+    /// otherwise the process wouldn't possess the keys it adds there, and
+    /// (with their default permissions) couldn't read them back.
+    pub(crate) fn serial(&self) -> std::result::Result<KeySerialId, KeyError> {
+        match self {
+            Target::Persistent => sys::persistent_serial(self.identifier()),
+            Target::UserSession => {
+                let serial = sys::keyring_serial(self.identifier(), false)?;
+                if sys::keyring_serial(KeyRingIdentifier::Session, false) != Ok(serial) {
+                    let process = sys::keyring_serial(KeyRingIdentifier::Process, true)?;
+                    sys::link(serial, process)?;
+                }
+                Ok(serial)
+            }
+            _ => sys::keyring_serial(self.identifier(), self.creates()),
+        }
+    }
+
+    /// The serial the credentials in this target pass to the kernel for their keyring.
+    ///
+    /// This is the keyring's serial, except for the thread keyring, whose
+    /// special id is passed instead, so that the kernel picks the keyring of
+    /// the calling thread (creating it when a key is first added).
+    pub(crate) fn keyring_id(&self) -> std::result::Result<KeySerialId, KeyError> {
+        match self {
+            Target::Thread => Ok(KeySerialId::new(KeyRingIdentifier::Thread as i32)),
+            _ => self.serial(),
+        }
+    }
+
+    /// Whether this target's keyring is created on demand (rather than
+    /// set up for the process by the session manager).
+    fn creates(&self) -> bool {
+        matches!(self, Target::Process | Target::Thread)
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "session" => Ok(Target::Session),
+            "process" => Ok(Target::Process),
+            "user" => Ok(Target::User),
+            "thread" => Ok(Target::Thread),
+            "persistent" => Ok(Target::Persistent),
+            "user_session" => Ok(Target::UserSession),
+            _ => Err(Error::Invalid(
+                "keyring".to_string(),
+                format!(
+                    "'{s}' is not one of session, process, user, thread, persistent, or user_session"
+                ),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,174 @@
+//! Conformance checks for keyring-core credential stores.
+//!
+//! These are the round-trip and threading tests this crate runs against
+//! its own [Store], made reusable: applications can run them against the
+//! store configurations they actually use, and other keyring-core stores
+//! against themselves. Each check builds entries with fresh random names,
+//! cleans up after itself, and panics (like a failed assertion) if the
+//! store misbehaves, so call them from `#[test]` functions:
+//!
+//! ```no_run
+//! use std::collections::HashMap;
+//! use linux_keyutils_keyring_store::test_util;
+//!
+//! // in a #[test] function
+//! test_util::check_config(&HashMap::from([("prefix", "my-app:"), ("keyring", "user")]));
+//! ```
+//!
+//! This module is only available with the `test_util` feature.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use keyring_core::{CredentialStore, Entry, Error};
+
+use super::Store;
+use super::crypto::{random_bytes, to_hex};
+
+/// How many threads the threading checks run at once.
+const THREADS: usize = 10;
+
+/// A random name for a service or user, so that checks don't collide.
+pub fn random_name() -> String {
+    let mut bytes = [0; 6];
+    random_bytes(&mut bytes).expect("the kernel's CSPRNG is available");
+    to_hex(&bytes)
+}
+
+/// Build an entry for `service` and `user` in `store`, panicking if it can't be built.
+pub fn entry(store: &Arc<CredentialStore>, service: &str, user: &str) -> Entry {
+    store.build(service, user, None).unwrap_or_else(|err| {
+        panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
+    })
+}
+
+/// Set and get a password, without deleting the credential afterward.
+pub fn round_trip_password_no_delete(case: &str, entry: &Entry, in_pass: &str) {
+    entry
+        .set_password(in_pass)
+        .unwrap_or_else(|err| panic!("Can't set password for {case}: {err:?}"));
+    let out_pass = entry
+        .get_password()
+        .unwrap_or_else(|err| panic!("Can't get password: {case}: {err:?}"));
+    assert_eq!(
+        in_pass, out_pass,
+        "Passwords don't match for {case}: set='{in_pass}', get='{out_pass}'",
+    )
+}
+
+/// Set and get a password, then delete the credential and check that it's gone.
+pub fn round_trip_password(case: &str, entry: &Entry, in_pass: &str) {
+    round_trip_password_no_delete(case, entry, in_pass);
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete password: {case}: {err:?}"));
+    let password = entry.get_password();
+    assert!(
+        matches!(password, Err(Error::NoEntry)),
+        "Got a deleted password: {case}",
+    );
+}
+
+/// Set and get a secret, then delete the credential and check that it's gone.
+pub fn round_trip_secret(case: &str, entry: &Entry, in_secret: &[u8]) {
+    entry
+        .set_secret(in_secret)
+        .unwrap_or_else(|err| panic!("Can't set secret for {case}: {err:?}"));
+    let out_secret = entry
+        .get_secret()
+        .unwrap_or_else(|err| panic!("Can't get secret for {case}: {err:?}"));
+    assert_eq!(
+        in_secret, &out_secret,
+        "Secrets don't match for {case}: set='{in_secret:?}', get='{out_secret:?}'",
+    );
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete credential for {case}: {err:?}"));
+    let secret = entry.get_secret();
+    assert!(
+        matches!(secret, Err(Error::NoEntry)),
+        "Got a deleted password: {case}",
+    );
+}
+
+/// Check that a credential that was never written isn't found.
+pub fn check_missing_entry(store: &Arc<CredentialStore>) {
+    let name = random_name();
+    let entry = entry(store, &name, &name);
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+/// Check round trips of ASCII and non-ASCII passwords and random secrets,
+/// and updates of existing credentials.
+pub fn check_round_trips(store: &Arc<CredentialStore>) {
+    let name = random_name();
+    let entry = entry(store, &name, &name);
+    round_trip_password("ascii password", &entry, "test ascii password");
+    round_trip_password("non-ascii password", &entry, "このきれいな花は桜です");
+    let mut secret = [0; 24];
+    random_bytes(&mut secret).expect("the kernel's CSPRNG is available");
+    round_trip_secret("random secret", &entry, &secret);
+    round_trip_password_no_delete("initial ascii password", &entry, "test ascii password");
+    round_trip_password(
+        "updated non-ascii password",
+        &entry,
+        "このきれいな花は桜です",
+    );
+}
+
+/// Check that entries with the same specifiers share a credential, and
+/// entries with different ones don't.
+pub fn check_specifiers(store: &Arc<CredentialStore>) {
+    let (name1, name2) = (random_name(), random_name());
+    let entry1 = entry(store, &name1, &name2);
+    let entry2 = entry(store, &name1, &name2);
+    let entry3 = entry(store, &name2, &name1);
+    entry1.set_password("test password").unwrap();
+    assert_eq!(entry2.get_password().unwrap(), "test password");
+    _ = entry3.get_password().unwrap_err();
+    entry1.delete_credential().unwrap();
+    _ = entry2.get_password().unwrap_err();
+    entry3.delete_credential().unwrap_err();
+}
+
+/// Check that entries can be used from threads other than the one that
+/// built them, and that many threads can use the store at once.
+pub fn check_threads(store: &Arc<CredentialStore>) {
+    // an entry built here and used on another thread
+    let name = random_name();
+    let moved = entry(store, &name, &name);
+    std::thread::spawn(move || round_trip_password("moved entry", &moved, "test password"))
+        .join()
+        .expect("Couldn't execute on thread");
+    // entries built on their own threads, each written repeatedly
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let store = store.clone();
+            let name = format!("{}-{t}", random_name());
+            std::thread::spawn(move || {
+                let entry = entry(&store, &name, &name);
+                for _ in 0..10 {
+                    round_trip_password("simultaneous entry", &entry, &name);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap()
+    }
+}
+
+/// Run every check against `store`.
+pub fn check_store(store: &Arc<CredentialStore>) {
+    check_missing_entry(store);
+    check_round_trips(store);
+    check_specifiers(store);
+    check_threads(store);
+}
+
+/// Run every check against a [Store] with the given config options (see
+/// [Store::new_with_configuration]).
+pub fn check_config(config: &HashMap<&str, &str>) {
+    let store: Arc<CredentialStore> = Store::new_with_configuration(config)
+        .unwrap_or_else(|err| panic!("Couldn't create store with {config:?}: {err:?}"));
+    check_store(&store);
+}
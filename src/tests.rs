@@ -1,8 +1,19 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Once};
 
-use keyring_core::{CredentialStore, Entry, Error, api::CredentialPersistence, get_default_store};
+use keyring_core::{
+    CredentialStore, Entry, Error,
+    api::{CredentialApi, CredentialPersistence, CredentialStoreApi},
+    get_default_store,
+};
+use linux_keyutils::{Key, KeyRing, KeyRingIdentifier};
 
+use super::compat::{bad_store_format, is_bad_store_format};
+use super::test_util::{
+    round_trip_password as test_round_trip,
+    round_trip_password_no_delete as test_round_trip_no_delete,
+    round_trip_secret as test_round_trip_secret,
+};
 use super::{Cred, Store};
 
 static SET_STORE: Once = Once::new();
@@ -25,7 +36,14 @@ fn test_store_methods() {
     let vendor3 = store2.vendor();
     let id3 = store2.id();
     assert_eq!(vendor1, vendor3);
-    assert_ne!(id1, id3);
+    // the same keyring with the same configuration is the same store
+    assert_eq!(id1, id3);
+    let store3: Arc<CredentialStore> =
+        Store::new_with_configuration(&HashMap::from([("prefix", "other:")])).unwrap();
+    assert_ne!(id1, store3.id());
+    let store4: Arc<CredentialStore> =
+        Store::new_with_configuration(&HashMap::from([("keyring", "process")])).unwrap();
+    assert_ne!(id1, store4.id());
 }
 
 fn entry_new(service: &str, user: &str) -> Entry {
@@ -47,53 +65,14 @@ fn generate_random_bytes() -> Vec<u8> {
     repeat_with(|| fastrand::u8(..)).take(24).collect()
 }
 
-// A round-trip password test that doesn't delete the credential afterward
-fn test_round_trip_no_delete(case: &str, entry: &Entry, in_pass: &str) {
-    entry
-        .set_password(in_pass)
-        .unwrap_or_else(|err| panic!("Can't set password for {case}: {err:?}"));
-    let out_pass = entry
-        .get_password()
-        .unwrap_or_else(|err| panic!("Can't get password: {case}: {err:?}"));
-    assert_eq!(
-        in_pass, out_pass,
-        "Passwords don't match for {case}: set='{in_pass}', get='{out_pass}'",
-    )
-}
-
-// A round-trip password test that does delete the credential afterward
-fn test_round_trip(case: &str, entry: &Entry, in_pass: &str) {
-    test_round_trip_no_delete(case, entry, in_pass);
-    entry
-        .delete_credential()
-        .unwrap_or_else(|err| panic!("Can't delete password: {case}: {err:?}"));
-    let password = entry.get_password();
-    assert!(
-        matches!(password, Err(Error::NoEntry)),
-        "Got a deleted password: {case}",
-    );
-}
-
-// A round-trip secret test that does delete the credential afterward
-pub fn test_round_trip_secret(case: &str, entry: &Entry, in_secret: &[u8]) {
-    entry
-        .set_secret(in_secret)
-        .unwrap_or_else(|err| panic!("Can't set secret for {case}: {err:?}"));
-    let out_secret = entry
-        .get_secret()
-        .unwrap_or_else(|err| panic!("Can't get secret for {case}: {err:?}"));
-    assert_eq!(
-        in_secret, &out_secret,
-        "Secrets don't match for {case}: set='{in_secret:?}', get='{out_secret:?}'",
-    );
-    entry
-        .delete_credential()
-        .unwrap_or_else(|err| panic!("Can't delete credential for {case}: {err:?}"));
-    let secret = entry.get_secret();
-    assert!(
-        matches!(secret, Err(Error::NoEntry)),
-        "Got a deleted password: {case}",
-    );
+/// Whether `key` is linked into `target`'s keyring.
+///
+/// `KeyRing::get_links` reads only a quarter of the links it's asked for,
+/// and returns uninitialized memory for the rest, so it can't be trusted
+/// with keyrings as full as the persistent keyring gets.
+fn is_linked(target: super::Target, key: Key) -> bool {
+    let links = super::sys::keyring_links(target.serial().unwrap()).unwrap();
+    links.contains(&key.get_id())
 }
 
 #[test]
@@ -106,13 +85,41 @@ fn test_invalid_parameter() {
         Store::new_with_configuration(&HashMap::from([("service_no_divider", "true")])).unwrap();
     let entry = store.build("ser@vice", "user", None);
     assert!(matches!(entry, Err(Error::Invalid(_, _))));
-    let store: Arc<CredentialStore> = Store::new_with_configuration(&HashMap::from([
+    let store = Store::new_with_configuration(&HashMap::from([
         ("service_no_divider", "true"),
         ("divider", ""),
-    ]))
-    .unwrap();
-    let entry = store.build("service", "user", None);
-    assert!(matches!(entry, Err(Error::Invalid(_, _))));
+    ]));
+    assert!(matches!(store, Err(Error::Invalid(_, _))));
+    let store: Arc<CredentialStore> =
+        Store::new_with_configuration(&HashMap::from([("user_no_divider", "true")])).unwrap();
+    assert!(matches!(
+        store.build("service", "us@er", None),
+        Err(Error::Invalid(_, _))
+    ));
+    store.build("ser@vice", "user", None).unwrap();
+    let modifiers = HashMap::from([("description", "any@thing")]);
+    store.build("", "us@er", Some(&modifiers)).unwrap();
+}
+
+#[test]
+fn test_ambiguous_delimiters() {
+    for (prefix, divider, suffix, option) in [
+        ("keyring:", "", "", "divider"),
+        ("app@", "@", "", "prefix"),
+        ("keyring:", "::", "::end", "suffix"),
+        ("a:b:", ":", ":", "prefix"),
+    ] {
+        let config = HashMap::from([("prefix", prefix), ("divider", divider), ("suffix", suffix)]);
+        match Store::new_with_configuration(&config) {
+            Err(Error::Invalid(attr, reason)) => {
+                assert_eq!(attr, option);
+                assert!(reason.contains("ambiguous"), "{reason}");
+            }
+            other => panic!("{prefix:?}/{divider:?}/{suffix:?} was accepted: {other:?}"),
+        }
+    }
+    let config = HashMap::from([("prefix", "app:"), ("divider", "/"), ("suffix", ":end")]);
+    Store::new_with_configuration(&config).unwrap();
 }
 
 #[test]
@@ -194,10 +201,22 @@ fn test_get_credential_and_specifiers() {
     let cred1 = entry1.as_any().downcast_ref::<Cred>().unwrap();
     let wrapper = entry1.get_credential().unwrap();
     let cred2 = wrapper.as_any().downcast_ref::<Cred>().unwrap();
-    assert_eq!(cred1 as *const _, cred2 as *const _);
+    assert_ne!(cred1 as *const _, cred2 as *const _);
+    assert_eq!(cred2.description, cred1.description);
+    assert!(cred2.serial.is_some());
+    // a wrapper is its own wrapper
+    let rewrapped = wrapper.get_credential().unwrap();
+    assert_eq!(
+        rewrapped.as_any().downcast_ref::<Cred>().unwrap() as *const _,
+        cred2 as *const _
+    );
     let (service, user) = wrapper.get_specifiers().unwrap();
     assert_eq!(service, name1);
     assert_eq!(user, name2);
+    // the wrapper keeps to the key it was made for, even once the entry's key is replaced
+    entry1.delete_credential().unwrap();
+    entry1.set_password("new password for entry1").unwrap();
+    assert!(matches!(wrapper.get_password(), Err(Error::NoEntry)));
     entry1.delete_credential().unwrap();
     wrapper.delete_credential().unwrap_err();
     let modifiers = HashMap::from([("description", name1.as_str())]);
@@ -340,6 +359,80 @@ fn test_simultaneous_multiple_create_delete_single_thread() {
     }
 }
 
+#[test]
+fn test_multiple_create_delete_thread_keyring() {
+    let store = Store::new_with_configuration(&HashMap::from([("keyring", "thread")])).unwrap();
+    let name = generate_random_string();
+    // one entry, shared by every worker
+    let entry = Arc::new(store.build(&name, &name, None).unwrap());
+    let mut handles = vec![];
+    for t in 0..10 {
+        let entry = entry.clone();
+        let test = move || {
+            let secret = format!("worker {t}");
+            // each worker starts with an empty thread keyring of its own
+            assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+            for _i in 0..10 {
+                entry.set_password(&secret).unwrap();
+                assert_eq!(entry.get_password().unwrap(), secret);
+                entry.delete_credential().unwrap();
+                assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+            }
+            // a worker's secret dies with it
+            entry.set_password(&secret).unwrap();
+        };
+        handles.push(std::thread::spawn(test))
+    }
+    for handle in handles {
+        handle.join().unwrap()
+    }
+    // none of the workers' secrets are visible to the thread that built the entry
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("builder").unwrap();
+    let other = entry.clone();
+    std::thread::spawn(move || assert!(matches!(other.get_password(), Err(Error::NoEntry))))
+        .join()
+        .unwrap();
+    assert_eq!(entry.get_password().unwrap(), "builder");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_create_then_move_thread_keyring() {
+    use super::Target;
+
+    let name = generate_random_string();
+    let process = Store::new_with_configuration(&HashMap::from([("keyring", "process")])).unwrap();
+    let shared = process.build(&name, &name, None).unwrap();
+    let worker = std::thread::spawn(move || {
+        let store = Store::new_with_configuration(&HashMap::from([("keyring", "thread")])).unwrap();
+        let entry = store.build(&name, &name, None).unwrap();
+        entry.set_password("worker secret").unwrap();
+        let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+        // moving the key out of the thread keyring publishes it to the process
+        let moved = cred.move_to(Target::Process).unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert_eq!(moved.get_password().unwrap(), "worker secret");
+    });
+    worker.join().unwrap();
+    // the moved key outlives the thread that created it
+    assert_eq!(shared.get_password().unwrap(), "worker secret");
+    shared.delete_credential().unwrap();
+}
+
+#[test]
+fn test_conformance() {
+    use super::test_util;
+    for keyring in ["session", "process"] {
+        test_util::check_config(&HashMap::from([("keyring", keyring)]));
+    }
+    test_util::check_config(&HashMap::from([("hash_descriptions", "true")]));
+    let store: Arc<CredentialStore> = Store::new().unwrap();
+    assert_eq!(test_util::random_name().len(), 12);
+    assert_ne!(test_util::random_name(), test_util::random_name());
+    test_util::check_store(&store);
+}
+
 #[test]
 fn test_persistence() {
     let store: Arc<CredentialStore> = Store::new().unwrap();
@@ -347,4 +440,4298 @@ fn test_persistence() {
         store.persistence(),
         CredentialPersistence::UntilReboot
     ));
+    let store: Arc<CredentialStore> =
+        Store::new_with_configuration(&HashMap::from([("keyring", "user")])).unwrap();
+    assert!(matches!(
+        store.persistence(),
+        CredentialPersistence::UntilReboot
+    ));
+    let store: Arc<CredentialStore> =
+        Store::new_with_configuration(&HashMap::from([("keyring", "process")])).unwrap();
+    assert!(matches!(
+        store.persistence(),
+        CredentialPersistence::ProcessOnly
+    ));
+    assert!(matches!(
+        Store::new_with_configuration(&HashMap::from([("keyring", "nonesuch")])),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_user_session_keyring() {
+    use super::Target;
+    use linux_keyutils::{KeyRing, KeyRingIdentifier};
+
+    let store =
+        Store::new_with_configuration(&HashMap::from([("keyring", "user_session")])).unwrap();
+    assert_eq!(store.keyring, Target::UserSession);
+    let store: Arc<CredentialStore> = store;
+    assert!(matches!(
+        store.persistence(),
+        CredentialPersistence::UntilReboot
+    ));
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry
+        .set_password("shared by the user's sessionless processes")
+        .unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let user_session = KeyRing::from_special_id(KeyRingIdentifier::UserSession, false).unwrap();
+    let key = user_session.search(&cred.description).unwrap();
+    assert!(is_linked(Target::UserSession, key));
+    // the test session keyring isn't the user's default one, so this process
+    // possesses the user-session keyring through its process keyring
+    let serial = |id| super::sys::keyring_serial(id, false).unwrap();
+    let links = super::sys::keyring_links(serial(KeyRingIdentifier::Process)).unwrap();
+    assert!(links.contains(&serial(KeyRingIdentifier::UserSession)));
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_round_trip_other_keyrings() {
+    for keyring in ["process", "user", "thread", "persistent", "user_session"] {
+        let store: Arc<CredentialStore> =
+            Store::new_with_configuration(&HashMap::from([("keyring", keyring)])).unwrap();
+        let name = generate_random_string();
+        let entry = store.build(&name, &name, None).unwrap();
+        test_round_trip(keyring, &entry, "test password");
+    }
+}
+
+#[test]
+fn test_crypto_vectors() {
+    use super::crypto::{
+        chacha20, hmac_sha256, hmac_sha512, pbkdf2_hmac_sha256, sha256, sha512, to_hex,
+    };
+    assert_eq!(
+        to_hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        to_hex(&sha256(&[b'a'; 1000])),
+        "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+    );
+    assert_eq!(
+        to_hex(&sha512(b"abc")),
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+         2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+    );
+    assert_eq!(
+        to_hex(&sha512(&[b'a'; 200])),
+        "4b11459c33f52a22ee8236782714c150a3b2c60994e9acee17fe68947a3e6789\
+         f31e7668394592da7bef827cddca88c4e6f86e4df7ed1ae6cba71f3e98faee9f"
+    );
+    // RFC 4231, test case 2
+    assert_eq!(
+        to_hex(&hmac_sha256(
+            b"Jefe",
+            &[b"what do ya want ", b"for nothing?"]
+        )),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        to_hex(&hmac_sha512(b"Jefe", &[b"what do ya want for nothing?"])),
+        "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+         9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+    );
+    // RFC 4231, test case 6 (a key longer than the block)
+    assert_eq!(
+        to_hex(&hmac_sha512(
+            &[0xaa; 131],
+            &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+        )),
+        "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+         6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598"
+    );
+    // RFC 7914, section 11
+    assert_eq!(
+        to_hex(&pbkdf2_hmac_sha256(b"passwd", b"salt", 1)),
+        "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+    );
+    assert_eq!(
+        to_hex(&pbkdf2_hmac_sha256(b"password", b"salt", 2)),
+        "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+    );
+    // RFC 8439, section 2.4.2
+    let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+    let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+    let mut data = plaintext.to_vec();
+    chacha20(&key, &nonce, &mut data);
+    assert_eq!(to_hex(&data[..16]), "6e2e359a2568f98041ba0728dd0d6981");
+    assert_eq!(to_hex(&data[data.len() - 2..]), "874d");
+    chacha20(&key, &nonce, &mut data);
+    assert_eq!(data, plaintext);
+}
+
+fn backup_store() -> (Arc<Store>, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("keyring-backup-{}", generate_random_string()));
+    let master = format!("keyring-test-master:{}", generate_random_string());
+    let store = Store::new_with_configuration(&HashMap::from([
+        ("backup_dir", dir.to_str().unwrap()),
+        ("backup_key", master.as_str()),
+    ]))
+    .unwrap();
+    (store, dir)
+}
+
+#[test]
+fn test_backup_requires_master_key() {
+    let (store, dir) = backup_store();
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    assert!(matches!(
+        entry.set_password("test password"),
+        Err(Error::NoStorageAccess(_))
+    ));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(!dir.exists());
+}
+
+#[test]
+fn test_backup_recovers_lost_key() {
+    let (store, dir) = backup_store();
+    store.provision_backup_key(b"test master key").unwrap();
+    assert!(matches!(
+        store.persistence(),
+        CredentialPersistence::UntilDelete
+    ));
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    // simulate a reboot by dropping the key from the kernel
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    cred.keyring
+        .search(&cred.description)
+        .unwrap()
+        .invalidate()
+        .unwrap();
+    assert_eq!(cred.secret_len().unwrap(), "test password".len());
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    _ = std::fs::remove_dir(&dir);
+}
+
+#[test]
+fn test_backup_survives_failed_revoke() {
+    use super::{FakeKeyctl, KeyctlOp, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let dir = std::env::temp_dir().join(format!("keyring-backup-{}", generate_random_string()));
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .backup_dir(&dir)
+        .backup_key(format!("keyring-test-master:{}", generate_random_string()))
+        .build()
+        .unwrap();
+    store.provision_backup_key(b"test master key").unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    let backups = || std::fs::read_dir(&dir).unwrap().count();
+    assert_eq!(backups(), 1);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    keyctl.fail(KeyctlOp::Revoke, KeyError::AccessDenied);
+    assert!(matches!(cred.revoke(), Err(Error::NoStorageAccess(_))));
+    assert_eq!(backups(), 1);
+    keyctl.recover(KeyctlOp::Revoke);
+    cred.revoke().unwrap();
+    assert_eq!(backups(), 0);
+    _ = std::fs::remove_dir(&dir);
+}
+
+#[test]
+fn test_backup_rejects_substituted_file() {
+    use super::crypto::{sha256, to_hex};
+    let (store, dir) = backup_store();
+    store.provision_backup_key(b"test master key").unwrap();
+    let name = generate_random_string();
+    let short = store.build(&name, &name, None).unwrap();
+    let long = store.build(&format!("{name}x"), &name, None).unwrap();
+    let short_cred = short.as_any().downcast_ref::<Cred>().unwrap();
+    let long_cred = long.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(
+        *long_cred.description,
+        format!("{}x", short_cred.description)
+    );
+    long.set_password("test password").unwrap();
+    let file = |cred: &Cred| dir.join(to_hex(&sha256(cred.description.as_bytes())));
+    let sealed = std::fs::read(file(long_cred)).unwrap();
+    // another entry's file, as is
+    std::fs::write(file(short_cred), &sealed).unwrap();
+    assert!(matches!(
+        short.get_password(),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    // another entry's file, with the byte its description is longer by moved
+    // in front of the tag
+    let mut moved = sealed.clone();
+    moved.insert(sealed.len() - 16, b'x');
+    std::fs::write(file(short_cred), &moved).unwrap();
+    assert!(matches!(
+        short.get_password(),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    std::fs::remove_file(file(short_cred)).unwrap();
+    assert!(matches!(short.get_password(), Err(Error::NoEntry)));
+    assert_eq!(long.get_password().unwrap(), "test password");
+    long.delete_credential().unwrap();
+    _ = std::fs::remove_dir(&dir);
+}
+
+#[test]
+fn test_get_secret_secure() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(cred.get_secret_secure(), Err(Error::NoEntry)));
+    let in_secret = generate_random_bytes();
+    entry.set_secret(&in_secret).unwrap();
+    let out_secret = cred.get_secret_secure().unwrap();
+    assert_eq!(out_secret.expose_secret(), in_secret.as_slice());
+    assert_eq!(
+        format!("{out_secret:?}"),
+        format!("SecretBytes([REDACTED; {}])", in_secret.len())
+    );
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_secret_bytes_read_from() {
+    use super::SecretBytes;
+
+    // long enough to outgrow the first few buffers, and not UTF-8
+    let in_secret: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+    let out_secret = SecretBytes::read_from(in_secret.as_slice()).unwrap();
+    assert_eq!(out_secret.expose_secret(), in_secret.as_slice());
+    let empty = SecretBytes::read_from(std::io::empty()).unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_get_secret_into() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        cred.get_secret_into(&mut buffer),
+        Err(Error::NoEntry)
+    ));
+    let in_secret = generate_random_bytes();
+    entry.set_secret(&in_secret).unwrap();
+    let len = cred.get_secret_into(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], in_secret.as_slice());
+    assert_eq!(cred.secret_len().unwrap(), in_secret.len());
+    let mut small = [0u8; 4];
+    assert!(matches!(
+        cred.get_secret_into(&mut small),
+        Err(Error::Invalid(_, _))
+    ));
+    assert_eq!(small, [0u8; 4]);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_create_new_and_update_only() {
+    SET_STORE.call_once(usually_goes_in_main);
+    let name = generate_random_string();
+    let create =
+        Entry::new_with_modifiers(&name, &name, &HashMap::from([("create_new", "true")])).unwrap();
+    let update =
+        Entry::new_with_modifiers(&name, &name, &HashMap::from([("update_only", "true")])).unwrap();
+    assert!(matches!(
+        update.set_password("update password"),
+        Err(Error::NoEntry)
+    ));
+    create.set_password("create password").unwrap();
+    assert!(matches!(
+        create.set_password("create password"),
+        Err(Error::Invalid(_, _))
+    ));
+    update.set_password("update password").unwrap();
+    assert_eq!(create.get_password().unwrap(), "update password");
+    update.delete_credential().unwrap();
+    let both = Entry::new_with_modifiers(
+        &name,
+        &name,
+        &HashMap::from([("create_new", "true"), ("update_only", "true")]),
+    );
+    assert!(matches!(both, Err(Error::Invalid(_, _))));
+}
+
+#[test]
+fn test_swap_secret() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.swap_secret(b"first").unwrap().is_none());
+    assert_eq!(cred.swap_secret(b"second").unwrap().unwrap(), b"first");
+    assert_eq!(entry.get_password().unwrap(), "second");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_get_or_create_with() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let created = cred
+        .get_or_create_with(|| Ok(b"generated".to_vec()))
+        .unwrap();
+    assert_eq!(created, b"generated");
+    let existing = cred
+        .get_or_create_with(|| panic!("generator called for existing credential"))
+        .unwrap();
+    assert_eq!(existing, b"generated");
+    entry.delete_credential().unwrap();
+    assert!(matches!(
+        cred.get_or_create_with(|| Err(Error::Invalid("test".into(), "failure".into()))),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_simultaneous_get_or_create_with() {
+    let name = generate_random_string();
+    let mut handles = vec![];
+    for i in 0..10 {
+        let entry = entry_new(&name, &name);
+        handles.push(std::thread::spawn(move || {
+            let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+            cred.get_or_create_with(|| Ok(format!("secret {i}").into_bytes()))
+                .unwrap()
+        }));
+    }
+    let results: Vec<Vec<u8>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    let entry = entry_new(&name, &name);
+    let stored = entry.get_secret().unwrap();
+    assert!(results.contains(&stored));
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_reads_during_key_replacement() {
+    use super::{Relink, StoreBuilder, Target, sys};
+    use linux_keyutils::{Key, KeyType};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let store = StoreBuilder::new()
+        .prefix(format!("{}:", generate_random_string()))
+        .keyring(Target::Process)
+        .relink(Relink::Never)
+        .build()
+        .unwrap();
+    let entry = store.build("service", "user", None).unwrap();
+    entry.set_password("value initial").unwrap();
+    let description = entry
+        .as_any()
+        .downcast_ref::<Cred>()
+        .unwrap()
+        .description
+        .clone();
+    let done = Arc::new(AtomicBool::new(false));
+    let mut readers = vec![];
+    for _ in 0..4 {
+        let entry = store.build("service", "user", None).unwrap();
+        let done = done.clone();
+        readers.push(std::thread::spawn(move || {
+            let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+            let mut reads = 0;
+            while !done.load(Ordering::Relaxed) {
+                assert!(entry.get_password().unwrap().starts_with("value "));
+                assert!(cred.secret_len().unwrap() > 6);
+                reads += 1;
+            }
+            reads
+        }));
+    }
+    // replace the key atomically, by linking a new key over it, and then
+    // invalidate the old one (which is kept in a keyring of the writer's so
+    // the writer still possesses it), possibly between a reader's search and read
+    let writer = std::thread::spawn(move || {
+        let process = Target::Process.serial().unwrap();
+        let scratch = Target::Thread.serial().unwrap();
+        let holding = sys::add_key(KeyType::KeyRing, "holding", &[], scratch).unwrap();
+        for i in 0..300 {
+            let old = sys::search(process, KeyType::User, &description).unwrap();
+            sys::link(old, holding).unwrap();
+            let payload = format!("value {i}");
+            let new =
+                sys::add_key(KeyType::User, &description, payload.as_bytes(), scratch).unwrap();
+            sys::link(new, process).unwrap();
+            sys::unlink(new, scratch).unwrap();
+            Key::from_id(old).invalidate().unwrap();
+        }
+    });
+    writer.join().unwrap();
+    done.store(true, Ordering::Relaxed);
+    for reader in readers {
+        assert!(reader.join().unwrap() > 0);
+    }
+    assert_eq!(entry.get_password().unwrap(), "value 299");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_reads_during_concurrent_writes() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    entry.set_password("value initial").unwrap();
+    let mut handles = vec![];
+    for i in 0..4 {
+        let entry = entry_new(&name, &name);
+        handles.push(std::thread::spawn(move || {
+            for j in 0..100 {
+                if i % 2 == 0 {
+                    entry.set_password(&format!("value {i}.{j}")).unwrap();
+                } else {
+                    assert!(entry.get_password().unwrap().starts_with("value "));
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_relink_policies() {
+    for relink in ["always", "best_effort", "never", "3"] {
+        let store: Arc<CredentialStore> =
+            Store::new_with_configuration(&HashMap::from([("relink", relink)])).unwrap();
+        let name = generate_random_string();
+        let entry = store.build(&name, &name, None).unwrap();
+        test_round_trip_no_delete(relink, &entry, "test password");
+        for _ in 0..5 {
+            assert_eq!(entry.get_password().unwrap(), "test password");
+        }
+        entry.delete_credential().unwrap();
+    }
+    for bad in ["sometimes", "0"] {
+        assert!(matches!(
+            Store::new_with_configuration(&HashMap::from([("relink", bad)])),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+}
+
+#[test]
+fn test_keep_alive_refresh() {
+    let name = generate_random_string();
+    // refreshes re-link every key the store manages, so they mustn't reach other tests' keys
+    let prefix = format!("keep-alive-{name}:");
+    let store =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    // simulate a logout dropping the key from the session keyring
+    cred.keyring.unlink_key(key).unwrap();
+    let keep_alive = super::KeepAlive::new(store.clone());
+    assert!(keep_alive.refresh().unwrap() >= 1);
+    assert!(is_linked(cred.target, key));
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    entry.delete_credential().unwrap();
+    let background = super::KeepAlive::spawn(store, std::time::Duration::from_millis(10));
+    std::thread::sleep(std::time::Duration::from_millis(30));
+    drop(background);
+}
+
+#[test]
+fn test_sync_engine() {
+    use super::{Provider, StoreBuilder, SyncEngine};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    #[derive(Debug, Default)]
+    struct Secrets {
+        secrets: Mutex<HashMap<(String, String), String>>,
+        fetches: AtomicUsize,
+    }
+
+    impl Provider for Secrets {
+        fn fetch(&self, service: &str, user: &str) -> keyring_core::Result<Vec<u8>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            let secrets = self.secrets.lock().unwrap();
+            let key = (service.to_string(), user.to_string());
+            secrets
+                .get(&key)
+                .map(|secret| secret.as_bytes().to_vec())
+                .ok_or(Error::NoEntry)
+        }
+
+        fn list(&self) -> keyring_core::Result<Vec<(String, String)>> {
+            Ok(self.secrets.lock().unwrap().keys().cloned().collect())
+        }
+    }
+
+    let name = generate_random_string();
+    let store = StoreBuilder::new()
+        .prefix(format!("sync-{name}:"))
+        .build()
+        .unwrap();
+    let provider = Arc::new(Secrets::default());
+    let set = |user: &str, secret: &str| {
+        let key = (name.clone(), user.to_string());
+        provider
+            .secrets
+            .lock()
+            .unwrap()
+            .insert(key, secret.to_string());
+    };
+    set("a", "first");
+    set("b", "second");
+    let engine = SyncEngine::new(store.clone(), provider.clone());
+    assert_eq!(engine.refresh().unwrap(), 2);
+    let entry = store.build(&name, "a", None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "first");
+    // reads are served from the store
+    let fetches = provider.fetches.load(Ordering::SeqCst);
+    assert_eq!(engine.get_password(&name, "b").unwrap(), "second");
+    assert_eq!(provider.fetches.load(Ordering::SeqCst), fetches);
+    // missing credentials are fetched, and kept
+    entry.delete_credential().unwrap();
+    set("a", "rotated");
+    assert_eq!(engine.get_password(&name, "a").unwrap(), "rotated");
+    assert_eq!(provider.fetches.load(Ordering::SeqCst), fetches + 1);
+    assert_eq!(entry.get_password().unwrap(), "rotated");
+    assert!(matches!(
+        engine.get_password(&name, "c"),
+        Err(Error::NoEntry)
+    ));
+    // a background engine refreshes right away
+    set("a", "refreshed");
+    let background = SyncEngine::spawn(store.clone(), provider.clone(), Duration::from_secs(60));
+    let started = Instant::now();
+    while entry.get_password().unwrap() != "refreshed" {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    drop(background);
+    for entry in store.entries().unwrap() {
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_pam_session() {
+    use super::{PamSession, PamStage, Provider, StoreBuilder};
+
+    #[derive(Debug)]
+    struct Staged(String);
+
+    impl Provider for Staged {
+        fn fetch(&self, _: &str, user: &str) -> keyring_core::Result<Vec<u8>> {
+            Ok(format!("staged for {user}").into_bytes())
+        }
+
+        fn list(&self) -> keyring_core::Result<Vec<(String, String)>> {
+            Ok(vec![(self.0.clone(), "login".to_string())])
+        }
+    }
+
+    let name = generate_random_string();
+    let store = StoreBuilder::new()
+        .prefix(format!("pam-{name}:"))
+        .build()
+        .unwrap();
+    let session = PamSession::new(store.clone()).provider(Arc::new(Staged(name.clone())));
+    assert_eq!(session.run(PamStage::Open).unwrap(), 1);
+    let entry = store.build(&name, "login", None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "staged for login");
+    // closing the session revokes its keys
+    assert_eq!(session.run(PamStage::Close).unwrap(), 1);
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // keys outside the session keyring are left alone
+    let process = StoreBuilder::new()
+        .prefix(format!("pam-{name}:"))
+        .keyring(super::Target::Process)
+        .build()
+        .unwrap();
+    let kept = process.build(&name, "kept", None).unwrap();
+    kept.set_password("kept").unwrap();
+    assert_eq!(PamSession::new(process).close().unwrap(), 0);
+    assert_eq!(kept.get_password().unwrap(), "kept");
+    kept.delete_credential().unwrap();
+}
+
+#[test]
+#[cfg(feature = "watch")]
+fn test_watch_notifications() {
+    use super::{KeyEvent, Notification, Watcher};
+    use std::time::Duration;
+
+    let mut watcher = match Watcher::new() {
+        Ok(watcher) => watcher,
+        // the kernel was built without key notifications
+        Err(Error::NotSupportedByStore(_)) => return,
+        Err(err) => panic!("Couldn't create a watcher: {err:?}"),
+    };
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(watcher.watch(cred), Err(Error::NoEntry)));
+    entry.set_password("first").unwrap();
+    let key = match watcher.watch(cred) {
+        Ok(key) => key,
+        Err(Error::NotSupportedByStore(_)) => {
+            entry.delete_credential().unwrap();
+            return;
+        }
+        Err(err) => panic!("Couldn't watch the credential: {err:?}"),
+    };
+    assert!(watcher.recv(Some(Duration::ZERO)).unwrap().is_empty());
+    entry.set_password("second").unwrap();
+    entry.delete_credential().unwrap();
+    let events: Vec<KeyEvent> = watcher
+        .recv(Some(Duration::from_secs(1)))
+        .unwrap()
+        .into_iter()
+        .filter_map(|n| match n {
+            Notification::Key {
+                key: k,
+                description,
+                event,
+            } if k == key => {
+                assert_eq!(description.as_deref(), Some(&*cred.description));
+                Some(event)
+            }
+            _ => None,
+        })
+        .collect();
+    assert!(events.contains(&KeyEvent::Updated));
+    assert!(events.contains(&KeyEvent::Invalidated));
+    watcher.unwatch(key).unwrap();
+}
+
+#[test]
+#[cfg(feature = "watch")]
+fn test_watch_finds_typed_key() {
+    use super::Watcher;
+
+    let mut watcher = match Watcher::new() {
+        Ok(watcher) => watcher,
+        Err(Error::NotSupportedByStore(_)) => return,
+        Err(err) => panic!("Couldn't create a watcher: {err:?}"),
+    };
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let modifiers = HashMap::from([("key_type", "logon")]);
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    entry.set_password("logon").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let found = entry.get_credential().unwrap();
+    let found = found.as_any().downcast_ref::<Cred>().unwrap();
+    match watcher.watch(cred) {
+        Ok(key) => assert_eq!(Some(key), found.serial),
+        Err(Error::NotSupportedByStore(_)) => {}
+        Err(err) => panic!("Couldn't watch the credential: {err:?}"),
+    }
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_request_key_callout() {
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let modifiers = HashMap::from([("callout", "test callout")]);
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(cred.callout.as_deref(), Some("test callout"));
+    // an existing key is found without any upcall
+    entry.set_password("test password").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    entry.delete_credential().unwrap();
+    // with no handler to supply it, a missing key stays missing
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("second password").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "second password");
+    // only a request-key handler holds the authority to instantiate a key
+    let key = cred.keyring.search(&cred.description).unwrap();
+    assert!(super::KeyRequest::assume(key.get_id()).is_err());
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_adopt_key() {
+    let store = Store::new().unwrap();
+    let session = KeyRing::from_special_id(KeyRingIdentifier::Session, false).unwrap();
+    // a key made by some other program, under its own description
+    let name = generate_random_string();
+    let foreign = session
+        .add_key(&format!("foreign:{name}"), b"foreign secret")
+        .unwrap();
+    let entry = store.adopt_key(foreign.get_id(), &name, &name).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "foreign secret");
+    assert_eq!(foreign.read_to_vec().unwrap(), b"foreign secret");
+    entry.delete_credential().unwrap();
+    foreign.invalidate().unwrap();
+    // a key that already has the store's description is adopted as is
+    let process = KeyRing::from_special_id(KeyRingIdentifier::Process, true).unwrap();
+    let name = generate_random_string();
+    let own = process
+        .add_key(&format!("keyring:{name}@{name}"), b"own secret")
+        .unwrap();
+    let entry = store.adopt_key(own.get_id(), &name, &name).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(cred.keyring.search(&cred.description).unwrap(), own);
+    assert_eq!(entry.get_password().unwrap(), "own secret");
+    entry.delete_credential().unwrap();
+    // keyrings can't be adopted
+    let keyring = super::sys::keyring_serial(KeyRingIdentifier::Session, false).unwrap();
+    assert!(matches!(
+        store.adopt_key(keyring, "service", "user"),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_entry_from_serial() {
+    let store = Store::new().unwrap();
+    let process = KeyRing::from_special_id(KeyRingIdentifier::Process, true).unwrap();
+    let name = generate_random_string();
+    let key = process
+        .add_key(&format!("handed-over:{name}"), b"first")
+        .unwrap();
+    let entry = store.entry_from_serial(key.get_id()).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(*cred.description, format!("handed-over:{name}"));
+    assert_eq!(entry.get_specifiers(), None);
+    assert_eq!(entry.get_password().unwrap(), "first");
+    entry.set_password("second").unwrap();
+    assert_eq!(key.read_to_vec().unwrap(), b"second");
+    // the key was updated in place, not added to the store's keyring
+    assert!(cred.keyring.search(&cred.description).is_err());
+    entry.get_credential().unwrap();
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.get_credential(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_move_to() {
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    let user = cred.move_to(super::Target::User).unwrap();
+    assert_eq!(user.target, super::Target::User);
+    assert!(!is_linked(cred.target, key));
+    assert!(is_linked(user.target, key));
+    assert_eq!(user.get_secret().unwrap(), b"test password");
+    // moving out of the persistent scope unlinks the key from the persistent keyring
+    let process = user.move_to(super::Target::Process).unwrap();
+    assert!(process.persistent.is_none());
+    if user.persistent.is_some() {
+        assert!(!is_linked(super::Target::Persistent, key));
+    }
+    assert!(matches!(user.get_secret(), Err(Error::NoEntry)));
+    assert_eq!(process.get_secret().unwrap(), b"test password");
+    process.delete_credential().unwrap();
+    assert!(matches!(
+        process.move_to(super::Target::User),
+        Err(Error::NoEntry)
+    ));
+}
+
+#[test]
+fn test_capabilities() {
+    let store = Store::new().unwrap();
+    let caps = store.capabilities();
+    assert_eq!(caps, super::Capabilities::get());
+    let version = caps
+        .kernel_version
+        .expect("Couldn't determine the kernel version");
+    if version >= (5, 3) {
+        assert!(caps.reported);
+    }
+    if caps.reported {
+        assert!(caps.invalidate);
+    }
+    // the store only links to the persistent keyring if the kernel has one
+    let entry = store.build("service", "user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    if !caps.persistent_keyrings {
+        assert!(cred.persistent.is_none());
+    }
+}
+
+#[test]
+fn test_diagnose() {
+    let line = " 1000:     5 5/5 4/200 101/20000";
+    let user = super::procfs::parse_key_user(line).unwrap();
+    assert_eq!(user.uid, 1000);
+    let quota = user.quota;
+    assert_eq!((quota.keys, quota.max_keys), (4, 200));
+    assert_eq!((quota.bytes, quota.max_bytes), (101, 20000));
+
+    let report = Store::new().unwrap().diagnose();
+    assert!(report.is_ok(), "{report}");
+    let session = report
+        .keyrings
+        .iter()
+        .find(|k| k.name == "session")
+        .unwrap();
+    assert!(session.serial.is_ok() && session.readable);
+    assert!(report.quota.is_some());
+    // stores can't be built with ambiguous delimiters, but can be changed to have them
+    let mut store = (*Store::new_with_configuration(&HashMap::from([
+        ("backup_dir", "/nonexistent"),
+        ("backup_key", "no such master key"),
+    ]))
+    .unwrap())
+    .clone();
+    store.delimiters[0] = "a@b:".to_string();
+    let report = store.diagnose();
+    assert_eq!(report.problems.len(), 2, "{report}");
+    assert!(report.to_string().contains("contains the divider"));
+}
+
+#[test]
+fn test_entries() {
+    let prefix = format!("{}:", generate_random_string());
+    let store =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    assert!(store.entries().unwrap().is_empty());
+    let names = [generate_random_string(), generate_random_string()];
+    for name in &names {
+        store
+            .build(name, name, None)
+            .unwrap()
+            .set_password(name)
+            .unwrap();
+    }
+    let mut found: Vec<String> = store
+        .entries()
+        .unwrap()
+        .iter()
+        .map(|entry| entry.get_password().unwrap())
+        .collect();
+    found.sort();
+    let mut expected = names.to_vec();
+    expected.sort();
+    assert_eq!(found, expected);
+    for entry in store.entries().unwrap() {
+        let (service, user) = entry.get_specifiers().unwrap();
+        assert_eq!(service, user);
+    }
+    for entry in store.entries().unwrap() {
+        entry.delete_credential().unwrap();
+    }
+    assert!(store.entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_scoped() {
+    let prefix = format!("{}:", generate_random_string());
+    let parent =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    let plugin_a = parent.scoped("plugin-a:").unwrap();
+    let plugin_b = parent.scoped("plugin-b:").unwrap();
+    assert_ne!(plugin_a.id(), parent.id());
+    assert_eq!(plugin_a.keyring, parent.keyring);
+    let entry_a = plugin_a.build("service", "user", None).unwrap();
+    let entry_b = plugin_b.build("service", "user", None).unwrap();
+    entry_a.set_password("a").unwrap();
+    entry_b.set_password("b").unwrap();
+    let cred = entry_a.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(*cred.description, format!("{prefix}plugin-a:user@service"));
+    // each namespace has its own credentials
+    assert_eq!(entry_a.get_password().unwrap(), "a");
+    assert_eq!(entry_b.get_password().unwrap(), "b");
+    assert_eq!(plugin_a.entries().unwrap().len(), 1);
+    assert!(matches!(
+        parent
+            .build("service", "user", None)
+            .unwrap()
+            .get_password(),
+        Err(Error::NoEntry)
+    ));
+    entry_a.delete_credential().unwrap();
+    entry_b.delete_credential().unwrap();
+    assert!(matches!(parent.scoped(""), Err(Error::Invalid(_, _))));
+    assert!(matches!(parent.scoped("a@b"), Err(Error::Invalid(_, _))));
+}
+
+#[test]
+fn test_parse_description() {
+    let parse = |config: &[(&str, &str)], description: &str| {
+        let store = Store::new_with_configuration(&config.iter().copied().collect()).unwrap();
+        store.parse_description(description)
+    };
+    let pair = |service: &str, user: &str| Some((service.to_string(), user.to_string()));
+    assert_eq!(parse(&[], "keyring:user@service"), pair("service", "user"));
+    assert_eq!(parse(&[], "keyring:@"), pair("", ""));
+    assert_eq!(parse(&[], "keyring:user"), None);
+    assert_eq!(parse(&[], "other:user@service"), None);
+    assert_eq!(parse(&[], "keyring:a@b@c"), None);
+    let user_no_divider = [("user_no_divider", "true")];
+    assert_eq!(parse(&user_no_divider, "keyring:a@b@c"), pair("b@c", "a"));
+    let service_no_divider = [("service_no_divider", "true")];
+    assert_eq!(
+        parse(&service_no_divider, "keyring:a@b@c"),
+        pair("c", "a@b")
+    );
+    let both = [("user_no_divider", "true"), ("service_no_divider", "true")];
+    assert_eq!(parse(&both, "keyring:a@b@c"), None);
+    let custom = [("prefix", "<"), ("divider", "|"), ("suffix", ">")];
+    assert_eq!(parse(&custom, "<me|it>"), pair("it", "me"));
+    assert_eq!(parse(&custom, "<me|it"), None);
+    let hashed = [("hash_descriptions", "true")];
+    let store = Store::new_with_configuration(&hashed.iter().copied().collect()).unwrap();
+    let entry = store.build("service", "user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(store.parse_description(&cred.description), None);
+}
+
+#[test]
+fn test_target_modifier() {
+    use super::{StoreBuilder, StoreConfig};
+
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let description = format!("target-{name}");
+    let target = HashMap::from([("target", description.as_str())]);
+    let entry = store.build(&name, "user", Some(&target)).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(*cred.description, description);
+    assert_eq!(entry.get_specifiers(), None);
+    // it's the same entry as one built with a description
+    entry.set_password("aliased").unwrap();
+    let custom = HashMap::from([("description", description.as_str())]);
+    let same = store.build("", "", Some(&custom)).unwrap();
+    assert_eq!(same.get_password().unwrap(), "aliased");
+    entry.delete_credential().unwrap();
+    let both = HashMap::from([
+        ("description", description.as_str()),
+        ("target", description.as_str()),
+    ]);
+    assert!(matches!(
+        store.build(&name, "user", Some(&both)),
+        Err(Error::Invalid(_, _))
+    ));
+    // strict stores only accept description
+    let strict = StoreBuilder::new().target_modifier(false).build().unwrap();
+    match strict.build(&name, "user", Some(&target)) {
+        Err(Error::Invalid(name, _)) => assert_eq!(name, "target"),
+        other => panic!("target was accepted: {other:?}"),
+    }
+    assert!(strict.build(&name, "user", Some(&custom)).is_ok());
+
+    let options = HashMap::from([("target_modifier", "false")]);
+    let config = StoreConfig::from_options(&options).unwrap();
+    assert_eq!(config.target_modifier, Some(false));
+}
+
+#[test]
+fn test_control_chars() {
+    let store = Store::new().unwrap();
+    for (service, user, attr, code) in [
+        ("ser\0vice", "user", "service", "U+0000"),
+        ("service", "us\ner", "user", "U+000A"),
+        ("service", "user\u{9b}", "user", "U+009B"),
+    ] {
+        match store.build(service, user, None) {
+            Err(Error::Invalid(name, reason)) => {
+                assert_eq!(name, attr);
+                assert!(reason.contains(code), "{reason}");
+            }
+            other => panic!("{service:?}/{user:?} was accepted: {other:?}"),
+        }
+    }
+    // custom descriptions are taken as they are
+    let modifiers = HashMap::from([("description", "custom")]);
+    store.build("ser\0vice", "user", Some(&modifiers)).unwrap();
+
+    let config = HashMap::from([("control_chars", "escape")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    let description = |service: &str, user: &str| {
+        let entry = store.build(service, user, None).unwrap();
+        let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+        cred.description.to_string()
+    };
+    assert_eq!(description("svc", "a\tb\0"), "keyring:a\\u{09}b\\u{00}@svc");
+    assert_eq!(description("svc", "a\\b"), "keyring:a\\\\b@svc");
+    assert_eq!(description("svc", "plain"), "keyring:plain@svc");
+    assert_ne!(description("svc", "\0"), description("svc", "\\u{00}"));
+    let name = generate_random_string();
+    let entry = store.build(&name, "line\nbreak", None).unwrap();
+    entry.set_password("escaped").unwrap();
+    assert_eq!(
+        store
+            .build(&name, "line\nbreak", None)
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "escaped"
+    );
+    entry.delete_credential().unwrap();
+    #[cfg(feature = "mock")]
+    {
+        use super::MockStore;
+
+        let mock = MockStore::new_with_configuration(&config).unwrap();
+        mock.build("svc", "a\tb", None)
+            .unwrap()
+            .set_password("x")
+            .unwrap();
+        assert_eq!(mock.descriptions(), vec!["keyring:a\\u{09}b@svc"]);
+        assert!(
+            MockStore::new()
+                .unwrap()
+                .build("svc", "a\tb", None)
+                .is_err()
+        );
+    }
+}
+
+#[test]
+fn test_case_insensitive() {
+    let name = generate_random_string();
+    let config = HashMap::from([("case_insensitive", "true")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    let writer = store
+        .build(&format!("GitHub-{name}"), "Octo", None)
+        .unwrap();
+    writer.set_password("folded").unwrap();
+    let reader = store
+        .build(&format!("github-{name}"), "OCTO", None)
+        .unwrap();
+    assert_eq!(reader.get_password().unwrap(), "folded");
+    let cred = reader.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(
+        *cred.description,
+        format!("keyring:octo@github-{}", name.to_lowercase())
+    );
+    // stores that don't fold case keep names apart
+    let sensitive = Store::new().unwrap();
+    let other = sensitive
+        .build(&format!("GitHub-{name}"), "Octo", None)
+        .unwrap();
+    assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+    reader.delete_credential().unwrap();
+}
+
+#[test]
+fn test_normalization() {
+    use super::Normalization;
+    // composition, canonical ordering of marks, and Hangul
+    assert_eq!(Normalization::Nfc.apply("e\u{301}"), "\u{e9}");
+    assert_eq!(Normalization::Nfc.apply("a\u{302}\u{323}"), "\u{1ead}");
+    assert_eq!(Normalization::Nfc.apply("a\u{323}\u{302}"), "\u{1ead}");
+    assert_eq!(
+        Normalization::Nfc.apply("\u{1100}\u{1161}\u{11a8}"),
+        "\u{ac01}"
+    );
+    // compatibility variants only unify under NFKC
+    assert_eq!(Normalization::Nfc.apply("\u{fb01}le"), "\u{fb01}le");
+    assert_eq!(Normalization::Nfkc.apply("\u{fb01}le"), "file");
+    assert_eq!(Normalization::Nfkc.apply("\u{ff21}\u{2075}"), "A5");
+    assert!(matches!(
+        "nfd".parse::<Normalization>(),
+        Err(Error::Invalid(key, _)) if key == "normalization"
+    ));
+
+    let name = generate_random_string();
+    let config = HashMap::from([("normalization", "nfc")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    let precomposed = store
+        .build(&format!("caf\u{e9}-{name}"), "user", None)
+        .unwrap();
+    precomposed.set_password("normalized").unwrap();
+    let decomposed = store
+        .build(&format!("cafe\u{301}-{name}"), "user", None)
+        .unwrap();
+    assert_eq!(decomposed.get_password().unwrap(), "normalized");
+    let cred = decomposed.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(*cred.description, format!("keyring:user@caf\u{e9}-{name}"));
+    // stores that don't normalize keep encodings apart
+    let plain = Store::new().unwrap();
+    let other = plain
+        .build(&format!("cafe\u{301}-{name}"), "user", None)
+        .unwrap();
+    assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+    decomposed.delete_credential().unwrap();
+}
+
+#[test]
+fn test_streamed_secret() {
+    use std::io::Read;
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let secret: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+    let len = cred.set_secret_from(std::io::Cursor::new(&secret)).unwrap();
+    assert_eq!(len, secret.len());
+    let mut reader = cred.get_secret_reader().unwrap();
+    assert_eq!(reader.len(), secret.len());
+    let mut chunk = [0u8; 4096];
+    let mut streamed = Vec::new();
+    loop {
+        match reader.read(&mut chunk).unwrap() {
+            0 => break,
+            n => streamed.extend_from_slice(&chunk[..n]),
+        }
+    }
+    assert_eq!(streamed, secret);
+    assert!(format!("{reader:?}").contains("REDACTED"));
+    // a user key can't hold a secret this long, which is noticed while reading
+    let endless = std::io::repeat(7);
+    assert!(matches!(
+        cred.set_secret_from(endless),
+        Err(Error::TooLong(_, 32767))
+    ));
+    assert_eq!(entry.get_secret().unwrap(), secret);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_max_read_len() {
+    let name = generate_random_string();
+    let config = HashMap::from([("max_read_len", "16")]);
+    let limited = Store::new_with_configuration(&config).unwrap();
+    let unlimited = Store::new().unwrap();
+    let guarded = limited.build(&name, &name, None).unwrap();
+    let writer = unlimited.build(&name, &name, None).unwrap();
+    writer.set_secret(&[1; 16]).unwrap();
+    assert_eq!(guarded.get_secret().unwrap(), vec![1; 16]);
+    // another writer stuffs the key with more than the reader will take
+    writer.set_secret(&[2; 1000]).unwrap();
+    assert!(matches!(
+        guarded.get_secret(),
+        Err(Error::TooLong(name, 16)) if name == "payload"
+    ));
+    assert_eq!(writer.get_secret().unwrap(), vec![2; 1000]);
+    let config = HashMap::from([("max_read_len", "lots")]);
+    assert!(matches!(
+        Store::new_with_configuration(&config),
+        Err(Error::Invalid(key, _)) if key == "max_read_len"
+    ));
+    writer.delete_credential().unwrap();
+}
+
+#[test]
+fn test_store_lock() {
+    use super::StoreLock;
+    use std::time::Duration;
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let lease = Duration::from_secs(30);
+    if !store.capabilities().move_key {
+        assert!(matches!(
+            StoreLock::try_acquire(&store, &name, lease),
+            Err(Error::NotSupportedByStore(_))
+        ));
+        return;
+    }
+    let lock = StoreLock::try_acquire(&store, &name, lease)
+        .unwrap()
+        .expect("an unheld lock can be taken");
+    assert!(lock.is_held());
+    assert_eq!(lock.name(), name);
+    assert!(
+        StoreLock::try_acquire(&store, &name, lease)
+            .unwrap()
+            .is_none()
+    );
+    // another thread (with its own thread keyring) can't take it either
+    let (store2, name2) = (store.clone(), name.clone());
+    let contended = std::thread::spawn(move || {
+        StoreLock::acquire(&store2, &name2, lease, Duration::from_millis(50))
+            .unwrap()
+            .is_none()
+    });
+    assert!(contended.join().unwrap());
+    lock.renew(Duration::from_secs(60)).unwrap();
+    lock.release().unwrap();
+    let relock = StoreLock::try_acquire(&store, &name, lease).unwrap();
+    assert!(relock.is_some());
+    drop(relock);
+    // the lease runs out if the holder never releases the lock
+    let abandoned = StoreLock::try_acquire(&store, &name, Duration::from_millis(1))
+        .unwrap()
+        .unwrap();
+    std::mem::forget(abandoned);
+    let taken = StoreLock::acquire(&store, &name, lease, Duration::from_secs(5)).unwrap();
+    assert!(taken.is_some());
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn test_async_store() {
+    use super::AsyncStore;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    // a minimal executor: park the thread until the future wakes it
+    struct Unparker(std::thread::Thread);
+    impl Wake for Unparker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let waker = Waker::from(Arc::new(Unparker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    let store = AsyncStore::new(Store::new().unwrap());
+    let name = generate_random_string();
+    let entry = store.entry(&name, &name).unwrap();
+    block_on(async {
+        entry.set_password("awaited").await.unwrap();
+        assert_eq!(entry.get_password().await.unwrap(), "awaited");
+        entry.set_secret(b"bytes").await.unwrap();
+        assert_eq!(entry.get_secret().await.unwrap(), b"bytes");
+        let found = store.entries().await.unwrap();
+        assert!(
+            found
+                .iter()
+                .any(|e| e.entry().get_specifiers() == Some((name.clone(), name.clone())))
+        );
+        let stats = store.run(|store| store.stats()).await;
+        assert!(stats.is_ok());
+        entry.delete_credential().await.unwrap();
+        assert!(matches!(entry.get_secret().await, Err(Error::NoEntry)));
+    });
+}
+
+#[test]
+#[cfg(feature = "ffi")]
+fn test_ffi() {
+    use super::ffi::*;
+    use std::ffi::{CStr, CString};
+    let names = [c"prefix".as_ptr()];
+    let values = [c"ffi:".as_ptr()];
+    let store = unsafe { keyutils_store_new(names.as_ptr(), values.as_ptr(), 1) };
+    assert!(!store.is_null());
+    let name = CString::new(generate_random_string()).unwrap();
+    let (service, user) = (name.as_ptr(), c"ffi-user".as_ptr());
+    unsafe {
+        let (mut secret, mut len) = (std::ptr::null_mut(), 0);
+        assert_eq!(
+            keyutils_store_get(store, service, user, &mut secret, &mut len),
+            KEYUTILS_STORE_NO_ENTRY
+        );
+        assert_eq!(
+            keyutils_store_set(store, service, user, b"from C".as_ptr(), 6),
+            KEYUTILS_STORE_OK
+        );
+        assert_eq!(
+            keyutils_store_get(store, service, user, &mut secret, &mut len),
+            KEYUTILS_STORE_OK
+        );
+        assert_eq!(std::slice::from_raw_parts(secret, len), b"from C");
+        keyutils_store_free_secret(secret, len);
+        // Rust sees the same credential
+        let config = HashMap::from([("prefix", "ffi:")]);
+        let rust = Store::new_with_configuration(&config).unwrap();
+        let entry = rust
+            .build(name.to_str().unwrap(), "ffi-user", None)
+            .unwrap();
+        assert_eq!(entry.get_password().unwrap(), "from C");
+
+        let (mut services, mut users, mut count) = (std::ptr::null_mut(), std::ptr::null_mut(), 0);
+        assert_eq!(
+            keyutils_store_list(store, &mut services, &mut users, &mut count),
+            KEYUTILS_STORE_OK
+        );
+        let listed = (0..count).any(|i| {
+            CStr::from_ptr(*services.add(i)) == name.as_c_str()
+                && CStr::from_ptr(*users.add(i)) == c"ffi-user"
+        });
+        assert!(listed);
+        keyutils_store_free_list(services, users, count);
+
+        assert_eq!(
+            keyutils_store_delete(store, service, user),
+            KEYUTILS_STORE_OK
+        );
+        assert_eq!(
+            keyutils_store_delete(store, service, user),
+            KEYUTILS_STORE_NO_ENTRY
+        );
+        assert!(!keyutils_store_last_error().is_null());
+        assert_eq!(
+            keyutils_store_set(store, std::ptr::null(), user, b"x".as_ptr(), 1),
+            KEYUTILS_STORE_BAD_ARGUMENT
+        );
+        let error = CStr::from_ptr(keyutils_store_last_error());
+        assert_eq!(error.to_str().unwrap(), "service is null");
+        keyutils_store_free(store);
+
+        let names = [c"keyring".as_ptr()];
+        let values = [c"nowhere".as_ptr()];
+        assert!(keyutils_store_new(names.as_ptr(), values.as_ptr(), 1).is_null());
+    }
+}
+
+#[test]
+fn test_export_import() {
+    let prefix = format!("{}:", generate_random_string());
+    let store =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    let names = [generate_random_string(), generate_random_string()];
+    for name in &names {
+        store
+            .build(name, "user", None)
+            .unwrap()
+            .set_password(name)
+            .unwrap();
+    }
+    let mut archive = Vec::new();
+    assert_eq!(
+        store
+            .export_with_iterations(&mut archive, b"passphrase", 10)
+            .unwrap(),
+        2
+    );
+    for entry in store.entries().unwrap() {
+        entry.delete_credential().unwrap();
+    }
+    assert!(matches!(
+        store.import(archive.as_slice(), b"wrong passphrase"),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    let mut corrupt = archive.clone();
+    corrupt[40] ^= 1;
+    assert!(matches!(
+        store.import(corrupt.as_slice(), b"passphrase"),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    assert!(store.entries().unwrap().is_empty());
+    assert_eq!(store.import(archive.as_slice(), b"passphrase").unwrap(), 2);
+    for name in &names {
+        let entry = store.build(name, "user", None).unwrap();
+        assert_eq!(entry.get_password().unwrap(), *name);
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_migrate_to() {
+    use super::{MigrateOptions, StoreBuilder};
+
+    let name = generate_random_string();
+    let old = StoreBuilder::new()
+        .prefix(format!("old-{name}:"))
+        .build()
+        .unwrap();
+    let new = StoreBuilder::new()
+        .prefix(format!("new-{name}:"))
+        .divider("/")
+        .build()
+        .unwrap();
+    for user in ["a", "b", "c"] {
+        old.build("service", user, None)
+            .unwrap()
+            .set_password(user)
+            .unwrap();
+    }
+    // ambiguous descriptions have no service and user to migrate under
+    let description = format!("old-{name}:a@b@c");
+    let modifiers = HashMap::from([("description", description.as_str())]);
+    let custom = old.build("", "", Some(&modifiers)).unwrap();
+    custom.set_password("custom").unwrap();
+    // credentials the other store has are kept
+    let kept = new.build("service", "c", None).unwrap();
+    kept.set_password("kept").unwrap();
+    let mut migrated = old.migrate_to(&new, MigrateOptions::default()).unwrap();
+    migrated.sort_by(|a, b| a.user.cmp(&b.user));
+    assert_eq!(migrated.len(), 2);
+    assert_eq!(migrated[0].from, format!("old-{name}:a@service"));
+    assert_eq!(migrated[0].to, format!("new-{name}:a/service"));
+    assert_eq!(kept.get_password().unwrap(), "kept");
+    for user in ["a", "b"] {
+        let entry = new.build("service", user, None).unwrap();
+        assert_eq!(entry.get_password().unwrap(), user);
+    }
+    assert_eq!(old.entries().unwrap().len(), 4);
+    // the originals can go, and other credentials can be overwritten
+    let options = MigrateOptions {
+        delete_originals: true,
+        overwrite: true,
+    };
+    assert_eq!(old.migrate_to(&new, options).unwrap().len(), 3);
+    assert_eq!(kept.get_password().unwrap(), "c");
+    assert_eq!(old.entries().unwrap().len(), 1);
+    assert_eq!(custom.get_password().unwrap(), "custom");
+    custom.delete_credential().unwrap();
+    for entry in new.entries().unwrap() {
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_copy_to_from() {
+    use super::StoreBuilder;
+
+    let name = generate_random_string();
+    let store = StoreBuilder::new()
+        .prefix(format!("copy-{name}:"))
+        .build()
+        .unwrap();
+    let other = keyring_core::mock::Store::new().unwrap();
+    for user in ["a", "b"] {
+        store
+            .build(&name, user, None)
+            .unwrap()
+            .set_password(user)
+            .unwrap();
+    }
+    let copied = store.copy_to(&*other, |_, user| user == "a").unwrap();
+    assert_eq!(copied, 1);
+    let a = other.build(&name, "a", None).unwrap();
+    assert_eq!(a.get_password().unwrap(), "a");
+    let b = other.build(&name, "b", None).unwrap();
+    assert!(matches!(b.get_password(), Err(Error::NoEntry)));
+    // and back again, replacing what's there
+    a.set_password("changed").unwrap();
+    b.set_password("new").unwrap();
+    for entry in store.entries().unwrap() {
+        entry.delete_credential().unwrap();
+    }
+    let spec = HashMap::from([("service", name.as_str())]);
+    assert_eq!(store.copy_from(&*other, &spec).unwrap(), 2);
+    for (user, secret) in [("a", "changed"), ("b", "new")] {
+        let entry = store.build(&name, user, None).unwrap();
+        assert_eq!(entry.get_password().unwrap(), secret);
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_disk_keys() {
+    use super::{DmCryptKey, EcryptfsKey, StoreBuilder};
+
+    // kernel-read payloads are written as they are, even in an enveloped store
+    let store = StoreBuilder::new().envelope(true).build().unwrap();
+    let name = generate_random_string();
+    let key = DmCryptKey::provision(&store, &name, &[0x42; 64]).unwrap();
+    assert_eq!(key.description, format!("cryptsetup:{name}"));
+    assert_eq!(key.table_key(), format!(":64:logon:cryptsetup:{name}"));
+    let entry = key.entry(&store).unwrap();
+    assert!(entry.get_credential().is_ok());
+    assert!(matches!(
+        entry.get_secret(),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    entry.delete_credential().unwrap();
+    assert!(matches!(
+        DmCryptKey::provision(&store, "", &[0x42; 64]),
+        Err(Error::Invalid(_, _))
+    ));
+
+    // the signature ecryptfs-add-passphrase prints for this passphrase
+    let key = EcryptfsKey::provision(&store, b"correct horse", None).unwrap();
+    assert_eq!(key.signature, "ffd652511eaff04a");
+    assert_eq!(key.mount_option(), "ecryptfs_sig=ffd652511eaff04a");
+    let entry = key.entry(&store).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let token = super::sys::read(cred.pin().unwrap().serial.unwrap(), usize::MAX)
+        .unwrap()
+        .unwrap();
+    assert_eq!(token.len(), 740);
+    assert_eq!(&token[..2], &4u16.to_ne_bytes());
+    assert_eq!(&token[628 + 84..628 + 100], b"ffd652511eaff04a");
+    entry.delete_credential().unwrap();
+    assert!(matches!(
+        EcryptfsKey::provision(&store, &[b'a'; 65], None),
+        Err(Error::TooLong(_, 64))
+    ));
+}
+
+#[test]
+fn test_systemd_credentials() {
+    use super::SystemdCredentials;
+
+    let dir = std::env::temp_dir().join(generate_random_string());
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("db-password"), "from systemd").unwrap();
+    std::fs::write(dir.join("api-token"), [0u8, 1, 2]).unwrap();
+    std::fs::create_dir(dir.join("not-a-credential")).unwrap();
+    let credentials = SystemdCredentials::new(&dir);
+    assert_eq!(
+        credentials.names().unwrap(),
+        vec!["api-token", "db-password"]
+    );
+    let service = generate_random_string();
+    let store = Store::new().unwrap();
+    let entries = credentials.load_into(&store, &service).unwrap();
+    assert_eq!(entries.len(), 2);
+    let entry = store.build(&service, "db-password", None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "from systemd");
+    let entry = store.build(&service, "api-token", None).unwrap();
+    assert_eq!(entry.get_secret().unwrap(), vec![0u8, 1, 2]);
+    for entry in entries {
+        entry.delete_credential().unwrap();
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(
+        credentials.names(),
+        Err(Error::NoStorageAccess(_))
+    ));
+}
+
+#[derive(Debug, Default)]
+struct RecordingSink(std::sync::Mutex<Vec<String>>);
+
+impl super::AuditSink for RecordingSink {
+    fn record(&self, event: &super::AuditEvent<'_>) {
+        self.0.lock().unwrap().push(event.to_string());
+    }
+}
+
+#[test]
+fn test_audit() {
+    use super::StoreBuilder;
+
+    let sink = Arc::new(RecordingSink::default());
+    let store = StoreBuilder::new().audit(sink.clone()).build().unwrap();
+    let name = generate_random_string();
+    let modifiers = HashMap::from([("audit_context", "request 42")]);
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    let description = format!("\"keyring:{name}@{name}\" (\"request 42\")");
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("audited secret").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "audited secret");
+    entry.delete_credential().unwrap();
+    let events = sink.0.lock().unwrap().clone();
+    assert_eq!(
+        events,
+        vec![
+            format!("get {description}: failed: {}", Error::NoEntry),
+            format!("set {description}: ok"),
+            format!("get {description}: ok"),
+            format!("delete {description}: ok"),
+        ]
+    );
+    assert!(events.iter().all(|event| !event.contains("audited secret")));
+}
+
+#[derive(Debug, Default)]
+struct CountingObserver(std::sync::Mutex<Vec<super::Observation>>);
+
+impl super::MetricsObserver for CountingObserver {
+    fn observe(&self, observation: &super::Observation) {
+        self.0.lock().unwrap().push(*observation);
+    }
+}
+
+#[test]
+fn test_metrics() {
+    use super::{ErrorCategory, Operation, StoreBuilder};
+
+    let observer = Arc::new(CountingObserver::default());
+    let prefix = format!("{}:", generate_random_string());
+    let store = StoreBuilder::new()
+        .prefix(prefix)
+        .metrics(observer.clone())
+        .build()
+        .unwrap();
+    let entry = store.build("service", "user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("twelve bytes").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "twelve bytes");
+    assert_eq!(store.entries().unwrap().len(), 1);
+    entry.delete_credential().unwrap();
+    let observed: Vec<_> = observer
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|o| (o.operation, o.size, o.error))
+        .collect();
+    assert_eq!(
+        observed,
+        vec![
+            (Operation::Get, None, Some(ErrorCategory::NoEntry)),
+            (Operation::Set, Some(12), None),
+            (Operation::Get, Some(12), None),
+            (Operation::Search, Some(1), None),
+            (Operation::Delete, Some(0), None),
+        ]
+    );
+}
+
+#[test]
+fn test_retry_policy() {
+    use super::RetryPolicy;
+    use super::retry::is_transient;
+    use linux_keyutils::KeyError;
+    use std::time::Duration;
+
+    assert_eq!(
+        "3/10".parse::<RetryPolicy>().unwrap(),
+        RetryPolicy::new(3, Duration::from_millis(10))
+    );
+    assert_eq!("1".parse::<RetryPolicy>().unwrap(), RetryPolicy::default());
+    for bad in ["0", "x", "3/", "3/x", "-1"] {
+        assert!(matches!(
+            bad.parse::<RetryPolicy>(),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+    let flaky = |failures: u32| {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            if calls <= failures {
+                Err(KeyError::Unknown(libc::EINTR))
+            } else {
+                Ok(calls)
+            }
+        }
+    };
+    let policy = RetryPolicy::new(3, Duration::from_millis(1));
+    assert_eq!(policy.run(is_transient, flaky(2)), Ok(3));
+    assert_eq!(
+        policy.run(is_transient, flaky(3)),
+        Err(KeyError::Unknown(libc::EINTR))
+    );
+    assert_eq!(
+        RetryPolicy::default().run(is_transient, flaky(1)),
+        Err(KeyError::Unknown(libc::EINTR))
+    );
+    let mut calls = 0;
+    let permanent = policy.run(is_transient, || {
+        calls += 1;
+        Err::<(), _>(KeyError::AccessDenied)
+    });
+    assert_eq!((permanent, calls), (Err(KeyError::AccessDenied), 1));
+    let store = Store::new_with_configuration(&HashMap::from([("retry", "4/2")])).unwrap();
+    assert_eq!(store.retry, RetryPolicy::new(4, Duration::from_millis(2)));
+}
+
+#[test]
+fn test_prune() {
+    use super::StoreBuilder;
+    use std::time::Duration;
+
+    let prefix = format!("{}:", generate_random_string());
+    let store = StoreBuilder::new().prefix(&prefix).build().unwrap();
+    let expiring = StoreBuilder::new()
+        .prefix(&prefix)
+        .timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    let live = store.build("live", "user", None).unwrap();
+    live.set_password("live").unwrap();
+    let revoked = store.build("revoked", "user", None).unwrap();
+    revoked.set_password("revoked").unwrap();
+    let cred = revoked.as_any().downcast_ref::<Cred>().unwrap();
+    cred.keyring
+        .search(&cred.description)
+        .unwrap()
+        .revoke()
+        .unwrap();
+    let expired = expiring.build("expired", "user", None).unwrap();
+    expired.set_password("expired").unwrap();
+    std::thread::sleep(Duration::from_millis(1500));
+    let mut pruned: Vec<(String, bool)> = store
+        .prune()
+        .unwrap()
+        .into_iter()
+        .map(|key| (key.description, key.revoked))
+        .collect();
+    pruned.sort();
+    pruned.dedup();
+    assert_eq!(
+        pruned,
+        vec![
+            (format!("{prefix}user@expired"), false),
+            (format!("{prefix}user@revoked"), true),
+        ]
+    );
+    assert!(store.prune().unwrap().is_empty());
+    assert_eq!(live.get_password().unwrap(), "live");
+    live.delete_credential().unwrap();
+}
+
+#[test]
+fn test_revoke() {
+    use linux_keyutils::KeyError;
+
+    let prefix = format!("{}:", generate_random_string());
+    let store =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    let entry = store.build("revoke", "user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(cred.revoke(), Err(Error::NoEntry)));
+    entry.set_password("doomed").unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    let key_serial = key.get_id();
+    cred.revoke().unwrap();
+    assert_eq!(key.read_to_vec(), Err(KeyError::KeyRevoked));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // the key may be pruned from both its keyring and the persistent keyring
+    let pruned = store.prune().unwrap();
+    assert!(!pruned.is_empty());
+    assert!(
+        pruned
+            .iter()
+            .all(|key| key.revoked && key.serial == key_serial)
+    );
+    entry.set_password("revived").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "revived");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_history() {
+    use super::HistoryPolicy;
+    use std::time::Duration;
+
+    assert_eq!(
+        "5/3600".parse::<HistoryPolicy>().unwrap(),
+        HistoryPolicy::new(5, Duration::from_secs(3600))
+    );
+    assert_eq!("2".parse::<HistoryPolicy>().unwrap().versions, 2);
+    for bad in ["0", "x", "3/", "3/0", "-1"] {
+        assert!(matches!(
+            bad.parse::<HistoryPolicy>(),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    let prefix = format!("{}:", generate_random_string());
+    let config = HashMap::from([("prefix", prefix.as_str()), ("history", "2/60")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    let entry = store.build("history", "user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.versions().unwrap().is_empty());
+    for password in ["one", "two", "three"] {
+        entry.set_password(password).unwrap();
+    }
+    assert_eq!(cred.versions().unwrap(), vec![2, 3]);
+    assert_eq!(cred.get_version(2).unwrap(), b"two");
+    assert_eq!(cred.get_version(3).unwrap(), b"three");
+    assert!(matches!(cred.get_version(1), Err(Error::NoEntry)));
+    // versions aren't credentials of their own
+    assert_eq!(store.entries().unwrap().len(), 1);
+    entry.delete_credential().unwrap();
+    assert_eq!(cred.get_version(3).unwrap(), b"three");
+    entry.set_password("four").unwrap();
+    assert_eq!(cred.versions().unwrap(), vec![3, 4]);
+    entry.delete_credential().unwrap();
+    for number in cred.versions().unwrap() {
+        let description = format!("{}@v{number}", cred.description);
+        cred.keyring
+            .search(&description)
+            .unwrap()
+            .invalidate()
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_generated_secret() {
+    use super::{Charset, SecretSpec};
+
+    let bytes = SecretSpec::Bytes(32).generate().unwrap();
+    assert_eq!(bytes.len(), 32);
+    assert_ne!(*bytes, *SecretSpec::Bytes(32).generate().unwrap());
+    let hex = SecretSpec::Text {
+        length: 40,
+        charset: Charset::Hex,
+    };
+    let text = hex.generate().unwrap();
+    assert_eq!(text.len(), 40);
+    assert!(
+        text.iter()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+    );
+    let custom = SecretSpec::Text {
+        length: 8,
+        charset: Charset::Custom("äö".to_string()),
+    };
+    let text = String::from_utf8(custom.generate().unwrap().to_vec()).unwrap();
+    assert!(text.chars().count() == 8 && text.chars().all(|c| c == 'ä' || c == 'ö'));
+    for bad in [
+        SecretSpec::Bytes(0),
+        SecretSpec::Text {
+            length: 8,
+            charset: Charset::Custom(String::new()),
+        },
+    ] {
+        assert!(matches!(bad.generate(), Err(Error::Invalid(_, _))));
+    }
+
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let spec = SecretSpec::Text {
+        length: 24,
+        charset: Charset::Alphanumeric,
+    };
+    let secret = cred.set_generated_secret(&spec).unwrap();
+    assert!(secret.iter().all(u8::is_ascii_alphanumeric));
+    assert_eq!(entry.get_secret().unwrap(), *secret);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_description_length() {
+    use super::{DescriptionOverflow, StoreBuilder};
+
+    let long = "x".repeat(5000);
+    let store = Store::new().unwrap();
+    match store.build("service", &long, None) {
+        Err(Error::Invalid(attr, reason)) => {
+            assert_eq!(attr, "description");
+            assert!(reason.contains("4095"), "{reason}");
+        }
+        other => panic!("overlong description was not rejected: {other:?}"),
+    }
+    for bad in [0, 4096] {
+        let result = StoreBuilder::new().max_description_len(bad).build();
+        assert!(matches!(result, Err(Error::Invalid(_, _))));
+    }
+    let result = StoreBuilder::new()
+        .max_description_len(60)
+        .description_overflow(DescriptionOverflow::Hash)
+        .build();
+    assert!(matches!(result, Err(Error::Invalid(_, _))));
+
+    let config = HashMap::from([
+        ("suffix", ":end"),
+        ("max_description_len", "100"),
+        ("description_overflow", "hash"),
+    ]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    let name = generate_random_string();
+    let short = store.build(&name, "user", None).unwrap();
+    let short = short.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(*short.description, format!("keyring:user@{name}:end"));
+    let user = format!("{name}-{}", "ü".repeat(100));
+    let entry = store.build("service", &user, None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.description.len() <= 100, "{}", cred.description);
+    assert!(cred.description.starts_with(&format!("keyring:{name}-")));
+    assert!(cred.description.ends_with(":end"));
+    let other = store.build("other", &user, None).unwrap();
+    let other = other.as_any().downcast_ref::<Cred>().unwrap();
+    assert_ne!(cred.description, other.description);
+    entry.set_password("hashed").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "hashed");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_stats() {
+    let prefix = format!("{}:", generate_random_string());
+    let store =
+        Store::new_with_configuration(&HashMap::from([("prefix", prefix.as_str())])).unwrap();
+    let stats = store.stats().unwrap();
+    assert_eq!((stats.keys, stats.bytes), (0, 0));
+    let entries = [
+        store.build("one", "user", None).unwrap(),
+        store.build("two", "user", None).unwrap(),
+    ];
+    entries[0].set_password("12345").unwrap();
+    entries[1].set_password("1234567").unwrap();
+    let stats = store.stats().unwrap();
+    assert_eq!((stats.keys, stats.bytes), (2, 12));
+    assert_eq!(stats.keyrings[0].name, "session");
+    assert_eq!((stats.keyrings[0].keys, stats.keyrings[0].bytes), (2, 12));
+    if let Some(quota) = stats.quota {
+        assert!(quota.keys >= 2);
+        assert_eq!(stats.keys_headroom(), Some(quota.max_keys - quota.keys));
+    }
+    for entry in entries {
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_parse_proc_keys() {
+    use super::procfs::parse_keys;
+
+    let keys = parse_keys(
+        "0005dd98 I--Q---     1 perm 3f010000     0     0 user      app:u@s: 19\n\
+         002ebfdc I--Q---     1 perm 0c030000     0 65534 keyring   .user_reg: 2\n\
+         0034b7a7 IR-Q---     1 expd 3f010000  1000  1000 user      a: b\n\
+         003d0090 I--Q---     1 perm 3f010000     0     0 big_key   big: 50000 [file]\n\
+         016feb81 I--Q---     1 perm 39010000     0     0 asymmetri CA: 82f8a1: X509.rsa 3db7e65a []\n\
+         garbage\n",
+    );
+    let summary: Vec<(i32, &str, &str, &str)> = keys
+        .iter()
+        .map(|key| {
+            (
+                key.serial.as_raw_id(),
+                key.flags.as_str(),
+                key.key_type.as_str(),
+                key.description.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (0x5dd98, "I--Q---", "user", "app:u@s"),
+            (0x2ebfdc, "I--Q---", "keyring", ".user_reg"),
+            (0x34b7a7, "IR-Q---", "user", "a: b"),
+            (0x3d0090, "I--Q---", "big_key", "big"),
+            (0x16feb81, "I--Q---", "asymmetri", "CA: 82f8a1"),
+        ]
+    );
+    let lengths: Vec<Option<usize>> = keys.iter().map(|key| key.payload_len).collect();
+    assert_eq!(lengths, vec![Some(19), None, None, Some(50000), None]);
+    assert_eq!(keys[4].summary.as_deref(), Some("X509.rsa 3db7e65a []"));
+    assert!(keys[2].is_revoked() && keys[2].is_expired());
+    assert!(!keys[0].is_revoked() && keys[0].is_instantiated() && keys[0].in_quota());
+    assert_eq!(keys[1].summary.as_deref(), Some("2"));
+    assert_eq!((keys[1].uid, keys[1].gid), (0, 65534));
+    assert_eq!(keys[1].permissions, super::Perm::from_bits(0x0c030000));
+}
+
+#[test]
+fn test_parse_proc_expiry_and_users() {
+    use super::procfs::{Expiry, parse_key, parse_key_users};
+    use std::time::Duration;
+
+    let expiry = |timeout: &str| {
+        let line = format!("00000001 I--Q---     1 {timeout} 3f010000     0     0 user      a: 1");
+        parse_key(&line).map(|key| key.expiry)
+    };
+    assert_eq!(expiry("perm"), Some(Expiry::Never));
+    assert_eq!(expiry("expd"), Some(Expiry::Expired));
+    assert_eq!(expiry("45s"), Some(Expiry::In(Duration::from_secs(45))));
+    assert_eq!(
+        expiry("59m"),
+        Some(Expiry::In(Duration::from_secs(59 * 60)))
+    );
+    assert_eq!(
+        expiry("2w"),
+        Some(Expiry::In(Duration::from_secs(14 * 24 * 3600)))
+    );
+    assert_eq!(expiry("soon"), None);
+
+    let users = parse_key_users(
+        "    0:    60 59/59 55/1000000 2177/25000000\n\
+         1000:     5 5/5 4/200 101/20000\n\
+         junk\n",
+    );
+    assert_eq!(users.len(), 2);
+    assert_eq!((users[0].uid, users[0].usage, users[0].keys), (0, 60, 59));
+    assert_eq!(users[1].uid, 1000);
+    assert_eq!(users[1].instantiated, 5);
+    assert_eq!(
+        users[1].quota,
+        super::Quota {
+            keys: 4,
+            max_keys: 200,
+            bytes: 101,
+            max_bytes: 20000,
+        }
+    );
+}
+
+#[test]
+fn test_store_builder() {
+    use super::{Perm, StoreBuilder, Target};
+    use linux_keyutils::Permission;
+    use std::time::Duration;
+
+    let store = StoreBuilder::new().build().unwrap();
+    let default = Store::new().unwrap();
+    assert_eq!(store.delimiters, default.delimiters);
+    assert_eq!(store.keyring, default.keyring);
+    assert!(store.timeout.is_none() && store.permissions.is_none());
+    assert!(matches!(
+        StoreBuilder::new().timeout(Duration::ZERO).build(),
+        Err(Error::Invalid(_, _))
+    ));
+    let config = HashMap::from([
+        ("prefix", "app:"),
+        ("keyring", "user"),
+        ("timeout", "60"),
+        ("permissions", "0x3f000000"),
+    ]);
+    let configured = Store::new_with_configuration(&config).unwrap();
+    let built = StoreBuilder::new()
+        .prefix("app:")
+        .keyring(Target::User)
+        .timeout(Duration::from_secs(60))
+        .permissions(Perm::possessor_all())
+        .build()
+        .unwrap();
+    assert_eq!(configured.delimiters, built.delimiters);
+    assert_eq!(configured.keyring, built.keyring);
+    assert_eq!(configured.timeout, built.timeout);
+    assert_eq!(configured.permissions, built.permissions);
+    for (key, value) in [("timeout", "soon"), ("permissions", "rwx")] {
+        let config = HashMap::from([(key, value)]);
+        assert!(matches!(
+            Store::new_with_configuration(&config),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    // keys get the store's permissions and expire after its timeout
+    let perm = Perm::possessor_all().user(Permission::VIEW | Permission::READ);
+    let store = StoreBuilder::new()
+        .timeout(Duration::from_secs(1))
+        .permissions(perm)
+        .build()
+        .unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("short-lived").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    assert_eq!(key.metadata().unwrap().get_perms().bits(), 0x3f03_0000);
+    assert_eq!(entry.get_password().unwrap(), "short-lived");
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_from_config() {
+    use super::{StoreConfig, Target};
+
+    let store = Store::from_config(StoreConfig::default()).unwrap();
+    assert_eq!(store.delimiters, Store::new().unwrap().delimiters);
+    let config = StoreConfig {
+        prefix: Some("app:".to_string()),
+        keyring: Some(Target::Process),
+        timeout: Some(std::time::Duration::from_secs(60)),
+        ..StoreConfig::default()
+    };
+    let store = Store::from_config(config.clone()).unwrap();
+    assert_eq!(store.delimiters[0], "app:");
+    assert_eq!(store.keyring, Target::Process);
+    let options = HashMap::from([
+        ("prefix", "app:"),
+        ("keyring", "process"),
+        ("timeout", "60"),
+    ]);
+    assert_eq!(StoreConfig::from_options(&options).unwrap(), config);
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_from_env() {
+    use super::{StoreConfig, Target};
+
+    let vars = |vars: &[(&str, &str)]| {
+        StoreConfig::from_vars(
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        )
+    };
+    let config = vars(&[
+        ("HOME", "/root"),
+        ("KEYUTILS_STORE_PREFIX", "ops:"),
+        ("KEYUTILS_STORE_KEYRING", "user"),
+        ("KEYUTILS_STORE_SERVICE_NO_DIVIDER", "true"),
+    ])
+    .unwrap();
+    assert_eq!(config.prefix.as_deref(), Some("ops:"));
+    assert_eq!(config.keyring, Some(Target::User));
+    assert_eq!(config.service_no_divider, Some(true));
+    assert_eq!(vars(&[]).unwrap(), StoreConfig::default());
+    assert!(matches!(
+        vars(&[("KEYUTILS_STORE_PREFX", "typo:")]),
+        Err(Error::Invalid(_, _))
+    ));
+    assert!(matches!(
+        vars(&[("KEYUTILS_STORE_KEYRING", "nowhere")]),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_keyring_modifier() {
+    use super::Target;
+
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    for keyring in ["thread", "process", "user", "persistent", "user_session"] {
+        let modifiers = HashMap::from([("keyring", keyring)]);
+        let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+        let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+        assert_eq!(cred.target, keyring.parse::<Target>().unwrap());
+        assert_eq!(cred.target.as_str(), keyring);
+        test_round_trip(keyring, &entry, keyring);
+    }
+    // a per-worker secret stays with its thread
+    let modifiers = HashMap::from([("keyring", "thread")]);
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    entry.set_password("thread secret").unwrap();
+    let worker = store.clone();
+    std::thread::spawn(move || {
+        let other = worker.build(&name, &name, Some(&modifiers)).unwrap();
+        assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+    })
+    .join()
+    .unwrap();
+    entry.delete_credential().unwrap();
+    let modifiers = HashMap::from([("keyring", "nowhere")]);
+    assert!(matches!(
+        store.build("service", "user", Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_key_type_modifier() {
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    // user keys top out at 32767 bytes
+    let entry = store.build(&name, &name, None).unwrap();
+    assert!(matches!(
+        entry.set_secret(&vec![7u8; 32768]),
+        Err(Error::TooLong(_, 32767))
+    ));
+    // logon keys can be written and deleted, but not read
+    let modifiers = HashMap::from([("key_type", "logon")]);
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    entry.set_password("logon secret").unwrap();
+    assert!(matches!(
+        entry.get_password(),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    let entries = store.entries().unwrap();
+    let found = entries.iter().find_map(|e| {
+        let cred = e.as_any().downcast_ref::<Cred>().unwrap();
+        (*cred.description == format!("keyring:{name}@{name}")).then_some(cred.key_type)
+    });
+    assert_eq!(found, Some(linux_keyutils::KeyType::Logon));
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+    // a logon key's description must have a prefix before a colon
+    let modifiers = HashMap::from([("key_type", "logon"), ("description", "no-colon")]);
+    assert!(matches!(
+        store.build("", "", Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+    // big keys can hold more than a read buffer's worth
+    let modifiers = HashMap::from([("key_type", "big_key")]);
+    if store.capabilities().big_key {
+        let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+        let secret: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        entry.set_secret(&secret).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), secret);
+        entry.delete_credential().unwrap();
+    } else {
+        assert!(matches!(
+            store.build(&name, &name, Some(&modifiers)),
+            Err(Error::NotSupportedByStore(_))
+        ));
+    }
+    let modifiers = HashMap::from([("key_type", "keyring")]);
+    assert!(matches!(
+        store.build(&name, &name, Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_owner_modifiers() {
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let modifiers = HashMap::from([("uid", "nobody")]);
+    assert!(matches!(
+        store.build(&name, &name, Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+    let modifiers = HashMap::from([("uid", "4321"), ("gid", "8765")]);
+    if unsafe { libc::geteuid() } != 0 {
+        assert!(matches!(
+            store.build(&name, &name, Some(&modifiers)),
+            Err(Error::Invalid(_, _))
+        ));
+        return;
+    }
+    let entry = store.build(&name, &name, Some(&modifiers)).unwrap();
+    entry.set_password("owned").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    let metadata = key.metadata().unwrap();
+    assert_eq!(metadata.get_uid(), 4321);
+    assert_eq!(metadata.get_gid(), 8765);
+    // possessors keep their access
+    assert_eq!(entry.get_password().unwrap(), "owned");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_restrict_keyring() {
+    use super::StoreBuilder;
+
+    let store = Store::new().unwrap();
+    assert!(matches!(
+        store.restrict_keyring(),
+        Err(Error::Invalid(_, _))
+    ));
+    // each thread has its own thread keyring, so restricting it doesn't affect other tests
+    std::thread::spawn(|| {
+        let store = StoreBuilder::new()
+            .keyring(super::Target::Thread)
+            .build()
+            .unwrap();
+        let name = generate_random_string();
+        let entry = store.build(&name, &name, None).unwrap();
+        entry.set_password("before").unwrap();
+        store.restrict_keyring().unwrap();
+        assert_eq!(entry.get_password().unwrap(), "before");
+        entry.set_password("after").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "after");
+        let other = store.build(&name, "other", None).unwrap();
+        assert!(matches!(
+            other.set_password("planted"),
+            Err(Error::NoStorageAccess(_))
+        ));
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_keyring_user() {
+    use super::{KeyringUser, StoreBuilder, StoreConfig, Target};
+
+    assert_eq!(KeyringUser::Current.other_uid(), None);
+    assert!(matches!(
+        "nobody".parse::<KeyringUser>(),
+        Err(Error::Invalid(_, _))
+    ));
+    let config = StoreConfig::from_options(&HashMap::from([("keyring_user", "invoking")]));
+    assert_eq!(config.unwrap().keyring_user, Some(KeyringUser::Invoking));
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+    // pretend to have been run with sudo by user 4321
+    // (no other test looks at SUDO_UID)
+    unsafe { std::env::set_var("SUDO_UID", "4321") };
+    assert_eq!(super::invoking_uid(), 4321);
+    assert_eq!(KeyringUser::Invoking.other_uid(), Some(4321));
+    assert!(matches!(
+        StoreBuilder::new()
+            .keyring(Target::Process)
+            .keyring_user(KeyringUser::Invoking)
+            .build(),
+        Err(Error::Invalid(_, _))
+    ));
+    if !super::Capabilities::get().persistent_keyrings {
+        return;
+    }
+    let store = StoreBuilder::new()
+        .keyring_user(KeyringUser::Invoking)
+        .build()
+        .unwrap();
+    assert_eq!(store.keyring, Target::Persistent);
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("for the invoking user").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "for the invoking user");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(cred.keyring_uid, Some(4321));
+    // the key is in user 4321's persistent keyring, not ours, and belongs to them
+    let own = Store::new().unwrap().build(&name, &name, None).unwrap();
+    assert!(matches!(own.get_password(), Err(Error::NoEntry)));
+    let entries = store.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    let modifiers = HashMap::from([("keyring", "session")]);
+    assert!(matches!(
+        store.build(&name, &name, Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_new_for_user() {
+    use super::{KeyringUser, Target};
+    use linux_keyutils::Key;
+
+    assert_eq!(
+        "5432".parse::<KeyringUser>().unwrap(),
+        KeyringUser::Uid(5432)
+    );
+    assert_eq!(KeyringUser::Uid(5432).to_string(), "5432");
+    if unsafe { libc::geteuid() } != 0 {
+        assert!(matches!(
+            Store::new_for_user(5432),
+            Err(Error::Invalid(_, _))
+        ));
+        return;
+    }
+    if !super::Capabilities::get().persistent_keyrings {
+        return;
+    }
+    let store = Store::new_for_user(5432).unwrap();
+    assert_eq!(store.keyring, Target::Persistent);
+    assert_eq!(store.keyring_uid, Some(5432));
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("staged").unwrap();
+    // the key is in the user's persistent keyring, and belongs to them
+    let keyring = super::user::persistent_serial(5432).unwrap();
+    let owners: Vec<u32> = super::sys::keyring_links(keyring)
+        .unwrap()
+        .into_iter()
+        .filter_map(|id| Key::from_id(id).metadata().ok())
+        .filter(|metadata| metadata.get_description() == format!("keyring:{name}@{name}"))
+        .map(|metadata| metadata.get_uid())
+        .collect();
+    assert_eq!(owners, vec![5432]);
+    let again = Store::new_for_user(5432).unwrap();
+    let entry2 = again.build(&name, &name, None).unwrap();
+    assert_eq!(entry2.get_password().unwrap(), "staged");
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry2.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_envelope() {
+    use super::{Envelope, StoreBuilder};
+
+    let store = StoreBuilder::new().envelope(true).build().unwrap();
+    let plain = Store::new().unwrap();
+    let name = generate_random_string();
+    let modifiers = HashMap::from([("content_type", "text/plain")]);
+    let entry = store.build(&name, "user", Some(&modifiers)).unwrap();
+    entry.set_password("enveloped").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "enveloped");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(cred.secret_len().unwrap(), "enveloped".len());
+    let mut buffer = [0u8; 32];
+    let len = cred.get_secret_into(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"enveloped");
+    let envelope = cred.get_envelope().unwrap().unwrap();
+    assert_eq!(
+        envelope.specifiers,
+        Some((name.clone(), "user".to_string()))
+    );
+    assert_eq!(envelope.content_type.as_deref(), Some("text/plain"));
+    assert!(envelope.created <= envelope.modified);
+    // stores without envelopes see the whole payload
+    let raw = plain
+        .build(&name, "user", None)
+        .unwrap()
+        .get_secret()
+        .unwrap();
+    assert!(raw.starts_with(b"LKKE"));
+    assert!(raw.ends_with(b"enveloped"));
+    // the previous secret comes out of its envelope, and the content type is kept
+    let previous = cred.swap_secret(b"swapped").unwrap().unwrap();
+    assert_eq!(previous, b"enveloped");
+    let swapped = cred.get_envelope().unwrap().unwrap();
+    assert_eq!(swapped.created, envelope.created);
+    assert_eq!(swapped.content_type.as_deref(), Some("text/plain"));
+    // wrappers built from a description find their specifiers in the envelope
+    let modifiers = HashMap::from([("description", &*cred.description)]);
+    let wrapper = store.build("", "", Some(&modifiers)).unwrap();
+    assert_eq!(
+        wrapper.get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    entry.delete_credential().unwrap();
+    // bare payloads written without an envelope still read
+    let bare = plain.build(&name, "user", None).unwrap();
+    bare.set_password("bare").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "bare");
+    assert_eq!(cred.get_envelope().unwrap(), None);
+    bare.delete_credential().unwrap();
+    // content types need envelopes
+    let modifiers = HashMap::from([("content_type", "text/plain")]);
+    assert!(matches!(
+        plain.build(&name, "user", Some(&modifiers)),
+        Err(Error::Invalid(_, _))
+    ));
+    // truncated envelopes are taken to be bare secrets
+    let sealed = Envelope {
+        created: std::time::UNIX_EPOCH,
+        modified: std::time::UNIX_EPOCH,
+        specifiers: Some(("s".to_string(), "u".to_string())),
+        content_type: None,
+    }
+    .header(false);
+    assert!(Envelope::open(&sealed).is_some());
+    assert!(Envelope::open(&sealed[..20]).is_none());
+}
+
+#[test]
+fn test_envelope_ambiguity() {
+    use super::StoreBuilder;
+
+    let store = StoreBuilder::new().envelope(true).build().unwrap();
+    let name = generate_random_string();
+    // both of these have the description keyring:{name}@a@b
+    let first = store.build("a@b", &name, None).unwrap();
+    let second = store.build("b", &format!("{name}@a"), None).unwrap();
+    first.set_password("first").unwrap();
+    assert!(matches!(second.get_password(), Err(Error::NoEntry)));
+    assert_eq!(first.get_password().unwrap(), "first");
+    first.delete_credential().unwrap();
+}
+
+#[test]
+fn test_integrity() {
+    use super::{Integrity, StoreBuilder, StoreConfig};
+
+    let plain = Store::new().unwrap();
+    let name = generate_random_string();
+    let store = StoreBuilder::new()
+        .integrity(Integrity::Checksum)
+        .build()
+        .unwrap();
+    assert!(store.envelope);
+    let entry = store.build(&name, "one", None).unwrap();
+    entry.set_password("checked").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "checked");
+    // a corrupted payload is reported, not returned
+    let raw = plain.build(&name, "one", None).unwrap();
+    let mut payload = raw.get_secret().unwrap();
+    *payload.last_mut().unwrap() ^= 1;
+    raw.set_secret(&payload).unwrap();
+    assert!(matches!(
+        entry.get_password(),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    // so is a bare payload
+    raw.set_password("untagged").unwrap();
+    assert!(matches!(
+        entry.get_password(),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    entry.delete_credential().unwrap();
+
+    let master = format!("integrity-test-master:{name}");
+    let store = StoreBuilder::new()
+        .integrity(Integrity::Hmac(master.clone()))
+        .build()
+        .unwrap();
+    let one = store.build(&name, "one", None).unwrap();
+    assert!(matches!(
+        one.set_password("unprovisioned"),
+        Err(Error::NoStorageAccess(_))
+    ));
+    store.provision_integrity_key(b"integrity master").unwrap();
+    one.set_password("first").unwrap();
+    let two = store.build(&name, "two", None).unwrap();
+    two.set_password("second").unwrap();
+    assert_eq!(one.get_password().unwrap(), "first");
+    // payloads can't be swapped between keys
+    let swapped = plain
+        .build(&name, "one", None)
+        .unwrap()
+        .get_secret()
+        .unwrap();
+    plain
+        .build(&name, "two", None)
+        .unwrap()
+        .set_secret(&swapped)
+        .unwrap();
+    assert!(matches!(two.get_password(), Err(err) if is_bad_store_format(&err)));
+    one.delete_credential().unwrap();
+    two.delete_credential().unwrap();
+    assert!(matches!(
+        plain.provision_integrity_key(b"material"),
+        Err(Error::Invalid(_, _))
+    ));
+
+    let config = |options: &[(&str, &str)]| {
+        StoreConfig::from_options(&options.iter().copied().collect()).map(|c| c.integrity)
+    };
+    assert_eq!(
+        config(&[("integrity", "checksum")]).unwrap(),
+        Some(Integrity::Checksum)
+    );
+    assert_eq!(
+        config(&[("integrity", "hmac"), ("integrity_key", "k")]).unwrap(),
+        Some(Integrity::Hmac("k".to_string()))
+    );
+    assert!(matches!(
+        config(&[("integrity", "checksum"), ("integrity_key", "k")]),
+        Err(Error::Invalid(_, _))
+    ));
+    assert!(matches!(
+        config(&[("integrity", "crc")]),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+/// A toy cipher that xors payloads with their description, behind a marker.
+#[derive(Debug)]
+struct XorCipher;
+
+impl super::Cipher for XorCipher {
+    fn encrypt(&self, description: &str, plaintext: &[u8]) -> keyring_core::Result<Vec<u8>> {
+        let pad = description.as_bytes().iter().cycle();
+        let mut ciphertext = b"XOR:".to_vec();
+        ciphertext.extend(plaintext.iter().zip(pad).map(|(b, p)| b ^ p));
+        Ok(ciphertext)
+    }
+
+    fn decrypt(&self, description: &str, ciphertext: &[u8]) -> keyring_core::Result<Vec<u8>> {
+        let Some(ciphertext) = ciphertext.strip_prefix(b"XOR:") else {
+            return Err(bad_store_format("not encrypted".to_string()));
+        };
+        let pad = description.as_bytes().iter().cycle();
+        Ok(ciphertext.iter().zip(pad).map(|(b, p)| b ^ p).collect())
+    }
+
+    fn overhead(&self) -> usize {
+        4
+    }
+}
+
+#[test]
+fn test_cipher() {
+    use super::{Integrity, StoreBuilder};
+
+    let plain = Store::new().unwrap();
+    let name = generate_random_string();
+    let store = StoreBuilder::new()
+        .cipher(Arc::new(XorCipher))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("encrypted").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "encrypted");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(cred.secret_len().unwrap(), "encrypted".len());
+    let mut buffer = [0u8; 32];
+    let len = cred.get_secret_into(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"encrypted");
+    // the kernel only sees ciphertext
+    let raw = plain.build(&name, "user", None).unwrap();
+    let ciphertext = raw.get_secret().unwrap();
+    assert!(ciphertext.starts_with(b"XOR:"));
+    assert_ne!(&ciphertext[4..], b"encrypted");
+    // payloads that don't decrypt are reported
+    raw.set_password("plaintext").unwrap();
+    assert!(matches!(
+        entry.get_password(),
+        Err(err) if is_bad_store_format(&err)
+    ));
+    // the cipher's overhead comes off the largest secret
+    assert!(matches!(
+        entry.set_secret(&[1u8; 32767]),
+        Err(Error::TooLong(_, 32763))
+    ));
+    entry.set_secret(&[1u8; 32763]).unwrap();
+    entry.delete_credential().unwrap();
+
+    // envelopes and integrity tags are encrypted along with the secret
+    let store = StoreBuilder::new()
+        .integrity(Integrity::Checksum)
+        .cipher(Arc::new(XorCipher))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("sealed").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "sealed");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let envelope = cred.get_envelope().unwrap().unwrap();
+    assert_eq!(
+        envelope.specifiers,
+        Some((name.clone(), "user".to_string()))
+    );
+    assert!(!raw.get_secret().unwrap().windows(4).any(|w| w == b"LKKE"));
+    assert_eq!(cred.swap_secret(b"swapped").unwrap().unwrap(), b"sealed");
+    assert_eq!(entry.get_password().unwrap(), "swapped");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_hash_descriptions() {
+    use super::{StoreBuilder, StoreConfig};
+
+    let store = StoreBuilder::new()
+        .prefix(format!("hashed-{}:", generate_random_string()))
+        .hash_descriptions(true)
+        .build()
+        .unwrap();
+    assert!(store.envelope);
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("hidden").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "hidden");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let [prefix, _, _] = &store.delimiters;
+    let hash = cred.description.strip_prefix(prefix.as_str()).unwrap();
+    assert_eq!(hash.len(), 64);
+    assert!(!cred.description.contains(&name));
+    // the same specifiers always hash the same way, and different ones don't
+    let again = store.build(&name, "user", None).unwrap();
+    assert_eq!(again.get_password().unwrap(), "hidden");
+    let other = store.build(&name, "other", None).unwrap();
+    assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+    // the specifiers are recovered from the envelope
+    let entries = store.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    // custom descriptions aren't hashed
+    let modifiers = HashMap::from([("description", "custom")]);
+    let custom = store.build(&name, "user", Some(&modifiers)).unwrap();
+    let custom = custom.as_any().downcast_ref::<Cred>().unwrap();
+    assert_eq!(&*custom.description, "custom");
+    entry.delete_credential().unwrap();
+
+    let options = HashMap::from([("hash_descriptions", "true")]);
+    let config = StoreConfig::from_options(&options).unwrap();
+    assert_eq!(config.hash_descriptions, Some(true));
+}
+
+#[test]
+fn test_record_specifiers() {
+    use super::{StoreBuilder, StoreConfig};
+
+    let store = StoreBuilder::new().record_specifiers(true).build().unwrap();
+    assert!(store.envelope);
+    let name = generate_random_string();
+    let description = format!("custom-{name}");
+    let modifiers = HashMap::from([("description", description.as_str())]);
+    let entry = store.build(&name, "user", Some(&modifiers)).unwrap();
+    assert_eq!(
+        entry.get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    entry.set_password("recorded").unwrap();
+    // entries that don't record their own find the recorded ones in the envelope
+    let unrecorded = HashMap::from([
+        ("description", description.as_str()),
+        ("record_specifiers", "false"),
+    ]);
+    let wrapper = store.build("", "", Some(&unrecorded)).unwrap();
+    assert_eq!(wrapper.get_password().unwrap(), "recorded");
+    assert_eq!(
+        wrapper.get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    // a key recorded for other specifiers is treated as missing
+    let other = store.build(&name, "other", Some(&modifiers)).unwrap();
+    assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+    entry.delete_credential().unwrap();
+    // recording needs envelopes
+    let plain = Store::new().unwrap();
+    let recorded = HashMap::from([
+        ("description", description.as_str()),
+        ("record_specifiers", "true"),
+    ]);
+    assert!(matches!(
+        plain.build(&name, "user", Some(&recorded)),
+        Err(Error::Invalid(_, _))
+    ));
+    // listed entries whose descriptions don't parse read the specifiers recorded for them
+    let prefix = format!("{name}:");
+    let hashed = StoreBuilder::new()
+        .prefix(&prefix)
+        .hash_descriptions(true)
+        .record_specifiers(true)
+        .build()
+        .unwrap();
+    let entry = hashed.build(&name, "user", None).unwrap();
+    entry.set_password("hashed").unwrap();
+    let listed = hashed.entries().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(
+        listed[0].get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    assert_eq!(listed[0].get_password().unwrap(), "hashed");
+    entry.delete_credential().unwrap();
+
+    let options = HashMap::from([("record_specifiers", "true")]);
+    let config = StoreConfig::from_options(&options).unwrap();
+    assert_eq!(config.record_specifiers, Some(true));
+}
+
+#[test]
+fn test_mirrors() {
+    use super::{Mirror, StoreBuilder, StoreConfig, Target, sys};
+    use linux_keyutils::KeyRingIdentifier;
+
+    let name = generate_random_string();
+    let app = Mirror::Named(format!("test-mirror-{name}"));
+    let store = StoreBuilder::new()
+        .mirrors([Mirror::Target(Target::Process), app.clone()])
+        .build()
+        .unwrap();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("mirrored").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap().get_id();
+    let process = Target::Process.serial().unwrap();
+    let named = app.serial().unwrap();
+    assert!(sys::keyring_links(process).unwrap().contains(&key));
+    assert!(sys::keyring_links(named).unwrap().contains(&key));
+    // the named keyring lives in the user keyring, and isn't created twice
+    let user = sys::keyring_serial(KeyRingIdentifier::User, false).unwrap();
+    assert!(sys::keyring_links(user).unwrap().contains(&named));
+    assert_eq!(app.serial().unwrap(), named);
+    // a key missing from the store's keyrings is found in a mirror, and re-linked
+    let session = Target::Session.serial().unwrap();
+    sys::unlink(key, session).unwrap();
+    if let Some(persistent) = cred.persistent {
+        persistent
+            .unlink_key(linux_keyutils::Key::from_id(key))
+            .unwrap();
+    }
+    assert_eq!(entry.get_password().unwrap(), "mirrored");
+    assert!(sys::keyring_links(session).unwrap().contains(&key));
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    sys::unlink(named, user).unwrap();
+
+    let options = HashMap::from([("mirrors", "user, %:my-app")]);
+    assert_eq!(
+        StoreConfig::from_options(&options).unwrap().mirrors,
+        Some(vec![
+            Mirror::Target(Target::User),
+            Mirror::Named("my-app".to_string())
+        ])
+    );
+    assert_eq!(Mirror::Named("my-app".to_string()).to_string(), "%:my-app");
+    for bad in ["%:", "bogus"] {
+        let options = HashMap::from([("mirrors", bad)]);
+        assert!(matches!(
+            StoreConfig::from_options(&options),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+}
+
+#[test]
+fn test_cached_store() {
+    use super::{CachedCred, CachedStore, StoreBuilder};
+
+    let backing = keyring_core::mock::Store::new().unwrap();
+    let cache = StoreBuilder::new()
+        .prefix(format!("cache-{}:", generate_random_string()))
+        .build()
+        .unwrap();
+    let store = CachedStore::new(cache.clone(), backing.clone());
+    assert!(store.vendor().contains(&backing.vendor()));
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    let direct = backing.build(&name, "user", None).unwrap();
+    let cached = cache.build(&name, "user", None).unwrap();
+    // misses fall through and populate the cache
+    direct.set_password("slow").unwrap();
+    assert!(matches!(cached.get_password(), Err(Error::NoEntry)));
+    assert_eq!(entry.get_password().unwrap(), "slow");
+    assert_eq!(cached.get_password().unwrap(), "slow");
+    // hits are served from the cache
+    direct.set_password("changed behind the cache").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "slow");
+    let cred = entry.as_any().downcast_ref::<CachedCred>().unwrap();
+    cred.evict().unwrap();
+    cred.evict().unwrap();
+    assert_eq!(entry.get_password().unwrap(), "changed behind the cache");
+    // writes go to both
+    entry.set_password("fast").unwrap();
+    assert_eq!(direct.get_password().unwrap(), "fast");
+    assert_eq!(cached.get_password().unwrap(), "fast");
+    assert_eq!(
+        entry.get_specifiers(),
+        Some((name.clone(), "user".to_string()))
+    );
+    // and so do deletes
+    entry.delete_credential().unwrap();
+    assert!(matches!(direct.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(cached.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_fallback_store() {
+    use super::{FallbackCred, FallbackPolicy, FallbackStore, Integrity, StoreBuilder};
+
+    let name = generate_random_string();
+    let primary = Store::new().unwrap();
+    primary.check_available().unwrap();
+    // keyutils is available, so the keyutils store is used
+    let secondary = keyring_core::mock::Store::new().unwrap();
+    let store = FallbackStore::new(primary.clone(), secondary.clone(), Default::default());
+    assert!(!store.fell_back());
+    let entry = store.build(&name, "user", None).unwrap();
+    assert!(entry.as_any().downcast_ref::<Cred>().is_some());
+    // when it isn't, the secondary store is used
+    let store = FallbackStore::new_internal(
+        primary,
+        secondary.clone(),
+        FallbackPolicy::Unavailable,
+        false,
+    );
+    assert!(store.fell_back());
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("secondary").unwrap();
+    let direct = secondary.build(&name, "user", None).unwrap();
+    assert_eq!(direct.get_password().unwrap(), "secondary");
+    direct.delete_credential().unwrap();
+
+    // with storage errors falling back, operations fall back one by one
+    let primary = StoreBuilder::new()
+        .integrity(Integrity::Hmac(format!("missing-master:{name}")))
+        .build()
+        .unwrap();
+    let store = FallbackStore::new(
+        primary.clone(),
+        secondary.clone(),
+        FallbackPolicy::StorageErrors,
+    );
+    let entry = store.build(&name, "user", None).unwrap();
+    assert!(entry.as_any().downcast_ref::<FallbackCred>().is_some());
+    entry.set_password("no master key").unwrap();
+    assert_eq!(direct.get_password().unwrap(), "no master key");
+    assert_eq!(entry.get_password().unwrap(), "no master key");
+    // other errors don't fall back
+    assert!(matches!(entry.set_password(""), Err(Error::Invalid(_, _))));
+    entry.delete_credential().unwrap();
+    assert!(matches!(direct.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+}
+
+#[cfg(feature = "mock")]
+#[test]
+fn test_mock_store() {
+    use super::{MockCred, MockStore};
+
+    let config = HashMap::from([("prefix", "app:"), ("service_no_divider", "true")]);
+    let store = MockStore::new_with_configuration(&config).unwrap();
+    let entry = store.build("service", "user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("mocked").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "mocked");
+    assert_eq!(store.descriptions(), vec!["app:user@service".to_string()]);
+    let cred = entry.as_any().downcast_ref::<MockCred>().unwrap();
+    assert_eq!(
+        cred.specifiers,
+        Some(("service".to_string(), "user".to_string()))
+    );
+    // the same description is the same credential, as in keyutils
+    let modifiers = HashMap::from([("description", "app:user@service")]);
+    let wrapper = store.build("", "", Some(&modifiers)).unwrap();
+    assert_eq!(wrapper.get_password().unwrap(), "mocked");
+    assert!(matches!(
+        store.build("ser@vice", "user", None),
+        Err(Error::Invalid(_, _))
+    ));
+    assert!(matches!(entry.set_password(""), Err(Error::Invalid(_, _))));
+    assert!(matches!(
+        entry.set_secret(&[0u8; 32768]),
+        Err(Error::TooLong(_, 32767))
+    ));
+    let modifiers = HashMap::from([("create_new", "true")]);
+    let create = store.build("service", "user", Some(&modifiers)).unwrap();
+    assert!(matches!(
+        create.set_password("x"),
+        Err(Error::Invalid(_, _))
+    ));
+    entry.delete_credential().unwrap();
+    assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+    let modifiers = HashMap::from([("update_only", "true")]);
+    let update = store.build("service", "user", Some(&modifiers)).unwrap();
+    assert!(matches!(update.set_password("x"), Err(Error::NoEntry)));
+    // other stores have their own secrets
+    let other = MockStore::new().unwrap();
+    assert!(other.descriptions().is_empty());
+}
+
+#[test]
+fn test_fake_keyctl() {
+    use super::{FakeKeyctl, KeyctlOp, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new().keyctl(keyctl.clone()).build().unwrap();
+    let entry = store.build("fake-service", "fake-user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // the credential's operations all go to the fake, not the kernel
+    test_round_trip_no_delete("fake keyctl", &entry, "test password");
+    assert_eq!(keyctl.len(), 1);
+    let kernel = Store::new().unwrap();
+    let real = kernel.build("fake-service", "fake-user", None).unwrap();
+    assert!(matches!(real.get_password(), Err(Error::NoEntry)));
+    // a full quota
+    keyctl.fail(KeyctlOp::Add, KeyError::QuotaExceeded);
+    assert!(matches!(
+        entry.set_password("new password"),
+        Err(Error::PlatformFailure(_))
+    ));
+    keyctl.recover(KeyctlOp::Add);
+    // a key the caller may not read
+    keyctl.fail(KeyctlOp::Read, KeyError::AccessDenied);
+    assert!(matches!(
+        entry.get_password(),
+        Err(Error::NoStorageAccess(_))
+    ));
+    keyctl.recover(KeyctlOp::Read);
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    // a revoked key
+    assert!(keyctl.revoke("keyring:fake-user@fake-service"));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // which a write replaces
+    test_round_trip("fake keyctl after revocation", &entry, "test password");
+    assert_eq!(keyctl.len(), 1);
+}
+
+#[test]
+fn test_fake_keyctl_attributes() {
+    use super::{FakeKeyctl, Keyctl, OwnerMismatch, Perm, StoreBuilder};
+    use linux_keyutils::Permission;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .permissions(Perm::possessor_all())
+        .verify_owner(true)
+        .build()
+        .unwrap();
+    let entry = store.build("fake-service", "fake-user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(cred.revoke(), Err(Error::NoEntry)));
+    // the key gets the store's permissions in the fake, and is verified there
+    entry.set_password("attributes").unwrap();
+    let serial = cred.pin().unwrap().serial.unwrap();
+    assert_eq!(keyctl.describe(serial).unwrap().perm, Perm::possessor_all());
+    assert_eq!(entry.get_password().unwrap(), "attributes");
+    let planted = Perm::possessor_all().world(Permission::VIEW | Permission::READ);
+    keyctl.set_perm(serial, planted).unwrap();
+    match entry.get_password() {
+        Err(Error::NoStorageAccess(err)) => {
+            let err = err.downcast_ref::<OwnerMismatch>().unwrap();
+            assert_eq!(err.permissions, planted.bits());
+        }
+        other => panic!("expected an owner mismatch, got {other:?}"),
+    }
+    entry.set_password("attributes").unwrap();
+    assert_eq!(keyctl.describe(serial).unwrap().perm, Perm::possessor_all());
+    // owners are given to the key in the fake too
+    if unsafe { libc::geteuid() } == 0 {
+        let modifiers = HashMap::from([("uid", "4321"), ("gid", "8765")]);
+        let owned = store
+            .build("fake-service", "owned", Some(&modifiers))
+            .unwrap();
+        owned.set_password("owned").unwrap();
+        let cred = owned.as_any().downcast_ref::<Cred>().unwrap();
+        let described = keyctl
+            .describe(cred.pin().unwrap().serial.unwrap())
+            .unwrap();
+        assert_eq!((described.uid, described.gid), (4321, 8765));
+        owned.delete_credential().unwrap();
+    }
+    // revoking the key revokes it in the fake
+    cred.revoke().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(cred.revoke(), Err(Error::NoEntry)));
+    test_round_trip("fake keyctl after revoke", &entry, "attributes");
+}
+
+#[test]
+fn test_fake_keyctl_history_and_aliases() {
+    use super::{FakeKeyctl, HistoryPolicy, StoreBuilder, Target};
+    use std::time::Duration;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .history(HistoryPolicy::new(2, Duration::from_secs(60)))
+        .build()
+        .unwrap();
+    let entry = store.build("fake-service", "new", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.versions().unwrap().is_empty());
+    for password in ["one", "two", "three"] {
+        entry.set_password(password).unwrap();
+    }
+    // the versions are listed from the fake's keyring
+    assert_eq!(cred.versions().unwrap(), vec![2, 3]);
+    assert_eq!(cred.get_version(2).unwrap(), b"two");
+    assert!(matches!(cred.get_version(1), Err(Error::NoEntry)));
+    // aliases are followed and removed in the fake
+    let old = "keyring:old@fake-service";
+    cred.add_alias(old).unwrap();
+    let aliased = store.build("fake-service", "old", None).unwrap();
+    assert_eq!(aliased.get_password().unwrap(), "three");
+    cred.remove_alias(old).unwrap();
+    assert!(matches!(aliased.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(cred.remove_alias(old), Err(Error::NoEntry)));
+    // and keys are moved between its keyrings
+    let moved = cred.move_to(Target::Process).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert_eq!(moved.get_password().unwrap(), "three");
+    moved.delete_credential().unwrap();
+}
+
+#[test]
+fn test_error_context() {
+    use super::{ErrorContext, FakeKeyctl, KeyctlOp, Redaction, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new().keyctl(keyctl.clone()).build().unwrap();
+    let entry = store
+        .build("context-service", "context-user", None)
+        .unwrap();
+    entry.set_password("test password").unwrap();
+    let context = |err: Error| match err {
+        Error::PlatformFailure(err) | Error::NoStorageAccess(err) => {
+            err.downcast_ref::<ErrorContext>().cloned()
+        }
+        _ => None,
+    };
+    // a read that fails says it was the read, and of which key
+    keyctl.fail(KeyctlOp::Read, KeyError::Unknown(libc::EIO));
+    let err = context(entry.get_password().unwrap_err()).unwrap();
+    assert_eq!(err.operation, "get");
+    assert_eq!(err.description, "keyring:context-user@context-service");
+    assert_eq!(err.call, Some(KeyctlOp::Read));
+    assert!(err.serial.is_some());
+    assert!(err.keyring.is_some());
+    assert_eq!(err.error, KeyError::Unknown(libc::EIO));
+    assert!(err.to_string().contains("in keyctl read on key"));
+    keyctl.recover(KeyctlOp::Read);
+    // a failed add has no key yet
+    keyctl.fail(KeyctlOp::Add, KeyError::QuotaExceeded);
+    let err = context(entry.set_password("new password").unwrap_err()).unwrap();
+    assert_eq!(err.operation, "set");
+    assert_eq!(err.call, Some(KeyctlOp::Add));
+    assert_eq!(err.serial, None);
+    keyctl.recover(KeyctlOp::Add);
+    // errors that aren't the kernel's are left as they are
+    assert!(matches!(entry.set_password(""), Err(Error::Invalid(_, _))));
+    // descriptions are redacted as debug output is
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .redaction(Redaction::Suppressed)
+        .build()
+        .unwrap();
+    let entry = store
+        .build("context-service", "context-user", None)
+        .unwrap();
+    keyctl.fail(KeyctlOp::Read, KeyError::AccessDenied);
+    let err = context(entry.get_password().unwrap_err()).unwrap();
+    assert_eq!(err.description, "<redacted>");
+    assert!(!err.to_string().contains("context-user"));
+    keyctl.recover(KeyctlOp::Read);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_traced_calls() {
+    use super::{ErrorContext, FailureKind, FakeKeyctl, KeyctlOp, StoreBuilder};
+    use linux_keyutils::{KeyError, KeySerialId};
+    use std::time::Duration;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .timeout(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let entry = store.build("traced-service", "traced-user", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let call = |err: Error| {
+        let context = ErrorContext::of(&err).unwrap();
+        (context.call, context.kind)
+    };
+    keyctl.fail(KeyctlOp::SetTimeout, KeyError::AccessDenied);
+    let err = entry.set_password("test password").unwrap_err();
+    assert_eq!(
+        call(err),
+        (Some(KeyctlOp::SetTimeout), FailureKind::KeyAccessDenied)
+    );
+    keyctl.recover(KeyctlOp::SetTimeout);
+    entry.set_password("test password").unwrap();
+    keyctl.fail(KeyctlOp::Update, KeyError::AccessDenied);
+    let err = cred.swap_secret(b"swapped").unwrap_err();
+    assert_eq!(
+        call(err),
+        (Some(KeyctlOp::Update), FailureKind::KeyAccessDenied)
+    );
+    keyctl.recover(KeyctlOp::Update);
+    assert_eq!(
+        cred.swap_secret(b"swapped").unwrap().unwrap(),
+        b"test password"
+    );
+    // the fake takes any serial for a keyring
+    let shared = KeySerialId::new(1_000_000);
+    cred.link_to(shared).unwrap();
+    keyctl.fail(KeyctlOp::Unlink, KeyError::PermissionDenied);
+    assert!(matches!(
+        cred.unlink_from(shared),
+        Err(Error::NoStorageAccess(_))
+    ));
+    keyctl.recover(KeyctlOp::Unlink);
+    cred.unlink_from(shared).unwrap();
+    assert!(matches!(cred.unlink_from(shared), Err(Error::NoEntry)));
+    entry.delete_credential().unwrap();
+    assert!(keyctl.is_empty());
+}
+
+#[test]
+fn test_failure_kinds() {
+    use super::{DmCryptKey, ErrorContext, FailureKind, FakeKeyctl, KeyctlOp, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new().keyctl(keyctl.clone()).build().unwrap();
+    let entry = store.build("kind-service", "kind-user", None).unwrap();
+    let kind = |err: Error| ErrorContext::of(&err).map(|context| context.kind);
+    keyctl.fail(KeyctlOp::Add, KeyError::QuotaExceeded);
+    let err = entry.set_password("test password").unwrap_err();
+    assert!(matches!(err, Error::PlatformFailure(_)));
+    assert_eq!(kind(err), Some(FailureKind::QuotaExceeded));
+    keyctl.fail(KeyctlOp::Add, KeyError::Unknown(libc::E2BIG));
+    let err = entry.set_password("test password").unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::PayloadTooLarge));
+    keyctl.fail(KeyctlOp::Add, KeyError::Unknown(libc::ENODEV));
+    let err = entry.set_password("test password").unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::UnknownKeyType));
+    // a missing keyring isn't a missing credential
+    keyctl.fail(KeyctlOp::Add, KeyError::KeyDoesNotExist);
+    let err = entry.set_password("test password").unwrap_err();
+    assert!(matches!(err, Error::NoStorageAccess(_)));
+    assert_eq!(kind(err), Some(FailureKind::MissingKeyring));
+    keyctl.recover(KeyctlOp::Add);
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // access is denied to the keyring, or just to the key: searches and
+    // adds are refused for either, so the keyring is probed
+    keyctl.fail(KeyctlOp::Search, KeyError::AccessDenied);
+    let err = entry.get_password().unwrap_err();
+    assert!(matches!(err, Error::NoStorageAccess(_)));
+    assert_eq!(kind(err), Some(FailureKind::KeyAccessDenied));
+    keyctl.fail(KeyctlOp::Describe, KeyError::AccessDenied);
+    let err = entry.get_password().unwrap_err();
+    assert!(matches!(err, Error::NoStorageAccess(_)));
+    assert_eq!(kind(err), Some(FailureKind::KeyringAccessDenied));
+    keyctl.recover(KeyctlOp::Search);
+    keyctl.fail(KeyctlOp::Add, KeyError::PermissionDenied);
+    let err = entry.set_password("test password").unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::KeyringAccessDenied));
+    keyctl.recover(KeyctlOp::Describe);
+    let err = entry.set_password("test password").unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::KeyAccessDenied));
+    keyctl.recover(KeyctlOp::Add);
+    entry.set_password("test password").unwrap();
+    keyctl.fail(KeyctlOp::Read, KeyError::AccessDenied);
+    let err = entry.get_password().unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::KeyAccessDenied));
+    keyctl.recover(KeyctlOp::Read);
+    // a link could be refused by either, so the keyring is probed
+    keyctl.fail(KeyctlOp::Link, KeyError::PermissionDenied);
+    let err = entry.get_password().unwrap_err();
+    assert_eq!(kind(err), Some(FailureKind::KeyAccessDenied));
+    keyctl.recover(KeyctlOp::Link);
+    entry.delete_credential().unwrap();
+    // a payload too large for its key type isn't blamed on the password
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    assert!(matches!(
+        DmCryptKey::provision(&store, &name, &[0x42; 40000]),
+        Err(Error::TooLong(field, 32767)) if field == "payload"
+    ));
+}
+
+#[test]
+fn test_lsm_hints() {
+    use super::{ErrorContext, FakeKeyctl, KeyctlOp, LsmHint, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let root = std::env::temp_dir().join(format!("keyring-lsm-{}", generate_random_string()));
+    let write = |path: &str, contents: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    assert_eq!(LsmHint::detect_in(&root), None);
+    write("sys/module/apparmor/parameters/enabled", "Y\n");
+    write("proc/self/attr/apparmor/current", "unconfined\n");
+    assert_eq!(LsmHint::detect_in(&root), None);
+    write("proc/self/attr/apparmor/current", "my-service (enforce)\n");
+    let hint = LsmHint::detect_in(&root).unwrap();
+    assert_eq!(hint.module, "AppArmor");
+    assert_eq!(hint.context.as_deref(), Some("my-service (enforce)"));
+    write("sys/fs/selinux/enforce", "1");
+    write("proc/self/attr/current", "system_u:system_r:my_t:s0\0");
+    let hint = LsmHint::detect_in(&root).unwrap();
+    assert_eq!(hint.module, "SELinux");
+    assert_eq!(hint.context.as_deref(), Some("system_u:system_r:my_t:s0"));
+    assert!(hint.to_string().contains("my_t"));
+    // permissive SELinux doesn't refuse anything
+    write("sys/fs/selinux/enforce", "0");
+    assert_eq!(LsmHint::detect_in(&root).unwrap().module, "AppArmor");
+    std::fs::remove_dir_all(&root).unwrap();
+    // access errors carry whatever is enforcing here
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .lsm_hints(true)
+        .build()
+        .unwrap();
+    let entry = store.build("lsm-service", "lsm-user", None).unwrap();
+    entry.set_password("test password").unwrap();
+    keyctl.fail(KeyctlOp::Read, KeyError::AccessDenied);
+    let Err(Error::NoStorageAccess(err)) = entry.get_password() else {
+        panic!("access wasn't denied");
+    };
+    let context = err.downcast_ref::<ErrorContext>().unwrap();
+    assert_eq!(context.lsm, LsmHint::detect());
+    keyctl.recover(KeyctlOp::Read);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_persistent_policy() {
+    use super::{FakeKeyctl, KeyctlOp, PersistentPolicy, StoreBuilder, StoreConfig, Target};
+    use linux_keyutils::KeyError;
+
+    let config = HashMap::from([("persistent", "require")]);
+    assert_eq!(
+        StoreConfig::from_options(&config).unwrap().persistent,
+        Some(PersistentPolicy::Require)
+    );
+    let config = HashMap::from([("persistent", "sometimes")]);
+    assert!(matches!(
+        StoreConfig::from_options(&config),
+        Err(Error::Invalid(option, _)) if option == "persistent"
+    ));
+    // process keys are never linked into the persistent keyring
+    assert!(matches!(
+        StoreBuilder::new()
+            .keyring(Target::Process)
+            .persistent(PersistentPolicy::Require)
+            .build(),
+        Err(Error::Invalid(option, _)) if option == "persistent"
+    ));
+    // a disabled persistent keyring is never linked to
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .persistent(PersistentPolicy::Disabled)
+        .build()
+        .unwrap();
+    let entry = store
+        .build("persistent-service", "persistent-user", None)
+        .unwrap();
+    let cred: &Cred = entry.as_any().downcast_ref().unwrap();
+    assert!(cred.persistent.is_none());
+    keyctl.fail(KeyctlOp::Link, KeyError::PermissionDenied);
+    entry.set_password("test password").unwrap();
+    keyctl.recover(KeyctlOp::Link);
+    entry.delete_credential().unwrap();
+    if !Target::Session.links_persistent() {
+        return;
+    }
+    // a failed persistent link only fails the write if the keyring is required
+    for (policy, fails) in [
+        (PersistentPolicy::BestEffort, false),
+        (PersistentPolicy::Require, true),
+    ] {
+        let keyctl = Arc::new(FakeKeyctl::new());
+        let store = StoreBuilder::new()
+            .keyctl(keyctl.clone())
+            .persistent(policy)
+            .build()
+            .unwrap();
+        let entry = store
+            .build("persistent-service", "persistent-user", None)
+            .unwrap();
+        keyctl.fail(KeyctlOp::Link, KeyError::PermissionDenied);
+        assert_eq!(entry.set_password("test password").is_err(), fails);
+        keyctl.recover(KeyctlOp::Link);
+        entry.set_password("test password").unwrap();
+        entry.delete_credential().unwrap();
+    }
+}
+
+#[test]
+fn test_lazy_keyrings() {
+    use super::{StoreBuilder, StoreConfig, Target};
+
+    let config = HashMap::from([("lazy_keyrings", "true")]);
+    let lazy = Store::from_config(StoreConfig::from_options(&config).unwrap()).unwrap();
+    assert!(lazy.lazy_keyrings);
+    assert!(lazy.keyrings.is_none());
+    let eager = StoreBuilder::new().build().unwrap();
+    assert!(eager.keyrings.is_some());
+    let name = generate_random_string();
+    let entry = eager.build(&name, "user", None).unwrap();
+    entry.set_password("test password").unwrap();
+    // both find the same key in the same keyring
+    let other = lazy.build(&name, "user", None).unwrap();
+    assert_eq!(other.get_password().unwrap(), "test password");
+    // an entry in another keyring resolves that keyring itself
+    let process = eager
+        .build(
+            &name,
+            "user",
+            Some(&HashMap::from([("keyring", "process")])),
+        )
+        .unwrap();
+    let cred: &Cred = process.as_any().downcast_ref().unwrap();
+    assert_eq!(cred.target, Target::Process);
+    assert!(matches!(process.get_password(), Err(Error::NoEntry)));
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_shared_keyrings() {
+    use super::{PersistentPolicy, StoreBuilder};
+
+    let store = StoreBuilder::new().build().unwrap();
+    let keyrings = store.keyrings.clone().unwrap();
+    let before = Arc::strong_count(&keyrings);
+    let name = generate_random_string();
+    let first = store.build(&name, "user", None).unwrap();
+    let second = store.build(&name, "other", None).unwrap();
+    assert_eq!(Arc::strong_count(&keyrings), before + 2);
+    first.set_password("test password").unwrap();
+    second.set_password("test password").unwrap();
+    drop(second);
+    assert_eq!(Arc::strong_count(&keyrings), before + 1);
+    first.delete_credential().unwrap();
+    store
+        .build(&name, "other", None)
+        .unwrap()
+        .delete_credential()
+        .unwrap();
+    // a store that disables the persistent keyring shares keyrings without it
+    let store = StoreBuilder::new()
+        .persistent(PersistentPolicy::Disabled)
+        .build()
+        .unwrap();
+    let keyrings = store.keyrings.clone().unwrap();
+    let entry = store.build(&name, "user", None).unwrap();
+    let cred: &Cred = entry.as_any().downcast_ref().unwrap();
+    assert!(cred.persistent.is_none());
+    assert_eq!(Arc::strong_count(&keyrings), 3);
+}
+
+#[test]
+fn test_cred_ref() {
+    use super::{CredRef, StoreBuilder, Target};
+
+    let store = StoreBuilder::new().envelope(true).build().unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("test password").unwrap();
+    let cred: &Cred = entry.as_any().downcast_ref().unwrap();
+    let cred_ref: CredRef = cred.to_ref();
+    assert_eq!(cred_ref.keyring, Target::Session);
+    assert_eq!(cred_ref.codec.key_type, "user");
+    assert!(cred_ref.codec.envelope);
+    #[cfg(feature = "serde")]
+    let cred_ref: CredRef =
+        serde_json::from_str(&serde_json::to_string(&cred_ref).unwrap()).unwrap();
+    // a store configured the same way (even in another process) gets the same entry back
+    let again = StoreBuilder::new().envelope(true).build().unwrap();
+    let restored = again.entry_from_ref(&cred_ref).unwrap();
+    assert_eq!(restored.get_password().unwrap(), "test password");
+    assert_eq!(restored.get_specifiers(), entry.get_specifiers());
+    // one that encodes payloads differently couldn't read it
+    assert!(matches!(
+        Store::new().unwrap().entry_from_ref(&cred_ref),
+        Err(Error::Invalid(field, _)) if field == "codec"
+    ));
+    restored.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_delete_credential_sync() {
+    use std::time::Duration;
+
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    let cred: &Cred = entry.as_any().downcast_ref().unwrap();
+    assert!(matches!(
+        cred.delete_credential_sync(Duration::from_secs(1)),
+        Err(Error::NoEntry)
+    ));
+    entry.set_password("test password").unwrap();
+    assert!(cred.delete_credential_sync(Duration::from_secs(1)).unwrap());
+    assert!(cred.keyring.search(&cred.description).is_err());
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_negative_cache() {
+    use super::{FakeKeyctl, KeyctlOp, StoreBuilder, StoreConfig, Target};
+    use linux_keyutils::KeyError;
+    use std::time::Duration;
+
+    let keyctl = Arc::new(FakeKeyctl::new());
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .negative_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let other = StoreBuilder::new().keyctl(keyctl.clone()).build().unwrap();
+    let entry = store.build("optional", "user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // the miss is answered without searching
+    keyctl.fail(KeyctlOp::Search, KeyError::AccessDenied);
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    keyctl.recover(KeyctlOp::Search);
+    // a key added behind the store's back isn't seen before the TTL runs out
+    let added = other.build("optional", "user", None).unwrap();
+    added.set_password("test password").unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // but any write through the store forgets its misses
+    let written = store.build("another", "user", None).unwrap();
+    written.set_password("test password").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    // misses expire
+    let store = StoreBuilder::new()
+        .keyctl(keyctl.clone())
+        .negative_cache(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let entry = store.build("late", "user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    let added = other.build("late", "user", None).unwrap();
+    added.set_password("test password").unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(entry.get_password().unwrap(), "test password");
+
+    // a miss in one thread's thread keyring says nothing about another's
+    let store = StoreBuilder::new()
+        .keyring(Target::Thread)
+        .negative_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let entry = store.build("per-thread", "user", None).unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    std::thread::spawn(move || {
+        let other = StoreBuilder::new().keyring(Target::Thread).build().unwrap();
+        let added = other.build("per-thread", "user", None).unwrap();
+        added.set_password("test password").unwrap();
+        let entry = store.build("per-thread", "user", None).unwrap();
+        assert_eq!(entry.get_password().unwrap(), "test password");
+    })
+    .join()
+    .unwrap();
+
+    let config = HashMap::from([("negative_cache", "250")]);
+    assert_eq!(
+        StoreConfig::from_options(&config).unwrap().negative_cache,
+        Some(Duration::from_millis(250))
+    );
+    let config = HashMap::from([("negative_cache", "soon")]);
+    assert!(matches!(
+        StoreConfig::from_options(&config),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_read_cache() {
+    use super::{OwnerMismatch, Perm, StoreBuilder, StoreConfig, Target};
+    use linux_keyutils::{KeyPermissions, Permission};
+    use std::time::Duration;
+
+    // with key notifications, changes by others are seen at once
+    #[cfg(feature = "watch")]
+    let watching = super::Watcher::new().is_ok();
+    #[cfg(not(feature = "watch"))]
+    let watching = false;
+    let store = StoreBuilder::new()
+        .read_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    // a cache that can't watch keys is a problem worth reporting
+    let reported = store
+        .diagnose()
+        .problems
+        .iter()
+        .any(|problem| problem.contains("read cache"));
+    assert_eq!(reported, !watching);
+    let other = Store::new().unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    let outside = other.build(&name, "user", None).unwrap();
+    entry.set_password("first").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "first");
+    // an update in place by others is only seen if the key is watched
+    outside.set_password("second").unwrap();
+    let expected = if watching { "second" } else { "first" };
+    assert_eq!(entry.get_password().unwrap(), expected);
+    // writes through the store empty the cache
+    entry.set_password("third").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "third");
+    // the key is still searched for, so a deleted one is missing
+    outside.delete_credential().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    // cached secrets expire
+    let store = StoreBuilder::new()
+        .read_cache(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("first").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "first");
+    outside.set_password("second").unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(entry.get_password().unwrap(), "second");
+    entry.delete_credential().unwrap();
+
+    // a key recorded for other specifiers is missing, cached or not
+    let store = StoreBuilder::new()
+        .record_specifiers(true)
+        .read_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let description = format!("custom-{name}");
+    let modifiers = HashMap::from([("description", description.as_str())]);
+    let entry = store.build(&name, "user", Some(&modifiers)).unwrap();
+    entry.set_password("recorded").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "recorded");
+    let sharing = store.build(&name, "other", Some(&modifiers)).unwrap();
+    assert!(matches!(sharing.get_password(), Err(Error::NoEntry)));
+    assert_eq!(entry.get_password().unwrap(), "recorded");
+    entry.delete_credential().unwrap();
+
+    // cached reads still check the key's owner
+    let store = StoreBuilder::new()
+        .verify_owner(true)
+        .read_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("mine").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "mine");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    let planted = Perm::kernel_default().world(Permission::VIEW | Permission::READ);
+    key.set_perms(KeyPermissions::from_u32(planted.bits()))
+        .unwrap();
+    match entry.get_password() {
+        Err(Error::NoStorageAccess(err)) => assert!(err.downcast_ref::<OwnerMismatch>().is_some()),
+        result => panic!("a planted key was read: {result:?}"),
+    }
+    key.invalidate().unwrap();
+
+    // and keep the key alive
+    let store = StoreBuilder::new()
+        .timeout(Duration::from_secs(600))
+        .keepalive_timeout(Duration::from_secs(60))
+        .read_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("session token").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "session token");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let key = cred.keyring.search(&cred.description).unwrap();
+    key.set_timeout(600).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "session token");
+    assert!(cred.time_remaining().unwrap().unwrap() <= Duration::from_secs(60));
+    entry.delete_credential().unwrap();
+
+    // each thread reads the keys in its own thread keyring
+    let store = StoreBuilder::new()
+        .keyring(Target::Thread)
+        .read_cache(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let (first, second) = (store.clone(), store.clone());
+    let (name1, name2) = (name.clone(), name.clone());
+    std::thread::spawn(move || {
+        let entry = first.build(&name1, "user", None).unwrap();
+        entry.set_password("first thread").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "first thread");
+        std::thread::spawn(move || {
+            let entry = second.build(&name2, "user", None).unwrap();
+            assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+            entry.set_password("second thread").unwrap();
+            assert_eq!(entry.get_password().unwrap(), "second thread");
+        })
+        .join()
+        .unwrap();
+        assert_eq!(entry.get_password().unwrap(), "first thread");
+    })
+    .join()
+    .unwrap();
+
+    // cached reads don't read the key, except from thread keyrings
+    #[cfg(feature = "fault_injection")]
+    {
+        use super::{FaultInjector, KeyctlOp};
+
+        for (target, cached) in [(Target::Session, true), (Target::Thread, false)] {
+            let faults = Arc::new(FaultInjector::kernel());
+            let store = StoreBuilder::new()
+                .keyring(target)
+                .keyctl(faults.clone())
+                .read_cache(Duration::from_secs(60))
+                .build()
+                .unwrap();
+            let entry = store.build(&name, "user", None).unwrap();
+            entry.set_password("test password").unwrap();
+            assert_eq!(entry.get_password().unwrap(), "test password");
+            let reads = faults.calls(KeyctlOp::Read);
+            for _ in 0..3 {
+                assert_eq!(entry.get_password().unwrap(), "test password");
+            }
+            let expected = if cached { reads } else { reads + 3 };
+            assert_eq!(faults.calls(KeyctlOp::Read), expected);
+            entry.delete_credential().unwrap();
+        }
+    }
+
+    let config = HashMap::from([("read_cache", "1000")]);
+    assert_eq!(
+        StoreConfig::from_options(&config).unwrap().read_cache,
+        Some(Duration::from_secs(1))
+    );
+}
+
+#[cfg(feature = "fault_injection")]
+#[test]
+fn test_fault_injection() {
+    use super::{FaultInjector, KeyctlOp, StoreBuilder};
+    use linux_keyutils::KeyError;
+
+    let faults = Arc::new(FaultInjector::kernel());
+    let store = StoreBuilder::new().keyctl(faults.clone()).build().unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, "user", None).unwrap();
+    entry.set_password("test password").unwrap();
+    assert_eq!(faults.calls(KeyctlOp::Add), 1);
+    // EDQUOT on add
+    faults.fail_next(KeyctlOp::Add, KeyError::QuotaExceeded);
+    assert!(matches!(
+        entry.set_password("new password"),
+        Err(Error::PlatformFailure(_))
+    ));
+    entry.set_password("new password").unwrap();
+    // AccessDenied on the nth read: each get of a small secret is one read
+    let reads = faults.calls(KeyctlOp::Read);
+    faults.fail_nth(KeyctlOp::Read, reads + 2, KeyError::AccessDenied);
+    assert_eq!(entry.get_password().unwrap(), "new password");
+    assert!(matches!(
+        entry.get_password(),
+        Err(Error::NoStorageAccess(_))
+    ));
+    assert_eq!(entry.get_password().unwrap(), "new password");
+    // KeyRevoked between search and read
+    faults.revoke_next_found();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+    // which a write replaces
+    test_round_trip("fault injection after revocation", &entry, "test password");
+}
+
+#[test]
+fn test_aliases() {
+    use super::StoreBuilder;
+
+    let name = generate_random_string();
+    let prefix = format!("alias-{name}:");
+    let store = StoreBuilder::new().prefix(&prefix).build().unwrap();
+    let entry = store.build(&name, "new", None).unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let old = format!("{prefix}old@{name}");
+    assert!(matches!(cred.add_alias(&old), Err(Error::NoEntry)));
+    entry.set_password("renamed").unwrap();
+    cred.add_alias(&old).unwrap();
+    // the old naming scheme finds the key, and so does the new one
+    let aliased = store.build(&name, "old", None).unwrap();
+    assert_eq!(aliased.get_password().unwrap(), "renamed");
+    entry.set_password("updated").unwrap();
+    assert_eq!(aliased.get_password().unwrap(), "updated");
+    // aliases of existing credentials are refused
+    let other = store.build(&name, "other", None).unwrap();
+    other.set_password("other").unwrap();
+    let taken = format!("{prefix}other@{name}");
+    assert!(matches!(cred.add_alias(&taken), Err(Error::Invalid(_, _))));
+    let other_cred = other.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(other_cred.remove_alias(&old), Err(Error::NoEntry)));
+    other.delete_credential().unwrap();
+    cred.remove_alias(&old).unwrap();
+    assert!(matches!(aliased.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(cred.remove_alias(&old), Err(Error::NoEntry)));
+    // aliases go away with the key
+    cred.add_alias(&old).unwrap();
+    entry.delete_credential().unwrap();
+    assert!(matches!(aliased.get_password(), Err(Error::NoEntry)));
+    entry.set_password("recreated").unwrap();
+    assert!(matches!(aliased.get_password(), Err(Error::NoEntry)));
+    // aliases aren't listed as credentials
+    assert_eq!(store.entries().unwrap().len(), 1);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_link_to() {
+    use linux_keyutils::{Key, KeyType};
+
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let session = super::sys::keyring_serial(KeyRingIdentifier::Session, true).unwrap();
+    let shared = super::sys::add_key(KeyType::KeyRing, &name, &[], session).unwrap();
+    assert!(matches!(cred.link_to(shared), Err(Error::NoEntry)));
+    entry.set_password("shared").unwrap();
+    cred.link_to(shared).unwrap();
+    // linking twice is harmless
+    cred.link_to(shared).unwrap();
+    let key = super::sys::search(shared, KeyType::User, &cred.description).unwrap();
+    assert_eq!(super::sys::keyring_links(shared).unwrap(), vec![key]);
+    // the shared link sees rewrites
+    entry.set_password("rewritten").unwrap();
+    assert_eq!(
+        super::sys::read(key, usize::MAX).unwrap().unwrap(),
+        b"rewritten"
+    );
+    cred.unlink_from(shared).unwrap();
+    assert!(super::sys::keyring_links(shared).unwrap().is_empty());
+    assert!(matches!(cred.unlink_from(shared), Err(Error::NoEntry)));
+    assert_eq!(entry.get_password().unwrap(), "rewritten");
+    // the credential's own keyring can't be unlinked from this way
+    assert!(matches!(
+        cred.unlink_from(session),
+        Err(Error::Invalid(_, _))
+    ));
+    entry.delete_credential().unwrap();
+    Key::from_id(shared).invalidate().unwrap();
+}
+
+#[test]
+fn test_pin() {
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(cred.pin(), Err(Error::NoEntry)));
+    entry.set_password("test password").unwrap();
+    let pinned = cred.pin().unwrap();
+    assert!(pinned.serial.is_some());
+    assert_eq!(pinned.get_password().unwrap(), "test password");
+    // writes through either credential reach the same key
+    pinned.set_password("pinned password").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "pinned password");
+    entry.set_password("new password").unwrap();
+    assert_eq!(pinned.get_password().unwrap(), "new password");
+    // a big secret is read in full
+    let secret = vec![7u8; 20000];
+    entry.set_secret(&secret).unwrap();
+    assert_eq!(pinned.get_secret().unwrap(), secret);
+    entry.delete_credential().unwrap();
+    assert!(matches!(pinned.get_password(), Err(Error::NoEntry)));
+    // a recreated credential is a new key
+    entry.set_password("test password").unwrap();
+    assert!(matches!(pinned.get_password(), Err(Error::NoEntry)));
+    entry.delete_credential().unwrap();
+}
+
+#[cfg(feature = "fault_injection")]
+#[test]
+fn test_pinned_read_syscalls() {
+    use super::{FaultInjector, KeyctlOp, Relink, StoreBuilder};
+
+    let faults = Arc::new(FaultInjector::kernel());
+    let store = StoreBuilder::new()
+        .relink(Relink::Never)
+        .keyctl(faults.clone())
+        .build()
+        .unwrap();
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    let calls = |op| faults.calls(op);
+    let (searches, reads) = (calls(KeyctlOp::Search), calls(KeyctlOp::Read));
+    // without re-linking, a get is a search and a read
+    assert_eq!(entry.get_password().unwrap(), "test password");
+    assert_eq!(calls(KeyctlOp::Search), searches + 1);
+    assert_eq!(calls(KeyctlOp::Read), reads + 1);
+    // pinned, it's just the read
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let pinned = cred.pin().unwrap();
+    let (searches, reads) = (calls(KeyctlOp::Search), calls(KeyctlOp::Read));
+    let links = calls(KeyctlOp::Link);
+    assert_eq!(pinned.get_password().unwrap(), "test password");
+    assert_eq!(calls(KeyctlOp::Search), searches);
+    assert_eq!(calls(KeyctlOp::Read), reads + 1);
+    assert_eq!(calls(KeyctlOp::Link), links);
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_time_remaining() {
+    use super::StoreBuilder;
+    use std::time::Duration;
+
+    let name = generate_random_string();
+    let entry = entry_new(&name, &name);
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(matches!(cred.time_remaining(), Err(Error::NoEntry)));
+    entry.set_password("test password").unwrap();
+    assert_eq!(cred.time_remaining().unwrap(), None);
+    entry.delete_credential().unwrap();
+
+    let store = StoreBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("test password").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let remaining = cred.time_remaining().unwrap().unwrap();
+    assert!(remaining > Duration::ZERO && remaining <= Duration::from_secs(30));
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_keepalive_timeout() {
+    use super::StoreBuilder;
+    use std::time::Duration;
+
+    for value in ["0", "soon"] {
+        let config = HashMap::from([("keepalive_timeout", value)]);
+        assert!(matches!(
+            Store::new_with_configuration(&config),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+    let config = HashMap::from([("keepalive_timeout", "3")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    assert_eq!(store.keepalive_timeout, Some(Duration::from_secs(3)));
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("session token").unwrap();
+    // each get pushes the expiry back (the kernel counts whole seconds, so
+    // a 3-second timeout expires the key between 2 and 3 seconds later)
+    for _ in 0..2 {
+        std::thread::sleep(Duration::from_millis(1500));
+        assert_eq!(entry.get_password().unwrap(), "session token");
+    }
+    std::thread::sleep(Duration::from_millis(3500));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+
+    // writes use the store's own timeout, if it has one
+    let store = StoreBuilder::new()
+        .timeout(Duration::from_secs(600))
+        .keepalive_timeout(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("session token").unwrap();
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.time_remaining().unwrap().unwrap() > Duration::from_secs(60));
+    entry.get_password().unwrap();
+    assert!(cred.time_remaining().unwrap().unwrap() <= Duration::from_secs(60));
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_expiry_monitor() {
+    use super::{ExpiryMonitor, StoreBuilder};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    let store = StoreBuilder::new()
+        .timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    let name = generate_random_string();
+    let monitor = ExpiryMonitor::new();
+    let expired = Arc::new(AtomicUsize::new(0));
+    let counter = expired.clone();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("first").unwrap();
+    monitor.on_expiry(entry, move |entry| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        entry.set_password("again").unwrap();
+    });
+    assert_eq!(monitor.len(), 1);
+    assert_eq!(monitor.check(), 0);
+    std::thread::sleep(Duration::from_millis(1500));
+    // the callback re-provisions the credential as soon as it's noticed gone
+    assert_eq!(monitor.check(), 1);
+    assert_eq!(expired.load(Ordering::SeqCst), 1);
+    let entry = store.build(&name, &name, None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "again");
+    entry.delete_credential().unwrap();
+    assert_eq!(monitor.check(), 1);
+    // a credential that isn't there doesn't go away again
+    let missing = store.build(&generate_random_string(), &name, None).unwrap();
+    monitor.on_expiry(missing, |_| panic!("nothing expired"));
+    assert_eq!(monitor.check(), 0);
+
+    // a background monitor notices an expiry long before its interval is up
+    let monitor = ExpiryMonitor::spawn(Duration::from_secs(60));
+    let (tx, rx) = std::sync::mpsc::channel();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("first").unwrap();
+    monitor.on_expiry(entry, move |_| _ = tx.send(()));
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+}
+
+#[test]
+fn test_verify_owner() {
+    use super::{OwnerMismatch, Perm, StoreBuilder};
+    use linux_keyutils::{KeyPermissions, Permission};
+
+    let config = HashMap::from([("verify_owner", "true")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    assert!(store.verify_owner);
+    let name = generate_random_string();
+    let entry = store.build(&name, &name, None).unwrap();
+    entry.set_password("mine").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "mine");
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    let mut buffer = [0u8; 16];
+    assert_eq!(cred.get_secret_into(&mut buffer).unwrap(), 4);
+    // a key planted with looser permissions than the store gives its own isn't trusted
+    let key = cred.keyring.search(&cred.description).unwrap();
+    let planted = Perm::kernel_default().world(Permission::VIEW | Permission::READ);
+    key.set_perms(KeyPermissions::from_u32(planted.bits()))
+        .unwrap();
+    let mismatch = |result: keyring_core::Result<_>| match result {
+        Err(Error::NoStorageAccess(err)) => err.downcast_ref::<OwnerMismatch>().cloned(),
+        _ => None,
+    };
+    let err = mismatch(entry.get_password().map(drop)).unwrap();
+    assert_eq!(err.permissions, planted.bits());
+    assert_eq!(err.expected_permissions, Perm::kernel_default().bits());
+    assert!(mismatch(cred.get_secret_into(&mut buffer).map(drop)).is_some());
+    // a store that doesn't verify reads it regardless
+    let trusting = Store::new().unwrap().build(&name, &name, None).unwrap();
+    assert_eq!(trusting.get_password().unwrap(), "mine");
+    // keys are expected to have the store's permissions, if it gives them any
+    let store = StoreBuilder::new()
+        .permissions(planted)
+        .verify_owner(true)
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "mine");
+    entry.delete_credential().unwrap();
+}
+
+#[test]
+fn test_read_only() {
+    use super::ReadOnly;
+
+    let name = generate_random_string();
+    let writable = Store::new().unwrap().build(&name, &name, None).unwrap();
+    writable.set_password("kept").unwrap();
+    let config = HashMap::from([("read_only", "true")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    assert!(store.read_only);
+    let entry = store.build(&name, &name, None).unwrap();
+    assert_eq!(entry.get_password().unwrap(), "kept");
+    let refused = |result: keyring_core::Result<()>| match result {
+        Err(Error::NoStorageAccess(err)) => err.downcast_ref::<ReadOnly>().cloned(),
+        _ => None,
+    };
+    let err = refused(entry.set_password("changed")).unwrap();
+    assert_eq!(err.operation, "set");
+    assert!(err.description.is_some());
+    assert_eq!(
+        refused(entry.delete_credential()).unwrap().operation,
+        "delete"
+    );
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(refused(cred.swap_secret(b"changed").map(drop)).is_some());
+    assert!(refused(cred.revoke()).is_some());
+    // nothing changed, and the store itself refuses too
+    assert_eq!(entry.get_password().unwrap(), "kept");
+    let err = refused(store.prune().map(drop)).unwrap();
+    assert_eq!(err.description, None);
+    // a missing credential can be read but not created
+    let missing = store.build(&name, "missing", None).unwrap();
+    assert!(matches!(missing.get_password(), Err(Error::NoEntry)));
+    let missing = missing.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(refused(missing.get_or_create_with(|| Ok(b"new".to_vec())).map(drop)).is_some());
+    writable.delete_credential().unwrap();
+}
+
+#[test]
+fn test_redaction() {
+    use super::{Redaction, StoreBuilder};
+
+    let name = generate_random_string();
+    let entry = Store::new().unwrap().build(&name, &name, None).unwrap();
+    assert!(format!("{entry:?}").contains(&name));
+    let config = HashMap::from([("redaction", "hashed")]);
+    let store = Store::new_with_configuration(&config).unwrap();
+    assert_eq!(store.redaction, Redaction::Hashed);
+    let hashed = format!("{:?}", store.build(&name, &name, None).unwrap());
+    assert!(!hashed.contains(&name));
+    assert!(hashed.contains("<sha256:"));
+    // the same description always hashes the same way
+    let again = format!("{:?}", store.build(&name, &name, None).unwrap());
+    assert_eq!(hashed, again);
+    let store = StoreBuilder::new()
+        .redaction(Redaction::Suppressed)
+        .build()
+        .unwrap();
+    let entry = store.build(&name, &name, None).unwrap();
+    let suppressed = format!("{entry:?}");
+    assert!(!suppressed.contains(&name));
+    assert!(!suppressed.contains("<sha256:"));
+    let cred = entry.as_any().downcast_ref::<Cred>().unwrap();
+    assert!(cred.describe_unredacted().contains(&name));
+    let config = HashMap::from([("redaction", "partial")]);
+    assert!(matches!(
+        Store::new_with_configuration(&config),
+        Err(Error::Invalid(key, _)) if key == "redaction"
+    ));
+}
+
+#[test]
+fn test_fscrypt_key() {
+    use super::FscryptKey;
+
+    let store = Store::new().unwrap();
+    let name = generate_random_string();
+    let key = match FscryptKey::provision(&store, &name, &[0x42; 64]) {
+        Ok(key) => key,
+        // a kernel without fscrypt
+        Err(Error::NotSupportedByStore(_)) => return,
+        Err(err) => panic!("{err:?}"),
+    };
+    assert_eq!(key.description, format!("fscrypt:{name}"));
+    // HKDF-SHA512 of the key, as the kernel derives it
+    assert_eq!(key.identifier, "21198fc33b6928296029e2cf19a0190d");
+    // most test filesystems can't take the key; those that can must take it back
+    let dir = std::env::temp_dir();
+    match key.add_to_filesystem(&dir) {
+        Ok(()) => key.remove_from_filesystem(&dir).unwrap(),
+        Err(Error::NotSupportedByStore(_) | Error::NoStorageAccess(_)) => {}
+        Err(err) => panic!("{err:?}"),
+    }
+    // a malformed identifier is refused before the filesystem is asked
+    for identifier in [
+        "21198fc3",
+        "21198fc33b6928296029e2cf19a0190g",
+        "é1198fc33b6928296029e2cf19a0190",
+    ] {
+        let malformed = FscryptKey {
+            identifier: identifier.to_string(),
+            ..key.clone()
+        };
+        assert!(matches!(
+            malformed.remove_from_filesystem(&dir),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+    key.delete().unwrap();
+    assert!(matches!(key.delete(), Err(Error::NoEntry)));
+    assert!(matches!(
+        FscryptKey::provision(&store, &name, &[0x42; 8]),
+        Err(Error::Invalid(_, _))
+    ));
+    assert!(matches!(
+        FscryptKey::provision(&store, &name, &[0x42; 65]),
+        Err(Error::TooLong(_, 64))
+    ));
+    assert!(matches!(
+        FscryptKey::provision(&store, "", &[0x42; 64]),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+fn test_id_resolver() {
+    use super::{IdMapping, IdResolver};
+    use linux_keyutils::{Key, KeyType};
+
+    for description in [
+        "uid:alice@example.com",
+        "gid:staff@example.com",
+        "user:1000",
+        "group:0",
+    ] {
+        let mapping: IdMapping = description.parse().unwrap();
+        assert_eq!(mapping.to_string(), description);
+    }
+    assert_eq!(
+        "user:1000".parse::<IdMapping>().unwrap(),
+        IdMapping::User(1000)
+    );
+    for description in ["uid:", "user:alice", "group:-1", "name:alice", "alice"] {
+        assert!(matches!(
+            description.parse::<IdMapping>(),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    // a scratch keyring stands in for the kernel's
+    let session = super::sys::keyring_serial(KeyRingIdentifier::Session, true).unwrap();
+    let scratch =
+        super::sys::add_key(KeyType::KeyRing, &generate_random_string(), &[], session).unwrap();
+    let resolver = IdResolver::new(scratch);
+    let alice = IdMapping::Uid("alice@example.com".to_string());
+    match resolver.seed(&alice, "1000") {
+        Ok(()) => {}
+        // the NFS client isn't loaded
+        Err(Error::NotSupportedByStore(_)) => return,
+        Err(err) => panic!("{err:?}"),
+    }
+    assert_eq!(resolver.get(&alice).unwrap(), "1000");
+    resolver
+        .seed(&IdMapping::User(1000), "alice@example.com")
+        .unwrap();
+    let mut mappings = resolver.mappings().unwrap();
+    mappings.sort_by_key(|(mapping, _)| mapping.to_string());
+    assert_eq!(
+        mappings,
+        vec![
+            (alice.clone(), "1000".to_string()),
+            (IdMapping::User(1000), "alice@example.com".to_string()),
+        ]
+    );
+    assert!(matches!(
+        resolver.seed(&alice, "alice"),
+        Err(Error::Invalid(_, _))
+    ));
+    resolver.clear(&alice).unwrap();
+    assert!(matches!(resolver.get(&alice), Err(Error::NoEntry)));
+    _ = Key::from_id(scratch).invalidate();
+}
+
+#[test]
+fn test_asymmetric_keys() {
+    use super::AsymmetricKey;
+    use linux_keyutils::Key;
+
+    // a self-signed certificate for "CN=keyring-store test"
+    let cert = include_bytes!("testdata/cert.der");
+    let store = Store::new().unwrap();
+    let (keyring, _) = store.keyring_serials().unwrap();
+    let description = generate_random_string();
+    let serial = match super::sys::add_key_of_type(c"asymmetric", &description, cert, keyring) {
+        Ok(serial) => serial,
+        // a kernel without X.509 parsing
+        Err(_) => return,
+    };
+    let entries = store.asymmetric_keys().unwrap();
+    let entry = entries
+        .iter()
+        .find(|entry| {
+            let key = entry.as_any().downcast_ref::<AsymmetricKey>().unwrap();
+            key.serial == serial
+        })
+        .unwrap();
+    let attributes = entry.get_attributes().unwrap();
+    assert_eq!(attributes["key_type"], "asymmetric");
+    assert_eq!(attributes["description"], description);
+    assert_eq!(attributes["serial"], serial.as_raw_id().to_string());
+    if let Some(subtype) = attributes.get("subtype") {
+        assert_eq!(subtype, "X509.rsa");
+        assert_eq!(attributes["fingerprint"], "3db7e65a");
+    }
+    assert!(entry.get_specifiers().is_none());
+    assert!(matches!(
+        entry.get_secret(),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    assert!(matches!(
+        entry.set_password("nope"),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    assert!(matches!(
+        entry.delete_credential(),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    // credentials aren't asymmetric keys, and asymmetric keys aren't credentials
+    let cred = store.build(&description, "user", None).unwrap();
+    cred.set_password("secret").unwrap();
+    assert!(
+        store
+            .entries()
+            .unwrap()
+            .iter()
+            .all(|entry| entry.as_any().downcast_ref::<AsymmetricKey>().is_none())
+    );
+    assert_eq!(store.asymmetric_keys().unwrap().len(), entries.len());
+    cred.delete_credential().unwrap();
+    Key::from_id(serial).invalidate().unwrap();
+    assert!(matches!(entry.get_attributes(), Err(Error::NoEntry)));
+}
+
+#[test]
+#[cfg(feature = "pkey")]
+fn test_pkey_operations() {
+    use super::AsymmetricKey;
+    use super::crypto::sha256;
+    use linux_keyutils::Key;
+
+    if !super::Capabilities::get().public_key {
+        return;
+    }
+    let cert = include_bytes!("testdata/cert.der");
+    // signed by the certificate's private key, with `enc=pkcs1 hash=sha256`
+    let signature = include_bytes!("testdata/cert.sig");
+    let store = Store::new().unwrap();
+    let (keyring, _) = store.keyring_serials().unwrap();
+    let description = generate_random_string();
+    let Ok(serial) = super::sys::add_key_of_type(c"asymmetric", &description, cert, keyring) else {
+        return;
+    };
+    let entries = store.asymmetric_keys().unwrap();
+    let key = entries
+        .iter()
+        .filter_map(|entry| entry.as_any().downcast_ref::<AsymmetricKey>())
+        .find(|key| key.serial == serial)
+        .unwrap();
+    let info = "enc=pkcs1 hash=sha256";
+    let query = key.query(info).unwrap();
+    assert_eq!(query.key_size, 2048);
+    assert!(query.verify && query.encrypt);
+    assert!(
+        !query.sign && !query.decrypt,
+        "certificates have no private key"
+    );
+    assert_eq!(query.max_sig_size, 256);
+
+    let digest = sha256(b"signed by the test key");
+    assert!(key.verify(info, &digest, signature).unwrap());
+    assert!(
+        !key.verify(info, &sha256(b"something else"), signature)
+            .unwrap()
+    );
+    let encrypted = key.encrypt(info, b"for the private key").unwrap();
+    assert_eq!(encrypted.len(), 256);
+    assert!(matches!(
+        key.sign(info, &digest),
+        Err(Error::NotSupportedByStore(_))
+    ));
+    assert!(matches!(
+        key.query("enc=nonsense"),
+        Err(Error::Invalid(_, _))
+    ));
+    Key::from_id(serial).invalidate().unwrap();
 }
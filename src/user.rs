@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeyRingIdentifier, KeySerialId};
+
+use super::sys;
+
+/// Whose keyrings a store keeps its keys in.
+///
+/// When a program runs under `sudo`, its session keyring usually still
+/// belongs to the user who invoked it, while its user keyring is root's.
+/// This picks which of those users the store's credentials belong to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyringUser {
+    /// The effective user's keyrings.
+    ///
+    /// This is the default, and matches the legacy keyring behavior.
+    #[default]
+    Current,
+    /// The keyrings of the user who invoked the program: the user named by
+    /// `SUDO_UID` when running as root under `sudo`, otherwise the real user
+    /// (which differs from the effective user in a setuid program).
+    ///
+    /// Another user's keyrings can only be reached by root, and only through
+    /// that user's persistent keyring, so the store keeps its keys there and
+    /// gives them to that user.
+    Invoking,
+    /// The keyrings of the user with this uid.
+    ///
+    /// As with [KeyringUser::Invoking], only root can use another user's
+    /// keyrings, and then only their persistent keyring. This lets
+    /// provisioning agents stage credentials for service accounts before
+    /// those accounts ever log in.
+    Uid(u32),
+}
+
+impl KeyringUser {
+    /// The uid of this user, if it's not the effective user.
+    pub fn other_uid(&self) -> Option<u32> {
+        let uid = match self {
+            KeyringUser::Current => return None,
+            KeyringUser::Invoking => invoking_uid(),
+            KeyringUser::Uid(uid) => *uid,
+        };
+        (uid != unsafe { libc::geteuid() }).then_some(uid)
+    }
+}
+
+/// The uid of the user who invoked the program, as far as it can be told.
+///
+/// That's `SUDO_UID` when running as root (`su` leaves no such trace, so
+/// under `su` it's root), otherwise the real uid.
+pub fn invoking_uid() -> u32 {
+    if unsafe { libc::geteuid() } == 0 {
+        if let Some(uid) = std::env::var("SUDO_UID").ok().and_then(|s| s.parse().ok()) {
+            return uid;
+        }
+    }
+    unsafe { libc::getuid() }
+}
+
+/// Get the serial of user `uid`'s persistent keyring.
+///
+/// The keyring is attached to the process keyring rather than the session
+/// keyring, so that searches of the session don't turn up the other user's keys.
+pub(crate) fn persistent_serial(uid: u32) -> std::result::Result<KeySerialId, KeyError> {
+    sys::persistent_serial_of(uid, KeyRingIdentifier::Process)
+}
+
+impl FromStr for KeyringUser {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "current" => Ok(KeyringUser::Current),
+            "invoking" => Ok(KeyringUser::Invoking),
+            _ => s.parse().map(KeyringUser::Uid).map_err(|_| {
+                Error::Invalid(
+                    "keyring_user".to_string(),
+                    format!("'{s}' is not current, invoking, or a numeric uid"),
+                )
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyringUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyringUser::Current => f.write_str("current"),
+            KeyringUser::Invoking => f.write_str("invoking"),
+            KeyringUser::Uid(uid) => write!(f, "{uid}"),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Key change notifications through the kernel's watch_queue.
+//!
+//! Requires a kernel built with `CONFIG_KEY_NOTIFICATIONS` and
+//! `CONFIG_WATCH_QUEUE` (5.8 or later). On other kernels,
+//! [Watcher::new] fails with [NotSupportedByStore](Error::NotSupportedByStore).
+use std::collections::HashMap;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+use keyring_core::{Error, Result};
+use linux_keyutils::{KeyError, KeySerialId};
+
+use super::error::KeyStoreError;
+use super::{Capabilities, Cred, Target, sys};
+
+const KEYCTL_WATCH_KEY: libc::c_int = 32;
+const O_NOTIFICATION_PIPE: libc::c_int = libc::O_EXCL;
+const IOC_WATCH_QUEUE_SET_SIZE: libc::c_ulong = 0x5760;
+
+/// How many notifications the kernel queues before it starts dropping them.
+const QUEUE_SIZE: libc::c_ulong = 256;
+/// The watch id we tag our subscriptions with (the kernel allows 0-255).
+const WATCH_ID: libc::c_ulong = 0x4b;
+
+const WATCH_TYPE_META: u32 = 0;
+const WATCH_TYPE_KEY_NOTIFY: u32 = 1;
+const WATCH_META_REMOVAL_NOTIFICATION: u32 = 0;
+const WATCH_META_LOSS_NOTIFICATION: u32 = 1;
+const WATCH_INFO_LENGTH: u32 = 0x7f;
+
+/// What happened to a watched key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    /// The key was instantiated.
+    Instantiated,
+    /// The key's payload was updated (e.g. the secret was changed).
+    Updated,
+    /// A key was linked into the watched keyring.
+    Linked(KeySerialId),
+    /// A key was unlinked from the watched keyring.
+    Unlinked(KeySerialId),
+    /// The watched keyring was cleared.
+    Cleared,
+    /// The key was revoked.
+    Revoked,
+    /// The key was invalidated (e.g. the credential was deleted).
+    Invalidated,
+    /// The key's attributes (permissions, owner, timeout) changed.
+    AttributesChanged,
+    /// The key was destroyed, so the watch on it is gone.
+    WatchRemoved,
+}
+
+/// A notification delivered by a [Watcher].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// Something happened to a watched key or keyring.
+    Key {
+        /// The serial of the watched key or keyring.
+        key: KeySerialId,
+        /// The description the key was watched under, if known.
+        description: Option<String>,
+        /// What happened.
+        event: KeyEvent,
+    },
+    /// The notification queue overflowed, so some notifications were lost.
+    Overrun,
+}
+
+/// A subscription to change notifications for keys and keyrings.
+///
+/// Watch credentials with [watch](Watcher::watch) (or whole keyrings with
+/// [watch_keyring](Watcher::watch_keyring)), then collect notifications
+/// with [recv](Watcher::recv). The watcher's file descriptor can also be
+/// registered with an event loop; it becomes readable when notifications
+/// are pending.
+///
+/// A watch follows a specific key, not a description: if a credential is
+/// deleted and then set again, the new key must be watched anew.
+#[derive(Debug)]
+pub struct Watcher {
+    read: OwnedFd,
+    _write: OwnedFd,
+    watched: HashMap<i32, String>,
+}
+
+impl Watcher {
+    /// Create a watcher with its own notification queue.
+    pub fn new() -> Result<Self> {
+        let caps = Capabilities::get();
+        if caps.reported && !caps.notifications {
+            return Err(Error::NotSupportedByStore(
+                "the kernel does not support key notifications".to_string(),
+            ));
+        }
+        let mut fds = [0 as libc::c_int; 2];
+        let flags = O_NOTIFICATION_PIPE | libc::O_CLOEXEC | libc::O_NONBLOCK;
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } < 0 {
+            return Err(unsupported_or_failure(std::io::Error::last_os_error()));
+        }
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        if unsafe { libc::ioctl(read.as_raw_fd(), IOC_WATCH_QUEUE_SET_SIZE, QUEUE_SIZE) } < 0 {
+            return Err(unsupported_or_failure(std::io::Error::last_os_error()));
+        }
+        Ok(Watcher {
+            read,
+            _write: write,
+            watched: HashMap::new(),
+        })
+    }
+
+    /// Watch the key currently holding `cred`'s secret.
+    ///
+    /// The key is found just as the credential's reads find it, so this is
+    /// the key of the credential's type, in its mirrors or behind an alias
+    /// if need be, or the key the credential was built on.
+    ///
+    /// Returns the serial of the watched key, or a [NoEntry](Error::NoEntry)
+    /// error if the credential has no secret yet.
+    pub fn watch(&mut self, cred: &Cred) -> Result<KeySerialId> {
+        let serial = cred.find()?.get_id();
+        self.watch_serial(serial, &cred.description)?;
+        Ok(serial)
+    }
+
+    /// Watch a target keyring, to be told when keys are linked into or unlinked from it.
+    pub fn watch_keyring(&mut self, target: Target) -> Result<KeySerialId> {
+        let serial = target.serial().map_err(KeyStoreError)?;
+        self.watch_serial(serial, target.as_str())?;
+        Ok(serial)
+    }
+
+    /// Stop watching the key or keyring with the given serial.
+    pub fn unwatch(&mut self, serial: KeySerialId) -> Result<()> {
+        self.watched.remove(&serial.as_raw_id());
+        match watch_key(serial, -1, 0) {
+            // the watch goes away by itself when the key is destroyed
+            Err(KeyError::KeyDoesNotExist) => Ok(()),
+            result => result.map_err(|e| KeyStoreError(e).into()),
+        }
+    }
+
+    /// Collect pending notifications, waiting up to `timeout` for the first
+    /// one (or indefinitely if `timeout` is `None`).
+    ///
+    /// Returns an empty list if the timeout elapses with nothing to report.
+    pub fn recv(&self, timeout: Option<Duration>) -> Result<Vec<Notification>> {
+        let millis = match timeout {
+            Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut pollfd = libc::pollfd {
+            fd: self.read.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            if unsafe { libc::poll(&mut pollfd, 1, millis) } >= 0 {
+                break;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(Error::PlatformFailure(Box::new(err)));
+            }
+        }
+        let mut notifications = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.read.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            if len < 0 {
+                let err = std::io::Error::last_os_error();
+                match err.kind() {
+                    std::io::ErrorKind::WouldBlock => break,
+                    std::io::ErrorKind::Interrupted => continue,
+                    _ => return Err(Error::PlatformFailure(Box::new(err))),
+                }
+            }
+            if len == 0 {
+                break;
+            }
+            self.parse(&buffer[..len as usize], &mut notifications);
+        }
+        Ok(notifications)
+    }
+
+    pub(crate) fn watch_serial(&mut self, serial: KeySerialId, description: &str) -> Result<()> {
+        watch_key(serial, self.read.as_raw_fd(), WATCH_ID).map_err(|e| match e {
+            KeyError::OperationNotSupported => Error::NotSupportedByStore(
+                "the kernel does not support key notifications".to_string(),
+            ),
+            e => KeyStoreError(e).into(),
+        })?;
+        self.watched
+            .insert(serial.as_raw_id(), description.to_string());
+        Ok(())
+    }
+
+    /// Decode the notification records in `data`.
+    ///
+    /// Each record starts with a `struct watch_notification` header
+    /// (type:24, subtype:8, info), whose info word holds the record length.
+    fn parse(&self, mut data: &[u8], notifications: &mut Vec<Notification>) {
+        let word = |data: &[u8], i: usize| {
+            u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+        };
+        while data.len() >= 8 {
+            let header = word(data, 0);
+            let (kind, subtype) = if cfg!(target_endian = "little") {
+                (header & 0xff_ffff, header >> 24)
+            } else {
+                (header >> 8, header & 0xff)
+            };
+            let len = (word(data, 4) & WATCH_INFO_LENGTH) as usize;
+            if len < 8 || len > data.len() {
+                break;
+            }
+            let record = &data[..len];
+            data = &data[len..];
+            let notification = match (kind, subtype) {
+                (WATCH_TYPE_META, WATCH_META_LOSS_NOTIFICATION) => Notification::Overrun,
+                // struct watch_notification_removal carries the key serial as a u64
+                (WATCH_TYPE_META, WATCH_META_REMOVAL_NOTIFICATION) if len >= 16 => {
+                    self.notification(word(record, 8) as i32, KeyEvent::WatchRemoved)
+                }
+                // struct key_notification carries the key serial and an aux word
+                (WATCH_TYPE_KEY_NOTIFY, subtype) if len >= 16 => {
+                    let aux = KeySerialId::new(word(record, 12) as i32);
+                    let event = match subtype {
+                        0 => KeyEvent::Instantiated,
+                        1 => KeyEvent::Updated,
+                        2 => KeyEvent::Linked(aux),
+                        3 => KeyEvent::Unlinked(aux),
+                        4 => KeyEvent::Cleared,
+                        5 => KeyEvent::Revoked,
+                        6 => KeyEvent::Invalidated,
+                        7 => KeyEvent::AttributesChanged,
+                        _ => continue,
+                    };
+                    self.notification(word(record, 8) as i32, event)
+                }
+                _ => continue,
+            };
+            notifications.push(notification);
+        }
+    }
+
+    fn notification(&self, serial: i32, event: KeyEvent) -> Notification {
+        Notification::Key {
+            key: KeySerialId::new(serial),
+            description: self.watched.get(&serial).cloned(),
+            event,
+        }
+    }
+}
+
+impl AsFd for Watcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.read.as_fd()
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.read.as_raw_fd()
+    }
+}
+
+/// Attach (or, with a `fd` of -1, detach) a watch on `key`.
+fn watch_key(
+    key: KeySerialId,
+    fd: RawFd,
+    watch_id: libc::c_ulong,
+) -> std::result::Result<(), KeyError> {
+    sys::keyctl(
+        KEYCTL_WATCH_KEY,
+        key.as_raw_id() as libc::c_ulong,
+        fd as libc::c_ulong,
+        watch_id,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Kernels without watch_queue support reject notification pipes outright.
+fn unsupported_or_failure(err: std::io::Error) -> Error {
+    match err.raw_os_error() {
+        Some(libc::ENOPKG | libc::EINVAL | libc::ENOTTY | libc::ENOSYS) => {
+            Error::NotSupportedByStore("the kernel does not support key notifications".to_string())
+        }
+        _ => Error::PlatformFailure(Box::new(err)),
+    }
+}